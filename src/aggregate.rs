@@ -0,0 +1,162 @@
+//! the aggregates shown in the footer of the list, computed by the database on all the rows
+//! matching the filter, not only on the loaded page
+use crate::db::{Platform, Value};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn all() -> Vec<Aggregate> {
+        vec![
+            Aggregate::Count,
+            Aggregate::Sum,
+            Aggregate::Avg,
+            Aggregate::Min,
+            Aggregate::Max,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Aggregate::Count => "Count",
+            Aggregate::Sum => "Sum",
+            Aggregate::Avg => "Average",
+            Aggregate::Min => "Minimum",
+            Aggregate::Max => "Maximum",
+        }
+    }
+
+    /// sum and average only make sense on numbers
+    pub fn numeric_only(&self) -> bool {
+        match self {
+            Aggregate::Sum | Aggregate::Avg => true,
+            _ => false,
+        }
+    }
+
+    fn function(&self) -> &'static str {
+        match self {
+            Aggregate::Count => "COUNT",
+            Aggregate::Sum => "SUM",
+            Aggregate::Avg => "AVG",
+            Aggregate::Min => "MIN",
+            Aggregate::Max => "MAX",
+        }
+    }
+
+    /// the aggregate of these values, as the database would compute it,
+    /// for the rows that are not in a database
+    pub fn compute(&self, values: &[Value]) -> Value {
+        let values: Vec<&str> = values.iter().filter_map(|v| v.as_deref()).collect();
+        let numbers: Option<Vec<f64>> = values.iter().map(|v| v.trim().parse().ok()).collect();
+        match self {
+            Aggregate::Count => Some(values.len().to_string()),
+            Aggregate::Sum | Aggregate::Avg if values.is_empty() => None,
+            Aggregate::Sum => numbers.map(|n| n.iter().sum::<f64>().to_string()),
+            Aggregate::Avg => numbers.map(|n| (n.iter().sum::<f64>() / n.len() as f64).to_string()),
+            Aggregate::Min | Aggregate::Max => {
+                // numbers when all the values are, text otherwise
+                let compare = |a: &(usize, &&str), b: &(usize, &&str)| match &numbers {
+                    Some(n) => n[a.0].partial_cmp(&n[b.0]).unwrap_or(Ordering::Equal),
+                    None => a.1.cmp(b.1),
+                };
+                let found = if *self == Aggregate::Min {
+                    values.iter().enumerate().min_by(compare)
+                } else {
+                    values.iter().enumerate().max_by(compare)
+                };
+                found.map(|(_, v)| v.to_string())
+            }
+        }
+    }
+}
+
+/// A query returning one row with the aggregate of each column, in order.
+/// `from` is the quoted table or a subquery, `filter` is the condition of the where clause.
+pub fn query(
+    platform: Platform,
+    from: &str,
+    filter: Option<&str>,
+    aggregates: &[(String, Aggregate)],
+) -> String {
+    let selected: Vec<String> = aggregates
+        .iter()
+        .map(|(column, aggregate)| {
+            format!("{}({})", aggregate.function(), platform.quote_ident(column))
+        })
+        .collect();
+    let condition = match filter {
+        Some(filter) => format!(" WHERE {}", filter),
+        None => String::new(),
+    };
+    format!("SELECT {} FROM {}{}", selected.join(", "), from, condition)
+}
+
+/// A query returning the values of the column in the rows matching the filter,
+/// each with its number of rows and the aggregate of each column in the group.
+pub fn group_query(
+    platform: Platform,
+    from: &str,
+    filter: Option<&str>,
+    column: &str,
+    aggregates: &[(String, Aggregate)],
+) -> String {
+    let column = platform.quote_ident(column);
+    let mut selected = vec![column.clone(), "COUNT(*)".to_string()];
+    for (aggregated, aggregate) in aggregates.iter() {
+        selected.push(format!(
+            "{}({})",
+            aggregate.function(),
+            platform.quote_ident(aggregated)
+        ));
+    }
+    let condition = match filter {
+        Some(filter) => format!(" WHERE {}", filter),
+        None => String::new(),
+    };
+    format!(
+        "SELECT {} FROM {}{} GROUP BY {} ORDER BY {}",
+        selected.join(", "),
+        from,
+        condition,
+        column,
+        column
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[Option<&str>]) -> Vec<Value> {
+        values.iter().map(|v| v.map(String::from)).collect()
+    }
+
+    #[test]
+    fn aggregates_are_computed_without_the_nulls() {
+        let numbers = values(&[Some("2"), None, Some("10"), Some(" 3 ")]);
+        assert_eq!(Aggregate::Count.compute(&numbers), Some("3".to_string()));
+        assert_eq!(Aggregate::Sum.compute(&numbers), Some("15".to_string()));
+        assert_eq!(Aggregate::Avg.compute(&numbers), Some("5".to_string()));
+        // compared as numbers, not as text
+        assert_eq!(Aggregate::Min.compute(&numbers), Some("2".to_string()));
+        assert_eq!(Aggregate::Max.compute(&numbers), Some("10".to_string()));
+        let names = values(&[Some("b"), Some("10"), Some("a")]);
+        assert_eq!(Aggregate::Min.compute(&names), Some("10".to_string()));
+        assert_eq!(Aggregate::Max.compute(&names), Some("b".to_string()));
+        assert_eq!(Aggregate::Sum.compute(&names), None);
+        let nulls = values(&[None, None]);
+        assert_eq!(Aggregate::Count.compute(&nulls), Some("0".to_string()));
+        assert_eq!(Aggregate::Sum.compute(&nulls), None);
+        assert_eq!(Aggregate::Max.compute(&nulls), None);
+    }
+}
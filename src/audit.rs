@@ -0,0 +1,420 @@
+//! the audit log of the changes saved from the data tabs: who changed which row of which table,
+//! when, and the values of its columns before and after
+//!
+//! It is kept in `audit.sqlite` of the config directory when `audit = true` is in `settings.toml`.
+//! The key of an inserted row is the one that was typed, a key generated by the database is not known.
+//! The rows are told apart by the url of their database, without its credentials.
+use crate::config::{self, Settings};
+use crate::db::{Platform, Value};
+use crate::edit;
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+static SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS changes (
+    id INTEGER PRIMARY KEY,
+    changed_at TEXT NOT NULL,
+    user_name TEXT NOT NULL,
+    table_name TEXT NOT NULL,
+    row_key TEXT NOT NULL,
+    action TEXT NOT NULL,
+    database_name TEXT NOT NULL DEFAULT '',
+    previous_key TEXT
+);
+CREATE TABLE IF NOT EXISTS change_values (
+    change_id INTEGER NOT NULL REFERENCES changes (id),
+    position INTEGER NOT NULL,
+    column_name TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT
+);
+CREATE INDEX IF NOT EXISTS change_values_change ON change_values (change_id);
+CREATE INDEX IF NOT EXISTS changes_database_row ON changes (database_name, table_name, row_key);
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Insert => "insert",
+            Action::Update => "update",
+            Action::Delete => "delete",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "insert" => Some(Action::Insert),
+            "update" => Some(Action::Update),
+            "delete" => Some(Action::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// a saved change of a row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// 0 until it is recorded
+    pub id: i64,
+    /// local time, set when it is recorded
+    pub changed_at: String,
+    pub user: String,
+    /// the url of the database, see `db::database_url`
+    pub database: String,
+    pub table: String,
+    /// the condition on the key columns, see `row_key`
+    pub key: String,
+    /// the key of the row before an update that changed it
+    pub previous_key: Option<String>,
+    pub action: Action,
+    /// the column, its value before and after the change
+    pub values: Vec<(String, Value, Value)>,
+}
+
+impl Change {
+    /// The change of a row from the `original` values to the new `values`,
+    /// `original` is none for an insert and `values` is none for a delete.
+    pub fn new(
+        database: &str,
+        table: &str,
+        columns: &[String],
+        key: &[String],
+        original: Option<&[Value]>,
+        values: Option<&[Value]>,
+    ) -> Self {
+        let action = match (original, values) {
+            (None, _) => Action::Insert,
+            (Some(_), Some(_)) => Action::Update,
+            (Some(_), None) => Action::Delete,
+        };
+        let row = values.or(original).unwrap_or(&[]);
+        let row_key = row_key(columns, key, row);
+        let previous_key = match (original, values) {
+            (Some(original), Some(_)) => Some(self::row_key(columns, key, original)),
+            _ => None,
+        }
+        .filter(|previous| *previous != row_key);
+        let value = |values: Option<&[Value]>, i: usize| -> Value {
+            values.and_then(|v| v.get(i).cloned()).unwrap_or(None)
+        };
+        Change {
+            id: 0,
+            changed_at: String::new(),
+            user: current_user(),
+            database: database.to_string(),
+            table: table.to_string(),
+            key: row_key,
+            previous_key: previous_key,
+            action: action,
+            values: columns
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (c.clone(), value(original, i), value(values, i)))
+                .collect(),
+        }
+    }
+
+    /// the columns whose value is not the same after the change
+    pub fn changed_columns(&self) -> Vec<&str> {
+        self.values
+            .iter()
+            .filter(|(_, old, new)| self.action != Action::Update || old != new)
+            .map(|(column, _, _)| column.as_str())
+            .collect()
+    }
+
+    /// the values of the row after the change, none for a delete
+    pub fn version(&self) -> Option<Vec<(&str, &Value)>> {
+        if self.action == Action::Delete {
+            return None;
+        }
+        Some(
+            self.values
+                .iter()
+                .map(|(column, _, new)| (column.as_str(), new))
+                .collect(),
+        )
+    }
+}
+
+/// the row is found again by the literal values of its key
+pub fn row_key(columns: &[String], key: &[String], values: &[Value]) -> String {
+    let indexes = edit::key_indexes(columns, key);
+    let names: Vec<String> = indexes.iter().map(|i| columns[*i].clone()).collect();
+    let values: Vec<Value> = indexes
+        .iter()
+        .map(|i| values.get(*i).cloned().unwrap_or(None))
+        .collect();
+    edit::literal_condition(Platform::Sqlite, &names, &values)
+}
+
+/// What the changes do, to be confirmed before they are saved:
+/// the count of each action, then a line for each row.
+pub fn summary(changes: &[Change]) -> String {
+    let count = |action: Action| changes.iter().filter(|c| c.action == action).count();
+    let mut lines = vec![format!(
+        "{} insert(s), {} update(s), {} delete(s)",
+        count(Action::Insert),
+        count(Action::Update),
+        count(Action::Delete)
+    )];
+    for change in changes.iter() {
+        lines.push(match change.action {
+            Action::Insert => format!("insert into {}: {}", change.table, change.key),
+            Action::Update => format!(
+                "update {} where {}: {}",
+                change.table,
+                change.previous_key.as_ref().unwrap_or(&change.key),
+                change.changed_columns().join(", ")
+            ),
+            Action::Delete => format!("delete from {} where {}", change.table, change.key),
+        });
+    }
+    lines.join("\n")
+}
+
+/// the login of the user running the application
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+/// whether the saved changes are to be recorded
+pub fn enabled() -> bool {
+    Settings::load().audit
+}
+
+/// record the changes of a save when the audit log is enabled
+pub fn record(changes: &[Change]) -> rusqlite::Result<()> {
+    if !enabled() || changes.is_empty() {
+        return Ok(());
+    }
+    AuditLog::open()?.record(changes)
+}
+
+pub struct AuditLog {
+    conn: Connection,
+}
+
+impl AuditLog {
+    /// the log of the config directory, created when it does not exist yet
+    pub fn open() -> rusqlite::Result<Self> {
+        let dir = config::config_dir();
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            println!("unable to create {}: {}", dir.display(), e);
+        }
+        Self::open_path(&dir.join("audit.sqlite"))
+    }
+
+    pub fn open_path(path: &Path) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(AuditLog { conn: conn })
+    }
+
+    /// record the changes of a save all at once
+    pub fn record(&mut self, changes: &[Change]) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        for change in changes.iter() {
+            tx.execute(
+                "INSERT INTO changes \
+                 (changed_at, user_name, table_name, row_key, action, database_name, previous_key) \
+                 VALUES (strftime('%Y-%m-%d %H:%M:%S', 'now', 'localtime'), ?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    change.user,
+                    change.table,
+                    change.key,
+                    change.action.as_str(),
+                    change.database,
+                    change.previous_key
+                ],
+            )?;
+            let id = tx.last_insert_rowid();
+            for (position, (column, old, new)) in change.values.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO change_values (change_id, position, column_name, old_value, new_value) \
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![id, position as i64, column, old, new],
+                )?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// The changes of a row of the database, the newest first.
+    /// The changes made before an update of its key are found by the key it had.
+    pub fn history(&self, database: &str, table: &str, key: &str) -> rusqlite::Result<Vec<Change>> {
+        let mut changes = vec![];
+        let mut next = Some((key.to_string(), i64::MAX));
+        while let Some((key, before)) = next.take() {
+            for change in self.changes_of(database, table, &key, before)? {
+                let previous = change.previous_key.clone().map(|p| (p, change.id));
+                changes.push(change);
+                // the older changes of this key were of another row
+                if previous.is_some() {
+                    next = previous;
+                    break;
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// the changes recorded with this key before the change `before`, the newest first
+    fn changes_of(
+        &self,
+        database: &str,
+        table: &str,
+        key: &str,
+        before: i64,
+    ) -> rusqlite::Result<Vec<Change>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, changed_at, user_name, action, previous_key FROM changes \
+             WHERE database_name = ?1 AND table_name = ?2 AND row_key = ?3 AND id < ?4 \
+             ORDER BY id DESC",
+        )?;
+        let rows = stmt.query_map(params![database, table, key, before], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })?;
+        let mut changes = vec![];
+        for row in rows {
+            let (id, changed_at, user, action, previous_key) = row?;
+            let action = match Action::parse(&action) {
+                Some(action) => action,
+                None => continue,
+            };
+            changes.push(Change {
+                id: id,
+                changed_at: changed_at,
+                user: user,
+                database: database.to_string(),
+                table: table.to_string(),
+                key: key.to_string(),
+                previous_key: previous_key,
+                action: action,
+                values: self.values(id)?,
+            });
+        }
+        Ok(changes)
+    }
+
+    fn values(&self, id: i64) -> rusqlite::Result<Vec<(String, Value, Value)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT column_name, old_value, new_value FROM change_values \
+             WHERE change_id = ?1 ORDER BY position",
+        )?;
+        let rows = stmt.query_map(params![id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?;
+        rows.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[Option<&str>]) -> Vec<Value> {
+        values.iter().map(|v| v.map(String::from)).collect()
+    }
+
+    #[test]
+    fn history_of_a_row() {
+        let mut log = AuditLog::open_path(Path::new(":memory:")).unwrap();
+        let columns = vec!["id".to_string(), "name".to_string(), "note".to_string()];
+        let key = vec!["id".to_string()];
+        let inserted = values(&[Some("1"), Some("Ann"), None]);
+        let updated = values(&[Some("1"), Some("Anne"), None]);
+        let other = values(&[Some("2"), Some("Bob"), None]);
+        let shop = "postgres://db/shop";
+        log.record(&[
+            Change::new(shop, "people", &columns, &key, None, Some(&inserted)),
+            Change::new(shop, "people", &columns, &key, None, Some(&other)),
+            // the same row of another database
+            Change::new(
+                "postgres://staging/shop",
+                "people",
+                &columns,
+                &key,
+                None,
+                Some(&inserted),
+            ),
+        ])
+        .unwrap();
+        log.record(&[Change::new(
+            shop,
+            "people",
+            &columns,
+            &key,
+            Some(&inserted),
+            Some(&updated),
+        )])
+        .unwrap();
+        let row = row_key(&columns, &key, &updated);
+        assert_eq!(row, "\"id\" = '1'");
+        let history = log.history(shop, "people", &row).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].action, Action::Update);
+        assert_eq!(history[0].changed_columns(), vec!["name"]);
+        assert_eq!(
+            history[0].values[1],
+            (
+                "name".to_string(),
+                Some("Ann".to_string()),
+                Some("Anne".to_string())
+            )
+        );
+        assert_eq!(history[0].values[2], ("note".to_string(), None, None));
+        assert_eq!(history[1].action, Action::Insert);
+        let version = history[1].version().unwrap();
+        assert_eq!(version[1], ("name", &Some("Ann".to_string())));
+
+        // the changes before an update of the key are still in the history of the row
+        let renumbered = values(&[Some("3"), Some("Anne"), None]);
+        let change = Change::new(
+            shop,
+            "people",
+            &columns,
+            &key,
+            Some(&updated),
+            Some(&renumbered),
+        );
+        assert_eq!(change.previous_key.as_deref(), Some("\"id\" = '1'"));
+        log.record(&[change]).unwrap();
+        let history = log
+            .history(shop, "people", &row_key(&columns, &key, &renumbered))
+            .unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[2].action, Action::Insert);
+
+        let deleted = Change::new(shop, "people", &columns, &key, Some(&other), None);
+        assert_eq!(deleted.action, Action::Delete);
+        assert_eq!(deleted.key, "\"id\" = '2'");
+        assert!(deleted.version().is_none());
+        assert_eq!(
+            summary(&[history[0].clone(), deleted]),
+            "0 insert(s), 1 update(s), 1 delete(s)\n\
+             update people where \"id\" = '1': id\n\
+             delete from people where \"id\" = '2'"
+        );
+        // without a primary key the row is found by all its values
+        assert_eq!(
+            row_key(&columns, &[], &other),
+            "\"id\" = '2' AND \"name\" = 'Bob' AND \"note\" IS NULL"
+        );
+    }
+}
@@ -0,0 +1,863 @@
+//! queries on the system catalog of each platform, to find out how a table is defined
+use crate::db::{Database, DbError, Platform, ResultSet, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    /// the declared length of char and varchar columns
+    pub max_length: Option<usize>,
+    /// precision and scale of numeric and decimal columns
+    pub precision: Option<(u32, u32)>,
+    pub default: Option<String>,
+}
+
+impl ColumnInfo {
+    /// the type with its length or precision, ie: `varchar(40)`
+    pub fn type_name(&self) -> String {
+        if self.data_type.contains('(') {
+            return self.data_type.clone();
+        }
+        match (self.max_length, self.precision) {
+            (Some(length), _) => format!("{}({})", self.data_type, length),
+            (_, Some((precision, scale))) => {
+                format!("{}({},{})", self.data_type, precision, scale)
+            }
+            _ => self.data_type.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ObjectKind {
+    Table,
+    View,
+    MaterializedView,
+    Function,
+}
+
+impl ObjectKind {
+    pub fn plural(&self) -> &'static str {
+        match self {
+            ObjectKind::Table => "Tables",
+            ObjectKind::View => "Views",
+            ObjectKind::MaterializedView => "Materialized views",
+            ObjectKind::Function => "Functions",
+        }
+    }
+
+    /// whether its rows can be shown in a data tab
+    pub fn has_rows(&self) -> bool {
+        *self != ObjectKind::Function
+    }
+}
+
+/// a table, view or function of the database
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbObject {
+    pub schema: Option<String>,
+    pub name: String,
+    pub kind: ObjectKind,
+    /// the number of rows according to the statistics of the database, not counted
+    pub row_estimate: Option<u64>,
+}
+
+impl DbObject {
+    /// the name as used for opening it, `schema.name` when it is in a schema
+    pub fn qualified_name(&self) -> String {
+        match &self.schema {
+            Some(schema) => format!("{}.{}", schema, self.name),
+            None => self.name.to_string(),
+        }
+    }
+}
+
+/// whether the values of this type are numbers, ie: `integer`, `numeric(10,2)`, `money`
+pub fn is_numeric(data_type: &str) -> bool {
+    let data_type = data_type.to_lowercase();
+    // without the modifiers
+    match data_type.split('(').next().unwrap_or("").trim() {
+        "smallint" | "int" | "int2" | "int4" | "int8" | "integer" | "bigint" | "numeric"
+        | "decimal" | "real" | "float" | "float4" | "float8" | "double" | "double precision"
+        | "money" => true,
+        _ => false,
+    }
+}
+
+/// the values of the first column
+fn first_column(rs: ResultSet) -> Vec<String> {
+    rs.rows
+        .into_iter()
+        .filter_map(|mut r| r.remove(0))
+        .collect()
+}
+
+/// the tables and views the user can open, schema qualified when the platform has schemas
+pub fn list_tables(db: &mut dyn Database) -> Result<Vec<String>, DbError> {
+    let sql = match db.platform() {
+        Platform::Postgres => {
+            "SELECT table_schema || '.' || table_name FROM information_schema.tables \
+                WHERE table_schema NOT IN ('pg_catalog', 'information_schema') \
+                ORDER BY table_schema, table_name"
+        }
+        Platform::Sqlite => {
+            "SELECT name FROM sqlite_master WHERE type IN ('table', 'view') \
+                AND name NOT LIKE 'sqlite_%' ORDER BY name"
+        }
+        Platform::Mysql => return Err(DbError::Unsupported("mysql".to_string())),
+    };
+    Ok(first_column(db.execute(sql, &[])?.rows()))
+}
+
+/// the tables, views and functions of all the schemas
+pub fn list_objects(db: &mut dyn Database) -> Result<Vec<DbObject>, DbError> {
+    match db.platform() {
+        Platform::Postgres => {
+            let sql = "SELECT n.nspname::text, c.relname::text, c.relkind::text, \
+                c.reltuples::bigint::text \
+                FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+                WHERE c.relkind IN ('r', 'p', 'f', 'v', 'm') \
+                AND n.nspname NOT IN ('pg_catalog', 'information_schema') \
+                AND n.nspname NOT LIKE 'pg\\_%' \
+                AND has_table_privilege(c.oid, 'SELECT') \
+                UNION ALL \
+                SELECT DISTINCT n.nspname::text, p.proname::text, 'function', NULL \
+                FROM pg_proc p JOIN pg_namespace n ON n.oid = p.pronamespace \
+                WHERE n.nspname NOT IN ('pg_catalog', 'information_schema') \
+                AND n.nspname NOT LIKE 'pg\\_%' \
+                ORDER BY 1, 2";
+            let rs = db.execute(sql, &[])?.rows();
+            Ok(rs
+                .rows
+                .into_iter()
+                .map(|r| DbObject {
+                    schema: r[0].clone(),
+                    name: r[1].clone().unwrap_or_default(),
+                    kind: match r[2].as_deref() {
+                        Some("v") => ObjectKind::View,
+                        Some("m") => ObjectKind::MaterializedView,
+                        Some("function") => ObjectKind::Function,
+                        _ => ObjectKind::Table,
+                    },
+                    // never analyzed tables have an estimate of -1 or 0
+                    row_estimate: r[3]
+                        .as_ref()
+                        .and_then(|n| n.parse::<i64>().ok())
+                        .filter(|n| *n > 0)
+                        .map(|n| n as u64),
+                })
+                .collect())
+        }
+        Platform::Sqlite => {
+            let sql = "SELECT name, type FROM sqlite_master WHERE type IN ('table', 'view') \
+                AND name NOT LIKE 'sqlite_%' ORDER BY name";
+            let rs = db.execute(sql, &[])?.rows();
+            // only there when the database was analyzed
+            let stats = "SELECT tbl, MAX(CAST(stat AS INTEGER)) FROM sqlite_stat1 GROUP BY tbl";
+            let estimates = db.execute(stats, &[]).map(|r| r.rows()).unwrap_or_default();
+            Ok(rs
+                .rows
+                .into_iter()
+                .map(|r| {
+                    let name = r[0].clone().unwrap_or_default();
+                    let row_estimate = estimates
+                        .rows
+                        .iter()
+                        .find(|e| e[0].as_ref() == Some(&name))
+                        .and_then(|e| e[1].as_ref()?.parse().ok());
+                    DbObject {
+                        schema: None,
+                        kind: match r[1].as_deref() {
+                            Some("view") => ObjectKind::View,
+                            _ => ObjectKind::Table,
+                        },
+                        name: name,
+                        row_estimate: row_estimate,
+                    }
+                })
+                .collect())
+        }
+        Platform::Mysql => Err(DbError::Unsupported("mysql".to_string())),
+    }
+}
+
+/// The primary key columns of this table, empty if it has none.
+pub fn primary_key(db: &mut dyn Database, table: &str) -> Result<Vec<String>, DbError> {
+    let (schema, name) = split_table_name(table);
+    match db.platform() {
+        Platform::Postgres => {
+            let sql = "SELECT kcu.column_name::text FROM information_schema.table_constraints tc \
+                JOIN information_schema.key_column_usage kcu \
+                ON kcu.constraint_name = tc.constraint_name AND kcu.table_schema = tc.table_schema \
+                WHERE tc.constraint_type = 'PRIMARY KEY' AND tc.table_name = $1 \
+                AND tc.table_schema = COALESCE($2, current_schema()) \
+                ORDER BY kcu.ordinal_position";
+            Ok(first_column(db.execute(sql, &[Some(name), schema])?.rows()))
+        }
+        Platform::Sqlite => {
+            let rs = sqlite_pragma(db, "table_info", &name)?;
+            let (name_idx, pk_idx) = match (rs.column_index("name"), rs.column_index("pk")) {
+                (Some(n), Some(p)) => (n, p),
+                _ => return Ok(vec![]),
+            };
+            let mut keys: Vec<(u32, String)> = rs
+                .rows
+                .iter()
+                .filter_map(|r| {
+                    let pk: u32 = r[pk_idx].as_ref()?.parse().ok()?;
+                    if pk > 0 {
+                        Some((pk, r[name_idx].clone()?))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            keys.sort();
+            Ok(keys.into_iter().map(|(_, name)| name).collect())
+        }
+        Platform::Mysql => Err(DbError::Unsupported("mysql".to_string())),
+    }
+}
+
+/// the columns of this table in their declared order
+pub fn columns(db: &mut dyn Database, table: &str) -> Result<Vec<ColumnInfo>, DbError> {
+    let (schema, name) = split_table_name(table);
+    match db.platform() {
+        Platform::Postgres => {
+            let sql = "SELECT column_name, data_type, is_nullable, character_maximum_length, \
+                numeric_precision, numeric_scale, column_default \
+                FROM information_schema.columns \
+                WHERE table_name = $1 AND table_schema = COALESCE($2, current_schema()) \
+                ORDER BY ordinal_position";
+            let rs = db.execute(sql, &[Some(name), schema])?.rows();
+            Ok(rs
+                .rows
+                .into_iter()
+                .map(|r| {
+                    let number = |i: usize| r[i].as_ref().and_then(|v| v.parse::<u32>().ok());
+                    let is_numeric = matches!(r[1].as_deref(), Some("numeric") | Some("decimal"));
+                    ColumnInfo {
+                        name: r[0].clone().unwrap_or_default(),
+                        data_type: r[1].clone().unwrap_or_default(),
+                        nullable: r[2].as_deref() == Some("YES"),
+                        max_length: number(3).map(|n| n as usize),
+                        precision: match (is_numeric, number(4), number(5)) {
+                            (true, Some(p), s) => Some((p, s.unwrap_or(0))),
+                            _ => None,
+                        },
+                        default: r[6].clone(),
+                    }
+                })
+                .collect())
+        }
+        Platform::Sqlite => {
+            let rs = sqlite_pragma(db, "table_info", &name)?;
+            let index = |column: &str| rs.column_index(column);
+            let (name_idx, type_idx, notnull_idx, default_idx, pk_idx) = match (
+                index("name"),
+                index("type"),
+                index("notnull"),
+                index("dflt_value"),
+                index("pk"),
+            ) {
+                (Some(n), Some(t), Some(nn), Some(d), Some(pk)) => (n, t, nn, d, pk),
+                _ => return Ok(vec![]),
+            };
+            Ok(rs
+                .rows
+                .iter()
+                .map(|r| {
+                    let data_type = r[type_idx].clone().unwrap_or_default();
+                    let (max_length, precision) = parse_type_modifiers(&data_type);
+                    let is_integer_key = r[pk_idx].as_deref() == Some("1")
+                        && data_type.eq_ignore_ascii_case("integer");
+                    ColumnInfo {
+                        name: r[name_idx].clone().unwrap_or_default(),
+                        nullable: r[notnull_idx].as_deref() == Some("0"),
+                        max_length: max_length,
+                        precision: precision,
+                        // an `INTEGER PRIMARY KEY` is assigned the rowid when left out
+                        default: r[default_idx]
+                            .clone()
+                            .or_else(|| Some("rowid".to_string()).filter(|_| is_integer_key)),
+                        data_type: data_type,
+                    }
+                })
+                .collect())
+        }
+        Platform::Mysql => Err(DbError::Unsupported("mysql".to_string())),
+    }
+}
+
+/// the expressions of the check constraints of this table
+pub fn check_constraints(db: &mut dyn Database, table: &str) -> Result<Vec<String>, DbError> {
+    match db.platform() {
+        Platform::Postgres => {
+            let sql = "SELECT pg_get_constraintdef(oid) FROM pg_constraint \
+                WHERE contype = 'c' AND conrelid = $1::regclass";
+            let quoted = Platform::Postgres.quote_table(table);
+            Ok(first_column(db.execute(sql, &[Some(quoted)])?.rows()))
+        }
+        Platform::Sqlite => {
+            let (_, name) = split_table_name(table);
+            let sql = "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?1";
+            let create = first_column(db.execute(sql, &[Some(name)])?.rows());
+            Ok(create
+                .first()
+                .map(|create| extract_checks(create))
+                .unwrap_or_default())
+        }
+        Platform::Mysql => Err(DbError::Unsupported("mysql".to_string())),
+    }
+}
+
+/// what a sqlite column declares beside its type, nullability and default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnClauses {
+    pub name: String,
+    /// ie: `NOCASE`
+    pub collation: Option<String>,
+    /// of an `INTEGER PRIMARY KEY AUTOINCREMENT`
+    pub autoincrement: bool,
+}
+
+/// The collation and autoincrement of the columns of this table, which only sqlite
+/// has to make again when it rebuilds a table. Postgres alters its columns in place.
+pub fn column_clauses(db: &mut dyn Database, table: &str) -> Result<Vec<ColumnClauses>, DbError> {
+    match db.platform() {
+        Platform::Sqlite => {
+            let (_, name) = split_table_name(table);
+            let sql = "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?1";
+            let create = first_column(db.execute(sql, &[Some(name)])?.rows());
+            Ok(create
+                .first()
+                .map(|create| extract_column_clauses(create))
+                .unwrap_or_default())
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+/// the columns of each unique constraint, including the primary key
+pub fn unique_keys(db: &mut dyn Database, table: &str) -> Result<Vec<Vec<String>>, DbError> {
+    match db.platform() {
+        Platform::Postgres => {
+            let sql = "SELECT string_agg(a.attname, ',' ORDER BY k.n) FROM pg_constraint c \
+                CROSS JOIN LATERAL unnest(c.conkey) WITH ORDINALITY AS k(attnum, n) \
+                JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = k.attnum \
+                WHERE c.contype IN ('p', 'u') AND c.conrelid = $1::regclass \
+                GROUP BY c.oid";
+            let quoted = Platform::Postgres.quote_table(table);
+            let keys = first_column(db.execute(sql, &[Some(quoted)])?.rows());
+            Ok(keys
+                .iter()
+                .map(|k| k.split(',').map(String::from).collect())
+                .collect())
+        }
+        Platform::Sqlite => {
+            let (_, name) = split_table_name(table);
+            let mut keys = vec![];
+            let primary = primary_key(db, table)?;
+            if !primary.is_empty() {
+                keys.push(primary);
+            }
+            let indexes = sqlite_pragma(db, "index_list", &name)?;
+            let (name_idx, unique_idx) =
+                match (indexes.column_index("name"), indexes.column_index("unique")) {
+                    (Some(n), Some(u)) => (n, u),
+                    _ => return Ok(keys),
+                };
+            for index in indexes.rows.iter() {
+                if index[unique_idx].as_deref() != Some("1") {
+                    continue;
+                }
+                if let Some(index_name) = &index[name_idx] {
+                    let info = sqlite_pragma(db, "index_info", index_name)?;
+                    if let Some(column_idx) = info.column_index("name") {
+                        let key: Vec<String> = info
+                            .rows
+                            .into_iter()
+                            .filter_map(|mut r| r.remove(column_idx))
+                            .collect();
+                        if !keys.contains(&key) {
+                            keys.push(key);
+                        }
+                    }
+                }
+            }
+            Ok(keys)
+        }
+        Platform::Mysql => Err(DbError::Unsupported("mysql".to_string())),
+    }
+}
+
+/// a foreign key of a table, with the columns it references
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    /// schema qualified when the database has schemas
+    pub ref_table: String,
+    pub ref_columns: Vec<String>,
+}
+
+/// the foreign keys of this table
+pub fn foreign_keys(db: &mut dyn Database, table: &str) -> Result<Vec<ForeignKey>, DbError> {
+    match db.platform() {
+        Platform::Postgres => {
+            let sql = "SELECT string_agg(a.attname, ',' ORDER BY k.n), \
+                rn.nspname || '.' || r.relname, string_agg(fa.attname, ',' ORDER BY k.n) \
+                FROM pg_constraint c \
+                CROSS JOIN LATERAL unnest(c.conkey, c.confkey) WITH ORDINALITY AS k(attnum, fattnum, n) \
+                JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = k.attnum \
+                JOIN pg_attribute fa ON fa.attrelid = c.confrelid AND fa.attnum = k.fattnum \
+                JOIN pg_class r ON r.oid = c.confrelid \
+                JOIN pg_namespace rn ON rn.oid = r.relnamespace \
+                WHERE c.contype = 'f' AND c.conrelid = $1::regclass \
+                GROUP BY c.oid, rn.nspname, r.relname";
+            let quoted = Platform::Postgres.quote_table(table);
+            let rs = db.execute(sql, &[Some(quoted)])?.rows();
+            let split = |v: &Value| -> Vec<String> {
+                v.as_deref()
+                    .unwrap_or("")
+                    .split(',')
+                    .map(String::from)
+                    .collect()
+            };
+            Ok(rs
+                .rows
+                .iter()
+                .map(|r| ForeignKey {
+                    columns: split(&r[0]),
+                    ref_table: r[1].clone().unwrap_or_default(),
+                    ref_columns: split(&r[2]),
+                })
+                .collect())
+        }
+        Platform::Sqlite => {
+            let (_, name) = split_table_name(table);
+            let rs = sqlite_pragma(db, "foreign_key_list", &name)?;
+            let index = |column: &str| rs.column_index(column);
+            let (id_idx, table_idx, from_idx, to_idx) =
+                match (index("id"), index("table"), index("from"), index("to")) {
+                    (Some(i), Some(t), Some(f), Some(to)) => (i, t, f, to),
+                    _ => return Ok(vec![]),
+                };
+            let mut keys: Vec<(String, ForeignKey)> = vec![];
+            for r in rs.rows.iter() {
+                let id = r[id_idx].clone().unwrap_or_default();
+                let ref_table = r[table_idx].clone().unwrap_or_default();
+                let position = match keys.iter().position(|(key_id, _)| *key_id == id) {
+                    Some(position) => position,
+                    None => {
+                        keys.push((
+                            id,
+                            ForeignKey {
+                                columns: vec![],
+                                ref_table: ref_table,
+                                ref_columns: vec![],
+                            },
+                        ));
+                        keys.len() - 1
+                    }
+                };
+                let key = &mut keys[position].1;
+                key.columns.push(r[from_idx].clone().unwrap_or_default());
+                // no column means the primary key of the referenced table
+                if let Some(to) = r[to_idx].clone() {
+                    key.ref_columns.push(to);
+                }
+            }
+            let mut foreign_keys = vec![];
+            for (_, mut key) in keys {
+                if key.ref_columns.len() != key.columns.len() {
+                    key.ref_columns = primary_key(db, &key.ref_table)?;
+                }
+                foreign_keys.push(key);
+            }
+            Ok(foreign_keys)
+        }
+        Platform::Mysql => Err(DbError::Unsupported("mysql".to_string())),
+    }
+}
+
+/// the foreign keys of the other tables referencing this one, with the referencing table
+pub fn referencing_keys(
+    db: &mut dyn Database,
+    table: &str,
+) -> Result<Vec<(String, ForeignKey)>, DbError> {
+    match db.platform() {
+        Platform::Postgres => {
+            let sql = "SELECT n.nspname || '.' || t.relname, \
+                string_agg(a.attname, ',' ORDER BY k.n), string_agg(fa.attname, ',' ORDER BY k.n) \
+                FROM pg_constraint c \
+                CROSS JOIN LATERAL unnest(c.conkey, c.confkey) WITH ORDINALITY AS k(attnum, fattnum, n) \
+                JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = k.attnum \
+                JOIN pg_attribute fa ON fa.attrelid = c.confrelid AND fa.attnum = k.fattnum \
+                JOIN pg_class t ON t.oid = c.conrelid \
+                JOIN pg_namespace n ON n.oid = t.relnamespace \
+                WHERE c.contype = 'f' AND c.confrelid = $1::regclass \
+                GROUP BY c.oid, n.nspname, t.relname ORDER BY 1";
+            let quoted = Platform::Postgres.quote_table(table);
+            let rs = db.execute(sql, &[Some(quoted)])?.rows();
+            let split = |v: &Value| -> Vec<String> {
+                v.as_deref()
+                    .unwrap_or("")
+                    .split(',')
+                    .map(String::from)
+                    .collect()
+            };
+            Ok(rs
+                .rows
+                .iter()
+                .map(|r| {
+                    let key = ForeignKey {
+                        columns: split(&r[1]),
+                        ref_table: table.to_string(),
+                        ref_columns: split(&r[2]),
+                    };
+                    (r[0].clone().unwrap_or_default(), key)
+                })
+                .collect())
+        }
+        Platform::Sqlite => {
+            // sqlite has no catalog of the references, the foreign keys of every table are read
+            let (_, name) = split_table_name(table);
+            let mut references = vec![];
+            for other in list_tables(db)? {
+                for key in foreign_keys(db, &other)? {
+                    if key.ref_table.eq_ignore_ascii_case(&name) {
+                        references.push((other.clone(), key));
+                    }
+                }
+            }
+            Ok(references)
+        }
+        Platform::Mysql => Err(DbError::Unsupported("mysql".to_string())),
+    }
+}
+
+/// an index of a table, including the ones of its primary key and unique constraints
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexInfo {
+    pub name: String,
+    /// empty for the parts of the index that are expressions
+    pub columns: Vec<String>,
+    pub unique: bool,
+    /// the create index statement, none for the indexes sqlite makes for the constraints
+    pub definition: Option<String>,
+}
+
+/// the indexes of this table, by name
+pub fn indexes(db: &mut dyn Database, table: &str) -> Result<Vec<IndexInfo>, DbError> {
+    match db.platform() {
+        Platform::Postgres => {
+            let sql = "SELECT i.relname::text, string_agg(a.attname, ',' ORDER BY k.n), \
+                ix.indisunique::text, pg_get_indexdef(ix.indexrelid) \
+                FROM pg_index ix JOIN pg_class i ON i.oid = ix.indexrelid \
+                CROSS JOIN LATERAL unnest(ix.indkey::int2[]) WITH ORDINALITY AS k(attnum, n) \
+                LEFT JOIN pg_attribute a ON a.attrelid = ix.indrelid AND a.attnum = k.attnum \
+                WHERE ix.indrelid = $1::regclass \
+                GROUP BY i.relname, ix.indisunique, ix.indexrelid ORDER BY 1";
+            let quoted = Platform::Postgres.quote_table(table);
+            let rs = db.execute(sql, &[Some(quoted)])?.rows();
+            Ok(rs
+                .rows
+                .into_iter()
+                .map(|r| IndexInfo {
+                    name: r[0].clone().unwrap_or_default(),
+                    columns: r[1]
+                        .as_deref()
+                        .map(|c| c.split(',').map(String::from).collect())
+                        .unwrap_or_default(),
+                    unique: r[2].as_deref() == Some("true"),
+                    definition: r[3].clone(),
+                })
+                .collect())
+        }
+        Platform::Sqlite => {
+            let (_, name) = split_table_name(table);
+            let list = sqlite_pragma(db, "index_list", &name)?;
+            let (name_idx, unique_idx) =
+                match (list.column_index("name"), list.column_index("unique")) {
+                    (Some(n), Some(u)) => (n, u),
+                    _ => return Ok(vec![]),
+                };
+            let mut indexes = vec![];
+            for index in list.rows.iter() {
+                let index_name = match &index[name_idx] {
+                    Some(index_name) => index_name.clone(),
+                    None => continue,
+                };
+                let info = sqlite_pragma(db, "index_info", &index_name)?;
+                let columns = match info.column_index("name") {
+                    Some(column_idx) => info
+                        .rows
+                        .into_iter()
+                        .filter_map(|mut r| r.remove(column_idx))
+                        .collect(),
+                    None => vec![],
+                };
+                let sql = "SELECT sql FROM sqlite_master WHERE type = 'index' AND name = ?1";
+                let definition = first_column(db.execute(sql, &[Some(index_name.clone())])?.rows());
+                indexes.push(IndexInfo {
+                    name: index_name,
+                    columns: columns,
+                    unique: index[unique_idx].as_deref() == Some("1"),
+                    definition: definition.into_iter().next(),
+                });
+            }
+            indexes.sort_by(|a, b| a.name.cmp(&b.name));
+            Ok(indexes)
+        }
+        Platform::Mysql => Err(DbError::Unsupported("mysql".to_string())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerInfo {
+    pub name: String,
+    /// the create trigger statement
+    pub definition: String,
+}
+
+/// the triggers on this table, without the internal ones of the constraints
+pub fn triggers(db: &mut dyn Database, table: &str) -> Result<Vec<TriggerInfo>, DbError> {
+    let rs = match db.platform() {
+        Platform::Postgres => {
+            let sql = "SELECT tgname::text, pg_get_triggerdef(oid) FROM pg_trigger \
+                WHERE tgrelid = $1::regclass AND NOT tgisinternal ORDER BY 1";
+            let quoted = Platform::Postgres.quote_table(table);
+            db.execute(sql, &[Some(quoted)])?.rows()
+        }
+        Platform::Sqlite => {
+            let (_, name) = split_table_name(table);
+            let sql = "SELECT name, sql FROM sqlite_master \
+                WHERE type = 'trigger' AND tbl_name = ?1 ORDER BY name";
+            db.execute(sql, &[Some(name)])?.rows()
+        }
+        Platform::Mysql => return Err(DbError::Unsupported("mysql".to_string())),
+    };
+    Ok(rs
+        .rows
+        .into_iter()
+        .map(|r| TriggerInfo {
+            name: r[0].clone().unwrap_or_default(),
+            definition: r[1].clone().unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// The statements creating this table with its constraints, indexes and triggers.
+/// Postgres has no such statement in its catalog, it is put together from the definitions.
+pub fn table_ddl(db: &mut dyn Database, table: &str) -> Result<String, DbError> {
+    match db.platform() {
+        Platform::Postgres => {
+            let quoted = Platform::Postgres.quote_table(table);
+            let sql = "SELECT relkind::text FROM pg_class WHERE oid = $1::regclass";
+            let kind = first_column(db.execute(sql, &[Some(quoted.clone())])?.rows());
+            match kind.first().map(|k| k.as_str()) {
+                Some("v") | Some("m") => {
+                    let sql = "SELECT pg_get_viewdef($1::regclass, true)";
+                    let select = first_column(db.execute(sql, &[Some(quoted.clone())])?.rows());
+                    let create = match kind[0].as_str() {
+                        "m" => "CREATE MATERIALIZED VIEW",
+                        _ => "CREATE VIEW",
+                    };
+                    return Ok(format!(
+                        "{} {} AS\n{}",
+                        create,
+                        quoted,
+                        select.first().map(|s| s.as_str()).unwrap_or("")
+                    ));
+                }
+                _ => (),
+            }
+            let sql = "SELECT a.attname::text, format_type(a.atttypid, a.atttypmod), \
+                a.attnotnull::text, pg_get_expr(d.adbin, d.adrelid) \
+                FROM pg_attribute a \
+                LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+                WHERE a.attrelid = $1::regclass AND a.attnum > 0 AND NOT a.attisdropped \
+                ORDER BY a.attnum";
+            let rs = db.execute(sql, &[Some(quoted.clone())])?.rows();
+            let mut lines: Vec<String> = rs
+                .rows
+                .iter()
+                .map(|r| {
+                    let mut line = format!(
+                        "    {} {}",
+                        Platform::Postgres.quote_ident(r[0].as_deref().unwrap_or("")),
+                        r[1].as_deref().unwrap_or("")
+                    );
+                    if r[2].as_deref() == Some("true") {
+                        line.push_str(" NOT NULL");
+                    }
+                    if let Some(default) = &r[3] {
+                        line.push_str(&format!(" DEFAULT {}", default));
+                    }
+                    line
+                })
+                .collect();
+            let sql = "SELECT conname::text, pg_get_constraintdef(oid) FROM pg_constraint \
+                WHERE conrelid = $1::regclass ORDER BY contype = 'p' DESC, conname";
+            let constraints = db.execute(sql, &[Some(quoted.clone())])?.rows();
+            for r in constraints.rows.iter() {
+                lines.push(format!(
+                    "    CONSTRAINT {} {}",
+                    Platform::Postgres.quote_ident(r[0].as_deref().unwrap_or("")),
+                    r[1].as_deref().unwrap_or("")
+                ));
+            }
+            let mut statements = vec![format!(
+                "CREATE TABLE {} (\n{}\n)",
+                quoted,
+                lines.join(",\n")
+            )];
+            // the indexes of the constraints are created with them
+            let sql = "SELECT pg_get_indexdef(ix.indexrelid) FROM pg_index ix \
+                WHERE ix.indrelid = $1::regclass AND NOT EXISTS \
+                (SELECT 1 FROM pg_constraint c WHERE c.conindid = ix.indexrelid) \
+                ORDER BY 1";
+            statements.extend(first_column(db.execute(sql, &[Some(quoted)])?.rows()));
+            statements.extend(triggers(db, table)?.into_iter().map(|t| t.definition));
+            Ok(format!("{};\n", statements.join(";\n\n")))
+        }
+        Platform::Sqlite => {
+            let (_, name) = split_table_name(table);
+            let sql = "SELECT sql FROM sqlite_master WHERE tbl_name = ?1 AND sql IS NOT NULL \
+                ORDER BY CASE type WHEN 'index' THEN 1 WHEN 'trigger' THEN 2 ELSE 0 END, name";
+            let statements = first_column(db.execute(sql, &[Some(name)])?.rows());
+            Ok(format!("{};\n", statements.join(";\n\n")))
+        }
+        Platform::Mysql => Err(DbError::Unsupported("mysql".to_string())),
+    }
+}
+
+fn sqlite_pragma(db: &mut dyn Database, pragma: &str, arg: &str) -> Result<ResultSet, DbError> {
+    let sql = format!("PRAGMA {}({})", pragma, Platform::Sqlite.quote_ident(arg));
+    Ok(db.execute(&sql, &[])?.rows())
+}
+
+/// split `schema.table` into its unquoted parts
+pub fn split_table_name(table: &str) -> (Option<String>, String) {
+    let mut parts: Vec<String> = table.split('.').map(crate::sql::unquote).collect();
+    let name = parts.pop().unwrap_or_default();
+    (parts.pop(), name)
+}
+
+/// the length of `varchar(40)` or the precision and scale of `decimal(10,2)`
+fn parse_type_modifiers(data_type: &str) -> (Option<usize>, Option<(u32, u32)>) {
+    let open = data_type.find('(');
+    let close = data_type.rfind(')');
+    let (open, close) = match (open, close) {
+        (Some(open), Some(close)) if open < close => (open, close),
+        _ => return (None, None),
+    };
+    let base = data_type[..open].trim().to_lowercase();
+    let args: Vec<u32> = data_type[open + 1..close]
+        .split(',')
+        .filter_map(|a| a.trim().parse().ok())
+        .collect();
+    match (base.as_str(), args.as_slice()) {
+        ("numeric", [p]) | ("decimal", [p]) => (None, Some((*p, 0))),
+        ("numeric", [p, s]) | ("decimal", [p, s]) => (None, Some((*p, *s))),
+        (_, [n]) if base.contains("char") => (Some(*n as usize), None),
+        _ => (None, None),
+    }
+}
+
+/// the expressions of the `CHECK (..)` clauses in a create table statement
+fn extract_checks(create: &str) -> Vec<String> {
+    use crate::sql::{tokenize, TokenKind};
+    let tokens: Vec<_> = tokenize(create)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Whitespace && t.kind != TokenKind::Comment)
+        .collect();
+    let mut checks = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_check = tokens[i].kind == TokenKind::Keyword
+            && tokens[i].text.eq_ignore_ascii_case("CHECK")
+            && tokens.get(i + 1).map(|t| t.text.as_str()) == Some("(");
+        if !is_check {
+            i += 1;
+            continue;
+        }
+        // the text of the expression as written, from its parenthesis to the closing one
+        let start = tokens[i + 1].start;
+        let mut depth = 0;
+        for token in tokens[i + 1..].iter() {
+            i += 1;
+            match token.text.as_str() {
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                _ => (),
+            }
+            if depth == 0 {
+                break;
+            }
+        }
+        let expr: String = create
+            .chars()
+            .skip(start)
+            .take(tokens[i].end - start)
+            .collect();
+        checks.push(format!("CHECK {}", expr));
+        i += 1;
+    }
+    checks
+}
+
+/// the clauses of each column definition in a create table statement
+fn extract_column_clauses(create: &str) -> Vec<ColumnClauses> {
+    use crate::sql::{tokenize, unquote, TokenKind};
+    let tokens: Vec<_> = tokenize(create)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Whitespace && t.kind != TokenKind::Comment)
+        .collect();
+    // the definitions between the parenthesis of the table, split on their commas
+    let mut definitions = vec![];
+    let mut definition = vec![];
+    let mut depth = 0;
+    for token in tokens.iter() {
+        match token.text.as_str() {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            _ => (),
+        }
+        if (depth == 0 && token.text == ")") || (depth == 1 && token.text == ",") {
+            definitions.push(std::mem::take(&mut definition));
+            if depth == 0 {
+                break;
+            }
+        } else if depth > 1 || (depth == 1 && token.text != "(") {
+            definition.push(token);
+        }
+    }
+    let constraints = ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"];
+    definitions
+        .into_iter()
+        .filter_map(|definition| {
+            let first = definition.first()?;
+            let is_constraint = first.kind == TokenKind::Keyword
+                && constraints
+                    .iter()
+                    .any(|c| first.text.eq_ignore_ascii_case(c));
+            if is_constraint {
+                return None;
+            }
+            let collation = definition
+                .iter()
+                .position(|t| t.text.eq_ignore_ascii_case("COLLATE"))
+                .and_then(|i| definition.get(i + 1))
+                .map(|t| unquote(&t.text));
+            Some(ColumnClauses {
+                name: unquote(&first.text),
+                collation: collation,
+                autoincrement: definition
+                    .iter()
+                    .any(|t| t.text.eq_ignore_ascii_case("AUTOINCREMENT")),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,125 @@
+//! copying ranges of the list to the clipboard and pasting them back
+//!
+//! Spreadsheets copy cells as tab separated text, a field with a tab, a line break
+//! or a quote in it is quoted. They also read an html table when it is offered.
+use crate::catalog;
+use crate::db::Value;
+use crate::format;
+
+/// A value as a field of tab separated text, quoted when it contains a tab,
+/// a line break or a quote. Null is an empty field.
+fn tsv_field(value: &Value) -> String {
+    let value = match value {
+        Some(value) => value,
+        None => return String::new(),
+    };
+    if value.contains(|c| c == '\t' || c == '\n' || c == '\r' || c == '"') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// the rows as tab separated text, one line per row
+pub fn to_tsv(rows: &[Vec<Value>]) -> String {
+    rows.iter()
+        .map(|row| row.iter().map(tsv_field).collect::<Vec<_>>().join("\t"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "<br>")
+}
+
+/// the rows as an html table
+pub fn to_html(rows: &[Vec<Value>]) -> String {
+    let mut html = String::from("<meta charset=\"utf-8\"><table>");
+    for row in rows.iter() {
+        html.push_str("<tr>");
+        for value in row.iter() {
+            html.push_str("<td>");
+            html.push_str(&escape_html(value.as_deref().unwrap_or("")));
+            html.push_str("</td>");
+        }
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>");
+    html
+}
+
+/// The cells of tab separated text, by row.
+/// The line break at the end of the last row does not start another row.
+pub fn parse_tsv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut field_start = true;
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field_start => {
+                quoted = true;
+                field_start = false;
+            }
+            '\t' => {
+                row.push(std::mem::take(&mut field));
+                field_start = true;
+            }
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' | '\r' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                field_start = true;
+            }
+            _ => {
+                field.push(c);
+                field_start = false;
+            }
+        }
+    }
+    if !field_start || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// The pasted text as a value for a column of this type.
+/// Numbers lose their grouping and currency, booleans become `true` or `false`
+/// and dates become iso dates. Text that can't be converted is kept for the validation
+/// to report, an empty text is null.
+pub fn convert(data_type: &str, text: &str) -> Value {
+    if text.trim().is_empty() {
+        return None;
+    }
+    let data_type = data_type.to_lowercase();
+    // without the modifiers, ie: `numeric(10,2)`
+    let base = data_type.split('(').next().unwrap_or("").trim();
+    let converted = match base {
+        "bool" | "boolean" => format::parse_bool(text).map(|b| b.to_string()),
+        _ if catalog::is_numeric(base) => format::parse_number(text),
+        _ if base.starts_with("date") || base.starts_with("timestamp") => {
+            format::parse_display_date(text)
+        }
+        _ => return Some(text.to_string()),
+    };
+    Some(converted.unwrap_or_else(|| text.trim().to_string()))
+}
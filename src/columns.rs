@@ -0,0 +1,333 @@
+//! the column chooser, a dialog to pick the columns of the list, their order and width,
+//! the sort and the filter, which can be saved as a named view of the table
+use crate::config::{ViewColumn, ViewConfig};
+use glib::Type;
+use gtk;
+use gtk::prelude::*;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::{
+    Adjustment, Button, CellRendererText, CellRendererToggle, CheckButton, ComboBoxText, Dialog,
+    DialogFlags, Entry, Grid, Label, ListStore, ResponseType, ScrolledWindow, SpinButton, TreeView,
+    TreeViewColumn, Window,
+};
+
+// the columns of the store
+static VISIBLE: u32 = 0;
+static NAME: u32 = 1;
+/// the width in pixels as text, empty for the natural width
+static WIDTH: u32 = 2;
+
+/// the response of the delete button
+static DELETE: ResponseType = ResponseType::Other(1);
+
+pub enum ChooserResponse {
+    /// show this layout without saving it
+    Apply(ViewConfig),
+    /// save the layout under this name and show it
+    Save(String, ViewConfig),
+    /// delete the view of this name
+    Delete(String),
+}
+
+#[derive(Clone)]
+pub struct ColumnChooser {
+    dialog: Dialog,
+    store: ListStore,
+    list: TreeView,
+    sort: ComboBoxText,
+    descending: CheckButton,
+    filter: Entry,
+    frozen: SpinButton,
+    name: Entry,
+    view_name: Option<String>,
+    /// the grouping of the view, kept as is
+    group_by: Vec<String>,
+}
+
+impl ColumnChooser {
+    /// `columns` are all the columns of the table, `view` is the layout shown now
+    /// and `view_name` the name of the saved view it comes from
+    pub fn new(
+        parent: Option<&Window>,
+        columns: &[String],
+        view: &ViewConfig,
+        view_name: Option<&str>,
+    ) -> Self {
+        let mut buttons = vec![
+            ("Cancel", ResponseType::Cancel),
+            ("Apply", ResponseType::Apply),
+            ("Save view", ResponseType::Accept),
+        ];
+        if view_name.is_some() {
+            buttons.insert(0, ("Delete view", DELETE));
+        }
+        let dialog = Dialog::with_buttons(
+            Some("Choose columns"),
+            parent,
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            &buttons,
+        );
+        let store = ListStore::new(&[Type::Bool, Type::String, Type::String]);
+        let list = Self::create_list(&store);
+        let sort = ComboBoxText::new();
+        let descending = CheckButton::with_label("Descending");
+        let filter = Entry::new();
+        filter.set_placeholder_text(Some("condition of the where clause"));
+        let frozen = SpinButton::with_range(0.0, columns.len() as f64, 1.0);
+        let name = Entry::new();
+        name.set_placeholder_text(Some("ie: Shipping view"));
+
+        let chooser = ColumnChooser {
+            dialog: dialog,
+            store: store,
+            list: list,
+            sort: sort,
+            descending: descending,
+            filter: filter,
+            frozen: frozen,
+            name: name,
+            view_name: view_name.map(String::from),
+            group_by: view.group_by.clone(),
+        };
+        chooser.build_content();
+        chooser.fill(columns, view);
+        chooser
+    }
+
+    fn create_list(store: &ListStore) -> TreeView {
+        let list = TreeView::new();
+        list.set_model(Some(store));
+        list.set_reorderable(true);
+        list.set_search_column(NAME as i32);
+
+        let visible_column = TreeViewColumn::new();
+        let visible_cell = CellRendererToggle::new();
+        visible_column.pack_start(&visible_cell, false);
+        visible_column.add_attribute(&visible_cell, "active", VISIBLE as i32);
+        visible_column.set_title("Show");
+        list.append_column(&visible_column);
+        let toggled_store = store.clone();
+        visible_cell.connect_toggled(move |_, path| {
+            if let Some(iter) = toggled_store.get_iter(&path) {
+                let visible: bool = toggled_store
+                    .get_value(&iter, VISIBLE as i32)
+                    .get_some()
+                    .unwrap_or(false);
+                toggled_store.set_value(&iter, VISIBLE, &(!visible).to_value());
+            }
+        });
+
+        let name_column = TreeViewColumn::new();
+        let name_cell = CellRendererText::new();
+        name_column.pack_start(&name_cell, true);
+        name_column.add_attribute(&name_cell, "text", NAME as i32);
+        name_column.set_title("Column");
+        name_column.set_expand(true);
+        list.append_column(&name_column);
+
+        let width_column = TreeViewColumn::new();
+        let width_cell = CellRendererText::new();
+        width_cell.set_property_editable(true);
+        width_column.pack_start(&width_cell, false);
+        width_column.add_attribute(&width_cell, "text", WIDTH as i32);
+        width_column.set_title("Width");
+        list.append_column(&width_column);
+        let edited_store = store.clone();
+        width_cell.connect_edited(move |_, path, text| {
+            let text = text.trim();
+            let valid = text.is_empty() || text.parse::<i32>().map(|w| w > 0) == Ok(true);
+            if let Some(iter) = edited_store.get_iter(&path).filter(|_| valid) {
+                edited_store.set_value(&iter, WIDTH, &text.to_value());
+            }
+        });
+        list
+    }
+
+    fn build_content(&self) {
+        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scroll.add(&self.list);
+        scroll.set_size_request(350, 350);
+        scroll.set_vexpand(true);
+
+        let buttons = gtk::Box::new(Vertical, 5);
+        let actions: Vec<(&str, Box<dyn Fn(&Self)>)> = vec![
+            ("Up", Box::new(|this: &Self| this.move_selected(false))),
+            ("Down", Box::new(|this: &Self| this.move_selected(true))),
+            (
+                "Show all",
+                Box::new(|this: &Self| this.set_all_visible(true)),
+            ),
+            (
+                "Hide all",
+                Box::new(|this: &Self| this.set_all_visible(false)),
+            ),
+        ];
+        for (label, activate) in actions {
+            let button = Button::with_label(label);
+            let this = self.clone();
+            button.connect_clicked(move |_| activate(&this));
+            buttons.add(&button);
+        }
+        let hbox = gtk::Box::new(Horizontal, 5);
+        hbox.pack_start(&scroll, true, true, 0);
+        hbox.pack_start(&buttons, false, false, 0);
+
+        let grid = Grid::new();
+        grid.set_row_spacing(5);
+        grid.set_column_spacing(5);
+        let rows: Vec<(&str, gtk::Widget)> = vec![
+            ("Sort by", self.sort.clone().upcast()),
+            ("", self.descending.clone().upcast()),
+            ("Filter", self.filter.clone().upcast()),
+            ("Frozen columns", self.frozen.clone().upcast()),
+            ("View name", self.name.clone().upcast()),
+        ];
+        for (i, (label, widget)) in rows.iter().enumerate() {
+            let label = Label::new(Some(label));
+            label.set_xalign(0.0);
+            widget.set_hexpand(true);
+            grid.attach(&label, 0, i as i32, 1, 1);
+            grid.attach(widget, 1, i as i32, 1, 1);
+        }
+
+        let content = self.dialog.get_content_area();
+        content.set_spacing(10);
+        content.set_border_width(10);
+        content.pack_start(&hbox, true, true, 0);
+        content.pack_start(&grid, false, false, 0);
+
+        let dialog = self.dialog.clone();
+        self.name.connect_changed(move |name| {
+            let named = !name.get_text().trim().is_empty();
+            dialog.set_response_sensitive(ResponseType::Accept, named);
+        });
+    }
+
+    /// the columns of the view first, in its order, then the others
+    fn fill(&self, columns: &[String], view: &ViewConfig) {
+        let all_visible = view.columns.is_empty();
+        let mut rows: Vec<(bool, &str, Option<i32>)> = view
+            .columns
+            .iter()
+            .filter(|c| columns.contains(&c.name))
+            .map(|c| (true, c.name.as_str(), c.width))
+            .collect();
+        for column in columns.iter() {
+            if !view.columns.iter().any(|c| &c.name == column) {
+                rows.push((all_visible, column, None));
+            }
+        }
+        for (visible, name, width) in rows {
+            let iter = self.store.append();
+            let width = width.map(|w| w.to_string()).unwrap_or_default();
+            self.store.set_value(&iter, VISIBLE, &visible.to_value());
+            self.store.set_value(&iter, NAME, &name.to_value());
+            self.store.set_value(&iter, WIDTH, &width.to_value());
+        }
+
+        self.sort.append(Some(""), "(none)");
+        for column in columns.iter() {
+            self.sort.append(Some(column), column);
+        }
+        self.sort
+            .set_active_id(Some(view.sort.as_deref().unwrap_or("")));
+        self.descending.set_active(view.descending);
+        self.filter.set_text(view.filter.as_deref().unwrap_or(""));
+        self.frozen.set_value(view.frozen as f64);
+        self.name.set_text(self.view_name.as_deref().unwrap_or(""));
+        self.dialog
+            .set_response_sensitive(ResponseType::Accept, self.view_name.is_some());
+    }
+
+    fn move_selected(&self, down: bool) {
+        let (_, iter) = match self.list.get_selection().get_selected() {
+            Some(selected) => selected,
+            None => return,
+        };
+        let other = iter.clone();
+        let moved = if down {
+            self.store.iter_next(&other)
+        } else {
+            self.store.iter_previous(&other)
+        };
+        if moved {
+            self.store.swap(&iter, &other);
+        }
+    }
+
+    fn set_all_visible(&self, visible: bool) {
+        if let Some(iter) = self.store.get_iter_first() {
+            loop {
+                self.store.set_value(&iter, VISIBLE, &visible.to_value());
+                if !self.store.iter_next(&iter) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// the layout as set in the dialog
+    fn view(&self) -> ViewConfig {
+        let mut columns = vec![];
+        if let Some(iter) = self.store.get_iter_first() {
+            loop {
+                let visible: bool = self
+                    .store
+                    .get_value(&iter, VISIBLE as i32)
+                    .get_some()
+                    .unwrap_or(false);
+                let name: Option<String> = self
+                    .store
+                    .get_value(&iter, NAME as i32)
+                    .get()
+                    .ok()
+                    .flatten();
+                let width: Option<String> = self
+                    .store
+                    .get_value(&iter, WIDTH as i32)
+                    .get()
+                    .ok()
+                    .flatten();
+                if let Some(name) = name.filter(|_| visible) {
+                    columns.push(ViewColumn {
+                        name: name,
+                        width: width.and_then(|w| w.parse().ok()),
+                    });
+                }
+                if !self.store.iter_next(&iter) {
+                    break;
+                }
+            }
+        }
+        let filter = self.filter.get_text().trim().to_string();
+        ViewConfig {
+            sort: self
+                .sort
+                .get_active_id()
+                .map(|id| id.to_string())
+                .filter(|id| !id.is_empty()),
+            descending: self.descending.get_active(),
+            filter: Some(filter).filter(|f| !f.is_empty()),
+            frozen: self.frozen.get_value_as_int().max(0) as usize,
+            group_by: self.group_by.clone(),
+            columns: columns,
+        }
+    }
+
+    /// show the dialog, `None` when it is cancelled
+    pub fn run(&self) -> Option<ChooserResponse> {
+        self.dialog.show_all();
+        let response = self.dialog.run();
+        let name = self.name.get_text().trim().to_string();
+        let chosen = match response {
+            ResponseType::Apply => Some(ChooserResponse::Apply(self.view())),
+            ResponseType::Accept if !name.is_empty() => {
+                Some(ChooserResponse::Save(name, self.view()))
+            }
+            r if r == DELETE => self.view_name.clone().map(ChooserResponse::Delete),
+            _ => None,
+        };
+        self.dialog.close();
+        chosen
+    }
+}
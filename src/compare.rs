@@ -0,0 +1,570 @@
+//! the comparison of two tables of the same shape, possibly on different connections
+//!
+//! The rows are matched by the primary key and their values compared column by column,
+//! on the columns both tables have. The values are compared as text, but for numbers, booleans
+//! and timestamps that are written differently by each platform, see `same_value`. The differences are turned into the statements
+//! that make one table like the other.
+use crate::audit::Change;
+use crate::catalog;
+use crate::db::{Database, DbError, Platform, ResultSet, Value};
+use crate::format;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// which table is made like the other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowDiff {
+    /// the index of the row in the left and right table
+    Same(usize, usize),
+    /// with the columns whose value is not the same
+    Changed(usize, usize, Vec<usize>),
+    OnlyLeft(usize),
+    OnlyRight(usize),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Comparison {
+    /// the columns of both tables, in the order of the left one
+    pub columns: Vec<String>,
+    pub key: Vec<String>,
+    /// the values of the columns in each row of the tables
+    pub left: Vec<Vec<Value>>,
+    pub right: Vec<Vec<Value>>,
+    /// in the order of the key
+    pub rows: Vec<RowDiff>,
+    /// the columns that are only in one of the tables, they are not compared
+    pub ignored: Vec<String>,
+}
+
+/// a statement making a row of the target like the row of the source
+enum Step<'a> {
+    Insert(&'a [Value]),
+    Update(&'a [Value], Vec<usize>),
+    Delete(&'a [Value]),
+}
+
+/// read all the rows of a table, in the order of its key
+pub fn load(db: &mut dyn Database, table: &str, key: &[String]) -> Result<ResultSet, DbError> {
+    let platform = db.platform();
+    let mut sql = format!("SELECT * FROM {}", platform.quote_table(table));
+    if !key.is_empty() {
+        let order: Vec<String> = key.iter().map(|k| platform.quote_ident(k)).collect();
+        sql.push_str(&format!(" ORDER BY {}", order.join(", ")));
+    }
+    Ok(db.execute(&sql, &[])?.rows())
+}
+
+/// the primary key of the table, the rows can not be matched without one
+pub fn table_key(db: &mut dyn Database, table: &str) -> Result<Vec<String>, DbError> {
+    let key = catalog::primary_key(db, table)?;
+    if key.is_empty() {
+        return Err(DbError::Unsupported(format!(
+            "comparing {}, which has no primary key",
+            table
+        )));
+    }
+    Ok(key)
+}
+
+/// True when the values are the same once written the same way: `1.50` and `1.5`, `t` and `1`,
+/// or `2020-12-31T10:00:00.000` and `2020-12-31 10:00:00`.
+fn same_value(left: &Value, right: &Value) -> bool {
+    let (left, right) = match (left, right) {
+        (Some(left), Some(right)) if left != right => (left, right),
+        _ => return left == right,
+    };
+    if let (Some(l), Some(r)) = (format::decimal_parts(left), format::decimal_parts(right)) {
+        return number_text(l) == number_text(r);
+    }
+    if let (Ok(l), Ok(r)) = (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+        return l == r;
+    }
+    if let (Some(l), Some(r)) = (format::parse_bool(left), format::parse_bool(right)) {
+        return l == r;
+    }
+    match (timestamp_text(left), timestamp_text(right)) {
+        (Some(l), Some(r)) => l == r,
+        _ => false,
+    }
+}
+
+/// the digits of a number without the zeros that don't change it
+fn number_text((negative, integer, fraction): (bool, String, String)) -> String {
+    let fraction = fraction.trim_end_matches('0');
+    let is_zero = integer == "0" && fraction.is_empty();
+    format!(
+        "{}{}.{}",
+        if negative && !is_zero { "-" } else { "" },
+        integer,
+        fraction
+    )
+}
+
+/// a timestamp with a space before the time and no trailing zeros in its fraction of seconds
+fn timestamp_text(value: &str) -> Option<String> {
+    let value = value.trim();
+    let bytes = value.as_bytes();
+    let is_timestamp = bytes.len() >= 19
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && (bytes[10] == b' ' || bytes[10] == b'T')
+        && bytes[13] == b':'
+        && bytes[16] == b':';
+    if !is_timestamp {
+        return None;
+    }
+    let rest = &value[19..];
+    let (fraction, zone) = match rest.strip_prefix('.') {
+        Some(rest) => {
+            let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+            (rest[..digits].trim_end_matches('0'), &rest[digits..])
+        }
+        None => ("", rest),
+    };
+    Some(format!(
+        "{} {}{}{}{}",
+        &value[..10],
+        &value[11..19],
+        if fraction.is_empty() { "" } else { "." },
+        fraction,
+        zone
+    ))
+}
+
+impl Comparison {
+    pub fn new(left: &ResultSet, right: &ResultSet, key: &[String]) -> Result<Self, String> {
+        let columns: Vec<String> = left
+            .columns
+            .iter()
+            .filter(|c| right.columns.contains(c))
+            .cloned()
+            .collect();
+        let ignored: Vec<String> = left
+            .columns
+            .iter()
+            .chain(right.columns.iter())
+            .filter(|c| !columns.contains(c))
+            .cloned()
+            .collect();
+        if let Some(missing) = key.iter().find(|k| !columns.contains(k)) {
+            return Err(format!("the key column {} is not in both tables", missing));
+        }
+        if key.is_empty() {
+            return Err("the rows can not be matched without a key".to_string());
+        }
+        let pick = |rs: &ResultSet| -> Vec<Vec<Value>> {
+            let indexes: Vec<usize> = columns.iter().filter_map(|c| rs.column_index(c)).collect();
+            rs.rows
+                .iter()
+                .map(|row| indexes.iter().map(|i| row[*i].clone()).collect())
+                .collect()
+        };
+        let mut comparison = Comparison {
+            key: key.to_vec(),
+            left: pick(left),
+            right: pick(right),
+            columns: columns,
+            rows: vec![],
+            ignored: ignored,
+        };
+        comparison.match_rows();
+        Ok(comparison)
+    }
+
+    fn key_indexes(&self) -> Vec<usize> {
+        self.key
+            .iter()
+            .filter_map(|k| self.columns.iter().position(|c| c == k))
+            .collect()
+    }
+
+    fn key_of(&self, row: &[Value]) -> Vec<Value> {
+        self.key_indexes().iter().map(|i| row[*i].clone()).collect()
+    }
+
+    fn match_rows(&mut self) {
+        let right: HashMap<Vec<Value>, usize> = self
+            .right
+            .iter()
+            .enumerate()
+            .map(|(i, row)| (self.key_of(row), i))
+            .collect();
+        let mut matched = vec![false; self.right.len()];
+        let mut rows = vec![];
+        for (l, row) in self.left.iter().enumerate() {
+            match right.get(&self.key_of(row)) {
+                Some(&r) => {
+                    matched[r] = true;
+                    let changed: Vec<usize> = (0..self.columns.len())
+                        .filter(|i| !same_value(&row[*i], &self.right[r][*i]))
+                        .collect();
+                    rows.push(match changed.is_empty() {
+                        true => RowDiff::Same(l, r),
+                        false => RowDiff::Changed(l, r, changed),
+                    });
+                }
+                None => rows.push(RowDiff::OnlyLeft(l)),
+            }
+        }
+        rows.extend(
+            matched
+                .iter()
+                .enumerate()
+                .filter(|(_, matched)| !**matched)
+                .map(|(r, _)| RowDiff::OnlyRight(r)),
+        );
+        rows.sort_by(|a, b| {
+            compare_keys(&self.key_of(self.row_of(a)), &self.key_of(self.row_of(b)))
+        });
+        self.rows = rows;
+    }
+
+    /// the values of the row, on the left when it is there
+    fn row_of(&self, diff: &RowDiff) -> &[Value] {
+        match diff {
+            RowDiff::Same(l, _) | RowDiff::Changed(l, _, _) | RowDiff::OnlyLeft(l) => {
+                &self.left[*l]
+            }
+            RowDiff::OnlyRight(r) => &self.right[*r],
+        }
+    }
+
+    /// the rows that are not the same
+    pub fn differences(&self) -> usize {
+        self.rows
+            .iter()
+            .filter(|row| !matches!(row, RowDiff::Same(_, _)))
+            .count()
+    }
+
+    pub fn summary(&self) -> String {
+        let (mut same, mut changed, mut left, mut right) = (0, 0, 0, 0);
+        for row in self.rows.iter() {
+            match row {
+                RowDiff::Same(_, _) => same += 1,
+                RowDiff::Changed(_, _, _) => changed += 1,
+                RowDiff::OnlyLeft(_) => left += 1,
+                RowDiff::OnlyRight(_) => right += 1,
+            }
+        }
+        let mut summary = format!(
+            "{} changed, {} only on the left, {} only on the right, {} the same",
+            changed, left, right, same
+        );
+        if !self.ignored.is_empty() {
+            summary.push_str(&format!(
+                " ({} not compared: {})",
+                match self.ignored.len() {
+                    1 => "column",
+                    _ => "columns",
+                },
+                self.ignored.join(", ")
+            ));
+        }
+        summary
+    }
+
+    /// The rows to delete, update and insert in the target, in that order
+    /// so that a key or unique value is free before it is used again.
+    fn steps(&self, direction: Direction) -> Vec<Step<'_>> {
+        let (mut deletes, mut updates, mut inserts) = (vec![], vec![], vec![]);
+        for row in self.rows.iter() {
+            match (row, direction) {
+                (RowDiff::Same(_, _), _) => (),
+                (RowDiff::Changed(l, _, columns), Direction::LeftToRight) => {
+                    updates.push(Step::Update(&self.left[*l], columns.clone()))
+                }
+                (RowDiff::Changed(_, r, columns), Direction::RightToLeft) => {
+                    updates.push(Step::Update(&self.right[*r], columns.clone()))
+                }
+                (RowDiff::OnlyLeft(l), Direction::LeftToRight) => {
+                    inserts.push(Step::Insert(&self.left[*l]))
+                }
+                (RowDiff::OnlyLeft(l), Direction::RightToLeft) => {
+                    deletes.push(Step::Delete(&self.left[*l]))
+                }
+                (RowDiff::OnlyRight(r), Direction::LeftToRight) => {
+                    deletes.push(Step::Delete(&self.right[*r]))
+                }
+                (RowDiff::OnlyRight(r), Direction::RightToLeft) => {
+                    inserts.push(Step::Insert(&self.right[*r]))
+                }
+            }
+        }
+        deletes.into_iter().chain(updates).chain(inserts).collect()
+    }
+
+    /// the statements with their parameters, to run on the target table
+    pub fn statements(
+        &self,
+        platform: Platform,
+        table: &str,
+        direction: Direction,
+    ) -> Vec<(String, Vec<Value>)> {
+        self.steps(direction)
+            .iter()
+            .map(|step| {
+                let mut params = vec![];
+                let sql = self.render(platform, table, step, &mut |value: &Value| {
+                    params.push(value.clone());
+                    platform.placeholder(params.len())
+                });
+                (sql, params)
+            })
+            .collect()
+    }
+
+    /// the statements with literal values, to be saved and run later
+    pub fn script(&self, platform: Platform, table: &str, direction: Direction) -> String {
+        let statements: Vec<String> = self
+            .steps(direction)
+            .iter()
+            .map(|step| {
+                self.render(platform, table, step, &mut |value: &Value| match value {
+                    Some(value) => platform.quote_literal(value),
+                    None => "NULL".to_string(),
+                })
+            })
+            .collect();
+        match statements.is_empty() {
+            true => String::new(),
+            false => format!("{};\n", statements.join(";\n")),
+        }
+    }
+
+    /// the changes made to the target table of this database, for the audit log
+    pub fn changes(&self, database: &str, table: &str, direction: Direction) -> Vec<Change> {
+        let target = |l: &usize, r: &usize| match direction {
+            Direction::LeftToRight => (&self.right[*r], &self.left[*l]),
+            Direction::RightToLeft => (&self.left[*l], &self.right[*r]),
+        };
+        let change = |original: Option<&[Value]>, values: Option<&[Value]>| {
+            Change::new(database, table, &self.columns, &self.key, original, values)
+        };
+        self.rows
+            .iter()
+            .filter_map(|row| match (row, direction) {
+                (RowDiff::Same(_, _), _) => None,
+                (RowDiff::Changed(l, r, _), _) => {
+                    let (original, values) = target(l, r);
+                    Some(change(Some(original), Some(values)))
+                }
+                (RowDiff::OnlyLeft(l), Direction::LeftToRight) => {
+                    Some(change(None, Some(&self.left[*l])))
+                }
+                (RowDiff::OnlyLeft(l), Direction::RightToLeft) => {
+                    Some(change(Some(&self.left[*l]), None))
+                }
+                (RowDiff::OnlyRight(r), Direction::LeftToRight) => {
+                    Some(change(Some(&self.right[*r]), None))
+                }
+                (RowDiff::OnlyRight(r), Direction::RightToLeft) => {
+                    Some(change(None, Some(&self.right[*r])))
+                }
+            })
+            .collect()
+    }
+
+    /// `value` gives the placeholder or the literal of a value
+    fn render(
+        &self,
+        platform: Platform,
+        table: &str,
+        step: &Step,
+        value: &mut dyn FnMut(&Value) -> String,
+    ) -> String {
+        let table = platform.quote_table(table);
+        let column = |i: usize| platform.quote_ident(&self.columns[i]);
+        let filter = |row: &[Value], value: &mut dyn FnMut(&Value) -> String| {
+            self.key_indexes()
+                .into_iter()
+                .map(|i| format!("{} = {}", column(i), value(&row[i])))
+                .collect::<Vec<_>>()
+                .join(" AND ")
+        };
+        match step {
+            Step::Insert(row) => {
+                let names: Vec<String> = (0..self.columns.len()).map(column).collect();
+                let values: Vec<String> = row.iter().map(value).collect();
+                format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    table,
+                    names.join(", "),
+                    values.join(", ")
+                )
+            }
+            Step::Update(row, columns) => {
+                let assignments: Vec<String> = columns
+                    .iter()
+                    .map(|i| format!("{} = {}", column(*i), value(&row[*i])))
+                    .collect();
+                format!(
+                    "UPDATE {} SET {} WHERE {}",
+                    table,
+                    assignments.join(", "),
+                    filter(row, value)
+                )
+            }
+            Step::Delete(row) => format!("DELETE FROM {} WHERE {}", table, filter(row, value)),
+        }
+    }
+}
+
+/// the keys in order, numbers as numbers
+fn compare_keys(a: &[Value], b: &[Value]) -> Ordering {
+    for (a, b) in a.iter().zip(b.iter()) {
+        let ordering = match (a, b) {
+            (Some(a), Some(b)) => match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                _ => a.cmp(b),
+            },
+            (a, b) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(columns: &[&str], rows: &[&[Option<&str>]]) -> ResultSet {
+        ResultSet {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: rows
+                .iter()
+                .map(|row| row.iter().map(|v| v.map(String::from)).collect())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rows_are_matched_by_key() {
+        let left = table(
+            &["code", "name", "rate"],
+            &[
+                &[Some("10"), Some("ten"), Some("1")],
+                &[Some("2"), Some("two"), None],
+                &[Some("3"), Some("three"), Some("3")],
+            ],
+        );
+        let right = table(
+            &["code", "name", "rate", "note"],
+            &[
+                &[Some("2"), Some("two"), Some("0")],
+                &[Some("3"), Some("three"), Some("3")],
+                &[Some("4"), Some("four"), None],
+            ],
+        );
+        let key = vec!["code".to_string()];
+        let comparison = Comparison::new(&left, &right, &key).unwrap();
+        assert_eq!(
+            comparison.rows,
+            vec![
+                RowDiff::Changed(1, 0, vec![2]),
+                RowDiff::Same(2, 1),
+                RowDiff::OnlyRight(2),
+                RowDiff::OnlyLeft(0),
+            ]
+        );
+        assert_eq!(comparison.differences(), 3);
+        assert_eq!(
+            comparison.summary(),
+            "1 changed, 1 only on the left, 1 only on the right, 1 the same (column not compared: note)"
+        );
+        assert_eq!(
+            comparison.script(Platform::Sqlite, "rates", Direction::LeftToRight),
+            "DELETE FROM \"rates\" WHERE \"code\" = '4';\n\
+             UPDATE \"rates\" SET \"rate\" = NULL WHERE \"code\" = '2';\n\
+             INSERT INTO \"rates\" (\"code\", \"name\", \"rate\") VALUES ('10', 'ten', '1');\n"
+        );
+        let statements = comparison.statements(Platform::Postgres, "rates", Direction::RightToLeft);
+        assert_eq!(
+            statements[0],
+            (
+                "DELETE FROM \"rates\" WHERE \"code\" = $1".to_string(),
+                vec![Some("10".to_string())]
+            )
+        );
+        assert_eq!(
+            statements[1],
+            (
+                "UPDATE \"rates\" SET \"rate\" = $1 WHERE \"code\" = $2".to_string(),
+                vec![Some("0".to_string()), Some("2".to_string())]
+            )
+        );
+        assert_eq!(statements.len(), 3);
+        let changes = comparison.changes("sqlite://left.db", "rates", Direction::RightToLeft);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].changed_columns(), vec!["rate"]);
+
+        let missing = Comparison::new(&left, &right, &["id".to_string()]);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn values_written_differently_are_the_same() {
+        // a sqlite table on the left, its copy on postgres on the right
+        let left = table(
+            &["id", "price", "active", "at"],
+            &[
+                &[
+                    Some("1"),
+                    Some("1.5"),
+                    Some("1"),
+                    Some("2020-12-31 10:00:00"),
+                ],
+                &[
+                    Some("2"),
+                    Some("2"),
+                    Some("0"),
+                    Some("2020-12-31 10:00:00.5"),
+                ],
+                &[Some("3"), Some("1e3"), Some("1"), None],
+            ],
+        );
+        let right = table(
+            &["id", "price", "active", "at"],
+            &[
+                &[
+                    Some("1"),
+                    Some("1.50"),
+                    Some("t"),
+                    Some("2020-12-31T10:00:00.000"),
+                ],
+                &[
+                    Some("2"),
+                    Some("2.01"),
+                    Some("true"),
+                    Some("2020-12-31 10:00:00.50"),
+                ],
+                &[Some("3"), Some("1000.00"), Some("true"), None],
+            ],
+        );
+        let key = vec!["id".to_string()];
+        let comparison = Comparison::new(&left, &right, &key).unwrap();
+        assert_eq!(
+            comparison.rows,
+            vec![
+                RowDiff::Same(0, 0),
+                RowDiff::Changed(1, 1, vec![1, 2]),
+                RowDiff::Same(2, 2),
+            ]
+        );
+        assert!(same_value(
+            &Some("-0.0".to_string()),
+            &Some("0".to_string())
+        ));
+        assert!(!same_value(&Some("1".to_string()), &None));
+        assert!(!same_value(&Some("10".to_string()), &Some("1".to_string())));
+    }
+}
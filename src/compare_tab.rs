@@ -0,0 +1,514 @@
+//! the comparison of two tables in a tab: their rows side by side with the differences
+//! highlighted, and the script or the changes making one table like the other
+use crate::audit;
+use crate::compare::{self, Comparison, Direction, RowDiff};
+use crate::db::{self, DbError, ResultSet, SharedDb, Value};
+use crate::guard;
+use glib::Type;
+use gtk;
+use gtk::prelude::*;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::{
+    Adjustment, ButtonsType, CellRendererText, ComboBoxText, Dialog, DialogFlags,
+    FileChooserAction, FileChooserDialog, Grid, IconSize, Image, Label, ListStore, MessageDialog,
+    MessageType, Paned, ResponseType, ScrolledWindow, TextView, ToggleToolButton, ToolButton,
+    ToolItem, Toolbar, TreeView, TreeViewColumn, Window, WrapMode,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+static CHANGED_BACKGROUND: &str = "#fce8b2";
+/// the row that is only in this table
+static ONLY_BACKGROUND: &str = "#d4f4d4";
+/// the place of the row that is only in the other table
+static MISSING_BACKGROUND: &str = "#eeeeee";
+
+/// a table of a connection
+#[derive(Clone)]
+pub struct CompareSide {
+    /// the table with the name of its connection
+    pub title: String,
+    pub table: String,
+    pub db: SharedDb,
+}
+
+#[derive(Clone)]
+pub struct CompareTab {
+    left: CompareSide,
+    right: CompareSide,
+    comparison: Rc<RefCell<Comparison>>,
+    toolbar: Toolbar,
+    refresh: ToolButton,
+    only_differences: ToggleToolButton,
+    direction: ComboBoxText,
+    script: ToolButton,
+    apply: ToolButton,
+    left_scroll: ScrolledWindow,
+    right_scroll: ScrolledWindow,
+    status: Label,
+}
+
+impl CompareTab {
+    pub fn new(left: CompareSide, right: CompareSide) -> Self {
+        let toolbar = Toolbar::new();
+        let refresh = Self::create_button(&toolbar, "view-refresh", "Refresh");
+        let only_differences = ToggleToolButton::new();
+        only_differences.set_label(Some("Only Differences"));
+        only_differences.set_is_important(true);
+        toolbar.add(&only_differences);
+        let direction = ComboBoxText::new();
+        direction.append(Some("left-to-right"), "Make the right like the left");
+        direction.append(Some("right-to-left"), "Make the left like the right");
+        direction.set_active_id(Some("left-to-right"));
+        let item = ToolItem::new();
+        item.add(&direction);
+        toolbar.add(&item);
+        let script = Self::create_button(&toolbar, "text-x-generic", "Sync Script");
+        let apply = Self::create_button(&toolbar, "system-run", "Apply");
+        let status = Label::new(None);
+        status.set_xalign(0.0);
+
+        let tab = CompareTab {
+            left: left,
+            right: right,
+            comparison: Rc::new(RefCell::new(Comparison::default())),
+            toolbar: toolbar,
+            refresh: refresh,
+            only_differences: only_differences,
+            direction: direction,
+            script: script,
+            apply: apply,
+            left_scroll: ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>),
+            right_scroll: ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>),
+            status: status,
+        };
+        tab.load();
+        tab
+    }
+
+    fn create_button(toolbar: &Toolbar, icon: &str, label: &str) -> ToolButton {
+        let image = Image::from_icon_name(Some(icon), IconSize::SmallToolbar);
+        let button = ToolButton::new::<Image>(Some(&image), Some(label));
+        button.set_is_important(true);
+        toolbar.add(&button);
+        button
+    }
+
+    pub fn build_widget(&self) -> gtk::Box {
+        let panes = Paned::new(Horizontal);
+        panes.pack1(&self.create_side(&self.left, &self.left_scroll), true, true);
+        panes.pack2(
+            &self.create_side(&self.right, &self.right_scroll),
+            true,
+            true,
+        );
+        let vbox = gtk::Box::new(Vertical, 0);
+        vbox.add(&self.toolbar);
+        vbox.pack_start(&panes, true, true, 0);
+        self.status.set_margin_start(5);
+        vbox.add(&self.status);
+        self.init_scrolling();
+        self.init_toolbar();
+        self.show_rows();
+        vbox
+    }
+
+    fn create_side(&self, side: &CompareSide, scroll: &ScrolledWindow) -> gtk::Box {
+        let title = Label::new(None);
+        title.set_markup(&format!("<b>{}</b>", glib::markup_escape_text(&side.title)));
+        title.set_xalign(0.0);
+        title.set_margin_start(5);
+        scroll.set_vexpand(true);
+        scroll.set_hexpand(true);
+        let vbox = gtk::Box::new(Vertical, 5);
+        vbox.add(&title);
+        vbox.pack_start(scroll, true, true, 0);
+        vbox
+    }
+
+    /// the rows of both tables are on the same lines, they scroll together
+    fn init_scrolling(&self) {
+        let left = self.left_scroll.get_vadjustment();
+        let right = self.right_scroll.get_vadjustment();
+        if let (Some(left), Some(right)) = (left, right) {
+            let other = right.clone();
+            left.connect_value_changed(move |adjustment| other.set_value(adjustment.get_value()));
+            right.connect_value_changed(move |adjustment| left.set_value(adjustment.get_value()));
+        }
+    }
+
+    fn init_toolbar(&self) {
+        let this = self.clone();
+        self.refresh.connect_clicked(move |_| {
+            this.load();
+            this.show_rows();
+        });
+        let this = self.clone();
+        self.only_differences
+            .connect_toggled(move |_| this.show_rows());
+        let this = self.clone();
+        self.script.connect_clicked(move |_| this.show_script());
+        let this = self.clone();
+        self.apply.connect_clicked(move |_| this.apply());
+    }
+
+    fn direction(&self) -> Direction {
+        match self.direction.get_active_id() {
+            Some(id) if id == "right-to-left" => Direction::RightToLeft,
+            _ => Direction::LeftToRight,
+        }
+    }
+
+    /// the table that is changed, in the direction that is chosen
+    fn target(&self) -> &CompareSide {
+        match self.direction() {
+            Direction::LeftToRight => &self.right,
+            Direction::RightToLeft => &self.left,
+        }
+    }
+
+    /// read both tables again and compare them
+    fn load(&self) {
+        let result = Self::read(&self.left, None).and_then(|(key, left)| {
+            let (_, right) = Self::read(&self.right, Some(&key))?;
+            Comparison::new(&left, &right, &key).map_err(DbError::Sql)
+        });
+        match result {
+            Ok(comparison) => {
+                self.status.set_text(&comparison.summary());
+                *self.comparison.borrow_mut() = comparison;
+            }
+            Err(e) => {
+                self.status.set_text(&format!("Unable to compare: {}", e));
+                *self.comparison.borrow_mut() = Comparison::default();
+            }
+        }
+    }
+
+    /// the rows of the table, matched by the key of the left table
+    fn read(
+        side: &CompareSide,
+        key: Option<&[String]>,
+    ) -> Result<(Vec<String>, ResultSet), DbError> {
+        let mut db = side.db.borrow_mut();
+        let db = match db.as_mut() {
+            Some(db) => db,
+            None => {
+                return Err(DbError::Connection(format!(
+                    "{} is not connected",
+                    side.title
+                )))
+            }
+        };
+        let key = match key {
+            Some(key) => key.to_vec(),
+            None => compare::table_key(db.as_mut(), &side.table)?,
+        };
+        let rows = compare::load(db.as_mut(), &side.table, &key)?;
+        Ok((key, rows))
+    }
+
+    /// fill both lists, the differences highlighted
+    fn show_rows(&self) {
+        let comparison = self.comparison.borrow();
+        let columns = &comparison.columns;
+        let (left_list, left_store) = Self::create_list(columns);
+        let (right_list, right_store) = Self::create_list(columns);
+        let only_differences = self.only_differences.get_active();
+        for row in comparison.rows.iter() {
+            let (left, right, changed): (Option<usize>, Option<usize>, &[usize]) = match row {
+                RowDiff::Same(_, _) if only_differences => continue,
+                RowDiff::Same(l, r) => (Some(*l), Some(*r), &[]),
+                RowDiff::Changed(l, r, changed) => (Some(*l), Some(*r), changed.as_slice()),
+                RowDiff::OnlyLeft(l) => (Some(*l), None, &[]),
+                RowDiff::OnlyRight(r) => (None, Some(*r), &[]),
+            };
+            let alone = left.is_none() || right.is_none();
+            let background = |i: usize| match changed.contains(&i) {
+                true => Some(CHANGED_BACKGROUND),
+                false if alone => Some(ONLY_BACKGROUND),
+                false => None,
+            };
+            let left = left.map(|l| comparison.left[l].as_slice());
+            let right = right.map(|r| comparison.right[r].as_slice());
+            Self::append_row(&left_store, columns.len(), left, &background);
+            Self::append_row(&right_store, columns.len(), right, &background);
+        }
+        for (scroll, list) in [
+            (&self.left_scroll, left_list),
+            (&self.right_scroll, right_list),
+        ]
+        .iter()
+        {
+            if let Some(child) = scroll.get_child() {
+                scroll.remove(&child);
+            }
+            scroll.add(list);
+            list.show();
+        }
+        let differences = comparison.differences();
+        self.script.set_sensitive(differences > 0);
+        self.apply.set_sensitive(differences > 0);
+    }
+
+    /// a text column for each column of the tables, with the background of each cell after them
+    fn create_list(columns: &[String]) -> (TreeView, ListStore) {
+        let types: Vec<Type> = (0..columns.len() * 2).map(|_| Type::String).collect();
+        let store = ListStore::new(&types);
+        let list = TreeView::with_model(&store);
+        for (i, title) in columns.iter().enumerate() {
+            let column = TreeViewColumn::new();
+            let cell = CellRendererText::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            column.pack_start(&cell, true);
+            column.add_attribute(&cell, "text", i as i32);
+            column.add_attribute(&cell, "background", (columns.len() + i) as i32);
+            list.append_column(&column);
+        }
+        (list, store)
+    }
+
+    /// the values of a row, or a blank line where the row is only in the other table
+    fn append_row(
+        store: &ListStore,
+        count: usize,
+        values: Option<&[Value]>,
+        background: &dyn Fn(usize) -> Option<&'static str>,
+    ) {
+        let iter = store.append();
+        for i in 0..count {
+            let (text, color) = match values {
+                Some(values) => (Some(values[i].as_deref().unwrap_or("NULL")), background(i)),
+                None => (None, Some(MISSING_BACKGROUND)),
+            };
+            store.set_value(&iter, i as u32, &text.to_value());
+            store.set_value(&iter, (count + i) as u32, &color.to_value());
+        }
+    }
+
+    fn window(&self) -> Option<Window> {
+        self.toolbar
+            .get_toplevel()
+            .and_then(|w| w.downcast::<Window>().ok())
+    }
+
+    /// the statements making the target like the other table, to be saved in a file
+    fn show_script(&self) {
+        let target = self.target();
+        let platform = match target.db.borrow().as_ref() {
+            Some(db) => db.platform(),
+            None => return,
+        };
+        let script = self
+            .comparison
+            .borrow()
+            .script(platform, &target.table, self.direction());
+        let dialog = Dialog::with_buttons(
+            Some(&format!("Sync script of {}", target.title)),
+            self.window().as_ref(),
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Close", ResponseType::Close),
+                ("Save", ResponseType::Accept),
+            ],
+        );
+        dialog.set_default_size(700, 500);
+        let text = TextView::new();
+        text.set_monospace(true);
+        text.set_wrap_mode(WrapMode::None);
+        if let Some(buffer) = text.get_buffer() {
+            buffer.set_text(&script);
+        }
+        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scroll.add(&text);
+        scroll.set_vexpand(true);
+        dialog.get_content_area().pack_start(&scroll, true, true, 0);
+        dialog.show_all();
+        let response = dialog.run();
+        dialog.close();
+        if response == ResponseType::Accept {
+            self.save_script(&script, &target.table);
+        }
+    }
+
+    fn save_script(&self, script: &str, table: &str) {
+        let dialog = FileChooserDialog::with_buttons(
+            Some("Save the sync script"),
+            self.window().as_ref(),
+            FileChooserAction::Save,
+            &[
+                ("Cancel", ResponseType::Cancel),
+                ("Save", ResponseType::Accept),
+            ],
+        );
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name(&format!("sync_{}.sql", table.replace('.', "_")));
+        let path = match dialog.run() {
+            ResponseType::Accept => dialog.get_filename(),
+            _ => None,
+        };
+        dialog.close();
+        if let Some(path) = path {
+            match std::fs::write(&path, script) {
+                Ok(()) => self
+                    .status
+                    .set_text(&format!("Saved the script to {}", path.display())),
+                Err(e) => {
+                    self.status
+                        .set_text(&format!("Unable to save {}: {}", path.display(), e))
+                }
+            }
+        }
+    }
+
+    /// Run the statements on the target after a confirmation, as a save of a data tab:
+    /// in one transaction, or in the open one of the manual commit mode.
+    fn apply(&self) {
+        let target = self.target().clone();
+        let direction = self.direction();
+        let (statements, changes, name) = {
+            let (platform, name, database) = match target.db.borrow().as_ref() {
+                Some(db) => (
+                    db.platform(),
+                    db.session().guard.clone(),
+                    db.session().database.clone(),
+                ),
+                None => return,
+            };
+            let comparison = self.comparison.borrow();
+            (
+                comparison.statements(platform, &target.table, direction),
+                comparison.changes(&database, &target.table, direction),
+                name,
+            )
+        };
+        // a guarded connection asks for its name instead
+        let confirmed = match name {
+            Some(name) => {
+                guard::confirm_save(self.window().as_ref(), &name, &audit::summary(&changes))
+            }
+            None => {
+                let question = MessageDialog::new(
+                    self.window().as_ref(),
+                    DialogFlags::MODAL,
+                    MessageType::Question,
+                    ButtonsType::YesNo,
+                    &format!("Run {} statement(s) on {}?", statements.len(), target.title),
+                );
+                let answer = question.run();
+                question.close();
+                answer == ResponseType::Yes
+            }
+        };
+        if !confirmed {
+            return;
+        }
+        let result = match target.db.borrow_mut().as_mut() {
+            Some(db) => Self::run(db.as_mut(), &statements, &changes),
+            None => return,
+        };
+        let message = match result {
+            Ok(false) => format!(
+                "Applied {} change(s) in the open transaction",
+                changes.len()
+            ),
+            Ok(true) => match audit::record(&changes) {
+                Ok(()) => format!("Applied {} change(s)", changes.len()),
+                Err(e) => format!(
+                    "Applied {} change(s), unable to write the audit log: {}",
+                    changes.len(),
+                    e
+                ),
+            },
+            Err(e) => {
+                self.status.set_text(&format!("Unable to apply: {}", e));
+                return;
+            }
+        };
+        self.load();
+        self.show_rows();
+        self.status.set_text(&format!(
+            "{}, {}",
+            message,
+            self.comparison.borrow().summary()
+        ));
+    }
+
+    /// true when the statements were committed, false when they are in the open transaction
+    fn run(
+        db: &mut dyn db::Database,
+        statements: &[(String, Vec<Value>)],
+        changes: &[audit::Change],
+    ) -> Result<bool, DbError> {
+        db::begin_save(db)?;
+        for (sql, params) in statements.iter() {
+            if let Err(e) = db.execute(sql, params) {
+                db::cancel_save(db);
+                return Err(e);
+            }
+        }
+        if let Err(e) = db::end_save(db, statements.len(), changes) {
+            db::cancel_save(db);
+            return Err(e);
+        }
+        Ok(db.session().transaction.is_none())
+    }
+}
+
+/// Ask which of the open tables to compare, `current` is the one on the left at first.
+/// Returns the index of the left and right one.
+pub fn choose_tables(
+    parent: Option<&Window>,
+    sides: &[CompareSide],
+    current: usize,
+) -> Option<(usize, usize)> {
+    let dialog = Dialog::with_buttons(
+        Some("Compare two tables"),
+        parent,
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", ResponseType::Cancel),
+            ("Compare", ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(ResponseType::Accept);
+    let grid = Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_margin_start(10);
+    grid.set_margin_end(10);
+    grid.set_margin_top(10);
+    grid.set_margin_bottom(10);
+    let mut combos = vec![];
+    for (row, (title, selected)) in [
+        ("Left", current),
+        ("Right", if current == 0 { 1 } else { 0 }),
+    ]
+    .iter()
+    .enumerate()
+    {
+        let label = Label::new(Some(*title));
+        label.set_xalign(0.0);
+        let combo = ComboBoxText::new();
+        for (i, side) in sides.iter().enumerate() {
+            combo.append(Some(&i.to_string()), &side.title);
+        }
+        combo.set_active(Some(*selected as u32));
+        grid.attach(&label, 0, row as i32, 1, 1);
+        grid.attach(&combo, 1, row as i32, 1, 1);
+        combos.push(combo);
+    }
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+    let response = dialog.run();
+    let chosen: Vec<Option<usize>> = combos
+        .iter()
+        .map(|combo| combo.get_active().map(|i| i as usize))
+        .collect();
+    dialog.close();
+    match (response, chosen[0], chosen[1]) {
+        (ResponseType::Accept, Some(left), Some(right)) => Some((left, right)),
+        _ => None,
+    }
+}
@@ -0,0 +1,352 @@
+//! per table settings, stored as toml files in the user's config directory
+//!
+//! ```toml
+//! version = "updated_at"
+//!
+//! [columns.phone]
+//! mask = "phone"
+//! pattern = "^\\(\\d{3}\\) \\d{3}-\\d{4}$"
+//! message = "must be a full phone number"
+//!
+//! [columns.quantity]
+//! min = 1
+//! max = 999
+//!
+//! [columns.price]
+//! aggregate = "sum"
+//!
+//! [columns.price.format]
+//! kind = "currency"
+//! symbol = "$"
+//!
+//! [filters]
+//! "Big orders" = "quantity > 100"
+//!
+//! [views."Shipping view"]
+//! sort = "shipped_at"
+//! descending = true
+//! filter = "shipped_at IS NOT NULL"
+//! frozen = 1
+//! group_by = ["country"]
+//!
+//! [[views."Shipping view".columns]]
+//! name = "id"
+//! width = 60
+//!
+//! [[views."Shipping view".columns]]
+//! name = "address"
+//! ```
+//!
+//! The saved connections are in `connections.toml`, the settings in `settings.toml`.
+use crate::aggregate::Aggregate;
+use crate::format::Format;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TableConfig {
+    /// the view shown when the table is opened, first as toml needs the values before the tables
+    pub view: Option<String>,
+    /// the version or last update column checked before saving a row,
+    /// all the values of the row are compared when there is none
+    pub version: Option<String>,
+    #[serde(default)]
+    pub columns: BTreeMap<String, ColumnConfig>,
+    /// saved filters, by name, as the condition of a where clause
+    #[serde(default)]
+    pub filters: BTreeMap<String, String>,
+    /// named layouts of the list
+    #[serde(default)]
+    pub views: BTreeMap<String, ViewConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ColumnConfig {
+    /// edit mask of the detail entry, see `format::apply_mask`
+    pub mask: Option<String>,
+    /// regex the value must match before it can be saved
+    pub pattern: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// shown instead of the default message when the pattern or range is not met
+    pub message: Option<String>,
+    /// shown in the footer of the list
+    pub aggregate: Option<Aggregate>,
+    pub format: Option<Format>,
+}
+
+/// The columns of the list with their order and width, the sort and the filter.
+/// The simple values come first, toml needs them before the tables.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ViewConfig {
+    pub sort: Option<String>,
+    #[serde(default)]
+    pub descending: bool,
+    /// the condition of the where clause
+    pub filter: Option<String>,
+    /// the number of columns on the left that stay in place when scrolling sideways
+    #[serde(default)]
+    pub frozen: usize,
+    /// the columns the rows are grouped by, in order, not grouped when empty
+    #[serde(default)]
+    pub group_by: Vec<String>,
+    /// the columns shown, in order, all the columns when empty
+    #[serde(default)]
+    pub columns: Vec<ViewColumn>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViewColumn {
+    pub name: String,
+    /// in pixels, the natural width when not set
+    pub width: Option<i32>,
+}
+
+/// a saved connection
+///
+/// ```toml
+/// [[profiles]]
+/// name = "staging"
+/// url = "postgres://app@staging/shop"
+/// manual_commit = true
+///
+/// [[profiles]]
+/// name = "production"
+/// url = "postgres://app@db/shop"
+/// production = true
+/// confirm_destructive = true
+///
+/// [[profiles]]
+/// name = "reporting"
+/// url = "postgres://report@replica/shop"
+/// read_only = true
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionProfile {
+    pub name: String,
+    pub url: String,
+    /// the saves are kept in a transaction until it is committed from the toolbar
+    #[serde(default)]
+    pub manual_commit: bool,
+    /// the badge on the tabs of the connection is red
+    #[serde(default)]
+    pub production: bool,
+    /// the color of the badge instead, ie: `"#e66100"` or `"orange"`
+    pub color: Option<String>,
+    /// nothing can be saved, the session is read-only on the server where it can be
+    #[serde(default)]
+    pub read_only: bool,
+    /// each save shows what it changes, and runs once the name of the connection is typed
+    #[serde(default)]
+    pub confirm_destructive: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Profiles {
+    #[serde(default)]
+    profiles: Vec<ConnectionProfile>,
+}
+
+/// the saved connections, none when there is no `connections.toml`
+pub fn load_profiles() -> Vec<ConnectionProfile> {
+    let path = config_dir().join("connections.toml");
+    match fs::read_to_string(&path) {
+        Ok(content) => match toml::from_str::<Profiles>(&content) {
+            Ok(profiles) => profiles.profiles,
+            Err(e) => {
+                println!("ignoring invalid config {}: {}", path.display(), e);
+                vec![]
+            }
+        },
+        Err(_) => vec![],
+    }
+}
+
+/// the favorites and recently opened tables of the sidebar, in `sidebar.toml`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SidebarConfig {
+    #[serde(default)]
+    pub favorites: Vec<String>,
+    /// the most recent first
+    #[serde(default)]
+    pub recent: Vec<String>,
+}
+
+static MAX_RECENT: usize = 10;
+
+impl SidebarConfig {
+    fn path() -> PathBuf {
+        config_dir().join("sidebar.toml")
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path();
+        match fs::read_to_string(&path) {
+            Ok(content) => match toml::from_str(&content) {
+                Ok(config) => config,
+                Err(e) => {
+                    println!("ignoring invalid config {}: {}", path.display(), e);
+                    SidebarConfig::default()
+                }
+            },
+            Err(_) => SidebarConfig::default(),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(config_dir())?;
+        let content = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        fs::write(Self::path(), content)
+    }
+
+    pub fn toggle_favorite(&mut self, table: &str) {
+        match self.favorites.iter().position(|f| f == table) {
+            Some(index) => {
+                self.favorites.remove(index);
+            }
+            None => self.favorites.push(table.to_string()),
+        }
+    }
+
+    pub fn add_recent(&mut self, table: &str) {
+        self.recent.retain(|r| r != table);
+        self.recent.insert(0, table.to_string());
+        self.recent.truncate(MAX_RECENT);
+    }
+}
+
+/// the settings of the application, in `settings.toml`
+///
+/// ```toml
+/// audit = true
+/// transaction_warning = 10
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    /// keep the changes saved from the data tabs in the audit log
+    #[serde(default)]
+    pub audit: bool,
+    /// the minutes a transaction of the manual commit mode stays open before a warning,
+    /// 10 when not set
+    pub transaction_warning: Option<u64>,
+}
+
+impl Settings {
+    pub fn transaction_warning(&self) -> Duration {
+        Duration::from_secs(self.transaction_warning.unwrap_or(10) * 60)
+    }
+
+    pub fn load() -> Self {
+        let path = config_dir().join("settings.toml");
+        match fs::read_to_string(&path) {
+            Ok(content) => match toml::from_str(&content) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    println!("ignoring invalid config {}: {}", path.display(), e);
+                    Settings::default()
+                }
+            },
+            Err(_) => Settings::default(),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/datawindow`, falling back to `~/.config/datawindow`
+pub fn config_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("datawindow")
+}
+
+/// the toml file of this table in this directory of the config directory
+pub fn table_file(dir: &str, table: &str) -> PathBuf {
+    let file_name: String = table
+        .chars()
+        .map(|c| if c == '/' || c == '\\' { '_' } else { c })
+        .collect();
+    config_dir().join(dir).join(format!("{}.toml", file_name))
+}
+
+impl TableConfig {
+    pub fn path(table: &str) -> PathBuf {
+        table_file("tables", table)
+    }
+
+    /// the saved config of this table, the defaults are used when there is none
+    pub fn load(table: &str) -> Self {
+        let path = Self::path(table);
+        match fs::read_to_string(&path) {
+            Ok(content) => match toml::from_str(&content) {
+                Ok(config) => config,
+                Err(e) => {
+                    println!("ignoring invalid config {}: {}", path.display(), e);
+                    TableConfig::default()
+                }
+            },
+            Err(_) => TableConfig::default(),
+        }
+    }
+
+    /// the tables that have a saved config, with their config
+    pub fn load_all() -> Vec<(String, Self)> {
+        let entries = match fs::read_dir(config_dir().join("tables")) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+        let mut tables: Vec<String> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "toml" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_string_lossy().to_string())
+            })
+            .collect();
+        tables.sort();
+        tables
+            .into_iter()
+            .map(|table| {
+                let config = Self::load(&table);
+                (table, config)
+            })
+            .collect()
+    }
+
+    pub fn save(&self, table: &str) -> io::Result<()> {
+        let path = Self::path(table);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        fs::write(path, content)
+    }
+
+    pub fn format(&self, column: &str) -> Option<&Format> {
+        self.columns.get(column).and_then(|c| c.format.as_ref())
+    }
+
+    pub fn mask(&self, column: &str) -> Option<&str> {
+        self.columns.get(column).and_then(|c| c.mask.as_deref())
+    }
+
+    pub fn set_format(&mut self, column: &str, format: Option<Format>) {
+        self.columns.entry(column.to_string()).or_default().format = format;
+    }
+
+    pub fn aggregate(&self, column: &str) -> Option<Aggregate> {
+        self.columns.get(column).and_then(|c| c.aggregate)
+    }
+
+    pub fn set_aggregate(&mut self, column: &str, aggregate: Option<Aggregate>) {
+        self.columns
+            .entry(column.to_string())
+            .or_default()
+            .aggregate = aggregate;
+    }
+}
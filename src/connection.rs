@@ -1,11 +1,12 @@
 //! database connection url dialogbox
+use crate::db::{self, Database};
 use gtk;
 use gtk::prelude::*;
 use gtk::Dialog;
 use gtk::Orientation::{Horizontal, Vertical};
 use gtk::{
-    ButtonBox, ComboBoxText, Entry, Grid, IconSize, Image, InputPurpose, Label, Stack,
-    StackSwitcher, ToolButton, Toolbar,
+    ButtonBox, ButtonsType, ComboBoxText, DialogFlags, Entry, Grid, IconSize, Image, InputPurpose,
+    Label, MessageDialog, MessageType, Stack, StackSwitcher, ToolButton, Toolbar,
 };
 
 pub enum Connection {
@@ -22,20 +23,105 @@ pub struct ConnectionScheme {
     password: String,
 }
 
+/// the entries of the simple connection form
+#[derive(Clone)]
+struct SchemeForm {
+    platform: ComboBoxText,
+    host: Entry,
+    port: Entry,
+    database: Entry,
+    user: Entry,
+    password: Entry,
+}
+
+impl SchemeForm {
+    fn to_scheme(&self) -> ConnectionScheme {
+        let platform = self
+            .platform
+            .get_active_id()
+            .map(|id| id.to_string())
+            .unwrap_or_default();
+        ConnectionScheme {
+            platform: platform,
+            host: self.host.get_text().to_string(),
+            port: self.port.get_text().parse().unwrap_or(0),
+            database: self.database.get_text().to_string(),
+            user: self.user.get_text().to_string(),
+            password: self.password.get_text().to_string(),
+        }
+    }
+}
+
+impl ConnectionScheme {
+    pub fn to_url(&self) -> String {
+        let (scheme, default_port) = match self.platform.as_str() {
+            "my" => ("mysql", 3306),
+            "sq" => return format!("sqlite://{}", self.database),
+            _ => ("postgres", 5432),
+        };
+        let port = if self.port == 0 {
+            default_port
+        } else {
+            self.port
+        };
+        let credentials = match (self.user.is_empty(), self.password.is_empty()) {
+            (true, _) => String::new(),
+            (false, true) => format!("{}@", percent_encode(&self.user)),
+            (false, false) => format!(
+                "{}:{}@",
+                percent_encode(&self.user),
+                percent_encode(&self.password)
+            ),
+        };
+        format!(
+            "{}://{}{}:{}/{}",
+            scheme, credentials, self.host, port, self.database
+        )
+    }
+}
+
+/// escape what has a meaning in an url, ie: a `@`, `:` or `/` in a password
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
 impl Connection {
     pub fn new() -> Self {
         Connection::Url("postgres://postgre".to_owned())
     }
 
-    pub fn build_widget(&self) -> Dialog {
+    pub fn url(&self) -> String {
+        match self {
+            Connection::Url(url) => url.to_string(),
+            Connection::Scheme(scheme) => scheme.to_url(),
+        }
+    }
+
+    /// show the connection dialog, `on_connect` is called with the url and the opened database
+    pub fn build_widget<F>(&self, on_connect: F) -> Dialog
+    where
+        F: Fn(&str, Box<dyn Database>) + 'static,
+    {
         let title = "Connect to Server";
         let dialog = Dialog::new();
         dialog.set_title(title);
         let area = dialog.get_content_area();
         let stack = Stack::new();
         let stack_switcher = StackSwitcher::new();
-        let simple = Self::connection_scheme_widget();
-        let advance = Self::connection_url_widget();
+        let (simple, scheme_form) = Self::connection_scheme_widget();
+        let (advance, url_entry) = Self::connection_url_widget();
+        if let Connection::Url(url) = self {
+            url_entry.set_text(url);
+        }
         let vbox = gtk::Box::new(Vertical, 0);
         stack.add_titled(&simple, "simple", "Simple");
         stack.add_titled(&advance, "advance", "Advance");
@@ -46,15 +132,47 @@ impl Connection {
         vbox.pack_start(&stack_box, true, true, 10);
         vbox.pack_start(&form_box, true, true, 10);
         stack_switcher.set_stack(Some(&stack));
-        let action_buttons = Self::create_connect_cancel_buttons();
+        let (action_buttons, ok_btn, cancel_btn) = Self::create_connect_cancel_buttons();
         vbox.pack_start(&action_buttons, true, true, 20);
         area.pack_start(&vbox, true, true, 0);
         dialog.set_default_size(300, 400);
         dialog.show_all();
+
+        let dialog_clone = dialog.clone();
+        ok_btn.connect_clicked(move |_| {
+            let connection = match stack.get_visible_child_name() {
+                Some(ref name) if name == "advance" => {
+                    Connection::Url(url_entry.get_text().to_string())
+                }
+                _ => Connection::Scheme(scheme_form.to_scheme()),
+            };
+            let url = connection.url();
+            match db::connect(&url) {
+                Ok(database) => {
+                    on_connect(&url, database);
+                    dialog_clone.close();
+                }
+                Err(e) => {
+                    let error = MessageDialog::new(
+                        Some(&dialog_clone),
+                        DialogFlags::MODAL,
+                        MessageType::Error,
+                        ButtonsType::Close,
+                        &e.to_string(),
+                    );
+                    error.run();
+                    error.close();
+                }
+            }
+        });
+        let dialog_clone = dialog.clone();
+        cancel_btn.connect_clicked(move |_| {
+            dialog_clone.close();
+        });
         dialog
     }
 
-    fn create_connect_cancel_buttons() -> gtk::Box {
+    fn create_connect_cancel_buttons() -> (gtk::Box, ToolButton, ToolButton) {
         let toolbox = Toolbar::new();
         let ok_icon = Image::from_icon_name(Some("dialog-apply"), IconSize::LargeToolbar);
         let ok_btn = ToolButton::new::<Image>(Some(&ok_icon), Some("Connect"));
@@ -66,19 +184,19 @@ impl Connection {
         toolbox.add(&ok_btn);
         let hbox = gtk::Box::new(Horizontal, 0);
         hbox.pack_end(&toolbox, false, false, 0);
-        hbox
+        (hbox, ok_btn, cancel_btn)
     }
 
-    fn connection_url_widget() -> gtk::Box {
+    fn connection_url_widget() -> (gtk::Box, Entry) {
         let bbox = gtk::Box::new(Horizontal, 10);
         let label = Label::new(Some("Connection Url: "));
         let entry = Entry::new();
         bbox.pack_start(&label, true, true, 0);
         bbox.pack_start(&entry, true, true, 0);
-        bbox
+        (bbox, entry)
     }
 
-    fn connection_scheme_widget() -> Grid {
+    fn connection_scheme_widget() -> (Grid, SchemeForm) {
         let grid = Grid::new();
 
         let platform_lbl = Label::new(Some("platform"));
@@ -87,6 +205,7 @@ impl Connection {
         platform_cb.append(Some("my"), "mysql");
         platform_cb.append(Some("sq"), "sqlite");
         grid.attach(&platform_lbl, 0, 0, 1, 1);
+        platform_cb.set_active_id(Some("pg"));
         grid.attach(&platform_cb, 1, 0, 1, 1);
 
         let host_lbl = Label::new(Some("host"));
@@ -115,6 +234,14 @@ impl Connection {
         grid.attach(&pwd_lbl, 0, 5, 1, 1);
         grid.attach(&pwd_entry, 1, 5, 1, 1);
 
-        grid
+        let form = SchemeForm {
+            platform: platform_cb,
+            host: host_entry,
+            port: port_entry,
+            database: db_entry,
+            user: user_entry,
+            password: pwd_entry,
+        };
+        (grid, form)
     }
 }
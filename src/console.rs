@@ -0,0 +1,492 @@
+//! free-form sql console, opened as a tab next to the data tabs
+use crate::audit::{self, Change};
+use crate::catalog;
+use crate::db::{self, DbError, Platform, QueryResult, ResultSet, SharedDb, Value};
+use crate::guard;
+use crate::sql::{self, StatementKind, TokenKind};
+use crate::tab::DataTab;
+use gdk::keys::constants as key;
+use gdk::ModifierType;
+use glib::Type;
+use gtk;
+use gtk::prelude::*;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::{
+    Adjustment, CellRendererText, IconSize, Image, Inhibit, Label, ListStore, Notebook, Paned,
+    ScrolledWindow, TextBuffer, TextTag, TextTagTable, TextView, ToolButton, Toolbar, TreeView,
+    TreeViewColumn, Window,
+};
+use std::time::Instant;
+
+// tag name and color used for highlighting each kind of token
+static HIGHLIGHTS: &[(&str, &str)] = &[
+    ("keyword", "#204a87"),
+    ("literal", "#a40000"),
+    ("number", "#4e9a06"),
+    ("comment", "#888a85"),
+];
+
+/// the table a result was selected from, when edits can be written back to it
+#[derive(Clone)]
+struct EditTarget {
+    platform: Platform,
+    table: String,
+    key: Vec<String>,
+    /// the result columns that are a bare column of the table, the others are computed
+    plain: Vec<bool>,
+}
+
+#[derive(Clone)]
+pub struct SqlConsole {
+    db: SharedDb,
+    run: ToolButton,
+    buffer: TextBuffer,
+    editor: TextView,
+    results: Notebook,
+    status: Label,
+    history: ListStore,
+    history_view: TreeView,
+}
+
+impl SqlConsole {
+    pub fn new(db: SharedDb) -> Self {
+        let run_icon = Image::from_icon_name(Some("media-playback-start"), IconSize::SmallToolbar);
+        let run = ToolButton::new::<Image>(Some(&run_icon), Some("Run (Ctrl+Enter)"));
+        run.set_is_important(true);
+        let buffer = TextBuffer::new(Some(&Self::create_tag_table()));
+        let editor = TextView::with_buffer(&buffer);
+        editor.set_monospace(true);
+        editor.set_hexpand(true);
+        editor.set_vexpand(true);
+        let results = Notebook::new();
+        results.set_scrollable(true);
+        let status = Label::new(None);
+        status.set_xalign(0.0);
+        let (history_view, history) = Self::create_history_list();
+
+        SqlConsole {
+            db: db,
+            run: run,
+            buffer: buffer,
+            editor: editor,
+            results: results,
+            status: status,
+            history: history,
+            history_view: history_view,
+        }
+    }
+
+    pub fn build_widget(&self) -> gtk::Box {
+        let vbox = gtk::Box::new(Vertical, 0);
+        let toolbar = Toolbar::new();
+        toolbar.add(&self.run);
+        vbox.add(&toolbar);
+
+        let editor_scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        editor_scroll.add(&self.editor);
+        let history_scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        history_scroll.add(&self.history_view);
+        history_scroll.set_size_request(200, -1);
+        let editor_pane = Paned::new(Horizontal);
+        editor_pane.pack1(&editor_scroll, true, false);
+        editor_pane.pack2(&history_scroll, false, true);
+
+        let pane = Paned::new(Vertical);
+        pane.pack1(&editor_pane, true, false);
+        pane.pack2(&self.results, true, false);
+        pane.set_position(200);
+        vbox.pack_start(&pane, true, true, 0);
+        vbox.pack_start(&self.status, false, false, 5);
+
+        self.init_highlighting();
+        self.init_run_actions();
+        self.init_history_selection();
+        vbox
+    }
+
+    fn create_tag_table() -> TextTagTable {
+        let table = TextTagTable::new();
+        for (name, color) in HIGHLIGHTS.iter() {
+            let tag = TextTag::new(Some(name));
+            tag.set_property_foreground(Some(color));
+            if *name == "keyword" {
+                tag.set_property_weight(700);
+            }
+            table.add(&tag);
+        }
+        table
+    }
+
+    fn create_history_list() -> (TreeView, ListStore) {
+        let tree = TreeView::new();
+        let store = ListStore::new(&[Type::String]);
+        tree.set_model(Some(&store));
+        let column = TreeViewColumn::new();
+        let cell = CellRendererText::new();
+        column.set_title("History");
+        column.pack_start(&cell, true);
+        column.add_attribute(&cell, "text", 0);
+        tree.append_column(&column);
+        (tree, store)
+    }
+
+    fn init_highlighting(&self) {
+        self.buffer.connect_changed(|buffer| {
+            Self::highlight(buffer);
+        });
+    }
+
+    fn highlight(buffer: &TextBuffer) {
+        let (start, end) = (buffer.get_start_iter(), buffer.get_end_iter());
+        buffer.remove_all_tags(&start, &end);
+        let text = match buffer.get_text(&start, &end, false) {
+            Some(text) => text,
+            None => return,
+        };
+        for token in sql::tokenize(&text) {
+            let tag = match token.kind {
+                TokenKind::Keyword => "keyword",
+                TokenKind::Literal => "literal",
+                TokenKind::Number => "number",
+                TokenKind::Comment => "comment",
+                _ => continue,
+            };
+            buffer.apply_tag_by_name(
+                tag,
+                &buffer.get_iter_at_offset(token.start as i32),
+                &buffer.get_iter_at_offset(token.end as i32),
+            );
+        }
+    }
+
+    fn init_run_actions(&self) {
+        let this = self.clone();
+        self.run.connect_clicked(move |_| {
+            this.run_current();
+        });
+        let this = self.clone();
+        self.editor.connect_key_press_event(move |_, event| {
+            let ctrl = event.get_state().contains(ModifierType::CONTROL_MASK);
+            if ctrl && (event.get_keyval() == key::Return || event.get_keyval() == key::KP_Enter) {
+                this.run_current();
+                Inhibit(true)
+            } else {
+                Inhibit(false)
+            }
+        });
+    }
+
+    fn init_history_selection(&self) {
+        let this = self.clone();
+        self.history_view.connect_row_activated(move |_, path, _| {
+            if let Some(iter) = this.history.get_iter(path) {
+                let sql: Option<String> = this.history.get_value(&iter, 0).get().ok().flatten();
+                if let Some(sql) = sql {
+                    this.buffer.set_text(&sql);
+                }
+            }
+        });
+    }
+
+    /// run the selected statements, or the statement under the cursor when nothing is selected
+    fn run_current(&self) {
+        let statements = match self.buffer.get_selection_bounds() {
+            Some((start, end)) => {
+                let selected = self.buffer.get_text(&start, &end, false);
+                selected
+                    .map(|s| sql::split_statements(&s))
+                    .unwrap_or_default()
+            }
+            None => {
+                let (start, end) = (self.buffer.get_start_iter(), self.buffer.get_end_iter());
+                let text = self
+                    .buffer
+                    .get_text(&start, &end, false)
+                    .map(|t| t.to_string())
+                    .unwrap_or_default();
+                let cursor = self.buffer.get_property_cursor_position() as usize;
+                let chars: Vec<char> = text.chars().collect();
+                sql::statement_at(&text, cursor)
+                    .map(|(start, end)| vec![chars[start..end].iter().collect()])
+                    .unwrap_or_default()
+            }
+        };
+        if !statements.is_empty() {
+            self.run_statements(&statements);
+        }
+    }
+
+    fn run_statements(&self, statements: &[String]) {
+        while self.results.get_n_pages() > 0 {
+            self.results.remove_page(None);
+        }
+        let session = self
+            .db
+            .borrow()
+            .as_ref()
+            .map(|db| (db.session().manual_commit, db.session().guard.clone()));
+        let (manual_commit, guard) = match session {
+            Some(session) => session,
+            None => {
+                self.status
+                    .set_text("Not connected, connect to a server first");
+                return;
+            }
+        };
+        if !self.confirm_guarded(guard, statements) {
+            self.status.set_text("Cancelled");
+            return;
+        }
+        let mut db = self.db.borrow_mut();
+        let db = match db.as_mut() {
+            Some(db) => db,
+            None => return,
+        };
+        for (i, statement) in statements.iter().enumerate() {
+            self.add_to_history(statement);
+            let started = Instant::now();
+            let result = match sql::statement_kind(statement) {
+                // the transaction is the one of the toolbar, ended by its commit or rollback
+                StatementKind::Transaction if manual_commit => Err(DbError::Unsupported(
+                    "use the commit and rollback of the toolbar".to_string(),
+                )),
+                StatementKind::Data => Self::save(db.as_mut(), statement, &[], &[]),
+                _ => db.execute(statement, &[]),
+            };
+            let elapsed = started.elapsed().as_millis();
+            let title = format!("Result {}", i + 1);
+            match result {
+                Ok(QueryResult::Rows(rs)) => {
+                    let summary = format!("{} rows in {} ms", rs.rows.len(), elapsed);
+                    let target = Self::edit_target(db.as_mut(), statement, &rs);
+                    let grid = self.create_result_grid(&rs, target);
+                    self.add_result_page(&title, &grid);
+                    self.status.set_text(&summary);
+                }
+                Ok(QueryResult::Affected(n)) => {
+                    let summary = format!("{} rows affected in {} ms", n, elapsed);
+                    self.add_result_page(&title, &Label::new(Some(&summary)));
+                    self.status.set_text(&summary);
+                }
+                Err(e) => {
+                    let error = format!("Error in statement {}: {}", i + 1, e);
+                    self.add_result_page(&title, &Label::new(Some(&error)));
+                    self.status.set_text(&error);
+                    break;
+                }
+            }
+        }
+        self.results.show_all();
+    }
+
+    /// on a guarded connection the statements that may change something run once its name is typed
+    fn confirm_guarded(&self, guard: Option<String>, statements: &[String]) -> bool {
+        let name = match guard {
+            Some(name) => name,
+            None => return true,
+        };
+        let changing: Vec<&str> = statements
+            .iter()
+            .filter(|s| sql::statement_kind(s) != StatementKind::Query)
+            .map(|s| s.trim())
+            .collect();
+        if changing.is_empty() {
+            return true;
+        }
+        guard::confirm_save(self.window().as_ref(), &name, &changing.join(";\n"))
+    }
+
+    fn window(&self) -> Option<Window> {
+        self.editor
+            .get_toplevel()
+            .and_then(|w| w.downcast::<Window>().ok())
+    }
+
+    /// Run a statement writing rows like the saves of the data tabs, so it is kept
+    /// with its `changes` in the open transaction, or committed when there is none.
+    fn save(
+        db: &mut dyn db::Database,
+        statement: &str,
+        params: &[Value],
+        changes: &[Change],
+    ) -> Result<QueryResult, DbError> {
+        db::begin_save(db)?;
+        let result = match db.execute(statement, params) {
+            Ok(result) => result,
+            Err(e) => {
+                db::cancel_save(db);
+                return Err(e);
+            }
+        };
+        if let Err(e) = db::end_save(db, 1, changes) {
+            db::cancel_save(db);
+            return Err(e);
+        }
+        Ok(result)
+    }
+
+    fn add_result_page<P: IsA<gtk::Widget>>(&self, title: &str, widget: &P) {
+        let page = self
+            .results
+            .append_page(widget, Some(&Label::new(Some(title))));
+        self.results.set_current_page(Some(page));
+    }
+
+    fn add_to_history(&self, statement: &str) {
+        let latest: Option<String> = self
+            .history
+            .get_iter_first()
+            .and_then(|iter| self.history.get_value(&iter, 0).get().ok().flatten());
+        if latest.as_deref() != Some(statement) {
+            let iter = self.history.insert(0);
+            self.history.set_value(&iter, 0, &statement.to_value());
+        }
+    }
+
+    /// The results are read-only, unless they come from a single table
+    /// and include all of its key columns, so an edit can be written back.
+    /// Only the columns selected by their bare name can be edited then,
+    /// and none on a read-only connection.
+    fn edit_target(
+        db: &mut dyn db::Database,
+        statement: &str,
+        rs: &ResultSet,
+    ) -> Option<EditTarget> {
+        if db.session().read_only {
+            return None;
+        }
+        let table = sql::single_table(statement)?;
+        let key = catalog::primary_key(db, &table).ok()?;
+        let plain = sql::plain_columns(statement, rs.columns.len());
+        let is_plain = |k: &String| matches!(rs.column_index(k), Some(i) if plain[i]);
+        if key.is_empty() || !key.iter().all(is_plain) {
+            return None;
+        }
+        Some(EditTarget {
+            platform: db.platform(),
+            table: table,
+            key: key,
+            plain: plain,
+        })
+    }
+
+    fn create_result_grid(&self, rs: &ResultSet, target: Option<EditTarget>) -> ScrolledWindow {
+        let (tree, store, cells) = DataTab::create_result_list(rs);
+        if let Some(target) = target {
+            for (i, cell) in cells.iter().enumerate() {
+                if !target.plain[i] {
+                    continue;
+                }
+                cell.set_property_editable(true);
+                let this = self.clone();
+                let store = store.clone();
+                let target = target.clone();
+                let columns = rs.columns.clone();
+                cell.connect_edited(move |_, path, new_text| {
+                    this.update_cell(&target, &columns, &store, &path, i, new_text);
+                });
+            }
+        }
+        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scroll.add(&tree);
+        scroll
+    }
+
+    fn update_cell(
+        &self,
+        target: &EditTarget,
+        columns: &[String],
+        store: &ListStore,
+        path: &gtk::TreePath,
+        column: usize,
+        new_text: &str,
+    ) {
+        // a computed column has no column of the table to write to
+        if !target.plain[column] {
+            return;
+        }
+        let iter = match store.get_iter(path) {
+            Some(iter) => iter,
+            None => return,
+        };
+        let mut params: Vec<Value> = vec![Some(new_text.to_string())];
+        let platform = target.platform;
+        let mut filter = vec![];
+        for k in target.key.iter() {
+            let index = match columns.iter().position(|c| c == k) {
+                Some(index) => index,
+                None => return,
+            };
+            let value: Value = store.get_value(&iter, index as i32).get().ok().flatten();
+            params.push(value);
+            filter.push(format!(
+                "{} = {}",
+                platform.quote_ident(k),
+                platform.placeholder(params.len())
+            ));
+        }
+        let update = format!(
+            "UPDATE {} SET {} = {} WHERE {}",
+            platform.quote_table(&target.table),
+            platform.quote_ident(&columns[column]),
+            platform.placeholder(1),
+            filter.join(" AND ")
+        );
+        let original: Vec<Value> = (0..columns.len())
+            .map(|i| store.get_value(&iter, i as i32).get().ok().flatten())
+            .collect();
+        let mut values = original.clone();
+        values[column] = Some(new_text.to_string());
+        let session = self.db.borrow().as_ref().map(|db| {
+            let session = db.session();
+            (session.database.clone(), session.guard.clone())
+        });
+        let (database, guard) = match session {
+            Some(session) => session,
+            None => return,
+        };
+        let change = Change::new(
+            &database,
+            &target.table,
+            columns,
+            &target.key,
+            Some(&original[..]),
+            Some(&values[..]),
+        );
+        if let Some(name) = guard {
+            let summary = audit::summary(&[change.clone()]);
+            if !guard::confirm_save(self.window().as_ref(), &name, &summary) {
+                self.status.set_text("Update cancelled");
+                return;
+            }
+        }
+        let result = match self.db.borrow_mut().as_mut() {
+            Some(db) => Self::save(db.as_mut(), &update, &params, &[change.clone()])
+                .map(|_| db.session().transaction.is_some()),
+            None => return,
+        };
+        match result {
+            Ok(in_transaction) => {
+                store.set_value(&iter, column as u32, &new_text.to_value());
+                // recorded in the audit log once the transaction is committed
+                let recorded = if in_transaction {
+                    Ok(())
+                } else {
+                    audit::record(&[change])
+                };
+                let status = match recorded {
+                    Ok(()) if in_transaction => {
+                        format!("Updated {} in the open transaction", target.table)
+                    }
+                    Ok(()) => format!("Updated {}", target.table),
+                    Err(e) => format!(
+                        "Updated {}, unable to write the audit log: {}",
+                        target.table, e
+                    ),
+                };
+                self.status.set_text(&status);
+            }
+            Err(e) => self.status.set_text(&format!("Update failed: {}", e)),
+        }
+    }
+}
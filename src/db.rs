@@ -0,0 +1,561 @@
+//! database access
+//!
+//! Values are passed around as text, the same way they are displayed and edited
+//! in the list and detail views. `None` is a NULL.
+use postgres::types::{Format, IsNull, ToSql, Type};
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+pub type Value = Option<String>;
+
+/// the connection shared by the window and its tabs, `None` while not connected
+pub type SharedDb = Rc<RefCell<Option<Box<dyn Database>>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Platform {
+    Postgres,
+    Mysql,
+    Sqlite,
+}
+
+impl Platform {
+    pub fn from_url(url: &str) -> Option<Self> {
+        let scheme = url.split("://").next()?;
+        match scheme {
+            "postgres" | "postgresql" => Some(Platform::Postgres),
+            "mysql" => Some(Platform::Mysql),
+            "sqlite" => Some(Platform::Sqlite),
+            _ => None,
+        }
+    }
+
+    /// the bind parameter for the nth (1 based) argument
+    pub fn placeholder(&self, n: usize) -> String {
+        match self {
+            Platform::Postgres => format!("${}", n),
+            Platform::Mysql => "?".to_string(),
+            Platform::Sqlite => format!("?{}", n),
+        }
+    }
+
+    pub fn quote_ident(&self, ident: &str) -> String {
+        match self {
+            Platform::Mysql => format!("`{}`", ident.replace('`', "``")),
+            _ => format!("\"{}\"", ident.replace('"', "\"\"")),
+        }
+    }
+
+    /// whether a failed create or alter table is rolled back with the transaction,
+    /// mysql commits each of them right away
+    pub fn transactional_ddl(&self) -> bool {
+        *self != Platform::Mysql
+    }
+
+    /// a string literal, for the conditions that are kept as text such as the filters
+    pub fn quote_literal(&self, value: &str) -> String {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+
+    /// quote a possibly schema qualified table name
+    pub fn quote_table(&self, table: &str) -> String {
+        table
+            .split('.')
+            .map(|part| self.quote_ident(&crate::sql::unquote(part)))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl ResultSet {
+    pub fn column_index(&self, column: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c == column)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum QueryResult {
+    Rows(ResultSet),
+    /// the number of rows affected by an insert, update or delete
+    Affected(u64),
+}
+
+impl QueryResult {
+    pub fn rows(self) -> ResultSet {
+        match self {
+            QueryResult::Rows(rs) => rs,
+            QueryResult::Affected(_) => ResultSet::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DbError {
+    InvalidUrl(String),
+    Connection(String),
+    Sql(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::InvalidUrl(url) => write!(f, "invalid connection url: {}", url),
+            DbError::Connection(e) => write!(f, "connection error: {}", e),
+            DbError::Sql(e) => write!(f, "{}", e),
+            DbError::Unsupported(e) => write!(f, "unsupported: {}", e),
+        }
+    }
+}
+
+impl Error for DbError {}
+
+/// the savepoint taken before the statements of a save in an open transaction
+static SAVEPOINT: &str = "datawindow_save";
+
+/// a transaction left open by the manual commit mode
+#[derive(Debug, Clone)]
+pub struct OpenTransaction {
+    pub started: Instant,
+    /// the statements saved in it so far
+    pub statements: usize,
+    /// the changes to record in the audit log once it is committed
+    pub changes: Vec<crate::audit::Change>,
+}
+
+/// How the saves of a connection are committed: right away, or in the manual commit mode
+/// into one transaction shared by all the tabs until it is committed or rolled back.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub manual_commit: bool,
+    pub transaction: Option<OpenTransaction>,
+    /// nothing is saved on the connection, see `set_read_only`
+    pub read_only: bool,
+    /// the name of the connection, to be typed before each save when it is guarded
+    pub guard: Option<String>,
+    /// the url of the database, see `database_url`
+    pub database: String,
+}
+
+impl Session {
+    fn of(url: &str) -> Self {
+        Session {
+            database: database_url(url),
+            ..Session::default()
+        }
+    }
+
+    pub fn pending_statements(&self) -> usize {
+        self.transaction.as_ref().map_or(0, |t| t.statements)
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        DbError::Sql(e.to_string())
+    }
+}
+
+impl From<postgres::Error> for DbError {
+    fn from(e: postgres::Error) -> Self {
+        match e.as_db_error() {
+            Some(db_error) => DbError::Sql(db_error.message().to_string()),
+            None => DbError::Sql(e.to_string()),
+        }
+    }
+}
+
+pub trait Database {
+    fn platform(&self) -> Platform;
+
+    /// execute a single statement, params are bound to the platform placeholders
+    fn execute(&mut self, sql: &str, params: &[Value]) -> Result<QueryResult, DbError>;
+
+    fn session(&self) -> &Session;
+
+    fn session_mut(&mut self) -> &mut Session;
+
+    /// receive the notifications sent by the other sessions on this channel
+    fn listen(&mut self, channel: &str) -> Result<(), DbError> {
+        Err(DbError::Unsupported(format!(
+            "no notifications on {:?} with {:?}",
+            channel,
+            self.platform()
+        )))
+    }
+
+    /// the payloads of the notifications received since the last call, without waiting for any
+    fn notifications(&mut self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// the url without the credentials and the parameters, it tells the databases apart
+pub fn database_url(url: &str) -> String {
+    let url = url.split('?').next().unwrap_or(url);
+    match url.find("://") {
+        Some(i) => {
+            let (scheme, rest) = url.split_at(i + 3);
+            // the credentials are before the @ of the host
+            let host_end = rest.find('/').unwrap_or(rest.len());
+            let start = rest[..host_end].rfind('@').map_or(0, |at| at + 1);
+            format!("{}{}", scheme, &rest[start..])
+        }
+        None => url.to_string(),
+    }
+}
+
+pub fn connect(url: &str) -> Result<Box<dyn Database>, DbError> {
+    match Platform::from_url(url) {
+        Some(Platform::Postgres) => {
+            let client = postgres::Client::connect(url, postgres::NoTls)
+                .map_err(|e| DbError::Connection(e.to_string()))?;
+            Ok(Box::new(PostgresDb {
+                client: client,
+                backend_pid: None,
+                session: Session::of(url),
+            }))
+        }
+        Some(Platform::Sqlite) => {
+            let path = url.trim_start_matches("sqlite://");
+            let conn =
+                rusqlite::Connection::open(path).map_err(|e| DbError::Connection(e.to_string()))?;
+            Ok(Box::new(SqliteDb {
+                conn: conn,
+                session: Session::of(url),
+            }))
+        }
+        Some(Platform::Mysql) => Err(DbError::Unsupported(
+            "mysql connections are not supported yet".to_string(),
+        )),
+        None => Err(DbError::InvalidUrl(url.to_string())),
+    }
+}
+
+/// Start the statements of a save: a transaction of their own, or a savepoint
+/// of the open transaction in the manual commit mode, which is begun by the first save.
+pub fn begin_save(db: &mut dyn Database) -> Result<(), DbError> {
+    if db.session().read_only {
+        return Err(DbError::Unsupported(
+            "the connection is read-only".to_string(),
+        ));
+    }
+    if !db.session().manual_commit {
+        db.execute("BEGIN", &[])?;
+        return Ok(());
+    }
+    if db.session().transaction.is_none() {
+        db.execute("BEGIN", &[])?;
+        db.session_mut().transaction = Some(OpenTransaction {
+            started: Instant::now(),
+            statements: 0,
+            changes: vec![],
+        });
+    }
+    db.execute(&format!("SAVEPOINT {}", SAVEPOINT), &[])?;
+    Ok(())
+}
+
+/// undo the statements of a save, the saves before it stay in the open transaction
+pub fn cancel_save(db: &mut dyn Database) {
+    if db.session().transaction.is_some() && db.session().pending_statements() == 0 {
+        // nothing else is in the transaction begun for this save
+        if let Err(e) = rollback(db) {
+            println!("unable to roll back the save: {}", e);
+        }
+        return;
+    }
+    let result = if db.session().transaction.is_some() {
+        db.execute(&format!("ROLLBACK TO SAVEPOINT {}", SAVEPOINT), &[])
+            .and_then(|_| db.execute(&format!("RELEASE SAVEPOINT {}", SAVEPOINT), &[]))
+    } else {
+        db.execute("ROLLBACK", &[])
+    };
+    if let Err(e) = result {
+        println!("unable to roll back the save: {}", e);
+    }
+}
+
+/// commit the statements of a save, or keep them with their changes in the open transaction
+pub fn end_save(
+    db: &mut dyn Database,
+    statements: usize,
+    changes: &[crate::audit::Change],
+) -> Result<(), DbError> {
+    if db.session().transaction.is_none() {
+        db.execute("COMMIT", &[])?;
+        return Ok(());
+    }
+    db.execute(&format!("RELEASE SAVEPOINT {}", SAVEPOINT), &[])?;
+    if let Some(transaction) = db.session_mut().transaction.as_mut() {
+        transaction.statements += statements;
+        transaction.changes.extend(changes.iter().cloned());
+    }
+    Ok(())
+}
+
+/// commit the open transaction, returns the changes saved in it
+pub fn commit(db: &mut dyn Database) -> Result<Vec<crate::audit::Change>, DbError> {
+    if db.session().transaction.is_none() {
+        return Ok(vec![]);
+    }
+    db.execute("COMMIT", &[])?;
+    Ok(db
+        .session_mut()
+        .transaction
+        .take()
+        .map(|t| t.changes)
+        .unwrap_or_default())
+}
+
+/// roll back the open transaction, its changes are forgotten even when the rollback fails
+pub fn rollback(db: &mut dyn Database) -> Result<(), DbError> {
+    if db.session_mut().transaction.take().is_none() {
+        return Ok(());
+    }
+    db.execute("ROLLBACK", &[])?;
+    Ok(())
+}
+
+/// Refuse the saves of the connection, and have the server refuse any write of the session
+/// on the platforms that allow it.
+pub fn set_read_only(db: &mut dyn Database) -> Result<(), DbError> {
+    db.session_mut().read_only = true;
+    let sql = match db.platform() {
+        Platform::Postgres => "SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY",
+        Platform::Sqlite => "PRAGMA query_only = ON",
+        Platform::Mysql => "SET SESSION TRANSACTION READ ONLY",
+    };
+    db.execute(sql, &[])?;
+    Ok(())
+}
+
+struct SqliteDb {
+    conn: rusqlite::Connection,
+    session: Session,
+}
+
+impl Database for SqliteDb {
+    fn platform(&self) -> Platform {
+        Platform::Sqlite
+    }
+
+    fn session(&self) -> &Session {
+        &self.session
+    }
+
+    fn session_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
+
+    fn execute(&mut self, sql: &str, params: &[Value]) -> Result<QueryResult, DbError> {
+        let mut stmt = self.conn.prepare(sql)?;
+        if stmt.column_count() == 0 {
+            let affected = stmt.execute(params)?;
+            return Ok(QueryResult::Affected(affected as u64));
+        }
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let mut rows = stmt.query(params)?;
+        let mut data = vec![];
+        while let Some(row) = rows.next()? {
+            let mut record = Vec::with_capacity(columns.len());
+            for i in 0..columns.len() {
+                record.push(sqlite_value_to_text(row.get_raw_checked(i)?));
+            }
+            data.push(record);
+        }
+        Ok(QueryResult::Rows(ResultSet {
+            columns,
+            rows: data,
+        }))
+    }
+}
+
+fn sqlite_value_to_text(value: rusqlite::types::ValueRef) -> Value {
+    use rusqlite::types::ValueRef;
+    match value {
+        ValueRef::Null => None,
+        ValueRef::Integer(v) => Some(v.to_string()),
+        ValueRef::Real(v) => Some(v.to_string()),
+        ValueRef::Text(v) => Some(String::from_utf8_lossy(v).to_string()),
+        ValueRef::Blob(v) => Some(format!("<{} bytes>", v.len())),
+    }
+}
+
+struct PostgresDb {
+    client: postgres::Client,
+    /// set once listening, to leave out the notifications sent by this session
+    backend_pid: Option<i32>,
+    session: Session,
+}
+
+impl Database for PostgresDb {
+    fn platform(&self) -> Platform {
+        Platform::Postgres
+    }
+
+    fn session(&self) -> &Session {
+        &self.session
+    }
+
+    fn session_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
+
+    fn execute(&mut self, sql: &str, params: &[Value]) -> Result<QueryResult, DbError> {
+        let sql = sql.trim().trim_end_matches(';');
+        if params.is_empty() {
+            return self.simple_query(sql);
+        }
+        let stmt = self.client.prepare(sql)?;
+        let params: Vec<TextParam> = params.iter().map(|p| TextParam(p.as_deref())).collect();
+        let params: Vec<&(dyn ToSql + Sync)> =
+            params.iter().map(|p| p as &(dyn ToSql + Sync)).collect();
+        if stmt.columns().is_empty() {
+            let affected = self.client.execute(&stmt, &params)?;
+            return Ok(QueryResult::Affected(affected));
+        }
+        // The results are decoded as binary, so every column is cast to text on the server
+        // instead of having to know how to decode each of the postgres types here.
+        // The positional column aliases also take care of duplicate column names.
+        let columns: Vec<String> = stmt
+            .columns()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect();
+        let aliases: Vec<String> = (0..columns.len()).map(|i| format!("c{}", i)).collect();
+        let casted: Vec<String> = aliases.iter().map(|a| format!("{}::text", a)).collect();
+        let wrapped = format!(
+            "WITH q({}) AS ({}) SELECT {} FROM q",
+            aliases.join(", "),
+            sql,
+            casted.join(", ")
+        );
+        let rows = self.client.query(wrapped.as_str(), &params)?;
+        let data = rows
+            .iter()
+            .map(|row| {
+                (0..columns.len())
+                    .map(|i| row.get::<_, Option<String>>(i))
+                    .collect()
+            })
+            .collect();
+        Ok(QueryResult::Rows(ResultSet {
+            columns,
+            rows: data,
+        }))
+    }
+
+    fn listen(&mut self, channel: &str) -> Result<(), DbError> {
+        let pid: i32 = self
+            .client
+            .query_one("SELECT pg_backend_pid()", &[])?
+            .get(0);
+        self.backend_pid = Some(pid);
+        self.client.batch_execute(&format!(
+            "LISTEN {}",
+            Platform::Postgres.quote_ident(channel)
+        ))?;
+        Ok(())
+    }
+
+    fn notifications(&mut self) -> Vec<String> {
+        use postgres::fallible_iterator::FallibleIterator;
+        let mut payloads = vec![];
+        let mut notifications = self.client.notifications();
+        // the non blocking iterator does not read the connection when no query is running,
+        // a short timeout does
+        let mut iter = notifications.timeout_iter(Duration::from_millis(1));
+        loop {
+            match iter.next() {
+                Ok(Some(notification)) => {
+                    if Some(notification.process_id()) != self.backend_pid {
+                        payloads.push(notification.payload().to_string());
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    println!("unable to read the notifications: {}", e);
+                    break;
+                }
+            }
+        }
+        payloads
+    }
+}
+
+impl PostgresDb {
+    // the simple query protocol returns every value as text,
+    // and works for statements that can not be prepared such as `SHOW` or `EXPLAIN`
+    fn simple_query(&mut self, sql: &str) -> Result<QueryResult, DbError> {
+        use postgres::SimpleQueryMessage;
+        let mut columns = vec![];
+        let mut data = vec![];
+        let mut affected = None;
+        for message in self.client.simple_query(sql)? {
+            match message {
+                SimpleQueryMessage::Row(row) => {
+                    if columns.is_empty() {
+                        columns = row.columns().iter().map(|c| c.name().to_string()).collect();
+                    }
+                    data.push(
+                        (0..row.len())
+                            .map(|i| row.get(i).map(String::from))
+                            .collect(),
+                    );
+                }
+                SimpleQueryMessage::CommandComplete(n) => affected = Some(n),
+                _ => (),
+            }
+        }
+        match (columns.is_empty(), affected) {
+            // a select returning no rows has no row to take the column names from
+            (true, Some(n)) if !sql.to_uppercase().starts_with("SELECT") => {
+                Ok(QueryResult::Affected(n))
+            }
+            _ => Ok(QueryResult::Rows(ResultSet {
+                columns,
+                rows: data,
+            })),
+        }
+    }
+}
+
+/// A parameter that is sent to postgres in text format,
+/// letting the server parse it into whatever type the parameter is.
+#[derive(Debug)]
+struct TextParam<'a>(Option<&'a str>);
+
+impl<'a> ToSql for TextParam<'a> {
+    fn to_sql(
+        &self,
+        _ty: &Type,
+        out: &mut bytes::BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        match self.0 {
+            Some(text) => {
+                out.extend_from_slice(text.as_bytes());
+                Ok(IsNull::No)
+            }
+            None => Ok(IsNull::Yes),
+        }
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+
+    fn encode_format(&self, _ty: &Type) -> Format {
+        Format::Text
+    }
+
+    postgres::types::to_sql_checked!();
+}
@@ -0,0 +1,894 @@
+//! the definition of a table as edited in the table editor,
+//! and the statements that create the table or alter it to the edited definition
+//!
+//! Sqlite only renames and adds columns, for the other changes the table is rebuilt:
+//! a new table is created, the rows are copied into it and it replaces the old one.
+use crate::catalog::{self, ForeignKey};
+use crate::db::{Database, DbError, Platform};
+use crate::sql::{tokenize, unquote, TokenKind};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    /// the name in the database, none for a column that is added
+    pub original: Option<String>,
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    /// an sql expression, ie: `0`, `'none'` or `now()`
+    pub default: Option<String>,
+    pub primary_key: bool,
+    /// ie: `NOCASE`, kept when sqlite rebuilds the table
+    pub collation: Option<String>,
+    /// the sqlite rowids of the key are never used again, kept when the table is rebuilt
+    pub autoincrement: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexDef {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableDef {
+    /// schema qualified when the database has schemas
+    pub name: String,
+    pub columns: Vec<ColumnDef>,
+    /// The unique constraints other than the primary key.
+    /// Like the foreign keys and indexes of the table, they are on the original column names.
+    pub unique_keys: Vec<Vec<String>>,
+    pub checks: Vec<String>,
+    pub foreign_keys: Vec<ForeignKey>,
+    /// the indexes that are not made for a constraint
+    pub indexes: Vec<IndexDef>,
+    /// the create trigger statements, made again when sqlite rebuilds the table
+    pub triggers: Vec<String>,
+}
+
+/// the statements of a change, as previewed and run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    pub statements: Vec<String>,
+    /// whether the statements are run in one transaction
+    pub transactional: bool,
+    /// sqlite checks the foreign keys when a rebuilt table is dropped, they are off meanwhile
+    pub foreign_keys_off: bool,
+}
+
+impl ColumnDef {
+    pub fn new(name: &str, data_type: &str) -> Self {
+        ColumnDef {
+            original: None,
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            nullable: true,
+            default: None,
+            primary_key: false,
+            collation: None,
+            autoincrement: false,
+        }
+    }
+
+    /// the column as in a create table statement
+    fn sql(&self, platform: Platform) -> String {
+        let mut sql = format!("{} {}", platform.quote_ident(&self.name), self.data_type);
+        if let Some(collation) = &self.collation {
+            sql.push_str(&format!(" COLLATE {}", collation));
+        }
+        if !self.nullable {
+            sql.push_str(" NOT NULL");
+        }
+        if let Some(default) = &self.default {
+            sql.push_str(&format!(" DEFAULT {}", default));
+        }
+        sql
+    }
+}
+
+impl TableDef {
+    /// read the definition of an existing table
+    pub fn load(db: &mut dyn Database, table: &str) -> Result<Self, DbError> {
+        let platform = db.platform();
+        let key = catalog::primary_key(db, table)?;
+        let clauses = catalog::column_clauses(db, table)?;
+        let columns = catalog::columns(db, table)?
+            .into_iter()
+            .map(|info| {
+                let clauses = clauses
+                    .iter()
+                    .find(|c| c.name.eq_ignore_ascii_case(&info.name))
+                    .cloned()
+                    .unwrap_or_default();
+                ColumnDef {
+                    original: Some(info.name.clone()),
+                    data_type: info.type_name(),
+                    nullable: info.nullable,
+                    // the rowid of an integer key is not a default of the column
+                    default: info
+                        .default
+                        .clone()
+                        .filter(|d| !(platform == Platform::Sqlite && d == "rowid")),
+                    primary_key: key.contains(&info.name),
+                    name: info.name,
+                    collation: clauses.collation,
+                    autoincrement: clauses.autoincrement,
+                }
+            })
+            .collect();
+        let unique_keys: Vec<Vec<String>> = catalog::unique_keys(db, table)?
+            .into_iter()
+            .filter(|k| *k != key)
+            .collect();
+        // the indexes of the constraints come with them
+        let indexes = catalog::indexes(db, table)?
+            .into_iter()
+            .filter(|i| i.definition.is_some())
+            .filter(|i| !(i.unique && (i.columns == key || unique_keys.contains(&i.columns))))
+            .map(|i| IndexDef {
+                name: i.name,
+                columns: i.columns,
+                unique: i.unique,
+            })
+            .collect();
+        Ok(TableDef {
+            name: table.to_string(),
+            columns: columns,
+            unique_keys: unique_keys,
+            checks: catalog::check_constraints(db, table)?,
+            foreign_keys: catalog::foreign_keys(db, table)?,
+            indexes: indexes,
+            triggers: catalog::triggers(db, table)?
+                .into_iter()
+                .map(|t| t.definition)
+                .collect(),
+        })
+    }
+
+    pub fn key(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .filter(|c| c.primary_key)
+            .map(|c| c.name.clone())
+            .collect()
+    }
+
+    /// the names are set and not repeated, every column has a type
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("The table has no name".to_string());
+        }
+        if self.columns.is_empty() {
+            return Err("The table has no columns".to_string());
+        }
+        for (i, column) in self.columns.iter().enumerate() {
+            if column.name.trim().is_empty() {
+                return Err(format!("Column {} has no name", i + 1));
+            }
+            if column.data_type.trim().is_empty() {
+                return Err(format!("Column {} has no type", column.name));
+            }
+            if self.columns[..i].iter().any(|c| c.name == column.name) {
+                return Err(format!("There are two columns named {}", column.name));
+            }
+        }
+        // the existing indexes keep the original names of the renamed columns
+        let names: Vec<&str> = self
+            .columns
+            .iter()
+            .flat_map(|c| c.original.iter().chain(Some(&c.name)))
+            .map(|name| name.as_str())
+            .collect();
+        for index in self.indexes.iter() {
+            if index.name.trim().is_empty() || index.columns.is_empty() {
+                return Err("An index has no name or no columns".to_string());
+            }
+            if let Some(missing) = index.columns.iter().find(|c| !names.contains(&c.as_str())) {
+                return Err(format!(
+                    "Index {} is on unknown column {}",
+                    index.name, missing
+                ));
+            }
+        }
+        for key in self.foreign_keys.iter() {
+            if key.columns.is_empty() || key.columns.len() != key.ref_columns.len() {
+                return Err(format!(
+                    "The foreign key on {} needs as many columns as it references",
+                    key.ref_table
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// the create table statement, under another name for a rebuild
+    fn create_table(&self, platform: Platform, name: &str) -> String {
+        let key = self.key();
+        // sqlite only has autoincrement on an integer key declared with its column
+        let autoincrement = self.columns.iter().find(|c| {
+            c.autoincrement
+                && key == [c.name.clone()]
+                && c.data_type.eq_ignore_ascii_case("integer")
+        });
+        let mut lines: Vec<String> = self
+            .columns
+            .iter()
+            .map(|c| match autoincrement {
+                Some(key) if key.name == c.name => {
+                    format!("{} PRIMARY KEY AUTOINCREMENT", c.sql(platform))
+                }
+                _ => c.sql(platform),
+            })
+            .collect();
+        let quote_all = |columns: &[String]| -> String {
+            columns
+                .iter()
+                .map(|c| platform.quote_ident(c))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if !key.is_empty() && autoincrement.is_none() {
+            lines.push(format!("PRIMARY KEY ({})", quote_all(&key)));
+        }
+        for unique in self.unique_keys.iter() {
+            lines.push(format!("UNIQUE ({})", quote_all(unique)));
+        }
+        lines.extend(self.checks.iter().cloned());
+        for key in self.foreign_keys.iter() {
+            lines.push(foreign_key_sql(platform, key));
+        }
+        format!(
+            "CREATE TABLE {} (\n    {}\n)",
+            platform.quote_table(name),
+            lines.join(",\n    ")
+        )
+    }
+
+    fn create_index(&self, platform: Platform, index: &IndexDef) -> String {
+        let columns: Vec<String> = index
+            .columns
+            .iter()
+            .map(|c| platform.quote_ident(c))
+            .collect();
+        format!(
+            "CREATE {}INDEX {} ON {} ({})",
+            if index.unique { "UNIQUE " } else { "" },
+            platform.quote_ident(&index.name),
+            platform.quote_table(&self.name),
+            columns.join(", ")
+        )
+    }
+
+    /// the index of this name, in the schema of the table
+    fn index_name(&self, platform: Platform, index: &str) -> String {
+        match catalog::split_table_name(&self.name) {
+            (Some(schema), _) => format!(
+                "{}.{}",
+                platform.quote_ident(&schema),
+                platform.quote_ident(index)
+            ),
+            (None, _) => platform.quote_ident(index),
+        }
+    }
+
+    /// the statements creating the table with its indexes
+    pub fn create_script(&self, platform: Platform) -> Result<Script, String> {
+        self.validate()?;
+        let mut statements = vec![self.create_table(platform, &self.name)];
+        for index in self.indexes.iter() {
+            statements.push(self.create_index(platform, index));
+        }
+        Ok(Script {
+            statements: statements,
+            transactional: platform.transactional_ddl(),
+            foreign_keys_off: false,
+        })
+    }
+
+    /// the statements altering the `original` definition of the table to this one
+    pub fn alter_script(&self, platform: Platform, original: &TableDef) -> Result<Script, String> {
+        self.validate()?;
+        let changes = Changes::new(original, self);
+        if changes.is_empty() {
+            return Err("Nothing was changed".to_string());
+        }
+        let statements = match platform {
+            Platform::Postgres => self.alter_statements(platform, original, &changes)?,
+            Platform::Sqlite if changes.needs_rebuild() => {
+                let script = Script {
+                    statements: self.rebuild_statements(original, &changes)?,
+                    transactional: true,
+                    foreign_keys_off: true,
+                };
+                return Ok(script);
+            }
+            Platform::Sqlite => self.alter_statements(platform, original, &changes)?,
+            Platform::Mysql => return Err("Altering mysql tables is not supported".to_string()),
+        };
+        Ok(Script {
+            statements: statements,
+            transactional: platform.transactional_ddl(),
+            foreign_keys_off: false,
+        })
+    }
+
+    /// alter table statements, for the changes that sqlite can make without a rebuild
+    fn alter_statements(
+        &self,
+        platform: Platform,
+        original: &TableDef,
+        changes: &Changes,
+    ) -> Result<Vec<String>, String> {
+        let table = platform.quote_table(&self.name);
+        let quote = |name: &str| platform.quote_ident(name);
+        if changes.key_changed && !original.key().is_empty() {
+            return Err("The primary key can only be set on a table that has none".to_string());
+        }
+        if !changes.dropped_keys.is_empty() {
+            return Err("Existing foreign keys can not be removed".to_string());
+        }
+        let mut statements = vec![];
+        for index in changes.dropped_indexes.iter() {
+            statements.push(format!("DROP INDEX {}", self.index_name(platform, index)));
+        }
+        for (from, to) in changes.renamed.iter() {
+            statements.push(format!(
+                "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                table,
+                quote(from),
+                quote(to)
+            ));
+        }
+        for column in changes.dropped.iter() {
+            statements.push(format!(
+                "ALTER TABLE {} DROP COLUMN {}",
+                table,
+                quote(column)
+            ));
+        }
+        for (old, new) in changes.changed.iter() {
+            let alter = format!("ALTER TABLE {} ALTER COLUMN {}", table, quote(&new.name));
+            if old.data_type != new.data_type {
+                statements.push(format!(
+                    "{} TYPE {} USING {}::{}",
+                    alter,
+                    new.data_type,
+                    quote(&new.name),
+                    new.data_type
+                ));
+            }
+            if old.nullable != new.nullable {
+                let nullable = if new.nullable { "DROP" } else { "SET" };
+                statements.push(format!("{} {} NOT NULL", alter, nullable));
+            }
+            if old.default != new.default {
+                statements.push(match &new.default {
+                    Some(default) => format!("{} SET DEFAULT {}", alter, default),
+                    None => format!("{} DROP DEFAULT", alter),
+                });
+            }
+        }
+        for column in changes.added.iter() {
+            statements.push(format!(
+                "ALTER TABLE {} ADD COLUMN {}",
+                table,
+                column.sql(platform)
+            ));
+        }
+        if changes.key_changed {
+            let key: Vec<String> = self.key().iter().map(|k| quote(k)).collect();
+            statements.push(format!(
+                "ALTER TABLE {} ADD PRIMARY KEY ({})",
+                table,
+                key.join(", ")
+            ));
+        }
+        for key in changes.added_keys.iter() {
+            statements.push(format!(
+                "ALTER TABLE {} ADD {}",
+                table,
+                foreign_key_sql(platform, key)
+            ));
+        }
+        for index in changes.added_indexes.iter() {
+            statements.push(self.create_index(platform, index));
+        }
+        Ok(statements)
+    }
+
+    /// Create the new table, copy the rows of the columns that are kept and replace the old one.
+    /// The constraints and indexes follow the renamed columns, those on a dropped column
+    /// are dropped with it. The checks and triggers are sql text: the checks follow the renamed
+    /// columns, but the rebuild is refused when one of them uses a dropped column,
+    /// or when a trigger uses a renamed one.
+    fn rebuild_statements(
+        &self,
+        original: &TableDef,
+        changes: &Changes,
+    ) -> Result<Vec<String>, String> {
+        let platform = Platform::Sqlite;
+        let rename = |columns: &[String]| -> Option<Vec<String>> {
+            columns
+                .iter()
+                .map(
+                    |c| match changes.renamed.iter().find(|(from, _)| from == c) {
+                        Some((_, to)) => Some(to.clone()),
+                        None if changes.dropped.contains(c) => None,
+                        None => Some(c.clone()),
+                    },
+                )
+                .collect()
+        };
+        let mut rebuilt = self.clone();
+        rebuilt.checks = vec![];
+        for check in self.checks.iter() {
+            if let Some(column) = changes.dropped.iter().find(|c| uses_column(check, c)) {
+                return Err(format!(
+                    "The constraint {} uses the dropped column {}",
+                    check, column
+                ));
+            }
+            rebuilt
+                .checks
+                .push(rename_columns(platform, check, &changes.renamed));
+        }
+        for trigger in original.triggers.iter() {
+            let renamed = changes.renamed.iter().map(|(from, _)| from);
+            if let Some(column) = changes
+                .dropped
+                .iter()
+                .chain(renamed)
+                .find(|c| uses_column(trigger, c))
+            {
+                return Err(format!(
+                    "A trigger on {} uses the column {}, drop the trigger before renaming \
+                     or dropping the column:\n{}",
+                    original.name, column, trigger
+                ));
+            }
+        }
+        rebuilt.unique_keys = self.unique_keys.iter().filter_map(|k| rename(k)).collect();
+        rebuilt.foreign_keys = self
+            .foreign_keys
+            .iter()
+            .filter_map(|k| {
+                Some(ForeignKey {
+                    columns: rename(&k.columns)?,
+                    ..k.clone()
+                })
+            })
+            .collect();
+        rebuilt.indexes = self
+            .indexes
+            .iter()
+            .filter_map(|i| {
+                Some(IndexDef {
+                    columns: rename(&i.columns)?,
+                    ..i.clone()
+                })
+            })
+            .collect();
+        let (_, name) = catalog::split_table_name(&self.name);
+        let new_table = format!("{}_new", name);
+        let copied: Vec<&ColumnDef> = self
+            .columns
+            .iter()
+            .filter(|c| c.original.is_some())
+            .collect();
+        let targets: Vec<String> = copied
+            .iter()
+            .map(|c| platform.quote_ident(&c.name))
+            .collect();
+        let sources: Vec<String> = copied
+            .iter()
+            .filter_map(|c| c.original.as_ref())
+            .map(|c| platform.quote_ident(c))
+            .collect();
+        let table = platform.quote_table(&original.name);
+        let mut statements = vec![rebuilt.create_table(platform, &new_table)];
+        if !copied.is_empty() {
+            statements.push(format!(
+                "INSERT INTO {} ({}) SELECT {} FROM {}",
+                platform.quote_ident(&new_table),
+                targets.join(", "),
+                sources.join(", "),
+                table
+            ));
+        }
+        statements.push(format!("DROP TABLE {}", table));
+        statements.push(format!(
+            "ALTER TABLE {} RENAME TO {}",
+            platform.quote_ident(&new_table),
+            platform.quote_ident(&name)
+        ));
+        for index in rebuilt.indexes.iter() {
+            statements.push(rebuilt.create_index(platform, index));
+        }
+        // the triggers are dropped with the table, they are made again as they were
+        statements.extend(original.triggers.iter().cloned());
+        Ok(statements)
+    }
+}
+
+/// whether the sql names this column, ie: in a check or a trigger
+fn uses_column(sql: &str, column: &str) -> bool {
+    tokenize(sql)
+        .iter()
+        .any(|t| t.kind == TokenKind::Identifier && unquote(&t.text).eq_ignore_ascii_case(column))
+}
+
+/// the sql with the renamed columns, from the original name to the new one, under their new name
+fn rename_columns(platform: Platform, sql: &str, renamed: &[(String, String)]) -> String {
+    let mut renamed_sql = String::new();
+    for token in tokenize(sql) {
+        let name = unquote(&token.text);
+        let to = renamed
+            .iter()
+            .find(|(from, _)| from.eq_ignore_ascii_case(&name))
+            .map(|(_, to)| to);
+        match to {
+            Some(to) if token.kind == TokenKind::Identifier => {
+                renamed_sql.push_str(&platform.quote_ident(to))
+            }
+            _ => renamed_sql.push_str(&token.text),
+        }
+    }
+    renamed_sql
+}
+
+fn foreign_key_sql(platform: Platform, key: &ForeignKey) -> String {
+    let quote_all = |columns: &[String]| -> String {
+        columns
+            .iter()
+            .map(|c| platform.quote_ident(c))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "FOREIGN KEY ({}) REFERENCES {} ({})",
+        quote_all(&key.columns),
+        platform.quote_table(&key.ref_table),
+        quote_all(&key.ref_columns)
+    )
+}
+
+/// what differs between the original definition and the edited one
+struct Changes<'a> {
+    /// from the original name to the new one
+    renamed: Vec<(String, String)>,
+    dropped: Vec<String>,
+    /// the columns whose type, nullability or default changed, before and after
+    changed: Vec<(&'a ColumnDef, &'a ColumnDef)>,
+    added: Vec<&'a ColumnDef>,
+    key_changed: bool,
+    added_keys: Vec<&'a ForeignKey>,
+    dropped_keys: Vec<&'a ForeignKey>,
+    added_indexes: Vec<&'a IndexDef>,
+    dropped_indexes: Vec<String>,
+}
+
+impl<'a> Changes<'a> {
+    fn new(original: &'a TableDef, edited: &'a TableDef) -> Self {
+        let mut renamed = vec![];
+        let mut changed = vec![];
+        let mut added = vec![];
+        for column in edited.columns.iter() {
+            let old = column
+                .original
+                .as_ref()
+                .and_then(|name| original.columns.iter().find(|c| c.name == *name));
+            let old = match old {
+                Some(old) => old,
+                None => {
+                    added.push(column);
+                    continue;
+                }
+            };
+            if old.name != column.name {
+                renamed.push((old.name.clone(), column.name.clone()));
+            }
+            if old.data_type != column.data_type
+                || old.nullable != column.nullable
+                || old.default != column.default
+            {
+                changed.push((old, column));
+            }
+        }
+        let dropped = original
+            .columns
+            .iter()
+            .filter(|c| {
+                !edited
+                    .columns
+                    .iter()
+                    .any(|e| e.original == Some(c.name.clone()))
+            })
+            .map(|c| c.name.clone())
+            .collect();
+        // the original key under the new names of its columns
+        let original_key: Vec<String> = original
+            .key()
+            .iter()
+            .map(|k| {
+                edited
+                    .columns
+                    .iter()
+                    .find(|c| c.original.as_ref() == Some(k))
+                    .map(|c| c.name.clone())
+                    .unwrap_or_default()
+            })
+            .collect();
+        Changes {
+            renamed: renamed,
+            dropped: dropped,
+            changed: changed,
+            added: added,
+            key_changed: original_key != edited.key(),
+            added_keys: edited
+                .foreign_keys
+                .iter()
+                .filter(|k| !original.foreign_keys.contains(k))
+                .collect(),
+            dropped_keys: original
+                .foreign_keys
+                .iter()
+                .filter(|k| !edited.foreign_keys.contains(k))
+                .collect(),
+            added_indexes: edited
+                .indexes
+                .iter()
+                .filter(|i| !original.indexes.iter().any(|o| o.name == i.name))
+                .collect(),
+            dropped_indexes: original
+                .indexes
+                .iter()
+                .filter(|o| !edited.indexes.iter().any(|i| i.name == o.name))
+                .map(|o| o.name.clone())
+                .collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.renamed.is_empty()
+            && self.dropped.is_empty()
+            && self.changed.is_empty()
+            && self.added.is_empty()
+            && !self.key_changed
+            && self.added_keys.is_empty()
+            && self.dropped_keys.is_empty()
+            && self.added_indexes.is_empty()
+            && self.dropped_indexes.is_empty()
+    }
+
+    /// Sqlite renames columns and adds the ones that may be null or have a default,
+    /// anything else is done by rebuilding the table.
+    fn needs_rebuild(&self) -> bool {
+        !self.dropped.is_empty()
+            || !self.changed.is_empty()
+            || self.key_changed
+            || !self.added_keys.is_empty()
+            || !self.dropped_keys.is_empty()
+            || self
+                .added
+                .iter()
+                .any(|c| c.primary_key || (!c.nullable && c.default.is_none()))
+    }
+}
+
+impl Script {
+    /// the statements as they are run, with the transaction
+    pub fn preview(&self) -> String {
+        let mut lines = vec![];
+        if self.foreign_keys_off {
+            lines.push("PRAGMA foreign_keys = OFF".to_string());
+        }
+        if self.transactional {
+            lines.push("BEGIN".to_string());
+        }
+        lines.extend(self.statements.iter().cloned());
+        if self.transactional {
+            lines.push("COMMIT".to_string());
+        }
+        if self.foreign_keys_off {
+            lines.push("PRAGMA foreign_keys = ON".to_string());
+        }
+        format!("{};\n", lines.join(";\n\n"))
+    }
+
+    /// Run the statements, all of them or none when the platform has transactional ddl.
+    /// The foreign keys of sqlite are set back as they were, even on an error.
+    pub fn run(&self, db: &mut dyn Database) -> Result<(), DbError> {
+        // its own transaction would commit the saves of the open one
+        if db.session().transaction.is_some() {
+            return Err(DbError::Unsupported(
+                "changing the tables while a transaction is open, commit or roll it back first"
+                    .to_string(),
+            ));
+        }
+        let foreign_keys = match self.foreign_keys_off {
+            true => {
+                let rs = db.execute("PRAGMA foreign_keys", &[])?.rows();
+                let enabled = rs.rows.first().and_then(|r| r[0].clone());
+                db.execute("PRAGMA foreign_keys = OFF", &[])?;
+                enabled
+            }
+            false => None,
+        };
+        let result = self.run_statements(db);
+        if let Some(enabled) = foreign_keys {
+            db.execute(&format!("PRAGMA foreign_keys = {}", enabled), &[])?;
+        }
+        result
+    }
+
+    fn run_statements(&self, db: &mut dyn Database) -> Result<(), DbError> {
+        if !self.transactional {
+            for sql in self.statements.iter() {
+                db.execute(sql, &[])?;
+            }
+            return Ok(());
+        }
+        db.execute("BEGIN", &[])?;
+        for sql in self.statements.iter() {
+            if let Err(e) = db.execute(sql, &[]) {
+                let _ = db.execute("ROLLBACK", &[]);
+                return Err(e);
+            }
+        }
+        db.execute("COMMIT", &[])?;
+        Ok(())
+    }
+}
+
+/// the types offered in the table editor, any other can be typed
+pub fn common_types(platform: Platform) -> &'static [&'static str] {
+    match platform {
+        Platform::Postgres => &[
+            "integer",
+            "bigint",
+            "serial",
+            "numeric(10,2)",
+            "double precision",
+            "boolean",
+            "varchar(255)",
+            "text",
+            "date",
+            "timestamp",
+            "timestamptz",
+            "uuid",
+            "jsonb",
+            "bytea",
+        ],
+        Platform::Mysql => &[
+            "int",
+            "bigint",
+            "decimal(10,2)",
+            "double",
+            "boolean",
+            "varchar(255)",
+            "text",
+            "date",
+            "datetime",
+            "blob",
+        ],
+        Platform::Sqlite => &[
+            "INTEGER",
+            "REAL",
+            "NUMERIC",
+            "TEXT",
+            "BLOB",
+            "BOOLEAN",
+            "DATE",
+            "DATETIME",
+            "VARCHAR(255)",
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn customers() -> TableDef {
+        let mut id = ColumnDef::new("id", "integer");
+        id.original = Some("id".to_string());
+        id.nullable = false;
+        id.primary_key = true;
+        let mut name = ColumnDef::new("name", "text");
+        name.original = Some("name".to_string());
+        TableDef {
+            name: "customers".to_string(),
+            columns: vec![id, name],
+            indexes: vec![IndexDef {
+                name: "customers_name".to_string(),
+                columns: vec!["name".to_string()],
+                unique: false,
+            }],
+            ..TableDef::default()
+        }
+    }
+
+    #[test]
+    fn create_and_alter() {
+        let original = customers();
+        let script = original.create_script(Platform::Postgres).unwrap();
+        assert_eq!(
+            script.statements,
+            vec![
+                "CREATE TABLE \"customers\" (\n    \"id\" integer NOT NULL,\n    \"name\" text,\n    \
+                 PRIMARY KEY (\"id\")\n)",
+                "CREATE INDEX \"customers_name\" ON \"customers\" (\"name\")",
+            ]
+        );
+        let mut edited = original.clone();
+        edited.columns[1].name = "full_name".to_string();
+        edited.columns[1].nullable = false;
+        edited.columns.push(ColumnDef::new("email", "varchar(80)"));
+        let script = edited.alter_script(Platform::Postgres, &original).unwrap();
+        assert_eq!(
+            script.statements,
+            vec![
+                "ALTER TABLE \"customers\" RENAME COLUMN \"name\" TO \"full_name\"",
+                "ALTER TABLE \"customers\" ALTER COLUMN \"full_name\" SET NOT NULL",
+                "ALTER TABLE \"customers\" ADD COLUMN \"email\" varchar(80)",
+            ]
+        );
+        assert!(original
+            .alter_script(Platform::Postgres, &original)
+            .is_err());
+    }
+
+    #[test]
+    fn sqlite_rebuild() {
+        let original = customers();
+        let mut edited = original.clone();
+        edited.columns.push(ColumnDef::new("email", "TEXT"));
+        let script = edited.alter_script(Platform::Sqlite, &original).unwrap();
+        assert!(!script.foreign_keys_off);
+        edited.columns[1].name = "full_name".to_string();
+        edited.columns[1].data_type = "VARCHAR(80)".to_string();
+        let script = edited.alter_script(Platform::Sqlite, &original).unwrap();
+        assert!(script.foreign_keys_off);
+        assert_eq!(
+            &script.statements[1..],
+            &[
+                "INSERT INTO \"customers_new\" (\"id\", \"full_name\") \
+                 SELECT \"id\", \"name\" FROM \"customers\"",
+                "DROP TABLE \"customers\"",
+                "ALTER TABLE \"customers_new\" RENAME TO \"customers\"",
+                "CREATE INDEX \"customers_name\" ON \"customers\" (\"full_name\")",
+            ]
+        );
+    }
+
+    #[test]
+    fn sqlite_rebuild_keeps_checks_and_clauses() {
+        let mut original = customers();
+        original.columns[0].autoincrement = true;
+        original.columns[1].collation = Some("NOCASE".to_string());
+        original.checks = vec!["CHECK (length(name) > 0)".to_string()];
+        original.triggers = vec![
+            "CREATE TRIGGER audit AFTER INSERT ON customers BEGIN SELECT NEW.name; END".to_string(),
+        ];
+        let mut edited = original.clone();
+        edited.columns[1].data_type = "VARCHAR(80)".to_string();
+        let script = edited.alter_script(Platform::Sqlite, &original).unwrap();
+        assert_eq!(
+            script.statements[0],
+            "CREATE TABLE \"customers_new\" (\n    \
+             \"id\" integer NOT NULL PRIMARY KEY AUTOINCREMENT,\n    \
+             \"name\" VARCHAR(80) COLLATE NOCASE,\n    \
+             CHECK (length(name) > 0)\n)"
+        );
+        // the trigger uses the renamed column
+        edited.columns[1].name = "full_name".to_string();
+        assert!(edited.alter_script(Platform::Sqlite, &original).is_err());
+        original.triggers.clear();
+        edited.triggers.clear();
+        let script = edited.alter_script(Platform::Sqlite, &original).unwrap();
+        assert!(script.statements[0].contains("CHECK (length(\"full_name\") > 0)"));
+        // the check uses the dropped column
+        edited.columns.remove(1);
+        assert!(edited.alter_script(Platform::Sqlite, &original).is_err());
+    }
+}
@@ -0,0 +1,429 @@
+//! datawindow definitions: curated screens on a table or a query, with the labels, formats,
+//! editors and rules of the columns, the layouts, the lookups and child tables,
+//! and the operations allowed
+//!
+//! They are the toml files of the `datawindows` directory of the config directory,
+//! named by their file name and listed in the sidebar.
+//!
+//! ```toml
+//! title = "Customers"
+//! table = "customers"
+//! filter = "active"
+//! operations = ["insert", "update"]
+//!
+//! [columns.name]
+//! label = "Name"
+//! pattern = "^\\S"
+//! message = "must not start with a space"
+//!
+//! [columns.balance]
+//! label = "Balance"
+//! editor = { kind = "readonly" }
+//! format = { kind = "currency", symbol = "$" }
+//!
+//! [columns.status]
+//! editor = { kind = "choice", values = ["new", "regular", "gold"] }
+//!
+//! [columns.country]
+//! lookup = { table = "countries", key = "code", display = "name" }
+//!
+//! [list]
+//! sort = "name"
+//!
+//! [[list.columns]]
+//! name = "name"
+//! width = 200
+//!
+//! [[detail.pages]]
+//! title = "Customer"
+//!
+//! [[children]]
+//! title = "Orders"
+//! table = "orders"
+//! link = { customer_id = "id" }
+//! ```
+//!
+//! A datawindow can be on a `query` instead of a `table`, with `:name` parameters
+//! that are asked for when it is opened:
+//!
+//! ```toml
+//! title = "Late orders"
+//! query = "SELECT * FROM orders WHERE due < :day AND region = :region"
+//!
+//! [params.day]
+//! type = "date"
+//! label = "Due before"
+//!
+//! [params.region]
+//! default = "EU"
+//! ```
+//!
+//! It is read only, unless it selects from a single table, see `sql::single_table`.
+//! Then only the columns selected by their bare name are written, see `sql::plain_columns`.
+use crate::config::{self, ColumnConfig, TableConfig, ViewConfig};
+use crate::db::{Platform, Value};
+use crate::detail::Field;
+use crate::layout::FormLayout;
+use crate::sql;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// the name of the view made of the `list` of a definition
+pub static LIST_VIEW: &'static str = "List";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Definition {
+    /// the file name, set when loaded
+    #[serde(skip)]
+    pub name: String,
+    /// the name of the tab and in the sidebar, the file name when not set
+    pub title: Option<String>,
+    pub table: Option<String>,
+    /// the rows come from this select instead of the table
+    pub query: Option<String>,
+    /// the condition of the rows of the table that are shown, whatever the user filters on
+    pub filter: Option<String>,
+    /// the version or last update column checked before saving a row
+    pub version: Option<String>,
+    /// all the operations when not set
+    #[serde(default = "Operation::all")]
+    pub operations: Vec<Operation>,
+    #[serde(default)]
+    pub columns: BTreeMap<String, ColumnDefinition>,
+    /// the saved filters, by name
+    #[serde(default)]
+    pub filters: BTreeMap<String, String>,
+    /// the view of the list, all the columns when not set
+    pub list: Option<ViewConfig>,
+    /// the layout of the detail, one field per row when not set
+    pub detail: Option<FormLayout>,
+    #[serde(default)]
+    pub children: Vec<ChildDefinition>,
+    /// the parameters of the query, by name, they are text when not declared
+    #[serde(default)]
+    pub params: BTreeMap<String, ParamDefinition>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ParamDefinition {
+    /// the data type, ie: `date`, `integer`, `boolean`
+    #[serde(rename = "type")]
+    pub data_type: Option<String>,
+    /// the name of the parameter when not set
+    pub label: Option<String>,
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Operation {
+    pub fn all() -> Vec<Operation> {
+        vec![Operation::Insert, Operation::Update, Operation::Delete]
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ColumnDefinition {
+    /// shown as the header of the list and in the detail, the column name when not set
+    pub label: Option<String>,
+    #[serde(default)]
+    pub editor: Editor,
+    pub lookup: Option<Lookup>,
+    /// the mask, validation, format and aggregate, as in the table config
+    #[serde(flatten)]
+    pub config: ColumnConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Editor {
+    Text,
+    ReadOnly,
+    /// one of these values
+    Choice {
+        values: Vec<String>,
+    },
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Editor::Text
+    }
+}
+
+/// the value is the key of a row of another table, shown as its display column
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Lookup {
+    pub table: String,
+    pub key: String,
+    pub display: String,
+}
+
+impl Lookup {
+    /// the keys and the display values, in the order of the display values
+    pub fn query(&self, platform: Platform) -> String {
+        format!(
+            "SELECT {}, {} FROM {} ORDER BY 2",
+            platform.quote_ident(&self.key),
+            platform.quote_ident(&self.display),
+            platform.quote_table(&self.table)
+        )
+    }
+}
+
+/// the rows of another table that belong to the selected record
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ChildDefinition {
+    pub title: String,
+    pub table: String,
+    /// the columns of the child table, by the column of this table they are equal to
+    pub link: BTreeMap<String, String>,
+}
+
+impl Definition {
+    pub fn dir() -> PathBuf {
+        config::config_dir().join("datawindows")
+    }
+
+    /// the definition in `<name>.toml`, none when it is missing or invalid
+    pub fn load(name: &str) -> Option<Self> {
+        let path = config::table_file("datawindows", name);
+        let content = fs::read_to_string(&path).ok()?;
+        match Self::parse(name, &content) {
+            Ok(definition) => Some(definition),
+            Err(e) => {
+                println!("ignoring invalid datawindow {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn parse(name: &str, content: &str) -> Result<Self, String> {
+        let mut definition: Definition = toml::from_str(content).map_err(|e| e.to_string())?;
+        definition.name = name.to_string();
+        match (&definition.table, &definition.query) {
+            (None, None) => Err("there is neither a table nor a query".to_string()),
+            (Some(_), Some(_)) => Err("there are both a table and a query".to_string()),
+            _ => Ok(definition),
+        }
+    }
+
+    /// all the valid definitions of the directory, by name
+    pub fn load_all() -> Vec<Self> {
+        let entries = match fs::read_dir(Self::dir()) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "toml" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_string_lossy().to_string())
+            })
+            .collect();
+        names.sort();
+        names.iter().filter_map(|name| Self::load(name)).collect()
+    }
+
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.name)
+    }
+
+    /// the table that is edited, the table of the query, or the name for any other query
+    pub fn table_name(&self) -> String {
+        self.table
+            .clone()
+            .or_else(|| self.base_table())
+            .unwrap_or_else(|| self.name.clone())
+    }
+
+    /// the single table the query selects from, its rows can be edited
+    pub fn base_table(&self) -> Option<String> {
+        sql::single_table(self.query.as_deref()?)
+    }
+
+    /// which of the result columns are a bare column of the table, the computed ones are not written
+    pub fn plain_columns(&self, count: usize) -> Vec<bool> {
+        match &self.query {
+            Some(query) => sql::plain_columns(query, count),
+            None => vec![true; count],
+        }
+    }
+
+    /// the select the rows come from, none when they come straight from the table
+    pub fn select(&self, platform: Platform) -> Option<String> {
+        match (&self.query, &self.table, &self.filter) {
+            (Some(query), _, _) => Some(query.clone()),
+            (None, Some(table), Some(filter)) => Some(format!(
+                "SELECT * FROM {} WHERE {}",
+                platform.quote_table(table),
+                filter
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn allows(&self, operation: Operation) -> bool {
+        let editable = self.query.is_none() || self.base_table().is_some();
+        editable && self.operations.contains(&operation)
+    }
+
+    /// the names of the parameters of the query, in order of appearance
+    pub fn param_names(&self) -> Vec<String> {
+        self.query
+            .as_deref()
+            .map(sql::param_names)
+            .unwrap_or_default()
+    }
+
+    /// a field of the type of each parameter, with its label
+    pub fn param_fields(&self) -> Vec<(Field, String)> {
+        self.param_names()
+            .into_iter()
+            .map(|name| {
+                let param = self.params.get(&name).cloned().unwrap_or_default();
+                let data_type = param.data_type.as_deref().unwrap_or("text");
+                let label = param.label.unwrap_or_else(|| name.clone());
+                (Field::with_type(&name, data_type), label)
+            })
+            .collect()
+    }
+
+    /// the default value of each parameter
+    pub fn param_defaults(&self) -> Vec<Value> {
+        self.param_names()
+            .iter()
+            .map(|name| self.params.get(name).and_then(|p| p.default.clone()))
+            .collect()
+    }
+
+    pub fn label<'a>(&'a self, column: &'a str) -> &'a str {
+        self.columns
+            .get(column)
+            .and_then(|c| c.label.as_deref())
+            .unwrap_or(column)
+    }
+
+    pub fn editor(&self, column: &str) -> Editor {
+        self.columns
+            .get(column)
+            .map(|c| c.editor.clone())
+            .unwrap_or_default()
+    }
+
+    /// the config of the columns, with the list as the view shown
+    pub fn table_config(&self) -> TableConfig {
+        let mut config = TableConfig::default();
+        for (name, column) in self.columns.iter() {
+            config.columns.insert(name.clone(), column.config.clone());
+        }
+        config.filters = self.filters.clone();
+        config.version = self.version.clone();
+        if let Some(list) = &self.list {
+            config.views.insert(LIST_VIEW.to_string(), list.clone());
+            config.view = Some(LIST_VIEW.to_string());
+        }
+        config
+    }
+
+    /// the detail of the definition, or the automatic layout with the labels of the columns
+    pub fn form_layout(&self, fields: &[Field]) -> FormLayout {
+        if let Some(detail) = &self.detail {
+            return detail.clone();
+        }
+        let mut layout = FormLayout::automatic(fields);
+        for item in layout.pages.iter_mut().flat_map(|p| p.items.iter_mut()) {
+            if let Some(column) = &item.column {
+                item.label = self.columns.get(column).and_then(|c| c.label.clone());
+            }
+        }
+        layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CUSTOMERS: &'static str = r#"
+title = "Customers"
+table = "customers"
+filter = "active"
+operations = ["insert", "update"]
+
+[columns.balance]
+label = "Balance"
+editor = { kind = "readonly" }
+min = 0
+format = { kind = "currency", symbol = "$" }
+
+[columns.status]
+editor = { kind = "choice", values = ["new", "gold"] }
+
+[columns.country]
+lookup = { table = "countries", key = "code", display = "name" }
+
+[list]
+sort = "name"
+
+[[children]]
+title = "Orders"
+table = "orders"
+link = { customer_id = "id" }
+"#;
+
+    #[test]
+    fn parse_definition() {
+        let definition = Definition::parse("customers", CUSTOMERS).unwrap();
+        assert_eq!(definition.title(), "Customers");
+        assert!(definition.allows(Operation::Update));
+        assert!(!definition.allows(Operation::Delete));
+        assert_eq!(definition.label("balance"), "Balance");
+        assert_eq!(definition.label("name"), "name");
+        assert_eq!(definition.editor("balance"), Editor::ReadOnly);
+        assert_eq!(definition.editor("name"), Editor::Text);
+        assert_eq!(
+            definition.select(Platform::Sqlite).as_deref(),
+            Some("SELECT * FROM \"customers\" WHERE active")
+        );
+        assert_eq!(definition.children[0].link["customer_id"], "id");
+        let config = definition.table_config();
+        assert_eq!(config.columns["balance"].min, Some(0.0));
+        assert!(config.format("balance").is_some());
+        assert_eq!(config.view.as_deref(), Some(LIST_VIEW));
+    }
+
+    #[test]
+    fn query_definitions() {
+        let content = r#"
+query = "SELECT * FROM orders o JOIN customers c ON c.id = o.customer_id WHERE due < :day"
+params.day = { type = "date", default = "2024-01-31" }
+"#;
+        let definition = Definition::parse("late", content).unwrap();
+        assert!(!definition.allows(Operation::Insert));
+        assert_eq!(definition.table_name(), "late");
+        assert_eq!(definition.param_names(), vec!["day"]);
+        assert_eq!(definition.param_fields()[0].0.data_type(), "date");
+        assert_eq!(
+            definition.param_defaults(),
+            vec![Some("2024-01-31".to_string())]
+        );
+
+        let content = "query = \"SELECT * FROM orders WHERE region = :region\"";
+        let definition = Definition::parse("regional", content).unwrap();
+        assert!(definition.allows(Operation::Update));
+        assert_eq!(definition.table_name(), "orders");
+        assert_eq!(definition.param_fields()[0].1, "region");
+        assert!(Definition::parse("none", "title = \"None\"").is_err());
+    }
+}
@@ -0,0 +1,474 @@
+//! the form designer, a dialog to lay out the detail form of a table:
+//! the fields are dragged to their place, sections and pages are added,
+//! and the labels, tooltips and spans are set on the selected item
+use crate::layout::{FormLayout, LayoutItem, LayoutPage};
+use gdk;
+use gtk;
+use gtk::prelude::*;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::{
+    Button, ComboBoxText, DestDefaults, Dialog, DialogFlags, Entry, EventBox, Grid, Label,
+    Notebook, ResponseType, SpinButton, TargetEntry, TargetFlags, Window,
+};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// the drag target of the items, their page and index as text
+static ITEM_TARGET: &'static str = "application/x-datawindow-form-item";
+/// the response of the automatic layout button
+static AUTOMATIC: ResponseType = ResponseType::Other(1);
+/// the form has at least this many columns to drop the fields in
+static MIN_COLUMNS: i32 = 2;
+/// the most columns or rows an item spans
+static MAX_SPAN: f64 = 6.0;
+
+pub enum DesignerResponse {
+    Save(FormLayout),
+    /// lay out the fields one per row again
+    Automatic,
+}
+
+#[derive(Clone)]
+pub struct FormDesigner {
+    dialog: Dialog,
+    notebook: Notebook,
+    layout: Rc<RefCell<FormLayout>>,
+    /// all the columns of the table
+    columns: Rc<Vec<String>>,
+    /// the page and index of the selected item
+    selected: Rc<Cell<Option<(usize, usize)>>>,
+    page_title: Entry,
+    hidden: ComboBoxText,
+    label: Entry,
+    tooltip: Entry,
+    width: SpinButton,
+    height: SpinButton,
+    page: ComboBoxText,
+    remove: Button,
+    /// set while the widgets are filled, so it is not taken as an edit
+    updating: Rc<Cell<bool>>,
+}
+
+impl FormDesigner {
+    pub fn new(parent: Option<&Window>, columns: &[String], layout: FormLayout) -> Self {
+        let dialog = Dialog::with_buttons(
+            Some("Design the form"),
+            parent,
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Automatic layout", AUTOMATIC),
+                ("Cancel", ResponseType::Cancel),
+                ("Save", ResponseType::Accept),
+            ],
+        );
+        dialog.set_default_size(800, 500);
+        let designer = FormDesigner {
+            dialog: dialog,
+            notebook: Notebook::new(),
+            layout: Rc::new(RefCell::new(layout)),
+            columns: Rc::new(columns.to_vec()),
+            selected: Rc::new(Cell::new(None)),
+            page_title: Entry::new(),
+            hidden: ComboBoxText::new(),
+            label: Entry::new(),
+            tooltip: Entry::new(),
+            width: SpinButton::with_range(1.0, MAX_SPAN, 1.0),
+            height: SpinButton::with_range(1.0, MAX_SPAN, 1.0),
+            page: ComboBoxText::new(),
+            remove: Button::with_label("Remove"),
+            updating: Rc::new(Cell::new(false)),
+        };
+        designer.build_content();
+        designer.init_properties();
+        designer.rebuild();
+        designer
+    }
+
+    fn build_content(&self) {
+        let actions = gtk::Box::new(Horizontal, 5);
+        let add_field = Button::with_label("Add field");
+        let add_section = Button::with_label("Add section");
+        let add_page = Button::with_label("Add page");
+        let remove_page = Button::with_label("Remove page");
+        actions.add(&self.hidden);
+        actions.add(&add_field);
+        actions.add(&add_section);
+        actions.add(&add_page);
+        actions.add(&remove_page);
+        let this = self.clone();
+        add_field.connect_clicked(move |_| {
+            if let Some(column) = this.hidden.get_active_id() {
+                this.add_item(|top| LayoutItem::field(&column, 0, top));
+            }
+        });
+        let this = self.clone();
+        add_section.connect_clicked(move |_| {
+            this.add_item(|top| LayoutItem::section("Section", 0, top));
+        });
+        let this = self.clone();
+        add_page.connect_clicked(move |_| {
+            let pages = {
+                let mut layout = this.layout.borrow_mut();
+                let title = format!("Page {}", layout.pages.len() + 1);
+                layout.pages.push(LayoutPage::new(&title));
+                layout.pages.len()
+            };
+            this.rebuild();
+            this.notebook.set_current_page(Some(pages as u32 - 1));
+        });
+        let this = self.clone();
+        remove_page.connect_clicked(move |_| this.remove_current_page());
+
+        let page_title = gtk::Box::new(Horizontal, 5);
+        page_title.add(&Label::new(Some("Page title")));
+        page_title.pack_start(&self.page_title, true, true, 0);
+
+        let left = gtk::Box::new(Vertical, 5);
+        left.add(&actions);
+        left.add(&page_title);
+        left.pack_start(&self.notebook, true, true, 0);
+
+        let grid = Grid::new();
+        grid.set_row_spacing(5);
+        grid.set_column_spacing(5);
+        let rows: Vec<(&str, gtk::Widget)> = vec![
+            ("Label", self.label.clone().upcast()),
+            ("Tooltip", self.tooltip.clone().upcast()),
+            ("Columns", self.width.clone().upcast()),
+            ("Rows", self.height.clone().upcast()),
+            ("Page", self.page.clone().upcast()),
+            ("", self.remove.clone().upcast()),
+        ];
+        for (i, (label, widget)) in rows.iter().enumerate() {
+            let label = Label::new(Some(label));
+            label.set_xalign(0.0);
+            grid.attach(&label, 0, i as i32, 1, 1);
+            grid.attach(widget, 1, i as i32, 1, 1);
+        }
+
+        let hbox = gtk::Box::new(Horizontal, 10);
+        hbox.pack_start(&left, true, true, 0);
+        hbox.pack_start(&grid, false, false, 0);
+        let content = self.dialog.get_content_area();
+        content.set_border_width(10);
+        content.pack_start(&hbox, true, true, 0);
+    }
+
+    /// the edits of the selected item and of the current page
+    fn init_properties(&self) {
+        let this = self.clone();
+        self.label.connect_changed(move |entry| {
+            let text = entry.get_text().to_string();
+            this.edit_selected(true, |item| {
+                item.label = Some(text).filter(|t| !t.is_empty());
+            });
+        });
+        let this = self.clone();
+        self.tooltip.connect_changed(move |entry| {
+            let text = entry.get_text().to_string();
+            this.edit_selected(false, |item| {
+                item.tooltip = Some(text).filter(|t| !t.is_empty());
+            });
+        });
+        let this = self.clone();
+        self.width.connect_value_changed(move |spin| {
+            let width = spin.get_value_as_int();
+            this.edit_selected(true, |item| {
+                item.width = width;
+            });
+        });
+        let this = self.clone();
+        self.height.connect_value_changed(move |spin| {
+            let height = spin.get_value_as_int();
+            this.edit_selected(true, |item| {
+                item.height = height;
+            });
+        });
+        let this = self.clone();
+        self.page.connect_changed(move |combo| {
+            let page = combo.get_active().map(|p| p as usize);
+            if let (Some(page), false) = (page, this.updating.get()) {
+                this.move_selected_to_page(page);
+            }
+        });
+        let this = self.clone();
+        self.remove.connect_clicked(move |_| {
+            if let Some((page, i)) = this.selected.get() {
+                this.layout.borrow_mut().pages[page].items.remove(i);
+                this.selected.set(None);
+                this.rebuild();
+            }
+        });
+        let this = self.clone();
+        self.page_title.connect_changed(move |entry| {
+            if this.updating.get() {
+                return;
+            }
+            let page = match this.notebook.get_current_page() {
+                Some(page) => page,
+                None => return,
+            };
+            let title = entry.get_text().to_string();
+            if let Some(layout_page) = this.layout.borrow_mut().pages.get_mut(page as usize) {
+                layout_page.title = title.clone();
+            }
+            if let Some(child) = this.notebook.get_nth_page(Some(page)) {
+                this.notebook.set_tab_label_text(&child, &title);
+            }
+        });
+        let this = self.clone();
+        self.notebook.connect_switch_page(move |_, _, page| {
+            let title = this
+                .layout
+                .borrow()
+                .pages
+                .get(page as usize)
+                .map(|p| p.title.clone())
+                .unwrap_or_default();
+            this.updating.set(true);
+            this.page_title.set_text(&title);
+            this.updating.set(false);
+        });
+    }
+
+    /// Change the selected item, the edit is undone when it overlaps another item.
+    /// The pages are redrawn when `redraw`.
+    fn edit_selected<F: FnOnce(&mut LayoutItem)>(&self, redraw: bool, edit: F) {
+        if self.updating.get() {
+            return;
+        }
+        let (page, i) = match self.selected.get() {
+            Some(selected) => selected,
+            None => return,
+        };
+        let fits = {
+            let mut layout = self.layout.borrow_mut();
+            let layout_page = &mut layout.pages[page];
+            let mut item = layout_page.items[i].clone();
+            edit(&mut item);
+            let fits = layout_page.fits(&item, Some(i));
+            if fits {
+                layout_page.items[i] = item;
+            }
+            fits
+        };
+        if !fits {
+            // back to the values of the item
+            self.show_selected();
+        } else if redraw {
+            self.rebuild();
+        }
+    }
+
+    /// put the item on the current page, below the other items, and select it
+    fn add_item<F: Fn(i32) -> LayoutItem>(&self, create: F) {
+        let page = self.notebook.get_current_page().unwrap_or(0) as usize;
+        {
+            let mut layout = self.layout.borrow_mut();
+            let layout_page = match layout.pages.get_mut(page) {
+                Some(layout_page) => layout_page,
+                None => return,
+            };
+            let item = create(layout_page.next_row());
+            layout_page.items.push(item);
+            self.selected.set(Some((page, layout_page.items.len() - 1)));
+        }
+        self.rebuild();
+    }
+
+    fn move_selected_to_page(&self, page: usize) {
+        let selected = match self.selected.get() {
+            Some(selected) if selected.0 != page => selected,
+            _ => return,
+        };
+        let moved = {
+            let mut layout = self.layout.borrow_mut();
+            let top = match layout.pages.get(page) {
+                Some(layout_page) => layout_page.next_row(),
+                None => return,
+            };
+            layout.move_item(selected, page, 0, top)
+        };
+        self.selected.set(Some(moved));
+        self.rebuild();
+        self.notebook.set_current_page(Some(moved.0 as u32));
+    }
+
+    /// the fields of the removed page are hidden, there is always a page left
+    fn remove_current_page(&self) {
+        let page = match self.notebook.get_current_page() {
+            Some(page) => page as usize,
+            None => return,
+        };
+        {
+            let mut layout = self.layout.borrow_mut();
+            if layout.pages.len() < 2 {
+                return;
+            }
+            layout.pages.remove(page);
+        }
+        self.selected.set(None);
+        self.rebuild();
+    }
+
+    /// draw the pages again, staying on the current one
+    fn rebuild(&self) {
+        let current = self.notebook.get_current_page();
+        while self.notebook.get_n_pages() > 0 {
+            self.notebook.remove_page(Some(0));
+        }
+        let titles: Vec<String> = self
+            .layout
+            .borrow()
+            .pages
+            .iter()
+            .map(|p| p.title.clone())
+            .collect();
+        for (p, title) in titles.iter().enumerate() {
+            let grid = self.create_page_grid(p);
+            self.notebook
+                .append_page(&grid, Some(&Label::new(Some(title))));
+        }
+        self.notebook.show_all();
+        if current.map(|c| c < self.notebook.get_n_pages()) == Some(true) {
+            self.notebook.set_current_page(current);
+        }
+
+        self.updating.set(true);
+        self.page.remove_all();
+        for title in titles.iter() {
+            self.page.append_text(title);
+        }
+        self.hidden.remove_all();
+        for column in self.layout.borrow().hidden(&self.columns) {
+            self.hidden.append(Some(&column), &column);
+        }
+        self.hidden.set_active(Some(0));
+        self.updating.set(false);
+        self.show_selected();
+    }
+
+    /// rebuild once the signal of the button being replaced is handled
+    fn rebuild_later(&self) {
+        let this = self.clone();
+        glib::idle_add_local(move || {
+            this.rebuild();
+            glib::Continue(false)
+        });
+    }
+
+    /// The items of the page as buttons at their place, they are dragged onto the empty cells.
+    /// There is an empty row and column more to make the form grow.
+    fn create_page_grid(&self, p: usize) -> Grid {
+        let grid = Grid::new();
+        grid.set_row_spacing(5);
+        grid.set_column_spacing(5);
+        grid.set_column_homogeneous(true);
+        grid.set_border_width(10);
+        let targets = vec![TargetEntry::new(ITEM_TARGET, TargetFlags::SAME_APP, 0)];
+        let layout = self.layout.borrow();
+        let page = &layout.pages[p];
+        for (i, item) in page.items.iter().enumerate() {
+            let text = match &item.column {
+                Some(column) if item.title() != column => format!("{} ({})", item.title(), column),
+                Some(column) => column.to_string(),
+                None => format!("\u{2014} {} \u{2014}", item.title()),
+            };
+            let button = Button::with_label(&text);
+            if self.selected.get() == Some((p, i)) {
+                button.get_style_context().add_class("suggested-action");
+            }
+            button.drag_source_set(
+                gdk::ModifierType::BUTTON1_MASK,
+                &targets,
+                gdk::DragAction::MOVE,
+            );
+            button.connect_drag_data_get(move |_, _, data, _, _| {
+                data.set_text(&format!("{} {}", p, i));
+            });
+            let this = self.clone();
+            button.connect_clicked(move |_| {
+                this.selected.set(Some((p, i)));
+                this.rebuild_later();
+            });
+            grid.attach(&button, item.left, item.top, item.width, item.height);
+        }
+        let (columns, rows) = page.size();
+        for left in 0..columns.max(MIN_COLUMNS) + 1 {
+            for top in 0..rows + 1 {
+                let cell = LayoutItem::section("", left, top);
+                if !page.fits(&cell, None) {
+                    continue;
+                }
+                let empty = EventBox::new();
+                empty.set_size_request(80, 30);
+                empty.drag_dest_set(DestDefaults::ALL, &targets, gdk::DragAction::MOVE);
+                let this = self.clone();
+                empty.connect_drag_data_received(move |_, _, _, _, data, _, _| {
+                    let from: Vec<usize> = data
+                        .get_text()
+                        .map(|text| text.split(' ').filter_map(|n| n.parse().ok()).collect())
+                        .unwrap_or_default();
+                    if let [from_page, from_item] = from[..] {
+                        let moved = this.layout.borrow_mut().move_item(
+                            (from_page, from_item),
+                            p,
+                            left,
+                            top,
+                        );
+                        this.selected.set(Some(moved));
+                        this.rebuild_later();
+                    }
+                });
+                grid.attach(&empty, left, top, 1, 1);
+            }
+        }
+        grid
+    }
+
+    /// put the values of the selected item in the property widgets
+    fn show_selected(&self) {
+        self.updating.set(true);
+        let layout = self.layout.borrow();
+        let item = self
+            .selected
+            .get()
+            .and_then(|(p, i)| Some((p, layout.pages.get(p)?.items.get(i)?)));
+        for widget in vec![
+            self.label.clone().upcast::<gtk::Widget>(),
+            self.tooltip.clone().upcast(),
+            self.width.clone().upcast(),
+            self.height.clone().upcast(),
+            self.page.clone().upcast(),
+            self.remove.clone().upcast(),
+        ] {
+            widget.set_sensitive(item.is_some());
+        }
+        if let Some((p, item)) = item {
+            self.label.set_text(item.label.as_deref().unwrap_or(""));
+            self.label
+                .set_placeholder_text(item.column.as_deref().or(Some("Section title")));
+            self.tooltip.set_text(item.tooltip.as_deref().unwrap_or(""));
+            self.tooltip.set_sensitive(!item.is_section());
+            self.width.set_value(item.width as f64);
+            self.height.set_value(item.height as f64);
+            self.page.set_active(Some(p as u32));
+            self.remove
+                .set_label(if item.is_section() { "Remove" } else { "Hide" });
+        }
+        self.updating.set(false);
+    }
+
+    /// show the dialog, `None` when it is cancelled
+    pub fn run(&self) -> Option<DesignerResponse> {
+        self.dialog.show_all();
+        let response = self.dialog.run();
+        let chosen = match response {
+            ResponseType::Accept => Some(DesignerResponse::Save(self.layout.borrow().clone())),
+            r if r == AUTOMATIC => Some(DesignerResponse::Automatic),
+            _ => None,
+        };
+        self.dialog.close();
+        chosen
+    }
+}
@@ -1,26 +1,70 @@
+use crate::catalog::ColumnInfo;
 
+pub struct Field {
+    column: String,
+    data_type: String,
+    // 20 is the average text field,
+    // more than 20 spans 1 more cell for 2 column cell
+    // nothing spans 3 columns will make the UI looks ugly
+    // more than 40 will spand 2 column and 2 rows in a multi-line entry
+    data_length: usize,
+    //hints or info, for tooltips
+    info: String,
+}
 
-pub struct Field{
-	column: String,
-	data_type: String,
-	// 20 is the average text field,
-	// more than 20 spans 1 more cell for 2 column cell
-	// nothing spans 3 columns will make the UI looks ugly
-	// more than 40 will spand 2 column and 2 rows in a multi-line entry
-	data_length: usize,
-	//this field must be put next to that field
-	next_to: String,
-	//hints or info, for tooltips
-	info: String,
+impl Field {
+    pub fn new(column: &str) -> Self {
+        Field {
+            column: column.to_string(),
+            data_type: String::new(),
+            data_length: 20,
+            info: String::new(),
+        }
+    }
 
-}
+    /// a field of this type, ie: for a parameter of a query
+    pub fn with_type(column: &str, data_type: &str) -> Self {
+        Field {
+            data_type: data_type.to_string(),
+            ..Field::new(column)
+        }
+    }
+
+    /// a field sized and described after the column definition
+    pub fn from_column_info(info: &ColumnInfo) -> Self {
+        let mut hints = vec![match info.max_length {
+            Some(length) => format!("{}({})", info.data_type, length),
+            None => info.data_type.to_string(),
+        }];
+        if !info.nullable {
+            hints.push("required".to_string());
+        }
+        Field {
+            column: info.name.to_string(),
+            data_type: info.data_type.to_string(),
+            data_length: info.max_length.unwrap_or(20),
+            info: hints.join(", "),
+        }
+    }
+
+    pub fn column(&self) -> &str {
+        &self.column
+    }
 
-impl Field{
+    pub fn data_type(&self) -> &str {
+        &self.data_type
+    }
 
-	
-	// compute row spans and column spans base on data length
-	fn compute_spans(&self){
-		//two columns or three columns	
-	}
+    pub fn info(&self) -> &str {
+        &self.info
+    }
 
+    /// the columns and rows of the form taken by the field, after its data length
+    pub fn spans(&self) -> (i32, i32) {
+        match self.data_length {
+            0..=20 => (1, 1),
+            21..=40 => (2, 1),
+            _ => (2, 2),
+        }
+    }
 }
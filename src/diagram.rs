@@ -0,0 +1,582 @@
+//! the diagram of the tables of a database and of the foreign keys between them,
+//! drawn with cairo on the diagram tab and into svg and png files
+//!
+//! The positions of the tables are saved in `diagram.toml` of the config directory,
+//! by the url of their database:
+//!
+//! ```toml
+//! [databases."postgres://db/shop".tables]
+//! "public.customers" = [20.0, 20.0]
+//! "public.orders" = [260.0, 20.0]
+//! ```
+use crate::catalog::{self, ColumnInfo, ForeignKey};
+use crate::config;
+use crate::db::{Database, DbError};
+use cairo::{Context, FontSlant, FontWeight, Format, ImageSurface, SvgSurface};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+static FONT: &'static str = "Sans";
+static FONT_SIZE: f64 = 10.0;
+static LINE_HEIGHT: f64 = 15.0;
+static PADDING: f64 = 6.0;
+/// between the tables placed by the automatic layout, and around the exported diagram
+static GAP: f64 = 40.0;
+static ARROW_SIZE: f64 = 8.0;
+
+/// the name, columns, primary key and foreign keys of a table
+pub type TableInfo = (String, Vec<ColumnInfo>, Vec<String>, Vec<ForeignKey>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: String,
+    pub data_type: String,
+    /// part of the primary key
+    pub key: bool,
+    /// part of a foreign key
+    pub foreign: bool,
+}
+
+/// the box of a table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    /// schema qualified when the database has schemas
+    pub table: String,
+    pub attributes: Vec<Attribute>,
+    pub x: f64,
+    pub y: f64,
+    /// set by `measure`
+    pub width: f64,
+    pub height: f64,
+}
+
+/// a foreign key, from the box of the referencing table to the referenced one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relation {
+    pub from: usize,
+    pub to: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagram {
+    pub entities: Vec<Entity>,
+    pub relations: Vec<Relation>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Positions {
+    /// the top left corner of each table
+    #[serde(default)]
+    pub tables: BTreeMap<String, (f64, f64)>,
+}
+
+/// the positions of the tables of each database
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct PositionsFile {
+    /// saved before they were kept by database, used by the databases that have none
+    #[serde(default)]
+    tables: BTreeMap<String, (f64, f64)>,
+    #[serde(default)]
+    databases: BTreeMap<String, Positions>,
+}
+
+impl PositionsFile {
+    fn path() -> PathBuf {
+        config::config_dir().join("diagram.toml")
+    }
+
+    fn load() -> Self {
+        let path = Self::path();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return PositionsFile::default(),
+        };
+        toml::from_str(&content).unwrap_or_else(|e| {
+            println!("ignoring invalid {}: {}", path.display(), e);
+            PositionsFile::default()
+        })
+    }
+
+    fn positions(&self, database: &str) -> Positions {
+        match self.databases.get(database) {
+            Some(positions) => positions.clone(),
+            None => Positions {
+                tables: self.tables.clone(),
+            },
+        }
+    }
+}
+
+impl Positions {
+    /// the positions of the tables of the database, see `db::database_url`
+    pub fn load(database: &str) -> Self {
+        PositionsFile::load().positions(database)
+    }
+
+    /// the positions of the other databases are kept in the same file
+    pub fn save(&self, database: &str) -> io::Result<()> {
+        let mut file = PositionsFile::load();
+        file.databases.insert(database.to_string(), self.clone());
+        let path = PositionsFile::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content =
+            toml::to_string(&file).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        fs::write(path, content)
+    }
+}
+
+/// the top left and bottom right corners around these tables
+fn bounds_of<'a>(entities: impl Iterator<Item = &'a Entity>) -> (f64, f64, f64, f64) {
+    let bounds = entities.fold(
+        (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+        |(left, top, right, bottom), e| {
+            (
+                left.min(e.x),
+                top.min(e.y),
+                right.max(e.x + e.width),
+                bottom.max(e.y + e.height),
+            )
+        },
+    );
+    match bounds.0 > bounds.2 {
+        // no table
+        true => (0.0, 0.0, 0.0, 0.0),
+        false => bounds,
+    }
+}
+
+fn set_font(cr: &Context, bold: bool) {
+    let weight = if bold {
+        FontWeight::Bold
+    } else {
+        FontWeight::Normal
+    };
+    cr.select_font_face(FONT, FontSlant::Normal, weight);
+    cr.set_font_size(FONT_SIZE);
+}
+
+impl Entity {
+    fn title_height(&self) -> f64 {
+        LINE_HEIGHT + PADDING
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+
+    fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    /// where the line from the center towards this point leaves the box
+    fn border_point(&self, towards: (f64, f64)) -> (f64, f64) {
+        let (cx, cy) = self.center();
+        let (dx, dy) = (towards.0 - cx, towards.1 - cy);
+        if dx == 0.0 && dy == 0.0 {
+            return (cx, cy);
+        }
+        let scale_x = if dx != 0.0 {
+            (self.width / 2.0) / dx.abs()
+        } else {
+            f64::INFINITY
+        };
+        let scale_y = if dy != 0.0 {
+            (self.height / 2.0) / dy.abs()
+        } else {
+            f64::INFINITY
+        };
+        let scale = scale_x.min(scale_y);
+        (cx + dx * scale, cy + dy * scale)
+    }
+
+    fn draw(&self, cr: &Context, selected: bool) {
+        cr.rectangle(self.x, self.y, self.width, self.height);
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        cr.fill_preserve();
+        if selected {
+            cr.set_source_rgb(0.2, 0.4, 0.8);
+            cr.set_line_width(2.0);
+        } else {
+            cr.set_source_rgb(0.3, 0.3, 0.3);
+            cr.set_line_width(1.0);
+        }
+        cr.stroke();
+        // the title on a shaded band
+        cr.rectangle(self.x, self.y, self.width, self.title_height());
+        cr.set_source_rgb(0.85, 0.89, 0.95);
+        cr.fill_preserve();
+        cr.set_source_rgb(0.3, 0.3, 0.3);
+        cr.set_line_width(1.0);
+        cr.stroke();
+        cr.set_source_rgb(0.0, 0.0, 0.0);
+        set_font(cr, true);
+        cr.move_to(self.x + PADDING, self.y + LINE_HEIGHT);
+        cr.show_text(&self.table);
+        let mut top = self.y + self.title_height() + LINE_HEIGHT - PADDING / 2.0;
+        for attribute in self.attributes.iter() {
+            set_font(cr, attribute.key);
+            if attribute.foreign {
+                cr.set_source_rgb(0.2, 0.4, 0.8);
+            } else {
+                cr.set_source_rgb(0.0, 0.0, 0.0);
+            }
+            cr.move_to(self.x + PADDING, top);
+            cr.show_text(&attribute.label());
+            top += LINE_HEIGHT;
+        }
+    }
+}
+
+impl Attribute {
+    fn label(&self) -> String {
+        format!("{}: {}", self.name, self.data_type)
+    }
+}
+
+impl Diagram {
+    pub fn new(tables: Vec<TableInfo>) -> Self {
+        let index: HashMap<String, usize> = tables
+            .iter()
+            .enumerate()
+            .map(|(i, (table, _, _, _))| (table.to_string(), i))
+            .collect();
+        let mut relations = vec![];
+        let mut entities = vec![];
+        for (i, (table, columns, key, foreign_keys)) in tables.into_iter().enumerate() {
+            for foreign_key in foreign_keys.iter() {
+                // the referenced table may be in a schema that is not listed
+                if let Some(to) = index.get(&foreign_key.ref_table) {
+                    relations.push(Relation { from: i, to: *to });
+                }
+            }
+            let attributes = columns
+                .into_iter()
+                .map(|c| Attribute {
+                    key: key.contains(&c.name),
+                    foreign: foreign_keys.iter().any(|k| k.columns.contains(&c.name)),
+                    data_type: c.type_name(),
+                    name: c.name,
+                })
+                .collect();
+            entities.push(Entity {
+                table: table,
+                attributes: attributes,
+                x: 0.0,
+                y: 0.0,
+                width: 0.0,
+                height: 0.0,
+            });
+        }
+        Diagram {
+            entities: entities,
+            relations: relations,
+        }
+    }
+
+    /// all the tables of the database with their foreign keys
+    pub fn load(db: &mut dyn Database) -> Result<Self, DbError> {
+        let mut tables = vec![];
+        for table in catalog::list_tables(db)? {
+            let columns = catalog::columns(db, &table)?;
+            let key = catalog::primary_key(db, &table)?;
+            let foreign_keys = catalog::foreign_keys(db, &table)?;
+            tables.push((table, columns, key, foreign_keys));
+        }
+        Ok(Self::new(tables))
+    }
+
+    /// the size of the boxes, from their text in the font of the diagram
+    pub fn measure(&mut self, cr: &Context) {
+        for entity in self.entities.iter_mut() {
+            set_font(cr, true);
+            let mut width = cr.text_extents(&entity.table).x_advance;
+            for attribute in entity.attributes.iter() {
+                set_font(cr, attribute.key);
+                width = width.max(cr.text_extents(&attribute.label()).x_advance);
+            }
+            entity.width = width + 2.0 * PADDING;
+            entity.height =
+                entity.title_height() + entity.attributes.len() as f64 * LINE_HEIGHT + PADDING;
+        }
+    }
+
+    /// The tables connected by foreign keys one after the other,
+    /// each group starting from the most referenced table.
+    fn layout_order(&self) -> Vec<usize> {
+        let mut neighbours = vec![vec![]; self.entities.len()];
+        for relation in self.relations.iter() {
+            neighbours[relation.from].push(relation.to);
+            neighbours[relation.to].push(relation.from);
+        }
+        let mut starts: Vec<usize> = (0..self.entities.len()).collect();
+        starts.sort_by_key(|i| std::cmp::Reverse(neighbours[*i].len()));
+        let mut visited = vec![false; self.entities.len()];
+        let mut order = vec![];
+        for start in starts {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            let mut queue = std::collections::VecDeque::new();
+            queue.push_back(start);
+            while let Some(i) = queue.pop_front() {
+                order.push(i);
+                for n in neighbours[i].iter() {
+                    if !visited[*n] {
+                        visited[*n] = true;
+                        queue.push_back(*n);
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    /// place the tables in rows, about as wide as the rows are high in all
+    pub fn auto_layout(&mut self) {
+        let area: f64 = self
+            .entities
+            .iter()
+            .map(|e| (e.width + GAP) * (e.height + GAP))
+            .sum();
+        let row_width = area.sqrt().max(1.0);
+        let (mut x, mut y, mut row_height) = (GAP, GAP, 0.0f64);
+        for i in self.layout_order() {
+            let entity = &mut self.entities[i];
+            if x > GAP && x + entity.width > row_width {
+                x = GAP;
+                y += row_height + GAP;
+                row_height = 0.0;
+            }
+            entity.x = x;
+            entity.y = y;
+            x += entity.width + GAP;
+            row_height = row_height.max(entity.height);
+        }
+    }
+
+    /// Put the tables at their saved position, the automatic layout places them when none
+    /// has one. The tables that have none are put in rows below the others.
+    pub fn apply_positions(&mut self, positions: &Positions) {
+        let mut missing = vec![];
+        for (i, entity) in self.entities.iter_mut().enumerate() {
+            match positions.tables.get(&entity.table) {
+                Some((x, y)) => {
+                    entity.x = *x;
+                    entity.y = *y;
+                }
+                None => missing.push(i),
+            }
+        }
+        if missing.len() == self.entities.len() {
+            self.auto_layout();
+            return;
+        }
+        let placed = self
+            .entities
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !missing.contains(i))
+            .map(|(_, e)| e);
+        let (left, _, right, bottom) = bounds_of(placed);
+        let (mut x, mut y, mut row_height) = (left, bottom + GAP, 0.0f64);
+        for i in missing {
+            let entity = &mut self.entities[i];
+            if x > left && x + entity.width > right {
+                x = left;
+                y += row_height + GAP;
+                row_height = 0.0;
+            }
+            entity.x = x;
+            entity.y = y;
+            x += entity.width + GAP;
+            row_height = row_height.max(entity.height);
+        }
+    }
+
+    pub fn positions(&self) -> Positions {
+        Positions {
+            tables: self
+                .entities
+                .iter()
+                .map(|e| (e.table.clone(), (e.x, e.y)))
+                .collect(),
+        }
+    }
+
+    /// the top left and bottom right corners around all the tables
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        bounds_of(self.entities.iter())
+    }
+
+    /// the topmost table at this point, the last drawn
+    pub fn entity_at(&self, x: f64, y: f64) -> Option<usize> {
+        self.entities.iter().rposition(|e| e.contains(x, y))
+    }
+
+    pub fn draw(&self, cr: &Context, selected: Option<usize>) {
+        cr.set_source_rgb(0.4, 0.4, 0.4);
+        cr.set_line_width(1.0);
+        for relation in self.relations.iter() {
+            self.draw_relation(cr, relation);
+        }
+        for (i, entity) in self.entities.iter().enumerate() {
+            entity.draw(cr, selected == Some(i));
+        }
+    }
+
+    /// a line between the borders of the boxes, with an arrow on the referenced table
+    fn draw_relation(&self, cr: &Context, relation: &Relation) {
+        let from = &self.entities[relation.from];
+        let to = &self.entities[relation.to];
+        cr.set_source_rgb(0.4, 0.4, 0.4);
+        if relation.from == relation.to {
+            // a loop on the right side for a table referencing itself
+            let x = from.x + from.width;
+            let y = from.y + from.title_height();
+            cr.move_to(x, y);
+            cr.curve_to(x + GAP, y, x + GAP, y + GAP, x, y + GAP);
+            cr.stroke();
+            return;
+        }
+        let start = from.border_point(to.center());
+        let end = to.border_point(from.center());
+        cr.move_to(start.0, start.1);
+        cr.line_to(end.0, end.1);
+        cr.stroke();
+        let angle = (end.1 - start.1).atan2(end.0 - start.0);
+        cr.move_to(end.0, end.1);
+        for side in [-0.4, 0.4].iter() {
+            cr.line_to(
+                end.0 - ARROW_SIZE * (angle + side).cos(),
+                end.1 - ARROW_SIZE * (angle + side).sin(),
+            );
+        }
+        cr.close_path();
+        cr.fill();
+    }
+
+    /// the whole diagram with a margin, on a white background
+    fn draw_page(&self, cr: &Context) {
+        let (left, top, _, _) = self.bounds();
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        cr.paint();
+        cr.translate(GAP - left, GAP - top);
+        self.draw(cr, None);
+    }
+
+    /// the size of the exported diagram
+    fn page_size(&self) -> (f64, f64) {
+        let (left, top, right, bottom) = self.bounds();
+        (right - left + 2.0 * GAP, bottom - top + 2.0 * GAP)
+    }
+
+    pub fn write_svg(&self, path: &Path) -> Result<(), cairo::Error> {
+        let (width, height) = self.page_size();
+        let surface = SvgSurface::new(width, height, Some(path))?;
+        let cr = Context::new(&surface);
+        self.draw_page(&cr);
+        surface.finish();
+        Ok(())
+    }
+
+    pub fn write_png(&self, path: &Path) -> Result<(), cairo::IoError> {
+        let (width, height) = self.page_size();
+        let surface =
+            ImageSurface::create(Format::ARgb32, width.ceil() as i32, height.ceil() as i32)?;
+        let cr = Context::new(&surface);
+        self.draw_page(&cr);
+        let mut file = File::create(path)?;
+        surface.write_to_png(&mut file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str) -> ColumnInfo {
+        ColumnInfo {
+            name: name.to_string(),
+            data_type: "integer".to_string(),
+            nullable: false,
+            max_length: None,
+            precision: None,
+            default: None,
+        }
+    }
+
+    fn shop() -> Diagram {
+        let key = vec!["id".to_string()];
+        let reference = |table: &str| ForeignKey {
+            columns: vec![format!("{}_id", table)],
+            ref_table: table.to_string(),
+            ref_columns: vec!["id".to_string()],
+        };
+        Diagram::new(vec![
+            (
+                "customers".to_string(),
+                vec![column("id")],
+                key.clone(),
+                vec![],
+            ),
+            (
+                "orders".to_string(),
+                vec![column("id"), column("customers_id")],
+                key.clone(),
+                vec![reference("customers"), reference("elsewhere")],
+            ),
+            ("notes".to_string(), vec![column("id")], key, vec![]),
+        ])
+    }
+
+    #[test]
+    fn layout_keeps_the_tables_apart() {
+        let mut diagram = shop();
+        assert_eq!(diagram.relations, vec![Relation { from: 1, to: 0 }]);
+        assert!(diagram.entities[1].attributes[1].foreign);
+        let surface = ImageSurface::create(Format::ARgb32, 1, 1).unwrap();
+        diagram.measure(&Context::new(&surface));
+        diagram.auto_layout();
+        for (i, a) in diagram.entities.iter().enumerate() {
+            assert!(a.width > 0.0 && a.height > 0.0);
+            for b in diagram.entities[i + 1..].iter() {
+                let apart = a.x + a.width <= b.x
+                    || b.x + b.width <= a.x
+                    || a.y + a.height <= b.y
+                    || b.y + b.height <= a.y;
+                assert!(apart, "{} overlaps {}", a.table, b.table);
+            }
+        }
+        let (x, y) = (diagram.entities[2].x + 1.0, diagram.entities[2].y + 1.0);
+        assert_eq!(diagram.entity_at(x, y), Some(2));
+
+        // the positions of a database are not the ones of another
+        let mut positions = diagram.positions();
+        positions.tables.remove("notes");
+        let mut file = PositionsFile::default();
+        file.databases
+            .insert("postgres://db/shop".to_string(), positions);
+        let file: PositionsFile = toml::from_str(&toml::to_string(&file).unwrap()).unwrap();
+        assert!(file.positions("sqlite:///shop.db").tables.is_empty());
+        let mut moved = shop();
+        moved.measure(&Context::new(&surface));
+        moved.apply_positions(&file.positions("postgres://db/shop"));
+        assert_eq!(moved.entities[1].x, diagram.entities[1].x);
+        // the table without a position is put below the others
+        let (_, _, _, bottom) = bounds_of(moved.entities[..2].iter());
+        assert!(moved.entities[2].y > bottom);
+
+        let path = std::env::temp_dir().join("datawindow-diagram.svg");
+        diagram.write_svg(&path).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("<svg"));
+        let path = std::env::temp_dir().join("datawindow-diagram.png");
+        diagram.write_png(&path).unwrap();
+        assert!(fs::read(&path).unwrap().starts_with(b"\x89PNG"));
+    }
+}
@@ -0,0 +1,343 @@
+//! the diagram of the schema in a tab, the tables can be moved and opened from it
+use crate::db::SharedDb;
+use crate::diagram::{Diagram, Positions};
+use cairo::{Context, Format, ImageSurface};
+use gdk::{EventMask, EventType, ScrollDirection};
+use gtk;
+use gtk::prelude::*;
+use gtk::Orientation::Vertical;
+use gtk::{
+    DrawingArea, FileChooserAction, FileChooserDialog, IconSize, Image, Inhibit, Label,
+    ResponseType, ToolButton, Toolbar, Window,
+};
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::rc::Rc;
+
+static MIN_SCALE: f64 = 0.2;
+static MAX_SCALE: f64 = 4.0;
+static ZOOM_STEP: f64 = 1.2;
+
+/// what the pointer does while the button is held down
+#[derive(Debug, Clone, Copy)]
+enum Drag {
+    /// the diagram, from where the button was pressed and the offset then
+    Pan((f64, f64), (f64, f64)),
+    /// a table, grabbed this far from its top left corner
+    Move(usize, f64, f64),
+}
+
+#[derive(Clone)]
+pub struct DiagramTab {
+    db: SharedDb,
+    area: DrawingArea,
+    diagram: Rc<RefCell<Diagram>>,
+    scale: Rc<Cell<f64>>,
+    offset: Rc<Cell<(f64, f64)>>,
+    drag: Rc<Cell<Option<Drag>>>,
+    selected: Rc<Cell<Option<usize>>>,
+    status: Label,
+    toolbar: Toolbar,
+    layout: ToolButton,
+    zoom_in: ToolButton,
+    zoom_out: ToolButton,
+    export_svg: ToolButton,
+    export_png: ToolButton,
+    open_table: Rc<RefCell<Option<Box<dyn Fn(&str)>>>>,
+}
+
+impl DiagramTab {
+    pub fn new(db: SharedDb) -> Self {
+        let area = DrawingArea::new();
+        area.set_hexpand(true);
+        area.set_vexpand(true);
+        area.set_can_focus(true);
+        area.add_events(
+            EventMask::BUTTON_PRESS_MASK
+                | EventMask::BUTTON_RELEASE_MASK
+                | EventMask::POINTER_MOTION_MASK
+                | EventMask::SCROLL_MASK
+                | EventMask::SMOOTH_SCROLL_MASK,
+        );
+        let status = Label::new(None);
+        status.set_xalign(0.0);
+        let toolbar = Toolbar::new();
+
+        let tab = DiagramTab {
+            db: db,
+            area: area,
+            diagram: Rc::new(RefCell::new(Diagram::default())),
+            scale: Rc::new(Cell::new(1.0)),
+            offset: Rc::new(Cell::new((0.0, 0.0))),
+            drag: Rc::new(Cell::new(None)),
+            selected: Rc::new(Cell::new(None)),
+            status: status,
+            layout: Self::create_button(&toolbar, "view-grid", "Auto Layout"),
+            zoom_in: Self::create_button(&toolbar, "zoom-in", "Zoom In"),
+            zoom_out: Self::create_button(&toolbar, "zoom-out", "Zoom Out"),
+            export_svg: Self::create_button(&toolbar, "document-save-as", "Export SVG"),
+            export_png: Self::create_button(&toolbar, "image-x-generic", "Export PNG"),
+            toolbar: toolbar,
+            open_table: Rc::new(RefCell::new(None)),
+        };
+        tab.load();
+        tab
+    }
+
+    fn create_button(toolbar: &Toolbar, icon: &str, label: &str) -> ToolButton {
+        let image = Image::from_icon_name(Some(icon), IconSize::SmallToolbar);
+        let button = ToolButton::new::<Image>(Some(&image), Some(label));
+        button.set_is_important(true);
+        toolbar.add(&button);
+        button
+    }
+
+    pub fn build_widget(&self) -> gtk::Box {
+        let vbox = gtk::Box::new(Vertical, 0);
+        vbox.add(&self.toolbar);
+        vbox.pack_start(&self.area, true, true, 0);
+        self.status.set_margin_start(5);
+        vbox.add(&self.status);
+        self.init_draw();
+        self.init_mouse();
+        self.init_toolbar();
+        vbox
+    }
+
+    /// called with the table double clicked in the diagram
+    pub fn connect_open_table<F: Fn(&str) + 'static>(&self, f: F) {
+        *self.open_table.borrow_mut() = Some(Box::new(f));
+    }
+
+    /// read the tables, placed where they were left or else by the automatic layout
+    fn load(&self) {
+        let diagram = match self.db.borrow_mut().as_mut() {
+            Some(db) => Diagram::load(db.as_mut()),
+            None => {
+                self.status.set_text("Not connected");
+                return;
+            }
+        };
+        let mut diagram = match diagram {
+            Ok(diagram) => diagram,
+            Err(e) => {
+                self.status
+                    .set_text(&format!("Unable to read the tables: {}", e));
+                return;
+            }
+        };
+        // the boxes are measured with the font of the diagram, before anything is drawn
+        if let Ok(surface) = ImageSurface::create(Format::ARgb32, 1, 1) {
+            diagram.measure(&Context::new(&surface));
+        }
+        diagram.apply_positions(&Positions::load(&self.database()));
+        self.status.set_text(&format!(
+            "{} table(s), {} foreign key(s)",
+            diagram.entities.len(),
+            diagram.relations.len()
+        ));
+        *self.diagram.borrow_mut() = diagram;
+    }
+
+    /// the point of the diagram under this point of the area
+    fn to_diagram(&self, x: f64, y: f64) -> (f64, f64) {
+        let (dx, dy) = self.offset.get();
+        let scale = self.scale.get();
+        ((x - dx) / scale, (y - dy) / scale)
+    }
+
+    fn init_draw(&self) {
+        let this = self.clone();
+        self.area.connect_draw(move |_, cr| {
+            cr.set_source_rgb(1.0, 1.0, 1.0);
+            cr.paint();
+            let (dx, dy) = this.offset.get();
+            cr.translate(dx, dy);
+            cr.scale(this.scale.get(), this.scale.get());
+            this.diagram.borrow().draw(cr, this.selected.get());
+            Inhibit(false)
+        });
+    }
+
+    /// drag a table to move it or the background to pan, double click a table to open it
+    /// and scroll to zoom
+    fn init_mouse(&self) {
+        let this = self.clone();
+        self.area.connect_button_press_event(move |area, event| {
+            if event.get_button() != 1 {
+                return Inhibit(false);
+            }
+            area.grab_focus();
+            let (x, y) = event.get_position();
+            let (px, py) = this.to_diagram(x, y);
+            let hit = this.diagram.borrow().entity_at(px, py);
+            if event.get_event_type() == EventType::DoubleButtonPress {
+                this.drag.set(None);
+                if let Some(i) = hit {
+                    let table = this.diagram.borrow().entities[i].table.clone();
+                    if let Some(open) = this.open_table.borrow().as_ref() {
+                        open(&table);
+                    }
+                }
+                return Inhibit(true);
+            }
+            this.selected.set(hit);
+            let drag = match hit {
+                Some(i) => {
+                    let diagram = this.diagram.borrow();
+                    let entity = &diagram.entities[i];
+                    Drag::Move(i, px - entity.x, py - entity.y)
+                }
+                None => Drag::Pan((x, y), this.offset.get()),
+            };
+            this.drag.set(Some(drag));
+            area.queue_draw();
+            Inhibit(true)
+        });
+        let this = self.clone();
+        self.area.connect_motion_notify_event(move |area, event| {
+            let (x, y) = event.get_position();
+            match this.drag.get() {
+                Some(Drag::Pan((sx, sy), (dx, dy))) => {
+                    this.offset.set((dx + x - sx, dy + y - sy));
+                }
+                Some(Drag::Move(i, gx, gy)) => {
+                    let (px, py) = this.to_diagram(x, y);
+                    let mut diagram = this.diagram.borrow_mut();
+                    diagram.entities[i].x = px - gx;
+                    diagram.entities[i].y = py - gy;
+                }
+                None => return Inhibit(false),
+            }
+            area.queue_draw();
+            Inhibit(true)
+        });
+        let this = self.clone();
+        self.area.connect_button_release_event(move |_, event| {
+            if event.get_button() != 1 {
+                return Inhibit(false);
+            }
+            if let Some(Drag::Move(_, _, _)) = this.drag.get() {
+                this.save_positions();
+            }
+            this.drag.set(None);
+            Inhibit(true)
+        });
+        let this = self.clone();
+        self.area.connect_scroll_event(move |_, event| {
+            let factor = match event.get_direction() {
+                ScrollDirection::Up => ZOOM_STEP,
+                ScrollDirection::Down => 1.0 / ZOOM_STEP,
+                ScrollDirection::Smooth => ZOOM_STEP.powf(-event.get_delta().1),
+                _ => return Inhibit(false),
+            };
+            let (x, y) = event.get_position();
+            this.zoom(factor, x, y);
+            Inhibit(true)
+        });
+    }
+
+    /// change the scale keeping the point of the diagram under this point of the area
+    fn zoom(&self, factor: f64, x: f64, y: f64) {
+        let (px, py) = self.to_diagram(x, y);
+        let scale = (self.scale.get() * factor).max(MIN_SCALE).min(MAX_SCALE);
+        self.scale.set(scale);
+        self.offset.set((x - px * scale, y - py * scale));
+        self.area.queue_draw();
+    }
+
+    /// zoom around the middle of the area
+    fn zoom_center(&self, factor: f64) {
+        let x = self.area.get_allocated_width() as f64 / 2.0;
+        let y = self.area.get_allocated_height() as f64 / 2.0;
+        self.zoom(factor, x, y);
+    }
+
+    /// the url of the database, the positions are saved by it
+    fn database(&self) -> String {
+        match self.db.borrow().as_ref() {
+            Some(db) => db.session().database.clone(),
+            None => String::new(),
+        }
+    }
+
+    /// the positions of the tables that are not in the diagram anymore are kept
+    fn save_positions(&self) {
+        let database = self.database();
+        let mut positions = Positions::load(&database);
+        positions
+            .tables
+            .extend(self.diagram.borrow().positions().tables);
+        if let Err(e) = positions.save(&database) {
+            println!("Unable to save the diagram positions: {}", e);
+        }
+    }
+
+    fn init_toolbar(&self) {
+        let this = self.clone();
+        self.layout.connect_clicked(move |_| {
+            this.diagram.borrow_mut().auto_layout();
+            this.offset.set((0.0, 0.0));
+            this.area.queue_draw();
+            this.save_positions();
+        });
+        let this = self.clone();
+        self.zoom_in
+            .connect_clicked(move |_| this.zoom_center(ZOOM_STEP));
+        let this = self.clone();
+        self.zoom_out
+            .connect_clicked(move |_| this.zoom_center(1.0 / ZOOM_STEP));
+        let this = self.clone();
+        self.export_svg.connect_clicked(move |_| {
+            this.export("SVG", "svg", |diagram, path| {
+                diagram.write_svg(path).map_err(|e| e.to_string())
+            });
+        });
+        let this = self.clone();
+        self.export_png.connect_clicked(move |_| {
+            this.export("PNG", "png", |diagram, path| {
+                diagram.write_png(path).map_err(|e| e.to_string())
+            });
+        });
+    }
+
+    fn export<F: Fn(&Diagram, &Path) -> Result<(), String>>(
+        &self,
+        format: &str,
+        extension: &str,
+        write: F,
+    ) {
+        let window = self
+            .area
+            .get_toplevel()
+            .and_then(|w| w.downcast::<Window>().ok());
+        let dialog = FileChooserDialog::with_buttons(
+            Some(&format!("Export to {}", format)),
+            window.as_ref(),
+            FileChooserAction::Save,
+            &[
+                ("Cancel", ResponseType::Cancel),
+                ("Export", ResponseType::Accept),
+            ],
+        );
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name(&format!("diagram.{}", extension));
+        let path = match dialog.run() {
+            ResponseType::Accept => dialog.get_filename(),
+            _ => None,
+        };
+        dialog.close();
+        let path = match path {
+            Some(path) => path,
+            None => return,
+        };
+        match write(&self.diagram.borrow(), &path) {
+            Ok(()) => self
+                .status
+                .set_text(&format!("Exported the diagram to {}", path.display())),
+            Err(e) => self
+                .status
+                .set_text(&format!("Unable to export to {}: {}", format, e)),
+        }
+    }
+}
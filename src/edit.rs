@@ -0,0 +1,468 @@
+//! the pending changes of a data tab and the statements that save them
+use crate::db::{Platform, Value};
+
+/// a row of the list, with the values it had when it was loaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// `None` for a record that is not yet inserted
+    pub original: Option<Vec<Value>>,
+    pub values: Vec<Value>,
+}
+
+impl Record {
+    pub fn loaded(values: Vec<Value>) -> Self {
+        Record {
+            original: Some(values.clone()),
+            values: values,
+        }
+    }
+
+    pub fn blank(column_count: usize) -> Self {
+        Record {
+            original: None,
+            values: vec![None; column_count],
+        }
+    }
+
+    pub fn is_new(&self) -> bool {
+        self.original.is_none()
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.original.as_ref() != Some(&self.values)
+    }
+
+    /// the index of the columns whose value was edited
+    pub fn changed_columns(&self) -> Vec<usize> {
+        match &self.original {
+            Some(original) => (0..self.values.len())
+                .filter(|i| original.get(*i) != self.values.get(*i))
+                .collect(),
+            None => (0..self.values.len()).collect(),
+        }
+    }
+}
+
+/// the index of the key columns, or of all the columns when the table has no primary key
+/// or when some of the key is not among the columns
+pub fn key_indexes(columns: &[String], key: &[String]) -> Vec<usize> {
+    let indexes: Vec<usize> = key
+        .iter()
+        .filter_map(|k| columns.iter().position(|c| c == k))
+        .collect();
+    if indexes.is_empty() || indexes.len() != key.len() {
+        (0..columns.len()).collect()
+    } else {
+        indexes
+    }
+}
+
+/// A condition that matches this row on its key columns,
+/// or on all the columns when the table has no primary key.
+fn where_clause(
+    platform: Platform,
+    columns: &[String],
+    key: &[String],
+    values: &[Value],
+    params: &mut Vec<Value>,
+) -> String {
+    key_indexes(columns, key)
+        .into_iter()
+        .map(|i| {
+            let column = platform.quote_ident(&columns[i]);
+            match &values[i] {
+                Some(value) => {
+                    params.push(Some(value.clone()));
+                    format!("{} = {}", column, platform.placeholder(params.len()))
+                }
+                None => format!("{} IS NULL", column),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// A condition matching these values with literals instead of parameters,
+/// for the filters which are kept as text.
+pub fn literal_condition(platform: Platform, columns: &[String], values: &[Value]) -> String {
+    columns
+        .iter()
+        .zip(values.iter())
+        .map(|(column, value)| {
+            let column = platform.quote_ident(column);
+            match value {
+                Some(value) => format!("{} = {}", column, platform.quote_literal(value)),
+                None => format!("{} IS NULL", column),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// insert the values that are set, the database fills in the defaults of the others
+pub fn insert(
+    platform: Platform,
+    table: &str,
+    columns: &[String],
+    values: &[Value],
+) -> (String, Vec<Value>) {
+    let mut names = vec![];
+    let mut params = vec![];
+    for (column, value) in columns.iter().zip(values.iter()) {
+        if value.is_some() {
+            names.push(platform.quote_ident(column));
+            params.push(value.clone());
+        }
+    }
+    let sql = if names.is_empty() {
+        format!("INSERT INTO {} DEFAULT VALUES", platform.quote_table(table))
+    } else {
+        let placeholders: Vec<String> = (1..=params.len())
+            .map(|n| platform.placeholder(n))
+            .collect();
+        format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            platform.quote_table(table),
+            names.join(", "),
+            placeholders.join(", ")
+        )
+    };
+    (sql, params)
+}
+
+/// Update the edited columns of this record, `None` when nothing was edited.
+/// The version column of the lock is moved to the next version.
+pub fn update(
+    platform: Platform,
+    table: &str,
+    columns: &[String],
+    key: &[String],
+    record: &Record,
+    lock: &RowLock,
+) -> Option<(String, Vec<Value>)> {
+    let original = record.original.as_ref()?;
+    let changed = record.changed_columns();
+    if changed.is_empty() {
+        return None;
+    }
+    let mut params = vec![];
+    let mut assignments: Vec<String> = changed
+        .iter()
+        .map(|i| {
+            params.push(record.values[*i].clone());
+            format!(
+                "{} = {}",
+                platform.quote_ident(&columns[*i]),
+                platform.placeholder(params.len())
+            )
+        })
+        .collect();
+    if let Some(version) = &lock.version {
+        let edited = changed.iter().any(|i| columns[*i] == version.column());
+        if !edited {
+            assignments.push(version.next(platform));
+        }
+    }
+    let filter = where_clause(platform, columns, key, original, &mut params);
+    let sql = format!(
+        "UPDATE {} SET {} WHERE {}",
+        platform.quote_table(table),
+        assignments.join(", "),
+        filter
+    );
+    Some((sql, params))
+}
+
+pub fn delete(
+    platform: Platform,
+    table: &str,
+    columns: &[String],
+    key: &[String],
+    original: &[Value],
+) -> (String, Vec<Value>) {
+    let mut params = vec![];
+    let filter = where_clause(platform, columns, key, original, &mut params);
+    let sql = format!(
+        "DELETE FROM {} WHERE {}",
+        platform.quote_table(table),
+        filter
+    );
+    (sql, params)
+}
+
+/// the pending changes of a tab as they were before an edit
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub records: Vec<Record>,
+    pub deleted: Vec<Vec<Value>>,
+}
+
+static MAX_UNDO: usize = 100;
+
+/// The edits that can be undone, newest last.
+/// Typing in the same cell again is part of the same edit.
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    edits: Vec<(Snapshot, Option<(usize, usize)>)>,
+}
+
+impl UndoStack {
+    /// remember the changes before an edit of this cell, or of many cells when `None`
+    pub fn push(&mut self, before: Snapshot, cell: Option<(usize, usize)>) {
+        if cell.is_some() && self.edits.last().map(|(_, last)| *last == cell) == Some(true) {
+            return;
+        }
+        if self.edits.len() == MAX_UNDO {
+            self.edits.remove(0);
+        }
+        self.edits.push((before, cell));
+    }
+
+    /// the changes before the last edit
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        self.edits.pop().map(|(snapshot, _)| snapshot)
+    }
+
+    pub fn clear(&mut self) {
+        self.edits.clear();
+    }
+}
+
+/// the column telling that a row was changed since it was read
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionColumn {
+    /// a number incremented by each update
+    Counter(String),
+    /// the time of the last update
+    Timestamp(String),
+}
+
+impl VersionColumn {
+    pub fn column(&self) -> &str {
+        match self {
+            VersionColumn::Counter(column) | VersionColumn::Timestamp(column) => column,
+        }
+    }
+
+    /// the assignment of the next version in an update
+    fn next(&self, platform: Platform) -> String {
+        let column = platform.quote_ident(self.column());
+        match self {
+            VersionColumn::Counter(_) => format!("{} = {} + 1", column, column),
+            VersionColumn::Timestamp(_) => format!("{} = CURRENT_TIMESTAMP", column),
+        }
+    }
+}
+
+/// How a row is checked to be the same on the server as when it was loaded,
+/// before it is updated or deleted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RowLock {
+    /// the columns of the list that are columns of the table, they are read again to be compared
+    pub columns: Vec<String>,
+    /// compared instead of all the columns, when the table has one
+    pub version: Option<VersionColumn>,
+}
+
+impl RowLock {
+    /// Read the columns of the row again, locked until the end of the transaction
+    /// where the database allows it.
+    pub fn select(
+        &self,
+        platform: Platform,
+        table: &str,
+        columns: &[String],
+        key: &[String],
+        original: &[Value],
+    ) -> (String, Vec<Value>) {
+        let mut params = vec![];
+        let filter = where_clause(platform, columns, key, original, &mut params);
+        let names: Vec<String> = self
+            .columns
+            .iter()
+            .map(|c| platform.quote_ident(c))
+            .collect();
+        let lock = match platform {
+            Platform::Sqlite => "",
+            _ => " FOR UPDATE",
+        };
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {}{}",
+            names.join(", "),
+            platform.quote_table(table),
+            filter,
+            lock
+        );
+        (sql, params)
+    }
+
+    /// The values of the row read by `select` in the order of the columns of the list,
+    /// the columns that are not in the table keep their original value.
+    pub fn current_row(
+        &self,
+        columns: &[String],
+        original: &[Value],
+        current: &[Value],
+    ) -> Vec<Value> {
+        columns
+            .iter()
+            .enumerate()
+            .map(
+                |(i, column)| match self.columns.iter().position(|c| c == column) {
+                    Some(j) => current.get(j).cloned().unwrap_or(None),
+                    None => original[i].clone(),
+                },
+            )
+            .collect()
+    }
+
+    /// whether the row was changed since it was loaded, on its version or any of its values
+    pub fn is_changed(&self, columns: &[String], original: &[Value], current: &[Value]) -> bool {
+        let compared: Vec<usize> = match &self.version {
+            Some(version) => columns
+                .iter()
+                .position(|c| c == version.column())
+                .into_iter()
+                .collect(),
+            None => (0..columns.len())
+                .filter(|i| self.columns.contains(&columns[*i]))
+                .collect(),
+        };
+        compared.into_iter().any(|i| original[i] != current[i])
+    }
+}
+
+/// a row that was changed or deleted on the server since it was loaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// the index of the edited record, none for a deleted record
+    pub row: Option<usize>,
+    /// the values when the row was loaded
+    pub original: Vec<Value>,
+    /// the values on the server, none when the row is not there anymore
+    pub current: Option<Vec<Value>>,
+}
+
+/// The columns changed on either side, with whether the edited value is kept:
+/// it is unless the value was only changed on the server.
+pub fn merge_choices(original: &[Value], mine: &[Value], theirs: &[Value]) -> Vec<(usize, bool)> {
+    (0..original.len())
+        .filter(|i| mine[*i] != original[*i] || theirs[*i] != original[*i])
+        .map(|i| (i, mine[i] != original[i]))
+        .collect()
+}
+
+/// the values of the server with the edited values that are kept
+pub fn merge(mine: &[Value], theirs: &[Value], choices: &[(usize, bool)]) -> Vec<Value> {
+    let mut merged = theirs.to_vec();
+    for (i, keep_mine) in choices.iter() {
+        if *keep_mine {
+            merged[*i] = mine[*i].clone();
+        }
+    }
+    merged
+}
+
+/// the channel of the notifications sent on saving, for the other sessions to mark the rows
+pub static CHANGES_CHANNEL: &str = "datawindow_changes";
+
+/// the payloads of postgres must be shorter than 8000 bytes
+static NOTICE_LIMIT: usize = 7900;
+
+/// The payload of the notification of a saved change of a row, see `audit::row_key`.
+/// The key is left out when it is too long, ie: the values of a row without a primary key.
+pub fn change_notice(table: &str, action: &str, key: &str) -> String {
+    let notice = format!("{}\t{}\t{}", table, action, key);
+    match notice.len() < NOTICE_LIMIT {
+        true => notice,
+        false => format!("{}\t{}\t", table, action),
+    }
+}
+
+/// the table, action and key of a notice
+pub fn parse_change_notice(payload: &str) -> Option<(&str, &str, &str)> {
+    let mut parts = payload.splitn(3, '\t');
+    Some((parts.next()?, parts.next()?, parts.next()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[Option<&str>]) -> Vec<Value> {
+        values.iter().map(|v| v.map(String::from)).collect()
+    }
+
+    #[test]
+    fn conflicts_are_merged() {
+        let columns: Vec<String> = vec!["id".into(), "name".into(), "city".into(), "total".into()];
+        let lock = RowLock {
+            columns: vec!["id".into(), "name".into(), "city".into()],
+            version: None,
+        };
+        let original = values(&[Some("1"), Some("Ann"), Some("Oslo"), Some("3")]);
+        let mine = values(&[Some("1"), Some("Anne"), Some("Oslo"), Some("3")]);
+        let current = lock.current_row(
+            &columns,
+            &original,
+            &values(&[Some("1"), Some("Ann"), Some("Bergen")]),
+        );
+        assert_eq!(current[3], Some("3".to_string()));
+        assert!(lock.is_changed(&columns, &original, &current));
+        assert!(!lock.is_changed(&columns, &original, &original));
+        let choices = merge_choices(&original, &mine, &current);
+        assert_eq!(choices, vec![(1, true), (2, false)]);
+        assert_eq!(
+            merge(&mine, &current, &choices),
+            values(&[Some("1"), Some("Anne"), Some("Bergen"), Some("3")])
+        );
+
+        let versioned = RowLock {
+            columns: lock.columns.clone(),
+            version: Some(VersionColumn::Counter("total".into())),
+        };
+        // only the version is compared
+        assert!(!versioned.is_changed(&columns, &original, &current));
+        let record = Record {
+            original: Some(original.clone()),
+            values: mine.clone(),
+        };
+        let (sql, params) = update(
+            Platform::Postgres,
+            "people",
+            &columns,
+            &["id".into()],
+            &record,
+            &versioned,
+        )
+        .unwrap();
+        assert_eq!(
+            sql,
+            "UPDATE \"people\" SET \"name\" = $1, \"total\" = \"total\" + 1 WHERE \"id\" = $2"
+        );
+        assert_eq!(params, values(&[Some("Anne"), Some("1")]));
+        let (sql, _) = lock.select(
+            Platform::Sqlite,
+            "people",
+            &columns,
+            &["id".into()],
+            &original,
+        );
+        assert_eq!(
+            sql,
+            "SELECT \"id\", \"name\", \"city\" FROM \"people\" WHERE \"id\" = ?1"
+        );
+
+        let notice = change_notice("public.people", "update", "\"id\" = '1'");
+        assert_eq!(
+            parse_change_notice(&notice),
+            Some(("public.people", "update", "\"id\" = '1'"))
+        );
+        let long = format!("\"note\" = '{}'", "x".repeat(10000));
+        let notice = change_notice("public.people", "update", &long);
+        assert_eq!(
+            parse_change_notice(&notice),
+            Some(("public.people", "update", ""))
+        );
+    }
+}
@@ -0,0 +1,609 @@
+//! display formats for the list columns and edit masks for the detail entries
+//!
+//! Values that can not be parsed for the chosen format are displayed as is.
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Format {
+    Number {
+        #[serde(default)]
+        decimals: usize,
+        #[serde(default = "default_true")]
+        thousands: bool,
+        /// ie: `en_US`, `de_DE`, defaults to the system locale
+        locale: Option<String>,
+    },
+    Currency {
+        symbol: String,
+        #[serde(default = "default_decimals")]
+        decimals: usize,
+        locale: Option<String>,
+    },
+    Percent {
+        #[serde(default)]
+        decimals: usize,
+    },
+    /// strftime like pattern: `%Y %y %m %d %b %B %H %I %M %S %p`
+    Date {
+        pattern: String,
+    },
+    Boolean {
+        #[serde(default)]
+        style: BoolStyle,
+    },
+    Truncate {
+        max_chars: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoolStyle {
+    YesNo,
+    Icons,
+}
+
+impl Default for BoolStyle {
+    fn default() -> Self {
+        BoolStyle::YesNo
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_decimals() -> usize {
+    2
+}
+
+static MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+impl Format {
+    /// the formats offered in the column header menu
+    pub fn presets() -> Vec<(&'static str, Option<Format>)> {
+        vec![
+            ("Raw value", None),
+            (
+                "Number",
+                Some(Format::Number {
+                    decimals: 0,
+                    thousands: true,
+                    locale: None,
+                }),
+            ),
+            (
+                "Number (2 decimals)",
+                Some(Format::Number {
+                    decimals: 2,
+                    thousands: true,
+                    locale: None,
+                }),
+            ),
+            (
+                "Currency",
+                Some(Format::Currency {
+                    symbol: "$".to_string(),
+                    decimals: 2,
+                    locale: None,
+                }),
+            ),
+            ("Percent", Some(Format::Percent { decimals: 1 })),
+            (
+                "Date (2020-12-31)",
+                Some(Format::Date {
+                    pattern: "%Y-%m-%d".to_string(),
+                }),
+            ),
+            (
+                "Date (31/12/2020)",
+                Some(Format::Date {
+                    pattern: "%d/%m/%Y".to_string(),
+                }),
+            ),
+            (
+                "Date (Dec 31, 2020)",
+                Some(Format::Date {
+                    pattern: "%b %d, %Y".to_string(),
+                }),
+            ),
+            (
+                "Time (11:59 PM)",
+                Some(Format::Date {
+                    pattern: "%I:%M %p".to_string(),
+                }),
+            ),
+            (
+                "Date and time",
+                Some(Format::Date {
+                    pattern: "%Y-%m-%d %H:%M".to_string(),
+                }),
+            ),
+            (
+                "Yes / No",
+                Some(Format::Boolean {
+                    style: BoolStyle::YesNo,
+                }),
+            ),
+            (
+                "Check marks",
+                Some(Format::Boolean {
+                    style: BoolStyle::Icons,
+                }),
+            ),
+            ("Truncate to 20", Some(Format::Truncate { max_chars: 20 })),
+            ("Truncate to 50", Some(Format::Truncate { max_chars: 50 })),
+        ]
+    }
+
+    pub fn format(&self, value: &str) -> String {
+        let formatted = match self {
+            Format::Number {
+                decimals,
+                thousands,
+                locale,
+            } => format_number(value, *decimals, *thousands, locale.as_deref()),
+            Format::Currency {
+                symbol,
+                decimals,
+                locale,
+            } => format_number(value, *decimals, true, locale.as_deref()).map(|n| {
+                match n.strip_prefix('-') {
+                    Some(abs) => format!("-{}{}", symbol, abs),
+                    None => format!("{}{}", symbol, n),
+                }
+            }),
+            Format::Percent { decimals } => percent_of(value)
+                .and_then(|v| format_number(&v, *decimals, true, None))
+                .map(|n| format!("{}%", n)),
+            Format::Date { pattern } => format_date(value, pattern),
+            Format::Boolean { style } => parse_bool(value).map(|b| match (style, b) {
+                (BoolStyle::YesNo, true) => "Yes".to_string(),
+                (BoolStyle::YesNo, false) => "No".to_string(),
+                (BoolStyle::Icons, true) => "\u{2714}".to_string(),
+                (BoolStyle::Icons, false) => "\u{2718}".to_string(),
+            }),
+            Format::Truncate { max_chars } => Some(truncate(value, *max_chars)),
+        };
+        formatted.unwrap_or_else(|| value.to_string())
+    }
+}
+
+/// the thousands and decimal separator used in this locale
+fn separators(locale: Option<&str>) -> (char, char) {
+    let system = std::env::var("LC_NUMERIC")
+        .or_else(|_| std::env::var("LANG"))
+        .unwrap_or_default();
+    let locale = locale.unwrap_or(&system);
+    let language = locale.split(|c| c == '_' || c == '-' || c == '.').next();
+    match language.unwrap_or("") {
+        "de" | "es" | "it" | "nl" | "pt" | "id" | "da" | "tr" => ('.', ','),
+        "fr" | "ru" | "pl" | "sv" | "cs" | "fi" | "nb" | "uk" => ('\u{a0}', ','),
+        _ => (',', '.'),
+    }
+}
+
+/// The sign, integer and fraction digits of a number written without an exponent, ie: `-012.50`
+/// gives `(true, "12", "50")`. The digits are kept as text, so no precision is lost.
+pub fn decimal_parts(value: &str) -> Option<(bool, String, String)> {
+    let value = value.trim();
+    let (negative, digits) = match value.chars().next()? {
+        '-' => (true, &value[1..]),
+        '+' => (false, &value[1..]),
+        _ => (false, value),
+    };
+    let mut parts = digits.splitn(2, '.');
+    let integer = parts.next().unwrap_or("");
+    let fraction = parts.next().unwrap_or("");
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if integer.len() + fraction.len() == 0 || !is_digits(integer) || !is_digits(fraction) {
+        return None;
+    }
+    let integer = integer.trim_start_matches('0');
+    let integer = if integer.is_empty() { "0" } else { integer };
+    Some((negative, integer.to_string(), fraction.to_string()))
+}
+
+/// round the digits to this many decimals, half away from zero
+fn round_digits(integer: &str, fraction: &str, decimals: usize) -> (String, String) {
+    if fraction.len() <= decimals {
+        return (integer.to_string(), format!("{:0<1$}", fraction, decimals));
+    }
+    let mut digits: Vec<u8> = integer
+        .bytes()
+        .chain(fraction.bytes().take(decimals))
+        .collect();
+    if fraction.as_bytes()[decimals] >= b'5' {
+        let mut i = digits.len();
+        loop {
+            if i == 0 {
+                digits.insert(0, b'1');
+                break;
+            }
+            i -= 1;
+            if digits[i] == b'9' {
+                digits[i] = b'0';
+            } else {
+                digits[i] += 1;
+                break;
+            }
+        }
+    }
+    let split = digits.len() - decimals;
+    let text = String::from_utf8(digits).unwrap_or_default();
+    (text[..split].to_string(), text[split..].to_string())
+}
+
+/// the value times 100, on the digits unless it is written with an exponent
+fn percent_of(value: &str) -> Option<String> {
+    match decimal_parts(value) {
+        Some((negative, integer, fraction)) => {
+            let fraction = format!("{:0<2}", fraction);
+            Some(format!(
+                "{}{}{}.{}",
+                if negative { "-" } else { "" },
+                integer,
+                &fraction[..2],
+                &fraction[2..]
+            ))
+        }
+        None => {
+            let number: f64 = value.trim().parse().ok()?;
+            Some((number * 100.0).to_string())
+        }
+    }
+}
+
+fn format_number(
+    value: &str,
+    decimals: usize,
+    thousands: bool,
+    locale: Option<&str>,
+) -> Option<String> {
+    // a numeric or a bigint may have more digits than a float keeps
+    let (negative, integer, fraction) = match decimal_parts(value) {
+        Some((negative, integer, fraction)) => {
+            let (integer, fraction) = round_digits(&integer, &fraction, decimals);
+            (negative, integer, fraction)
+        }
+        None => {
+            let number: f64 = value.trim().parse().ok()?;
+            if !number.is_finite() {
+                return None;
+            }
+            let fixed = format!("{:.*}", decimals, number.abs());
+            let mut parts = fixed.splitn(2, '.');
+            let integer = parts.next()?.to_string();
+            let fraction = parts.next().unwrap_or("").to_string();
+            (number < 0.0, integer, fraction)
+        }
+    };
+    let (thousands_sep, decimal_sep) = separators(locale);
+    let integer = integer.trim_start_matches('0');
+    let integer = if integer.is_empty() { "0" } else { integer };
+    let mut grouped = String::new();
+    for (i, c) in integer.chars().enumerate() {
+        if thousands && i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(thousands_sep);
+        }
+        grouped.push(c);
+    }
+    if !fraction.is_empty() {
+        grouped.push(decimal_sep);
+        grouped.push_str(&fraction);
+    }
+    let is_zero = integer.chars().chain(fraction.chars()).all(|c| c == '0');
+    if negative && !is_zero {
+        grouped.insert(0, '-');
+    }
+    Some(grouped)
+}
+
+/// the usual ways of writing a boolean, including the check marks of the icons style
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "t" | "true" | "y" | "yes" | "1" | "\u{2714}" => Some(true),
+        "f" | "false" | "n" | "no" | "0" | "\u{2718}" => Some(false),
+        _ => None,
+    }
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    if value.chars().count() <= max_chars {
+        value.to_string()
+    } else {
+        let kept: String = value.chars().take(max_chars.saturating_sub(1)).collect();
+        format!("{}\u{2026}", kept)
+    }
+}
+
+/// the numeric parts of an iso date, time or timestamp as returned by the database
+#[derive(Default)]
+struct DateParts {
+    date: Option<(u32, u32, u32)>,
+    time: Option<(u32, u32, u32)>,
+}
+
+fn parse_date(value: &str) -> Option<DateParts> {
+    let value = value.trim();
+    let mut parts = DateParts::default();
+    let (date, time) = if value.len() >= 10 && value.as_bytes().get(4) == Some(&b'-') {
+        // not a date when the 10th byte is inside a char
+        let rest = value
+            .get(10..)?
+            .trim_start_matches(|c| c == ' ' || c == 'T');
+        (Some(&value[..10]), Some(rest).filter(|r| !r.is_empty()))
+    } else {
+        (None, Some(value))
+    };
+    if let Some(date) = date {
+        let ymd: Vec<u32> = date.split('-').filter_map(|p| p.parse().ok()).collect();
+        if ymd.len() != 3 || ymd[1] == 0 || ymd[1] > 12 {
+            return None;
+        }
+        parts.date = Some((ymd[0], ymd[1], ymd[2]));
+    }
+    if let Some(time) = time {
+        // drop the fraction of a second and the timezone
+        let time = time
+            .split(|c| c == '.' || c == '+' || c == 'Z')
+            .next()
+            .unwrap_or("");
+        let time = match time.rfind('-') {
+            Some(i) if i > 5 => &time[..i],
+            _ => time,
+        };
+        let hms: Vec<u32> = time.split(':').filter_map(|p| p.parse().ok()).collect();
+        if hms.len() < 2 || hms[0] > 24 {
+            return None;
+        }
+        parts.time = Some((hms[0], hms[1], hms.get(2).cloned().unwrap_or(0)));
+    }
+    Some(parts)
+}
+
+fn format_date(value: &str, pattern: &str) -> Option<String> {
+    let parts = parse_date(value)?;
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let spec = chars.next()?;
+        let formatted = match spec {
+            'Y' => parts.date.map(|(y, _, _)| format!("{:04}", y)),
+            'y' => parts.date.map(|(y, _, _)| format!("{:02}", y % 100)),
+            'm' => parts.date.map(|(_, m, _)| format!("{:02}", m)),
+            'd' => parts.date.map(|(_, _, d)| format!("{:02}", d)),
+            'B' => parts
+                .date
+                .map(|(_, m, _)| MONTHS[m as usize - 1].to_string()),
+            'b' => parts
+                .date
+                .map(|(_, m, _)| MONTHS[m as usize - 1][..3].to_string()),
+            'H' => parts.time.map(|(h, _, _)| format!("{:02}", h)),
+            'I' => parts
+                .time
+                .map(|(h, _, _)| format!("{:02}", (h + 11) % 12 + 1)),
+            'M' => parts.time.map(|(_, m, _)| format!("{:02}", m)),
+            'S' => parts.time.map(|(_, _, s)| format!("{:02}", s)),
+            'p' => parts
+                .time
+                .map(|(h, _, _)| if h < 12 { "AM" } else { "PM" }.to_string()),
+            '%' => Some("%".to_string()),
+            _ => None,
+        };
+        out.push_str(&formatted?);
+    }
+    Some(out)
+}
+
+/// A number as written in a spreadsheet, ie: `$1,234.50`, `1.234,5` or `(12)`,
+/// as the plain number the database expects. A percent is read as a fraction.
+pub fn parse_number(text: &str) -> Option<String> {
+    let mut text: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '$' | '\u{20ac}' | '\u{a3}' | '\u{a5}'))
+        .collect();
+    let negative = text.starts_with('(') && text.ends_with(')');
+    if negative {
+        text = text[1..text.len() - 1].to_string();
+    }
+    let percent = text.ends_with('%');
+    if percent {
+        text.pop();
+    }
+    // the separator that comes last is the decimal one, a repeated one is for the thousands;
+    // a lone comma followed by 3 digits is for the thousands as in `1,234`,
+    // a lone dot is the decimal one as in `1.234`
+    let decimal = match (text.rfind('.'), text.rfind(',')) {
+        (Some(dot), Some(comma)) => Some(if dot > comma { '.' } else { ',' }),
+        (Some(i), None) | (None, Some(i)) => {
+            let separator = text[i..].chars().next()?;
+            let repeated = text.matches(separator).count() > 1;
+            if repeated || (separator == ',' && text.len() - i == 4) {
+                None
+            } else {
+                Some(separator)
+            }
+        }
+        (None, None) => None,
+    };
+    let mut number: String = text
+        .chars()
+        .filter(|c| (*c != '.' && *c != ',') || Some(*c) == decimal)
+        .map(|c| if Some(c) == decimal { '.' } else { c })
+        .collect();
+    if negative {
+        number.insert(0, '-');
+    }
+    let value: f64 = number.parse().ok()?;
+    if percent {
+        Some((value / 100.0).to_string())
+    } else {
+        Some(number)
+    }
+}
+
+/// A date as written in a spreadsheet, ie: `31/12/2020`, `31.12.2020` or `Dec 31, 2020`,
+/// as an iso date followed by the time when there is one.
+/// Slashes are read as day first, unless the second part can only be a day.
+pub fn parse_display_date(text: &str) -> Option<String> {
+    let text = text.trim();
+    if parse_date(text).map(|p| p.date.is_some()).unwrap_or(false) {
+        return Some(text.to_string());
+    }
+    let (date, time) = match text.find(' ').filter(|i| text[*i..].contains(':')) {
+        Some(i) => (&text[..i], Some(text[i + 1..].trim())),
+        None => (text, None),
+    };
+    let month_of = |name: &str| -> Option<u32> {
+        let name = name.to_lowercase();
+        MONTHS
+            .iter()
+            .position(|m| name.len() >= 3 && m.to_lowercase().starts_with(&name))
+            .map(|m| m as u32 + 1)
+    };
+    let parts: Vec<&str> = date
+        .split(|c| c == '/' || c == '.' || c == '-' || c == ' ' || c == ',')
+        .filter(|p| !p.is_empty())
+        .collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let number = |part: &str| part.parse::<u32>().ok();
+    let (year, month, day) = match (number(parts[0]), number(parts[1]), number(parts[2])) {
+        (Some(a), Some(b), Some(year)) if b > 12 => (year, a, b),
+        (Some(day), Some(month), Some(year)) => (year, month, day),
+        // Dec 31, 2020
+        (None, Some(day), Some(year)) => (year, month_of(parts[0])?, day),
+        // 31 Dec 2020
+        (Some(day), None, Some(year)) => (year, month_of(parts[1])?, day),
+        _ => return None,
+    };
+    let year = if year < 100 { year + 2000 } else { year };
+    if month == 0 || month > 12 || day == 0 || day > 31 {
+        return None;
+    }
+    let iso = format!("{:04}-{:02}-{:02}", year, month, day);
+    match time {
+        Some(time) => Some(format!("{} {}", iso, time)),
+        None => Some(iso),
+    }
+}
+
+/// the pattern of the named edit masks, any other name is used as the pattern itself
+pub fn mask_pattern(mask: &str) -> &str {
+    match mask {
+        "phone" => "(###) ###-####",
+        "postal" | "zip" => "#####",
+        "zip+4" => "#####-####",
+        "postal_ca" => "A#A #A#",
+        "postal_uk" => "**** ***",
+        _ => mask,
+    }
+}
+
+/// Lay out the typed text over the edit mask, where `#` is a digit, `A` is a letter
+/// and `*` is a letter or a digit. Every other char in the mask is inserted literally.
+/// Typed chars that don't fit are dropped. Applying the mask again gives the same text.
+pub fn apply_mask(mask: &str, input: &str) -> String {
+    let mut out = String::new();
+    let mut chars = input.chars().peekable();
+    for m in mask_pattern(mask).chars() {
+        if chars.peek().is_none() {
+            break;
+        }
+        let fits = |c: &char| match m {
+            '#' => c.is_ascii_digit(),
+            'A' => c.is_alphabetic(),
+            _ => c.is_alphanumeric(),
+        };
+        if matches!(m, '#' | 'A' | '*') {
+            match chars.by_ref().find(fits) {
+                Some(c) => out.push(c),
+                None => break,
+            }
+        } else {
+            out.push(m);
+            if chars.peek() == Some(&m) {
+                chars.next();
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_are_parsed() {
+        let parts = parse_date("2020-12-31 23:59:58.123+01").unwrap();
+        assert_eq!(parts.date, Some((2020, 12, 31)));
+        assert_eq!(parts.time, Some((23, 59, 58)));
+        let parts = parse_date("2020-01-02T03:04:05Z").unwrap();
+        assert_eq!(parts.time, Some((3, 4, 5)));
+        assert_eq!(parse_date("12:30").unwrap().time, Some((12, 30, 0)));
+        assert!(parse_date("2020-13-01").is_none());
+        // text typed into a date column
+        assert!(parse_date("2020-1\u{e9}-\u{e9}\u{e9}").is_none());
+        assert!(parse_date("soon").is_none());
+    }
+
+    #[test]
+    fn numbers_are_parsed() {
+        assert_eq!(parse_number("$1,234.50").as_deref(), Some("1234.50"));
+        assert_eq!(parse_number("1.234,5").as_deref(), Some("1234.5"));
+        assert_eq!(parse_number("(12)").as_deref(), Some("-12"));
+        assert_eq!(parse_number("50%").as_deref(), Some("0.5"));
+        assert_eq!(parse_number("1 000 000").as_deref(), Some("1000000"));
+        assert_eq!(parse_number("1,234").as_deref(), Some("1234"));
+        assert_eq!(parse_number("1,5").as_deref(), Some("1.5"));
+        assert_eq!(parse_number("1,2345").as_deref(), Some("1.2345"));
+        assert_eq!(parse_number("1.234").as_deref(), Some("1.234"));
+        assert_eq!(parse_number("1.234.567").as_deref(), Some("1234567"));
+        assert_eq!(parse_number("twelve"), None);
+    }
+
+    #[test]
+    fn numbers_keep_their_digits() {
+        let number = |value: &str, decimals: usize| {
+            format_number(value, decimals, true, Some("en_US")).unwrap_or_default()
+        };
+        assert_eq!(
+            number("12345678901234567.89", 2),
+            "12,345,678,901,234,567.89"
+        );
+        assert_eq!(
+            number("9223372036854775807", 0),
+            "9,223,372,036,854,775,807"
+        );
+        assert_eq!(number("999.995", 2), "1,000.00");
+        assert_eq!(number("-0.004", 2), "0.00");
+        assert_eq!(number("-1.5", 0), "-2");
+        assert_eq!(number(".5", 1), "0.5");
+        assert_eq!(number("1.5e3", 1), "1,500.0");
+        let percent = Format::Percent { decimals: 1 };
+        assert_eq!(percent.format("0.1234"), "12.3%");
+        assert_eq!(percent.format("-1.5"), "-150.0%");
+        assert_eq!(percent.format("abc"), "abc");
+    }
+}
@@ -0,0 +1,193 @@
+//! fuzzy matching of the names typed in the command palette
+//!
+//! The chars of the pattern must appear in the candidate in the same order, ignoring case.
+//! Matches at the start of words and runs of consecutive chars score higher,
+//! so `cust` ranks `customers` above `cancelled_subscriptions`.
+
+/// bonus for a char right after the previous matched char
+const CONSECUTIVE: i64 = 5;
+/// bonus for a char that starts a word, ie: after `.`, `_`, or a lower to upper case change
+const WORD_START: i64 = 8;
+/// penalty for each skipped char between two matched chars
+const GAP: i64 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub score: i64,
+    /// the char index of the matched chars in the candidate, for highlighting
+    pub positions: Vec<usize>,
+}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    match i {
+        0 => true,
+        _ => {
+            let prev = chars[i - 1];
+            !prev.is_alphanumeric() || (prev.is_lowercase() && chars[i].is_uppercase())
+        }
+    }
+}
+
+/// Match the pattern against the candidate, `None` when it does not match.
+/// Each pattern char is matched at the best scoring place that still lets the rest match.
+pub fn fuzzy_match(pattern: &str, candidate: &str) -> Option<Match> {
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    let chars: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let (n, m) = (pattern.len(), chars.len());
+    if n == 0 {
+        return Some(Match {
+            score: 0,
+            positions: vec![],
+        });
+    }
+    if n > m {
+        return None;
+    }
+    // best[i][j]: the best score of matching pattern[..=i] with pattern[i] at chars[j]
+    let mut best: Vec<Vec<Option<i64>>> = vec![vec![None; m]; n];
+    let mut from: Vec<Vec<usize>> = vec![vec![0; m]; n];
+    for i in 0..n {
+        for j in i..m {
+            if lower[j] != pattern[i] {
+                continue;
+            }
+            let bonus = 1 + if is_word_start(&chars, j) {
+                WORD_START
+            } else {
+                0
+            };
+            if i == 0 {
+                best[i][j] = Some(bonus - j as i64 * GAP / 2);
+                continue;
+            }
+            for k in (i - 1)..j {
+                let prev = match best[i - 1][k] {
+                    Some(prev) => prev,
+                    None => continue,
+                };
+                let score = if k + 1 == j {
+                    prev + bonus + CONSECUTIVE
+                } else {
+                    prev + bonus - (j - k - 1) as i64 * GAP
+                };
+                if best[i][j].map(|b| score > b).unwrap_or(true) {
+                    best[i][j] = Some(score);
+                    from[i][j] = k;
+                }
+            }
+        }
+    }
+    let (mut j, score) = best[n - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (j, s)))
+        .max_by_key(|(j, s)| (*s, std::cmp::Reverse(*j)))?;
+    let mut positions = vec![j; n];
+    for i in (1..n).rev() {
+        j = from[i][j];
+        positions[i - 1] = j;
+    }
+    Some(Match {
+        score: score,
+        positions: positions,
+    })
+}
+
+/// The items that match the pattern, best first.
+/// Items with the same score keep their order.
+pub fn rank<'a, T, F>(pattern: &str, items: &'a [T], key: F) -> Vec<(&'a T, Match)>
+where
+    F: Fn(&T) -> String,
+{
+    let mut matches: Vec<(&T, Match)> = items
+        .iter()
+        .filter_map(|item| fuzzy_match(pattern, &key(item)).map(|m| (item, m)))
+        .collect();
+    matches.sort_by(|a, b| b.1.score.cmp(&a.1.score));
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_pattern_matches_everything() {
+        let m = fuzzy_match("", "customers").unwrap();
+        assert_eq!(m.score, 0);
+        assert!(m.positions.is_empty());
+    }
+
+    #[test]
+    fn chars_must_be_in_order() {
+        assert!(fuzzy_match("cus", "customers").is_some());
+        assert!(fuzzy_match("suc", "customers").is_none());
+        assert!(fuzzy_match("customerss", "customers").is_none());
+    }
+
+    #[test]
+    fn ignores_case_and_spaces() {
+        assert!(fuzzy_match("CUST", "customers").is_some());
+        assert!(fuzzy_match("order items", "OrderItems").is_some());
+    }
+
+    #[test]
+    fn positions_of_the_matched_chars() {
+        let m = fuzzy_match("pbo", "public.orders").unwrap();
+        assert_eq!(m.positions, vec![0, 2, 7]);
+    }
+
+    #[test]
+    fn prefers_word_starts() {
+        // `o` at the start of `orders`, not the `o` in `customer`
+        let m = fuzzy_match("co", "customer_orders").unwrap();
+        assert_eq!(m.positions, vec![0, 9]);
+        let m = fuzzy_match("oi", "OrderItems").unwrap();
+        assert_eq!(m.positions, vec![0, 5]);
+    }
+
+    #[test]
+    fn consecutive_beats_scattered() {
+        let tight = fuzzy_match("cust", "customers").unwrap();
+        let loose = fuzzy_match("cust", "cancelled_subscriptions").unwrap();
+        assert!(tight.score > loose.score);
+    }
+
+    #[test]
+    fn matches_across_schemas() {
+        let tables = vec![
+            "public.orders".to_string(),
+            "sales.order_lines".to_string(),
+            "sales.customers".to_string(),
+            "archive.old_orders".to_string(),
+        ];
+        let ranked: Vec<&str> = rank("sal.ord", &tables, |t| t.to_string())
+            .into_iter()
+            .map(|(t, _)| t.as_str())
+            .collect();
+        assert_eq!(ranked, vec!["sales.order_lines"]);
+        let ranked: Vec<&str> = rank("orders", &tables, |t| t.to_string())
+            .into_iter()
+            .map(|(t, _)| t.as_str())
+            .collect();
+        assert_eq!(ranked[0], "public.orders");
+        assert_eq!(ranked.len(), 3);
+    }
+
+    #[test]
+    fn rank_keeps_order_of_equal_scores() {
+        let items = vec!["ab", "ab", "xab"];
+        let ranked = rank("ab", &items, |s| s.to_string());
+        assert_eq!(ranked.len(), 3);
+        assert!(std::ptr::eq(ranked[0].0, &items[0]));
+        assert!(std::ptr::eq(ranked[1].0, &items[1]));
+    }
+}
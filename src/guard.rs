@@ -0,0 +1,63 @@
+//! the confirmation of a save on a guarded connection: what it changes is shown,
+//! and it runs once the name of the connection is typed
+use gtk;
+use gtk::prelude::*;
+use gtk::{
+    Adjustment, Dialog, DialogFlags, Entry, Label, ResponseType, ScrolledWindow, TextView, Window,
+    WrapMode,
+};
+
+/// true when the name of the connection was typed and the save confirmed
+pub fn confirm_save(parent: Option<&Window>, connection: &str, summary: &str) -> bool {
+    let dialog = Dialog::with_buttons(
+        Some(&format!("Save on {}", connection)),
+        parent,
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", ResponseType::Cancel),
+            ("Save", ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_size(600, 400);
+    dialog.set_default_response(ResponseType::Accept);
+    dialog.set_response_sensitive(ResponseType::Accept, false);
+    let message = Label::new(None);
+    message.set_markup(&format!(
+        "These changes are about to be saved on <b>{}</b>.\n\
+         Type the name of the connection to run them.",
+        glib::markup_escape_text(connection)
+    ));
+    message.set_xalign(0.0);
+    let text = TextView::new();
+    text.set_editable(false);
+    text.set_monospace(true);
+    text.set_wrap_mode(WrapMode::None);
+    if let Some(buffer) = text.get_buffer() {
+        buffer.set_text(summary);
+    }
+    let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+    scroll.add(&text);
+    scroll.set_vexpand(true);
+    let name = Entry::new();
+    name.set_placeholder_text(Some(connection));
+    name.set_activates_default(true);
+    let expected = connection.to_string();
+    let this = dialog.clone();
+    name.connect_changed(move |entry| {
+        let typed = entry.get_text().as_str() == expected;
+        this.set_response_sensitive(ResponseType::Accept, typed);
+    });
+    let content = dialog.get_content_area();
+    content.set_spacing(5);
+    content.set_margin_start(10);
+    content.set_margin_end(10);
+    content.set_margin_top(10);
+    content.add(&message);
+    content.pack_start(&scroll, true, true, 0);
+    content.add(&name);
+    dialog.show_all();
+    let response = dialog.run();
+    let typed = name.get_text().as_str() == connection;
+    dialog.close();
+    response == ResponseType::Accept && typed
+}
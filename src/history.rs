@@ -0,0 +1,138 @@
+//! the changes of the record shown in the detail, as recorded in the audit log,
+//! next to the form of the data tab
+use crate::audit::Change;
+use crate::db::ResultSet;
+use crate::tab::DataTab;
+use gtk;
+use gtk::prelude::*;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::{Adjustment, Button, Label, ListStore, ScrolledWindow, TreeView};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Clone)]
+pub struct HistoryView {
+    pub container: gtk::Box,
+    message: Label,
+    /// the time, user, action and changed columns of each change, the newest first
+    timeline: TreeView,
+    timeline_store: ListStore,
+    /// the values of the columns before and after the selected change
+    values_store: ListStore,
+    changes: Rc<RefCell<Vec<Change>>>,
+}
+
+impl HistoryView {
+    pub fn new() -> Self {
+        let (timeline, timeline_store) = Self::create_list(&["When", "Who", "Action", "Columns"]);
+        let (values, values_store) = Self::create_list(&["Column", "Before", "After"]);
+        let title = Label::new(None);
+        title.set_markup("<b>History</b>");
+        title.set_xalign(0.0);
+        // the action of the data tab the view is in
+        let revert = Button::with_label("Revert to this version");
+        revert.set_action_name(Some("tab.revert-version"));
+        revert.set_tooltip_text(Some(
+            "Put the values of the record back as they were after the selected change",
+        ));
+        let header = gtk::Box::new(Horizontal, 5);
+        header.pack_start(&title, true, true, 0);
+        header.pack_end(&revert, false, false, 0);
+        let message = Label::new(None);
+        message.set_xalign(0.0);
+        message.set_line_wrap(true);
+        let container = gtk::Box::new(Vertical, 5);
+        container.set_margin_start(10);
+        container.set_size_request(350, -1);
+        container.add(&header);
+        container.add(&message);
+        container.pack_start(&Self::scrolled(&timeline), true, true, 0);
+        container.pack_start(&Self::scrolled(&values), true, true, 0);
+
+        let view = HistoryView {
+            container: container,
+            message: message,
+            timeline: timeline,
+            timeline_store: timeline_store,
+            values_store: values_store,
+            changes: Rc::new(RefCell::new(vec![])),
+        };
+        view.init_selection();
+        view
+    }
+
+    fn create_list(columns: &[&str]) -> (TreeView, ListStore) {
+        let rs = ResultSet {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: vec![],
+        };
+        let (list, store, _) = DataTab::create_result_list(&rs);
+        (list, store)
+    }
+
+    fn scrolled(list: &TreeView) -> ScrolledWindow {
+        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scroll.add(list);
+        scroll
+    }
+
+    fn init_selection(&self) {
+        let this = self.clone();
+        self.timeline.get_selection().connect_changed(move |_| {
+            this.values_store.clear();
+            let change = match this.selected() {
+                Some(change) => change,
+                None => return,
+            };
+            for (column, old, new) in change.values.iter() {
+                if old == new {
+                    continue;
+                }
+                let iter = this.values_store.append();
+                let row = [Some(column.as_str()), old.as_deref(), new.as_deref()];
+                for (i, value) in row.iter().enumerate() {
+                    this.values_store
+                        .set_value(&iter, i as u32, &value.to_value());
+                }
+            }
+        });
+    }
+
+    /// the changes of a record, or the reason why there are none
+    pub fn show(&self, changes: Result<Vec<Change>, String>) {
+        self.timeline_store.clear();
+        self.values_store.clear();
+        let changes = match changes {
+            Ok(changes) => changes,
+            Err(message) => {
+                self.message.set_text(&message);
+                self.changes.borrow_mut().clear();
+                return;
+            }
+        };
+        self.message.set_text(match changes.len() {
+            0 => "No recorded change of this record",
+            _ => "",
+        });
+        for change in changes.iter() {
+            let iter = self.timeline_store.append();
+            let row = [
+                change.changed_at.clone(),
+                change.user.clone(),
+                change.action.as_str().to_string(),
+                change.changed_columns().join(", "),
+            ];
+            for (i, value) in row.iter().enumerate() {
+                self.timeline_store
+                    .set_value(&iter, i as u32, &value.to_value());
+            }
+        }
+        *self.changes.borrow_mut() = changes;
+    }
+
+    pub fn selected(&self) -> Option<Change> {
+        let (paths, _) = self.timeline.get_selection().get_selected_rows();
+        let index = paths.first()?.get_indices().first().cloned()?;
+        self.changes.borrow().get(index as usize).cloned()
+    }
+}
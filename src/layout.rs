@@ -0,0 +1,360 @@
+//! the layout of the detail form of a table, made in the form designer and saved in
+//! `layouts/<table>.toml` of the config directory
+//!
+//! ```toml
+//! [[pages]]
+//! title = "Customer"
+//!
+//! [[pages.items]]
+//! label = "Contact"
+//! top = 0
+//! width = 2
+//!
+//! [[pages.items]]
+//! column = "email"
+//! tooltip = "where the invoices are sent"
+//! top = 1
+//! width = 2
+//!
+//! [[pages]]
+//! title = "Notes"
+//! ```
+//!
+//! Without a layout the fields are shown two per row, a long one takes the whole row.
+use crate::config;
+use crate::detail::Field;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FormLayout {
+    /// a tab page each, there are no tabs when there is only one
+    #[serde(default)]
+    pub pages: Vec<LayoutPage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutPage {
+    pub title: String,
+    #[serde(default)]
+    pub items: Vec<LayoutItem>,
+}
+
+/// A field, or a section header when it has no column.
+/// The place is in the columns and rows of the form, a field column has the label and the entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutItem {
+    pub column: Option<String>,
+    /// the label of the field, the column name when not set, or the title of the section
+    pub label: Option<String>,
+    /// shown on the entry instead of the column definition
+    pub tooltip: Option<String>,
+    #[serde(default)]
+    pub left: i32,
+    #[serde(default)]
+    pub top: i32,
+    #[serde(default = "one", deserialize_with = "at_least_one")]
+    pub width: i32,
+    #[serde(default = "one", deserialize_with = "at_least_one")]
+    pub height: i32,
+}
+
+fn one() -> i32 {
+    1
+}
+
+/// a span of 0 edited by hand is read as 1, the grid has no empty spans
+fn at_least_one<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    Ok(i32::deserialize(deserializer)?.max(1))
+}
+
+impl LayoutItem {
+    pub fn field(column: &str, left: i32, top: i32) -> Self {
+        LayoutItem {
+            column: Some(column.to_string()),
+            label: None,
+            tooltip: None,
+            left: left,
+            top: top,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    pub fn section(title: &str, left: i32, top: i32) -> Self {
+        LayoutItem {
+            column: None,
+            label: Some(title.to_string()),
+            tooltip: None,
+            left: left,
+            top: top,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    pub fn is_section(&self) -> bool {
+        self.column.is_none()
+    }
+
+    /// the label, or the column name
+    pub fn title(&self) -> &str {
+        self.label
+            .as_deref()
+            .or(self.column.as_deref())
+            .unwrap_or("")
+    }
+
+    pub fn overlaps(&self, other: &LayoutItem) -> bool {
+        self.left < other.left + other.width
+            && other.left < self.left + self.width
+            && self.top < other.top + other.height
+            && other.top < self.top + self.height
+    }
+}
+
+impl LayoutPage {
+    pub fn new(title: &str) -> Self {
+        LayoutPage {
+            title: title.to_string(),
+            items: vec![],
+        }
+    }
+
+    /// the first row below all the items
+    pub fn next_row(&self) -> i32 {
+        self.items
+            .iter()
+            .map(|item| item.top + item.height)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// the columns and rows taken by the items
+    pub fn size(&self) -> (i32, i32) {
+        let columns = self
+            .items
+            .iter()
+            .map(|item| item.left + item.width)
+            .max()
+            .unwrap_or(0);
+        (columns, self.next_row())
+    }
+
+    /// whether the item would overlap any other item of the page
+    pub fn fits(&self, item: &LayoutItem, except: Option<usize>) -> bool {
+        self.items
+            .iter()
+            .enumerate()
+            .all(|(i, other)| Some(i) == except || !item.overlaps(other))
+    }
+}
+
+impl FormLayout {
+    /// One page with the fields in two columns, in the order of the columns.
+    /// A long field takes both columns, and two rows when it is longer still, see `Field::spans`.
+    pub fn automatic(fields: &[Field]) -> Self {
+        let mut items = vec![];
+        let (mut left, mut top) = (0, 0);
+        for field in fields.iter() {
+            let (width, height) = field.spans();
+            if left + width > 2 {
+                left = 0;
+                top += 1;
+            }
+            let mut item = LayoutItem::field(field.column(), left, top);
+            item.width = width;
+            item.height = height;
+            items.push(item);
+            left += width;
+            if left >= 2 {
+                left = 0;
+                top += height;
+            }
+        }
+        FormLayout {
+            pages: vec![LayoutPage {
+                title: "Fields".to_string(),
+                items: items,
+            }],
+        }
+    }
+
+    pub fn path(table: &str) -> PathBuf {
+        config::table_file("layouts", table)
+    }
+
+    /// the layout made for this table, none when the fields are laid out automatically
+    pub fn load(table: &str) -> Option<Self> {
+        let path = Self::path(table);
+        let content = fs::read_to_string(&path).ok()?;
+        match toml::from_str(&content) {
+            Ok(layout) => Some(layout),
+            Err(e) => {
+                println!("ignoring invalid layout {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, table: &str) -> io::Result<()> {
+        let path = Self::path(table);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        fs::write(path, content)
+    }
+
+    /// go back to the automatic layout
+    pub fn delete(table: &str) -> io::Result<()> {
+        match fs::remove_file(Self::path(table)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// the page and the index of the field of this column
+    pub fn find(&self, column: &str) -> Option<(usize, usize)> {
+        self.pages.iter().enumerate().find_map(|(p, page)| {
+            let i = page
+                .items
+                .iter()
+                .position(|item| item.column.as_deref() == Some(column))?;
+            Some((p, i))
+        })
+    }
+
+    /// the columns that are on none of the pages
+    pub fn hidden(&self, columns: &[String]) -> Vec<String> {
+        columns
+            .iter()
+            .filter(|column| self.find(column).is_none())
+            .cloned()
+            .collect()
+    }
+
+    /// Move the item to this place, on the same page or another one.
+    /// Nothing is moved when it would overlap another item, returns where the item is.
+    pub fn move_item(
+        &mut self,
+        from: (usize, usize),
+        page: usize,
+        left: i32,
+        top: i32,
+    ) -> (usize, usize) {
+        let mut item = match self.pages.get(from.0).and_then(|p| p.items.get(from.1)) {
+            Some(item) => item.clone(),
+            None => return from,
+        };
+        item.left = left;
+        item.top = top;
+        let except = Some(from.1).filter(|_| from.0 == page);
+        match self.pages.get(page) {
+            Some(target) if target.fits(&item, except) => (),
+            _ => return from,
+        }
+        if from.0 == page {
+            self.pages[page].items[from.1] = item;
+            return from;
+        }
+        self.pages[from.0].items.remove(from.1);
+        self.pages[page].items.push(item);
+        (page, self.pages[page].items.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::ColumnInfo;
+
+    fn page(items: Vec<LayoutItem>) -> LayoutPage {
+        LayoutPage {
+            title: "Fields".to_string(),
+            items: items,
+        }
+    }
+
+    #[test]
+    fn items_overlap_when_they_share_a_cell() {
+        let mut wide = LayoutItem::field("name", 0, 0);
+        wide.width = 2;
+        assert!(wide.overlaps(&LayoutItem::field("email", 1, 0)));
+        assert!(!wide.overlaps(&LayoutItem::field("email", 2, 0)));
+        assert!(!wide.overlaps(&LayoutItem::field("email", 0, 1)));
+        let page = page(vec![wide, LayoutItem::field("email", 0, 1)]);
+        assert!(!page.fits(&LayoutItem::field("phone", 1, 0), None));
+        assert!(page.fits(&LayoutItem::field("phone", 1, 1), None));
+        // the item itself is left out when it is moved on its page
+        assert!(page.fits(&LayoutItem::field("email", 0, 1), Some(1)));
+    }
+
+    #[test]
+    fn items_are_moved_where_they_fit() {
+        let mut layout = FormLayout::automatic(&[Field::new("id"), Field::new("name")]);
+        layout.pages.push(LayoutPage::new("Notes"));
+        // taken by name
+        assert_eq!(layout.move_item((0, 0), 0, 1, 0), (0, 0));
+        assert_eq!(layout.pages[0].items[0].left, 0);
+        assert_eq!(layout.move_item((0, 0), 0, 1, 1), (0, 0));
+        assert_eq!(layout.pages[0].items[0].top, 1);
+        assert_eq!(layout.move_item((0, 1), 1, 0, 0), (1, 0));
+        assert_eq!(layout.find("name"), Some((1, 0)));
+        assert_eq!(layout.pages[0].items.len(), 1);
+        // no such page
+        assert_eq!(layout.move_item((1, 0), 5, 0, 0), (1, 0));
+    }
+
+    #[test]
+    fn empty_spans_are_read_as_one() {
+        let layout: FormLayout = toml::from_str(
+            "[[pages]]\ntitle = \"Fields\"\n\n[[pages.items]]\ncolumn = \"name\"\nwidth = 0\n",
+        )
+        .unwrap();
+        let item = &layout.pages[0].items[0];
+        assert_eq!((item.width, item.height), (1, 1));
+    }
+
+    #[test]
+    fn long_fields_span_the_automatic_layout() {
+        let field = |column: &str, length: usize| {
+            Field::from_column_info(&ColumnInfo {
+                name: column.to_string(),
+                data_type: "varchar".to_string(),
+                nullable: true,
+                max_length: Some(length),
+                precision: None,
+                default: None,
+            })
+        };
+        let fields = vec![
+            field("code", 10),
+            field("email", 30),
+            field("city", 20),
+            field("zip", 5),
+            field("notes", 200),
+            field("phone", 15),
+        ];
+        let layout = FormLayout::automatic(&fields);
+        let places: Vec<(i32, i32, i32, i32)> = layout.pages[0]
+            .items
+            .iter()
+            .map(|item| (item.left, item.top, item.width, item.height))
+            .collect();
+        assert_eq!(
+            places,
+            vec![
+                (0, 0, 1, 1),
+                (0, 1, 2, 1),
+                (0, 2, 1, 1),
+                (1, 2, 1, 1),
+                (0, 3, 2, 2),
+                (0, 5, 1, 1),
+            ]
+        );
+        assert_eq!(layout.pages[0].size(), (2, 6));
+    }
+}
@@ -1,4 +1,37 @@
 //#![deny(warnings)]
+pub mod aggregate;
+pub mod audit;
+pub mod catalog;
+pub mod clipboard;
+mod columns;
+pub mod compare;
+mod compare_tab;
+mod config;
 mod connection;
+mod connections;
+mod console;
+pub mod db;
+pub mod ddl;
+mod definition;
+mod designer;
+pub mod detail;
+pub mod diagram;
+mod diagram_tab;
+pub mod edit;
+pub mod format;
+pub mod fuzzy;
+mod guard;
+mod history;
+mod layout;
+mod merge;
+mod palette;
+mod params;
+pub mod report;
+mod shortcuts;
+mod sidebar;
+pub mod sql;
+mod structure;
 pub mod tab;
+mod table_editor;
+pub mod validate;
 pub mod window;
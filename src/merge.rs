@@ -0,0 +1,104 @@
+//! the dialog merging the edits of a record with the changes saved by someone else meanwhile
+use crate::db::Value;
+use crate::edit;
+use gtk;
+use gtk::prelude::*;
+use gtk::{Dialog, DialogFlags, Grid, Label, RadioButton, ResponseType, Window};
+
+pub struct MergeDialog {
+    dialog: Dialog,
+    mine: Vec<Value>,
+    theirs: Vec<Value>,
+    /// the column of each choice, with the button keeping the edited value
+    choices: Vec<(usize, RadioButton)>,
+}
+
+impl MergeDialog {
+    pub fn new(
+        parent: Option<&Window>,
+        columns: &[String],
+        original: &[Value],
+        mine: &[Value],
+        theirs: &[Value],
+    ) -> Self {
+        let dialog = Dialog::with_buttons(
+            Some("Merge the changes"),
+            parent,
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Cancel", ResponseType::Cancel),
+                ("Merge", ResponseType::Accept),
+            ],
+        );
+        dialog.set_default_response(ResponseType::Accept);
+        let message = Label::new(Some(
+            "The record was changed on the server since it was loaded.\n\
+             Choose the value to keep for each column, the columns changed on both sides are in bold.",
+        ));
+        message.set_xalign(0.0);
+        let grid = Grid::new();
+        grid.set_row_spacing(5);
+        grid.set_column_spacing(15);
+        grid.set_margin_start(10);
+        grid.set_margin_end(10);
+        grid.set_margin_top(10);
+        grid.set_margin_bottom(10);
+        grid.attach(&message, 0, 0, 4, 1);
+        for (i, title) in ["Column", "Loaded", "Yours", "Theirs"].iter().enumerate() {
+            let header = Label::new(None);
+            header.set_markup(&format!("<b>{}</b>", title));
+            header.set_xalign(0.0);
+            grid.attach(&header, i as i32, 1, 1, 1);
+        }
+        let text = |value: &Value| value.clone().unwrap_or_else(|| "null".to_string());
+        let mut choices = vec![];
+        for (row, (i, keep_mine)) in edit::merge_choices(original, mine, theirs)
+            .into_iter()
+            .enumerate()
+        {
+            let top = row as i32 + 2;
+            let both = mine[i] != original[i] && theirs[i] != original[i];
+            let name = Label::new(None);
+            if both {
+                name.set_markup(&format!("<b>{}</b>", glib::markup_escape_text(&columns[i])));
+            } else {
+                name.set_text(&columns[i]);
+            }
+            name.set_xalign(0.0);
+            let loaded = Label::new(Some(&text(&original[i])));
+            loaded.set_xalign(0.0);
+            let yours = RadioButton::with_label(&text(&mine[i]));
+            let theirs_button = RadioButton::with_label_from_widget(&yours, &text(&theirs[i]));
+            yours.set_active(keep_mine);
+            theirs_button.set_active(!keep_mine);
+            grid.attach(&name, 0, top, 1, 1);
+            grid.attach(&loaded, 1, top, 1, 1);
+            grid.attach(&yours, 2, top, 1, 1);
+            grid.attach(&theirs_button, 3, top, 1, 1);
+            choices.push((i, yours));
+        }
+        dialog.get_content_area().add(&grid);
+        MergeDialog {
+            dialog: dialog,
+            mine: mine.to_vec(),
+            theirs: theirs.to_vec(),
+            choices: choices,
+        }
+    }
+
+    /// the merged values, none when cancelled
+    pub fn run(&self) -> Option<Vec<Value>> {
+        self.dialog.show_all();
+        let response = self.dialog.run();
+        let choices: Vec<(usize, bool)> = self
+            .choices
+            .iter()
+            .map(|(i, yours)| (*i, yours.get_active()))
+            .collect();
+        self.dialog.close();
+        match response {
+            ResponseType::Accept => Some(edit::merge(&self.mine, &self.theirs, &choices)),
+            _ => None,
+        }
+    }
+}
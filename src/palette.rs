@@ -0,0 +1,261 @@
+//! the command palette, a popover to open tables and run actions by typing part of their name
+use crate::config::ConnectionProfile;
+use crate::fuzzy;
+use crate::shortcuts::SHORTCUTS;
+use gdk::keys::constants as key;
+use glib::Type;
+use gtk;
+use gtk::prelude::*;
+use gtk::Orientation::Vertical;
+use gtk::{
+    Adjustment, CellRendererText, Inhibit, ListStore, Popover, PositionType, ScrolledWindow,
+    SearchEntry, TreeView, TreeViewColumn, Widget,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// the toolbar actions that have no shortcut
+static TOOLBAR_ACTIONS: &[(&str, &str)] = &[
+    ("win.connect", "Connect to server"),
+    ("win.console", "Open SQL console"),
+    ("win.create-table", "Create a table"),
+    ("win.diagram", "Schema diagram"),
+    ("win.compare", "Compare two tables"),
+    ("win.commit", "Commit the open transaction"),
+    ("win.rollback", "Roll back the open transaction"),
+    ("tab.print", "Print"),
+    ("tab.export-pdf", "Export to PDF"),
+    ("tab.design-form", "Design the form"),
+    ("tab.alter-table", "Alter the table"),
+    ("tab.toggle-history", "History of the record"),
+];
+
+static MAX_RESULTS: usize = 50;
+
+#[derive(Debug, Clone)]
+pub enum PaletteItem {
+    /// schema qualified when the database has schemas
+    Table(String),
+    Filter {
+        table: String,
+        name: String,
+        condition: String,
+    },
+    Profile(ConnectionProfile),
+    Action {
+        action: String,
+        title: String,
+    },
+}
+
+impl PaletteItem {
+    /// the actions of the window and of the data tabs
+    pub fn actions() -> Vec<PaletteItem> {
+        let shortcuts = SHORTCUTS.iter().map(|s| (s.action, s.title));
+        TOOLBAR_ACTIONS
+            .iter()
+            .cloned()
+            .chain(shortcuts)
+            .map(|(action, title)| PaletteItem::Action {
+                action: action.to_string(),
+                title: title.to_string(),
+            })
+            .collect()
+    }
+
+    /// the text the typed pattern is matched against
+    pub fn label(&self) -> String {
+        match self {
+            PaletteItem::Table(table) => table.to_string(),
+            PaletteItem::Filter { table, name, .. } => format!("{}: {}", table, name),
+            PaletteItem::Profile(profile) => profile.name.to_string(),
+            PaletteItem::Action { title, .. } => title.to_string(),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            PaletteItem::Table(_) => "Table",
+            PaletteItem::Filter { .. } => "Filter",
+            PaletteItem::Profile(_) => "Connection",
+            PaletteItem::Action { .. } => "Action",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CommandPalette {
+    popover: Popover,
+    entry: SearchEntry,
+    list: TreeView,
+    /// the label as markup, the kind and the index of the item
+    store: ListStore,
+    items: Rc<RefCell<Vec<PaletteItem>>>,
+    on_choose: Rc<RefCell<Option<Rc<dyn Fn(PaletteItem)>>>>,
+}
+
+impl CommandPalette {
+    pub fn new<P: IsA<Widget>>(relative_to: &P) -> Self {
+        let popover = Popover::new(Some(relative_to));
+        popover.set_position(PositionType::Bottom);
+        let entry = SearchEntry::new();
+        entry.set_placeholder_text(Some("Open a table or run an action"));
+        let (list, store) = Self::create_result_list();
+        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scroll.add(&list);
+        scroll.set_size_request(450, 300);
+        let vbox = gtk::Box::new(Vertical, 5);
+        vbox.set_border_width(5);
+        vbox.add(&entry);
+        vbox.pack_start(&scroll, true, true, 0);
+        popover.add(&vbox);
+
+        let palette = CommandPalette {
+            popover: popover,
+            entry: entry,
+            list: list,
+            store: store,
+            items: Rc::new(RefCell::new(vec![])),
+            on_choose: Rc::new(RefCell::new(None)),
+        };
+        palette.init_search();
+        palette
+    }
+
+    fn create_result_list() -> (TreeView, ListStore) {
+        let store = ListStore::new(&[Type::String, Type::String, Type::U32]);
+        let list = TreeView::new();
+        list.set_model(Some(&store));
+        list.set_headers_visible(false);
+        list.set_enable_search(false);
+
+        let label_column = TreeViewColumn::new();
+        let label_cell = CellRendererText::new();
+        label_column.pack_start(&label_cell, true);
+        label_column.add_attribute(&label_cell, "markup", 0);
+        label_column.set_expand(true);
+        list.append_column(&label_column);
+
+        let kind_column = TreeViewColumn::new();
+        let kind_cell = CellRendererText::new();
+        kind_cell.set_property_foreground(Some("gray"));
+        kind_column.pack_start(&kind_cell, false);
+        kind_column.add_attribute(&kind_cell, "text", 1);
+        list.append_column(&kind_column);
+        (list, store)
+    }
+
+    fn init_search(&self) {
+        let this = self.clone();
+        self.entry.connect_search_changed(move |_| {
+            this.refilter();
+        });
+        let this = self.clone();
+        self.entry.connect_activate(move |_| {
+            this.choose_selected();
+        });
+        // the arrow keys move through the results while typing
+        let this = self.clone();
+        self.entry.connect_key_press_event(move |_, event| {
+            let keyval = event.get_keyval();
+            if keyval == key::Down || keyval == key::Up {
+                this.move_selection(keyval == key::Down);
+                Inhibit(true)
+            } else {
+                Inhibit(false)
+            }
+        });
+        let this = self.clone();
+        self.list.connect_row_activated(move |_, _, _| {
+            this.choose_selected();
+        });
+    }
+
+    /// show the palette with these items, `on_choose` is called with the chosen one
+    pub fn popup<F: Fn(PaletteItem) + 'static>(&self, items: Vec<PaletteItem>, on_choose: F) {
+        *self.items.borrow_mut() = items;
+        *self.on_choose.borrow_mut() = Some(Rc::new(on_choose));
+        if let Some(relative_to) = self.popover.get_relative_to() {
+            let allocation = relative_to.get_allocation();
+            self.popover.set_pointing_to(&gdk::Rectangle {
+                x: allocation.width / 2,
+                y: 0,
+                width: 1,
+                height: 1,
+            });
+        }
+        self.entry.set_text("");
+        self.refilter();
+        self.popover.show_all();
+        self.entry.grab_focus();
+    }
+
+    fn refilter(&self) {
+        let pattern = self.entry.get_text();
+        let items = self.items.borrow();
+        let indexes: Vec<usize> = (0..items.len()).collect();
+        self.store.clear();
+        let ranked = fuzzy::rank(&pattern, &indexes, |i| items[*i].label());
+        for (index, found) in ranked.into_iter().take(MAX_RESULTS) {
+            let item = &items[*index];
+            let iter = self.store.append();
+            let markup = highlight(&item.label(), &found.positions);
+            self.store.set_value(&iter, 0, &markup.to_value());
+            self.store.set_value(&iter, 1, &item.kind().to_value());
+            self.store.set_value(&iter, 2, &(*index as u32).to_value());
+        }
+        if let Some(first) = self.store.get_iter_first() {
+            self.list.get_selection().select_iter(&first);
+        }
+    }
+
+    fn move_selection(&self, forward: bool) {
+        let selection = self.list.get_selection();
+        if let Some((_, iter)) = selection.get_selected() {
+            let moved = if forward {
+                self.store.iter_next(&iter)
+            } else {
+                self.store.iter_previous(&iter)
+            };
+            if moved {
+                selection.select_iter(&iter);
+                if let Some(path) = self.store.get_path(&iter) {
+                    self.list
+                        .scroll_to_cell(Some(&path), None::<&TreeViewColumn>, false, 0.0, 0.0);
+                }
+            }
+        }
+    }
+
+    fn choose_selected(&self) {
+        let (model, iter) = match self.list.get_selection().get_selected() {
+            Some(selected) => selected,
+            None => return,
+        };
+        let index: Option<u32> = model.get_value(&iter, 2).get().ok().flatten();
+        let item = match index.and_then(|i| self.items.borrow().get(i as usize).cloned()) {
+            Some(item) => item,
+            None => return,
+        };
+        self.popover.hide();
+        // not borrowed during the call, choosing may show the palette again
+        let on_choose = self.on_choose.borrow().clone();
+        if let Some(on_choose) = on_choose {
+            on_choose(item);
+        }
+    }
+}
+
+/// the label as pango markup with the matched chars in bold
+fn highlight(label: &str, positions: &[usize]) -> String {
+    let mut markup = String::new();
+    for (i, c) in label.chars().enumerate() {
+        let escaped = glib::markup_escape_text(&c.to_string());
+        if positions.contains(&i) {
+            markup.push_str(&format!("<b>{}</b>", escaped));
+        } else {
+            markup.push_str(&escaped);
+        }
+    }
+    markup
+}
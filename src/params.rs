@@ -0,0 +1,144 @@
+//! the form of the parameters of a query, an input for each parameter matched to its type
+//!
+//! The values are converted like pasted text, see `clipboard::convert`,
+//! so a date can be typed as it is displayed.
+use crate::catalog;
+use crate::clipboard;
+use crate::db::Value;
+use crate::detail::Field;
+use crate::format;
+use gtk::prelude::*;
+use gtk::{
+    CheckButton, Dialog, DialogFlags, Entry, Grid, InputPurpose, Label, ResponseType, Widget,
+    Window,
+};
+use std::rc::Rc;
+
+#[derive(Clone)]
+enum Input {
+    Entry(Entry),
+    Check(CheckButton),
+}
+
+#[derive(Clone)]
+pub struct ParamForm {
+    pub grid: Grid,
+    fields: Rc<Vec<Field>>,
+    inputs: Rc<Vec<Input>>,
+}
+
+impl ParamForm {
+    /// the fields of the parameters with their label, and their current values
+    pub fn new(params: Vec<(Field, String)>, values: &[Value]) -> Self {
+        let grid = Grid::new();
+        grid.set_row_spacing(5);
+        grid.set_column_spacing(10);
+        let mut fields = vec![];
+        let mut inputs = vec![];
+        for (i, (field, label)) in params.into_iter().enumerate() {
+            let label = Label::new(Some(&label));
+            label.set_xalign(0.0);
+            let input = Self::create_input(&field);
+            let widget = match &input {
+                Input::Entry(entry) => entry.clone().upcast::<Widget>(),
+                Input::Check(check) => check.clone().upcast::<Widget>(),
+            };
+            if !field.info().is_empty() {
+                widget.set_tooltip_text(Some(field.info()));
+            }
+            grid.attach(&label, 0, i as i32, 1, 1);
+            grid.attach(&widget, 1, i as i32, 1, 1);
+            fields.push(field);
+            inputs.push(input);
+        }
+        let form = ParamForm {
+            grid: grid,
+            fields: Rc::new(fields),
+            inputs: Rc::new(inputs),
+        };
+        form.set_values(values);
+        form
+    }
+
+    fn create_input(field: &Field) -> Input {
+        let data_type = field.data_type().to_lowercase();
+        if data_type.starts_with("bool") {
+            return Input::Check(CheckButton::new());
+        }
+        let entry = Entry::new();
+        entry.set_width_chars(20);
+        if catalog::is_numeric(&data_type) {
+            entry.set_input_purpose(InputPurpose::Number);
+            entry.set_alignment(1.0);
+        } else if data_type.starts_with("date") || data_type.starts_with("timestamp") {
+            entry.set_placeholder_text(Some("YYYY-MM-DD"));
+        }
+        Input::Entry(entry)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// the values in the order of the parameters, an empty entry is null
+    pub fn values(&self) -> Vec<Value> {
+        self.inputs
+            .iter()
+            .zip(self.fields.iter())
+            .map(|(input, field)| match input {
+                Input::Entry(entry) => clipboard::convert(field.data_type(), &entry.get_text()),
+                Input::Check(check) => Some(check.get_active().to_string()),
+            })
+            .collect()
+    }
+
+    pub fn set_values(&self, values: &[Value]) {
+        for (input, value) in self.inputs.iter().zip(values.iter()) {
+            let value = value.as_deref().unwrap_or("");
+            match input {
+                Input::Entry(entry) => entry.set_text(value),
+                Input::Check(check) => check.set_active(format::parse_bool(value).unwrap_or(false)),
+            }
+        }
+    }
+
+    /// the parameters with their values on one line, ie: `region = EU, day = 2024-01-31`
+    pub fn summary(&self) -> String {
+        self.fields
+            .iter()
+            .zip(self.values().iter())
+            .map(|(field, value)| {
+                format!(
+                    "{} = {}",
+                    field.column(),
+                    value.as_deref().unwrap_or("null")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// ask for the parameters in a dialog, false when it is cancelled
+pub fn prompt(parent: Option<&Window>, title: &str, form: &ParamForm) -> bool {
+    let dialog = Dialog::with_buttons(
+        Some(title),
+        parent,
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", ResponseType::Cancel),
+            ("Run", ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(ResponseType::Accept);
+    let content = dialog.get_content_area();
+    form.grid.set_margin_start(10);
+    form.grid.set_margin_end(10);
+    form.grid.set_margin_top(10);
+    form.grid.set_margin_bottom(10);
+    content.add(&form.grid);
+    dialog.show_all();
+    let accepted = dialog.run() == ResponseType::Accept;
+    dialog.close();
+    accepted
+}
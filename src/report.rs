@@ -0,0 +1,361 @@
+//! Printed reports of a data tab, drawn with cairo so the same pages
+//! go to the printer and to pdf files.
+//!
+//! The sizes are in points, the unit of the print context and of the pdf surface.
+use cairo::{Context, FontSlant, FontWeight, PdfSurface};
+use std::cell::RefCell;
+use std::path::Path;
+
+/// the size of an A4 page in portrait
+pub static A4: (f64, f64) = (595.0, 842.0);
+static MARGIN: f64 = 36.0;
+static FONT: &'static str = "Sans";
+static FONT_SIZE: f64 = 9.0;
+static TITLE_SIZE: f64 = 14.0;
+static LINE_HEIGHT: f64 = 14.0;
+/// between the columns of the list, and between the labels and the values of the form
+static GAP: f64 = 8.0;
+/// of each group level
+static INDENT: f64 = 12.0;
+
+pub trait Report {
+    /// the number of pages of this size
+    fn page_count(&self, cr: &Context, width: f64, height: f64) -> usize;
+    /// `page` starts at 0
+    fn draw_page(&self, cr: &Context, width: f64, height: f64, page: usize);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    /// the values of a record, as displayed
+    Row(Vec<String>),
+    /// the header of a group, indented by its level
+    Group(usize, String),
+    /// the aggregates of all the rows, at the end
+    Total(String),
+}
+
+/// the list with its column headers repeated on every page
+pub struct ListReport {
+    title: String,
+    /// ie: the filter and the grouping
+    subtitle: String,
+    columns: Vec<String>,
+    lines: Vec<Line>,
+    /// the widths of the columns for a page width, they depend on all the rows
+    widths: RefCell<Option<(f64, Vec<f64>)>>,
+}
+
+/// every record on its own page, the labels next to the values,
+/// continued on the next pages when it has more fields than fit on one
+pub struct FormReport {
+    pub title: String,
+    pub labels: Vec<String>,
+    pub records: Vec<Vec<String>>,
+}
+
+/// the top and bottom of the part of the page between the header and the footer
+fn body(height: f64) -> (f64, f64) {
+    (
+        MARGIN + TITLE_SIZE + 2.0 * LINE_HEIGHT,
+        height - MARGIN - 2.0 * LINE_HEIGHT,
+    )
+}
+
+fn set_font(cr: &Context, bold: bool) {
+    let weight = if bold {
+        FontWeight::Bold
+    } else {
+        FontWeight::Normal
+    };
+    cr.select_font_face(FONT, FontSlant::Normal, weight);
+    cr.set_font_size(FONT_SIZE);
+}
+
+/// the title and subtitle on top, the page number at the bottom
+fn draw_frame(
+    cr: &Context,
+    width: f64,
+    height: f64,
+    title: &str,
+    subtitle: &str,
+    page: usize,
+    pages: usize,
+) {
+    cr.set_source_rgb(0.0, 0.0, 0.0);
+    cr.select_font_face(FONT, FontSlant::Normal, FontWeight::Bold);
+    cr.set_font_size(TITLE_SIZE);
+    cr.move_to(MARGIN, MARGIN + TITLE_SIZE);
+    cr.show_text(title);
+    set_font(cr, false);
+    cr.move_to(MARGIN, MARGIN + TITLE_SIZE + LINE_HEIGHT);
+    cr.show_text(subtitle);
+    let number = format!("Page {} of {}", page + 1, pages);
+    let extents = cr.text_extents(&number);
+    cr.move_to((width - extents.width) / 2.0, height - MARGIN);
+    cr.show_text(&number);
+}
+
+/// a line of text cut at the width, `top` is the top of the line
+fn draw_text(cr: &Context, x: f64, top: f64, width: f64, text: &str) {
+    cr.save();
+    cr.rectangle(x, top, width.max(0.0), LINE_HEIGHT);
+    cr.clip();
+    cr.move_to(x, top + LINE_HEIGHT - 4.0);
+    cr.show_text(text);
+    cr.restore();
+}
+
+fn draw_rule(cr: &Context, x: f64, y: f64, width: f64) {
+    cr.set_line_width(0.5);
+    cr.move_to(x, y);
+    cr.line_to(x + width, y);
+    cr.stroke();
+}
+
+/// the natural widths, shrunk in proportion when they don't fit
+pub fn fit_widths(natural: &[f64], available: f64) -> Vec<f64> {
+    let total: f64 = natural.iter().sum();
+    if total <= available || total <= 0.0 {
+        return natural.to_vec();
+    }
+    natural.iter().map(|w| w * available / total).collect()
+}
+
+impl ListReport {
+    pub fn new(title: &str, subtitle: &str, columns: Vec<String>, lines: Vec<Line>) -> Self {
+        ListReport {
+            title: title.to_string(),
+            subtitle: subtitle.to_string(),
+            columns: columns,
+            lines: lines,
+            widths: RefCell::new(None),
+        }
+    }
+
+    /// the lines below the column headers
+    fn lines_per_page(height: f64) -> usize {
+        let (top, bottom) = body(height);
+        (((bottom - top) / LINE_HEIGHT) as usize)
+            .saturating_sub(1)
+            .max(1)
+    }
+
+    /// as wide as the widest value of each column, when the page is wide enough
+    fn widths(&self, cr: &Context, width: f64) -> Vec<f64> {
+        if let Some((measured, widths)) = self.widths.borrow().as_ref() {
+            if *measured == width {
+                return widths.clone();
+            }
+        }
+        set_font(cr, true);
+        let mut natural: Vec<f64> = self
+            .columns
+            .iter()
+            .map(|c| cr.text_extents(c).x_advance)
+            .collect();
+        set_font(cr, false);
+        for line in self.lines.iter() {
+            if let Line::Row(values) = line {
+                for (natural, value) in natural.iter_mut().zip(values.iter()) {
+                    *natural = natural.max(cr.text_extents(value).x_advance);
+                }
+            }
+        }
+        let natural: Vec<f64> = natural.into_iter().map(|w| w + GAP).collect();
+        let widths = fit_widths(&natural, width - 2.0 * MARGIN);
+        *self.widths.borrow_mut() = Some((width, widths.clone()));
+        widths
+    }
+}
+
+impl Report for ListReport {
+    fn page_count(&self, _cr: &Context, _width: f64, height: f64) -> usize {
+        let per_page = Self::lines_per_page(height);
+        ((self.lines.len() + per_page - 1) / per_page).max(1)
+    }
+
+    fn draw_page(&self, cr: &Context, width: f64, height: f64, page: usize) {
+        let pages = self.page_count(cr, width, height);
+        draw_frame(cr, width, height, &self.title, &self.subtitle, page, pages);
+        let widths = self.widths(cr, width);
+        let total: f64 = widths.iter().sum();
+        let (mut top, _) = body(height);
+
+        set_font(cr, true);
+        let mut x = MARGIN;
+        for (column, width) in self.columns.iter().zip(widths.iter()) {
+            draw_text(cr, x, top, width - GAP, column);
+            x += width;
+        }
+        top += LINE_HEIGHT;
+        draw_rule(cr, MARGIN, top, total);
+
+        let per_page = Self::lines_per_page(height);
+        for line in self.lines.iter().skip(page * per_page).take(per_page) {
+            match line {
+                Line::Row(values) => {
+                    set_font(cr, false);
+                    let mut x = MARGIN;
+                    for (value, width) in values.iter().zip(widths.iter()) {
+                        draw_text(cr, x, top, width - GAP, value);
+                        x += width;
+                    }
+                }
+                Line::Group(level, text) => {
+                    cr.set_source_rgb(0.9, 0.9, 0.9);
+                    cr.rectangle(MARGIN, top, total, LINE_HEIGHT);
+                    cr.fill();
+                    cr.set_source_rgb(0.0, 0.0, 0.0);
+                    set_font(cr, true);
+                    let indent = *level as f64 * INDENT;
+                    draw_text(cr, MARGIN + indent, top, total - indent, text);
+                }
+                Line::Total(text) => {
+                    draw_rule(cr, MARGIN, top, total);
+                    set_font(cr, true);
+                    draw_text(cr, MARGIN, top, total, text);
+                }
+            }
+            top += LINE_HEIGHT;
+        }
+    }
+}
+
+impl FormReport {
+    fn fields_per_page(height: f64) -> usize {
+        let (top, bottom) = body(height);
+        (((bottom - top - LINE_HEIGHT) / (LINE_HEIGHT * 1.5)) as usize + 1).max(1)
+    }
+
+    /// the pages each record takes
+    fn pages_per_record(height: f64, fields: usize) -> usize {
+        let per_page = Self::fields_per_page(height);
+        ((fields + per_page - 1) / per_page).max(1)
+    }
+}
+
+impl Report for FormReport {
+    fn page_count(&self, _cr: &Context, _width: f64, height: f64) -> usize {
+        (self.records.len() * Self::pages_per_record(height, self.labels.len())).max(1)
+    }
+
+    fn draw_page(&self, cr: &Context, width: f64, height: f64, page: usize) {
+        let pages = self.page_count(cr, width, height);
+        let per_record = Self::pages_per_record(height, self.labels.len());
+        let (record, part) = (page / per_record, page % per_record);
+        let mut subtitle = format!("Record {} of {}", record + 1, self.records.len());
+        if part > 0 {
+            subtitle.push_str(" (continued)");
+        }
+        draw_frame(cr, width, height, &self.title, &subtitle, page, pages);
+        let values = match self.records.get(record) {
+            Some(values) => values,
+            None => return,
+        };
+        set_font(cr, true);
+        let label_width = self
+            .labels
+            .iter()
+            .map(|l| cr.text_extents(l).x_advance)
+            .fold(0.0, f64::max)
+            + GAP;
+        let value_width = width - 2.0 * MARGIN - label_width;
+        let (mut top, _) = body(height);
+        let per_page = Self::fields_per_page(height);
+        let fields = self.labels.iter().zip(values.iter());
+        for (label, value) in fields.skip(part * per_page).take(per_page) {
+            set_font(cr, true);
+            draw_text(cr, MARGIN, top, label_width - GAP, label);
+            set_font(cr, false);
+            draw_text(cr, MARGIN + label_width, top, value_width, value);
+            top += LINE_HEIGHT * 1.5;
+        }
+    }
+}
+
+/// Write all the pages of the report to a pdf file, on A4 paper.
+/// Returns the number of pages.
+pub fn write_pdf(report: &dyn Report, path: &Path, landscape: bool) -> Result<usize, cairo::Error> {
+    let (width, height) = if landscape { (A4.1, A4.0) } else { A4 };
+    let surface = PdfSurface::new(width, height, path)?;
+    let cr = Context::new(&surface);
+    let pages = report.page_count(&cr, width, height);
+    for page in 0..pages {
+        report.draw_page(&cr, width, height, page);
+        cr.show_page();
+    }
+    surface.finish();
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn pdf_pages(path: &Path) -> usize {
+        let content = fs::read(path).unwrap();
+        let content = String::from_utf8_lossy(&content);
+        assert!(content.starts_with("%PDF"));
+        content.matches("/Type /Page").count() - content.matches("/Type /Pages").count()
+    }
+
+    #[test]
+    fn list_report_pages() {
+        let lines: Vec<Line> = (0..120)
+            .map(|i| Line::Row(vec![i.to_string(), format!("row {}", i)]))
+            .collect();
+        let report = ListReport::new(
+            "orders",
+            "Filtered on total > 10",
+            vec!["id".to_string(), "name".to_string()],
+            lines,
+        );
+        let path = std::env::temp_dir().join("datawindow-list-report.pdf");
+        let pages = write_pdf(&report, &path, false).unwrap();
+        // 49 lines on a portrait page, 31 on a landscape one
+        assert_eq!(pages, 3);
+        assert_eq!(pdf_pages(&path), 3);
+        let pages = write_pdf(&report, &path, true).unwrap();
+        assert_eq!(pages, 4);
+        assert_eq!(pdf_pages(&path), 4);
+    }
+
+    #[test]
+    fn form_report_has_a_page_per_record() {
+        let report = FormReport {
+            title: "orders".to_string(),
+            labels: vec!["id".to_string(), "name".to_string()],
+            records: vec![
+                vec!["1".to_string(), "first".to_string()],
+                vec!["2".to_string(), "second".to_string()],
+            ],
+        };
+        let path = std::env::temp_dir().join("datawindow-form-report.pdf");
+        assert_eq!(write_pdf(&report, &path, false).unwrap(), 2);
+        assert_eq!(pdf_pages(&path), 2);
+    }
+
+    #[test]
+    fn form_report_continues_the_fields_past_the_page() {
+        let labels: Vec<String> = (0..50).map(|i| format!("field {}", i)).collect();
+        let report = FormReport {
+            title: "orders".to_string(),
+            records: vec![labels.clone(), labels.clone()],
+            labels: labels,
+        };
+        let path = std::env::temp_dir().join("datawindow-long-form-report.pdf");
+        // 33 fields on a portrait page, 21 on a landscape one
+        assert_eq!(write_pdf(&report, &path, false).unwrap(), 4);
+        assert_eq!(pdf_pages(&path), 4);
+        assert_eq!(write_pdf(&report, &path, true).unwrap(), 6);
+        assert_eq!(pdf_pages(&path), 6);
+    }
+
+    #[test]
+    fn widths_shrink_to_fit() {
+        assert_eq!(fit_widths(&[10.0, 30.0], 100.0), vec![10.0, 30.0]);
+        assert_eq!(fit_widths(&[100.0, 300.0], 200.0), vec![50.0, 150.0]);
+    }
+}
@@ -0,0 +1,200 @@
+//! the keyboard shortcuts of the window and of the data tabs
+//!
+//! The shortcuts activate actions by their detailed name, `win.*` actions belong to the window
+//! and `tab.*` actions to the data tab that is shown.
+use gdk::keys::constants as key;
+use gdk::EventKey;
+use gtk;
+use gtk::prelude::*;
+use gtk::{Builder, Window};
+
+pub struct Shortcut {
+    pub action: &'static str,
+    /// in the format of `gtk::accelerator_parse`
+    pub accel: &'static str,
+    pub title: &'static str,
+    pub group: &'static str,
+}
+
+pub static SHORTCUTS: &[Shortcut] = &[
+    Shortcut {
+        action: "tab.new",
+        accel: "<Primary>n",
+        title: "New record",
+        group: "Records",
+    },
+    Shortcut {
+        action: "tab.save",
+        accel: "<Primary>s",
+        title: "Save changes",
+        group: "Records",
+    },
+    Shortcut {
+        action: "tab.refresh",
+        accel: "F5",
+        title: "Refresh",
+        group: "Records",
+    },
+    Shortcut {
+        action: "tab.delete",
+        accel: "Delete",
+        title: "Delete the selected record",
+        group: "Records",
+    },
+    Shortcut {
+        action: "tab.edit",
+        accel: "F2",
+        title: "Edit the selected cell",
+        group: "Records",
+    },
+    Shortcut {
+        action: "tab.find",
+        accel: "<Primary>f",
+        title: "Find",
+        group: "Records",
+    },
+    Shortcut {
+        action: "tab.copy",
+        accel: "<Primary>c",
+        title: "Copy the selected cells",
+        group: "Editing",
+    },
+    Shortcut {
+        action: "tab.paste",
+        accel: "<Primary>v",
+        title: "Paste cells",
+        group: "Editing",
+    },
+    Shortcut {
+        action: "tab.undo",
+        accel: "<Primary>z",
+        title: "Undo",
+        group: "Editing",
+    },
+    Shortcut {
+        action: "tab.toggle-detail",
+        accel: "<Primary>Return",
+        title: "Switch between list and detail view",
+        group: "Navigation",
+    },
+    Shortcut {
+        action: "tab.toggle-structure",
+        accel: "<Primary><Shift>Return",
+        title: "Show the structure of the table",
+        group: "Navigation",
+    },
+    Shortcut {
+        action: "tab.previous-page",
+        accel: "Page_Up",
+        title: "Previous page",
+        group: "Navigation",
+    },
+    Shortcut {
+        action: "tab.next-page",
+        accel: "Page_Down",
+        title: "Next page",
+        group: "Navigation",
+    },
+    Shortcut {
+        action: "win.next-tab",
+        accel: "<Primary>Tab",
+        title: "Next tab",
+        group: "Tabs",
+    },
+    Shortcut {
+        action: "win.previous-tab",
+        accel: "<Primary><Shift>Tab",
+        title: "Previous tab",
+        group: "Tabs",
+    },
+    Shortcut {
+        action: "win.close-tab",
+        accel: "<Primary>w",
+        title: "Close tab",
+        group: "Tabs",
+    },
+    Shortcut {
+        action: "win.palette",
+        accel: "<Primary>p",
+        title: "Command palette",
+        group: "General",
+    },
+    Shortcut {
+        action: "win.shortcuts",
+        accel: "F1",
+        title: "Keyboard shortcuts",
+        group: "General",
+    },
+];
+
+/// the clipboard and undo keys, which the entries use too
+static TEXT_EDITING: &[&str] = &["<Primary>c", "<Primary>v", "<Primary>z"];
+
+impl Shortcut {
+    /// A key the focused entry should get instead, such as `Delete` or `<Primary>c`.
+    /// The function keys are not used by the entries.
+    pub fn is_text_editing(&self) -> bool {
+        (!self.accel.starts_with('<') && !self.accel.starts_with('F'))
+            || TEXT_EDITING.contains(&self.accel)
+    }
+}
+
+/// the shortcut of this key press
+pub fn find(event: &EventKey) -> Option<&'static Shortcut> {
+    let mut keyval = gdk::keyval_to_lower(*event.get_keyval());
+    // shift turns tab into left tab
+    if keyval == *key::ISO_Left_Tab {
+        keyval = *key::Tab;
+    }
+    let state = event.get_state() & gtk::accelerator_get_default_mod_mask();
+    SHORTCUTS.iter().find(|shortcut| {
+        let (accel_key, accel_mods) = gtk::accelerator_parse(shortcut.accel);
+        accel_key == keyval && accel_mods == state
+    })
+}
+
+/// a `GtkShortcutsWindow` listing all the shortcuts, by group
+pub fn create_help_window() -> Option<Window> {
+    let mut groups: Vec<&str> = vec![];
+    for shortcut in SHORTCUTS.iter() {
+        if !groups.contains(&shortcut.group) {
+            groups.push(shortcut.group);
+        }
+    }
+    let mut xml = String::from(
+        "<interface><object class=\"GtkShortcutsWindow\" id=\"shortcuts\">\
+         <property name=\"modal\">1</property><child>\
+         <object class=\"GtkShortcutsSection\">\
+         <property name=\"visible\">1</property>\
+         <property name=\"section-name\">shortcuts</property>",
+    );
+    for group in groups {
+        xml.push_str(&format!(
+            "<child><object class=\"GtkShortcutsGroup\">\
+             <property name=\"visible\">1</property>\
+             <property name=\"title\">{}</property>",
+            group
+        ));
+        for shortcut in SHORTCUTS.iter().filter(|s| s.group == group) {
+            xml.push_str(&format!(
+                "<child><object class=\"GtkShortcutsShortcut\">\
+                 <property name=\"visible\">1</property>\
+                 <property name=\"accelerator\">{}</property>\
+                 <property name=\"title\">{}</property>\
+                 </object></child>",
+                escape(shortcut.accel),
+                escape(shortcut.title)
+            ));
+        }
+        xml.push_str("</object></child>");
+    }
+    xml.push_str("</object></child></object></interface>");
+    let builder = Builder::from_string(&xml);
+    builder.get_object::<Window>("shortcuts")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
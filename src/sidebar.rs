@@ -0,0 +1,479 @@
+//! the list of tables at the side of the window
+//!
+//! The tables are grouped by connection, schema and kind, after the datawindows.
+//! The favorites and the recently opened come first in each connection.
+//! Typing in the search entry only shows the names that fuzzy match.
+use crate::catalog::{DbObject, ObjectKind};
+use crate::config::SidebarConfig;
+use crate::fuzzy;
+use glib::Type;
+use gtk;
+use gtk::prelude::*;
+use gtk::Orientation::Vertical;
+use gtk::{
+    Adjustment, CellRendererText, Inhibit, Menu, MenuItem, ScrolledWindow, SearchEntry, TreeIter,
+    TreeModel, TreeModelFilter, TreePath, TreeStore, TreeView, TreeViewColumn,
+};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+// the columns of the store
+static LABEL: u32 = 0;
+static ESTIMATE: u32 = 1;
+/// the qualified name of an object row, empty on the group rows
+static NAME: u32 = 2;
+/// whether the row is a table or view that can be opened
+static OPENABLE: u32 = 3;
+/// whether the row is a datawindow, its name is the name of the definition
+static DATAWINDOW: u32 = 4;
+/// the name of the connection the row belongs to, empty while not connected
+static CONNECTION: u32 = 5;
+/// the color of the badge on the connection rows
+static COLOR: u32 = 6;
+
+#[derive(Clone)]
+pub struct Sidebar {
+    pub container: gtk::Box,
+    search: SearchEntry,
+    tree: TreeView,
+    store: TreeStore,
+    filter: TreeModelFilter,
+    /// the objects listed while not connected
+    objects: Rc<RefCell<Vec<DbObject>>>,
+    /// the name, badge color and objects of each open connection
+    connections: Rc<RefCell<Vec<(String, String, Vec<DbObject>)>>>,
+    disconnect: Rc<RefCell<Option<Box<dyn Fn(&str)>>>>,
+    /// the name and title of each datawindow definition
+    datawindows: Rc<RefCell<Vec<(String, String)>>>,
+    config: Rc<RefCell<SidebarConfig>>,
+}
+
+impl Sidebar {
+    pub fn new() -> Self {
+        let search = SearchEntry::new();
+        search.set_placeholder_text(Some("Search tables"));
+        let store = TreeStore::new(&[
+            Type::String,
+            Type::String,
+            Type::String,
+            Type::Bool,
+            Type::Bool,
+            Type::String,
+            Type::String,
+        ]);
+        let filter = TreeModelFilter::new(&store, None);
+        let tree = Self::create_tree(&filter);
+        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scroll.add(&tree);
+        scroll.set_vexpand(true);
+        let container = gtk::Box::new(Vertical, 0);
+        container.add(&search);
+        container.add(&scroll);
+        container.set_size_request(200, 600);
+
+        let sidebar = Sidebar {
+            container: container,
+            search: search,
+            tree: tree,
+            store: store,
+            filter: filter,
+            objects: Rc::new(RefCell::new(vec![])),
+            connections: Rc::new(RefCell::new(vec![])),
+            disconnect: Rc::new(RefCell::new(None)),
+            datawindows: Rc::new(RefCell::new(vec![])),
+            config: Rc::new(RefCell::new(SidebarConfig::load())),
+        };
+        sidebar.init_search();
+        sidebar.init_context_menu();
+        sidebar
+    }
+
+    fn create_tree(filter: &TreeModelFilter) -> TreeView {
+        let tree = TreeView::new();
+        tree.set_model(Some(filter));
+        tree.set_headers_visible(false);
+        tree.set_enable_search(false);
+        tree.set_activate_on_single_click(true);
+
+        let label_column = TreeViewColumn::new();
+        let label_cell = CellRendererText::new();
+        label_column.pack_start(&label_cell, true);
+        label_column.add_attribute(&label_cell, "text", LABEL as i32);
+        label_column.add_attribute(&label_cell, "foreground", COLOR as i32);
+        label_column.set_expand(true);
+        tree.append_column(&label_column);
+
+        let estimate_column = TreeViewColumn::new();
+        let estimate_cell = CellRendererText::new();
+        estimate_cell.set_property_foreground(Some("gray"));
+        estimate_cell.set_alignment(1.0, 0.5);
+        estimate_column.pack_start(&estimate_cell, false);
+        estimate_column.add_attribute(&estimate_cell, "text", ESTIMATE as i32);
+        tree.append_column(&estimate_column);
+        tree
+    }
+
+    fn init_search(&self) {
+        let search = self.search.clone();
+        self.filter.set_visible_func(move |model, iter| {
+            let pattern = search.get_text();
+            Self::is_visible(model, iter, &pattern)
+        });
+        let this = self.clone();
+        self.search.connect_search_changed(move |search| {
+            this.filter.refilter();
+            if search.get_text().is_empty() {
+                this.expand_top_level();
+            } else {
+                this.tree.expand_all();
+            }
+        });
+    }
+
+    /// an object row is visible when its name matches,
+    /// a group row when any of the rows in it is visible
+    fn is_visible(model: &TreeModel, iter: &TreeIter, pattern: &str) -> bool {
+        if pattern.is_empty() {
+            return true;
+        }
+        let name: Option<String> = model.get_value(iter, NAME as i32).get().ok().flatten();
+        if let Some(name) = name.filter(|n| !n.is_empty()) {
+            return fuzzy::fuzzy_match(pattern, &name).is_some();
+        }
+        if let Some(child) = model.iter_children(Some(iter)) {
+            loop {
+                if Self::is_visible(model, &child, pattern) {
+                    return true;
+                }
+                if !model.iter_next(&child) {
+                    break;
+                }
+            }
+        }
+        false
+    }
+
+    /// Call `open` with the connection and qualified name of the table or view that is clicked,
+    /// the connection is none while not connected.
+    pub fn connect_open<F: Fn(Option<&str>, &str) + 'static>(&self, open: F) {
+        self.tree.connect_row_activated(move |tree, path, _| {
+            let model = match tree.get_model() {
+                Some(model) => model,
+                None => return,
+            };
+            let iter = match model.get_iter(path) {
+                Some(iter) => iter,
+                None => return,
+            };
+            let openable: bool = model
+                .get_value(&iter, OPENABLE as i32)
+                .get_some()
+                .unwrap_or(false);
+            let name: Option<String> = model.get_value(&iter, NAME as i32).get().ok().flatten();
+            let connection: Option<String> = model
+                .get_value(&iter, CONNECTION as i32)
+                .get()
+                .ok()
+                .flatten();
+            let connection = connection.filter(|c| !c.is_empty());
+            match name.filter(|n| !n.is_empty()) {
+                Some(name) if openable => open(connection.as_deref(), &name),
+                Some(_) => (),
+                None if tree.row_expanded(path) => {
+                    tree.collapse_row(path);
+                }
+                None => {
+                    tree.expand_row(path, false);
+                }
+            }
+        });
+    }
+
+    /// call `open` with the name of the datawindow that is clicked
+    pub fn connect_open_datawindow<F: Fn(&str) + 'static>(&self, open: F) {
+        self.tree.connect_row_activated(move |tree, path, _| {
+            let model = match tree.get_model() {
+                Some(model) => model,
+                None => return,
+            };
+            let iter = match model.get_iter(path) {
+                Some(iter) => iter,
+                None => return,
+            };
+            let datawindow: bool = model
+                .get_value(&iter, DATAWINDOW as i32)
+                .get_some()
+                .unwrap_or(false);
+            let name: Option<String> = model.get_value(&iter, NAME as i32).get().ok().flatten();
+            if let Some(name) = name.filter(|_| datawindow) {
+                open(&name);
+            }
+        });
+    }
+
+    /// call `disconnect` with the name of the connection to close
+    pub fn connect_disconnect<F: Fn(&str) + 'static>(&self, disconnect: F) {
+        *self.disconnect.borrow_mut() = Some(Box::new(disconnect));
+    }
+
+    /// the connection of the selected row
+    pub fn selected_connection(&self) -> Option<String> {
+        let (model, iter) = self.tree.get_selection().get_selected()?;
+        let connection: Option<String> = model
+            .get_value(&iter, CONNECTION as i32)
+            .get()
+            .ok()
+            .flatten();
+        connection.filter(|c| !c.is_empty())
+    }
+
+    fn init_context_menu(&self) {
+        let this = self.clone();
+        self.tree.connect_button_press_event(move |tree, event| {
+            if event.get_button() != 3 {
+                return Inhibit(false);
+            }
+            let (x, y) = event.get_position();
+            let path = match tree.get_path_at_pos(x as i32, y as i32) {
+                Some((Some(path), _, _, _)) => path,
+                _ => return Inhibit(false),
+            };
+            let menu = match (this.name_at(&path), this.connection_at(&path)) {
+                (Some(name), _) => this.create_context_menu(&name),
+                (None, Some(connection)) => this.create_connection_menu(&connection),
+                (None, None) => return Inhibit(true),
+            };
+            menu.show_all();
+            menu.popup_easy(event.get_button(), event.get_time());
+            Inhibit(true)
+        });
+    }
+
+    /// the table or view at this path
+    fn name_at(&self, path: &TreePath) -> Option<String> {
+        let iter = self.filter.get_iter(path)?;
+        let datawindow: bool = self
+            .filter
+            .get_value(&iter, DATAWINDOW as i32)
+            .get_some()
+            .unwrap_or(false);
+        if datawindow {
+            return None;
+        }
+        let name: Option<String> = self
+            .filter
+            .get_value(&iter, NAME as i32)
+            .get()
+            .ok()
+            .flatten();
+        name.filter(|n| !n.is_empty())
+    }
+
+    /// the connection when the row at this path is the one of a connection
+    fn connection_at(&self, path: &TreePath) -> Option<String> {
+        if path.get_depth() != 1 {
+            return None;
+        }
+        let iter = self.filter.get_iter(path)?;
+        let connection: Option<String> = self
+            .filter
+            .get_value(&iter, CONNECTION as i32)
+            .get()
+            .ok()
+            .flatten();
+        connection.filter(|c| !c.is_empty())
+    }
+
+    fn create_connection_menu(&self, connection: &str) -> Menu {
+        let menu = Menu::new();
+        let item = MenuItem::with_label("Disconnect");
+        let this = self.clone();
+        let connection = connection.to_string();
+        item.connect_activate(move |_| {
+            if let Some(disconnect) = this.disconnect.borrow().as_ref() {
+                disconnect(&connection);
+            }
+        });
+        menu.append(&item);
+        menu
+    }
+
+    fn create_context_menu(&self, name: &str) -> Menu {
+        let menu = Menu::new();
+        let label = if self.config.borrow().favorites.iter().any(|f| f == name) {
+            "Remove from favorites"
+        } else {
+            "Add to favorites"
+        };
+        let item = MenuItem::with_label(label);
+        let this = self.clone();
+        let name = name.to_string();
+        item.connect_activate(move |_| {
+            this.config.borrow_mut().toggle_favorite(&name);
+            this.save_config();
+            this.rebuild();
+        });
+        menu.append(&item);
+        menu
+    }
+
+    fn save_config(&self) {
+        if let Err(e) = self.config.borrow().save() {
+            println!("unable to save the sidebar config: {}", e);
+        }
+    }
+
+    /// put this table on top of the recently opened
+    pub fn add_recent(&self, name: &str) {
+        self.config.borrow_mut().add_recent(name);
+        self.save_config();
+        self.rebuild();
+    }
+
+    /// the objects listed while not connected
+    pub fn set_objects(&self, objects: Vec<DbObject>) {
+        *self.objects.borrow_mut() = objects;
+        self.rebuild();
+    }
+
+    /// list the objects of a connection, it is added after the others when it is new
+    pub fn set_connection_objects(&self, connection: &str, color: &str, objects: Vec<DbObject>) {
+        let mut connections = self.connections.borrow_mut();
+        match connections
+            .iter_mut()
+            .find(|(name, _, _)| name == connection)
+        {
+            Some(entry) => entry.2 = objects,
+            None => connections.push((connection.to_string(), color.to_string(), objects)),
+        }
+        drop(connections);
+        self.rebuild();
+    }
+
+    pub fn remove_connection(&self, connection: &str) {
+        self.connections
+            .borrow_mut()
+            .retain(|(name, _, _)| name != connection);
+        self.rebuild();
+    }
+
+    /// list these datawindows, by name and title, on top
+    pub fn set_datawindows(&self, datawindows: Vec<(String, String)>) {
+        *self.datawindows.borrow_mut() = datawindows;
+        self.rebuild();
+    }
+
+    fn rebuild(&self) {
+        self.store.clear();
+        let datawindows = self.datawindows.borrow();
+        if !datawindows.is_empty() {
+            let group = self.append_group(None, "", "Datawindows");
+            for (name, title) in datawindows.iter() {
+                let iter = self.store.append(Some(&group));
+                self.store.set_value(&iter, LABEL, &title.to_value());
+                self.store.set_value(&iter, NAME, &name.to_value());
+                self.store.set_value(&iter, OPENABLE, &false.to_value());
+                self.store.set_value(&iter, DATAWINDOW, &true.to_value());
+                self.store.set_value(&iter, CONNECTION, &"".to_value());
+            }
+        }
+        let connections = self.connections.borrow();
+        if connections.is_empty() {
+            self.append_objects(None, "", &self.objects.borrow());
+        }
+        for (connection, color, objects) in connections.iter() {
+            let iter = self.append_group(None, connection, connection);
+            self.store.set_value(&iter, COLOR, &color.to_value());
+            self.append_objects(Some(&iter), connection, objects);
+        }
+        self.expand_top_level();
+    }
+
+    /// the favorites, the recently opened and the objects of each schema by kind
+    fn append_objects(&self, parent: Option<&TreeIter>, connection: &str, objects: &[DbObject]) {
+        let config = self.config.borrow();
+        // only the favorites and recent tables of this database
+        let find = |name: &String| objects.iter().find(|o| &o.qualified_name() == name);
+        let favorites: Vec<&DbObject> = config.favorites.iter().filter_map(find).collect();
+        let recent: Vec<&DbObject> = config.recent.iter().filter_map(find).collect();
+        for (title, section) in vec![("Favorites", favorites), ("Recently opened", recent)] {
+            if section.is_empty() {
+                continue;
+            }
+            let group = self.append_group(parent, connection, title);
+            for object in section {
+                self.append_object(&group, connection, object, &object.qualified_name());
+            }
+        }
+
+        let mut schemas: BTreeMap<Option<&str>, BTreeMap<ObjectKind, Vec<&DbObject>>> =
+            BTreeMap::new();
+        for object in objects.iter() {
+            schemas
+                .entry(object.schema.as_deref())
+                .or_default()
+                .entry(object.kind)
+                .or_default()
+                .push(object);
+        }
+        for (schema, kinds) in schemas {
+            let schema_group = schema.map(|schema| self.append_group(parent, connection, schema));
+            for (kind, objects) in kinds {
+                let title = format!("{} ({})", kind.plural(), objects.len());
+                let group = self.append_group(schema_group.as_ref().or(parent), connection, &title);
+                for object in objects {
+                    self.append_object(&group, connection, object, &object.name);
+                }
+            }
+        }
+    }
+
+    fn append_group(&self, parent: Option<&TreeIter>, connection: &str, title: &str) -> TreeIter {
+        let iter = self.store.append(parent);
+        self.store
+            .set_value(&iter, CONNECTION, &connection.to_value());
+        self.store.set_value(&iter, LABEL, &title.to_value());
+        self.store.set_value(&iter, NAME, &"".to_value());
+        self.store.set_value(&iter, OPENABLE, &false.to_value());
+        self.store.set_value(&iter, DATAWINDOW, &false.to_value());
+        iter
+    }
+
+    fn append_object(&self, parent: &TreeIter, connection: &str, object: &DbObject, label: &str) {
+        let iter = self.store.append(Some(parent));
+        self.store
+            .set_value(&iter, CONNECTION, &connection.to_value());
+        let estimate = object.row_estimate.map(estimate_label).unwrap_or_default();
+        self.store.set_value(&iter, LABEL, &label.to_value());
+        self.store.set_value(&iter, ESTIMATE, &estimate.to_value());
+        self.store
+            .set_value(&iter, NAME, &object.qualified_name().to_value());
+        self.store
+            .set_value(&iter, OPENABLE, &object.kind.has_rows().to_value());
+        self.store.set_value(&iter, DATAWINDOW, &false.to_value());
+    }
+
+    fn expand_top_level(&self) {
+        self.tree.collapse_all();
+        if let Some(iter) = self.filter.get_iter_first() {
+            loop {
+                if let Some(path) = self.filter.get_path(&iter) {
+                    self.tree.expand_row(&path, false);
+                }
+                if !self.filter.iter_next(&iter) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// a short row count, ie: `~1.2k`
+fn estimate_label(rows: u64) -> String {
+    match rows {
+        0..=999 => format!("~{}", rows),
+        1_000..=999_999 => format!("~{:.1}k", rows as f64 / 1_000.0),
+        _ => format!("~{:.1}M", rows as f64 / 1_000_000.0),
+    }
+}
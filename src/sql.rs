@@ -0,0 +1,512 @@
+//! a small sql lexer, used for syntax highlighting and for
+//! figuring out which statement is under the cursor in the sql console
+//!
+//! All offsets are in chars, not bytes, since that is what gtk TextBuffer uses.
+use crate::db::Platform;
+
+static KEYWORDS: &[&str] = &[
+    "ADD",
+    "ALL",
+    "ALTER",
+    "AND",
+    "AS",
+    "ASC",
+    "BEGIN",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CAST",
+    "CHECK",
+    "COLUMN",
+    "COMMIT",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "DEFAULT",
+    "DELETE",
+    "DESC",
+    "DISTINCT",
+    "DROP",
+    "ELSE",
+    "END",
+    "EXCEPT",
+    "EXISTS",
+    "EXPLAIN",
+    "FALSE",
+    "FOREIGN",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "IN",
+    "INDEX",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "JOIN",
+    "KEY",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "NOT",
+    "NULL",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "PRIMARY",
+    "REFERENCES",
+    "RETURNING",
+    "RIGHT",
+    "ROLLBACK",
+    "SELECT",
+    "SET",
+    "TABLE",
+    "THEN",
+    "TRUE",
+    "UNION",
+    "UNIQUE",
+    "UPDATE",
+    "USING",
+    "VALUES",
+    "VIEW",
+    "WHEN",
+    "WHERE",
+    "WITH",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Keyword,
+    Identifier,
+    /// 'quoted string'
+    Literal,
+    Number,
+    Comment,
+    Whitespace,
+    /// operators, parenthesis, commas and the `;` statement separator
+    Symbol,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+impl Token {
+    fn is(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Keyword && self.text.eq_ignore_ascii_case(keyword)
+    }
+}
+
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(word))
+}
+
+pub fn tokenize(sql: &str) -> Vec<Token> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        let kind = if c.is_whitespace() {
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+            TokenKind::Whitespace
+        } else if c == '-' && chars.get(i + 1) == Some(&'-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            TokenKind::Comment
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i = (i + 2).min(chars.len());
+            TokenKind::Comment
+        } else if c == '\'' || c == '"' || c == '`' {
+            i = skip_quoted(&chars, i, c);
+            if c == '\'' {
+                TokenKind::Literal
+            } else {
+                TokenKind::Identifier
+            }
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            TokenKind::Number
+        } else if c.is_alphanumeric() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if is_keyword(&word) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Identifier
+            }
+        } else {
+            i += 1;
+            TokenKind::Symbol
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: i,
+            text: chars[start..i].iter().collect(),
+        });
+    }
+    tokens
+}
+
+// a doubled quote inside a quoted string is an escaped quote
+fn skip_quoted(chars: &[char], start: usize, quote: char) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// the char ranges of each statement in this sql text, separated by `;`,
+/// with the surrounding whitespace trimmed off. Empty statements are skipped.
+pub fn statement_ranges(sql: &str) -> Vec<(usize, usize)> {
+    let tokens = tokenize(sql);
+    let mut ranges = vec![];
+    let mut current: Option<(usize, usize)> = None;
+    for token in tokens.iter() {
+        match token.kind {
+            TokenKind::Symbol if token.text == ";" => {
+                if let Some(range) = current.take() {
+                    ranges.push(range);
+                }
+            }
+            TokenKind::Whitespace | TokenKind::Comment => (),
+            _ => {
+                current = match current {
+                    Some((start, _)) => Some((start, token.end)),
+                    None => Some((token.start, token.end)),
+                };
+            }
+        }
+    }
+    if let Some(range) = current {
+        ranges.push(range);
+    }
+    ranges
+}
+
+/// split the sql text into individual statements
+pub fn split_statements(sql: &str) -> Vec<String> {
+    let chars: Vec<char> = sql.chars().collect();
+    statement_ranges(sql)
+        .into_iter()
+        .map(|(start, end)| chars[start..end].iter().collect())
+        .collect()
+}
+
+/// the statement the cursor is in, or the closest one before it
+pub fn statement_at(sql: &str, cursor: usize) -> Option<(usize, usize)> {
+    let ranges = statement_ranges(sql);
+    ranges
+        .iter()
+        .rev()
+        .find(|(start, _)| *start <= cursor)
+        .or_else(|| ranges.first())
+        .cloned()
+}
+
+/// the index of the tokens that are a `:name` parameter, a `:` right before a name,
+/// which is not part of a `::type` cast
+fn param_tokens(tokens: &[Token]) -> Vec<usize> {
+    let mut params = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Symbol || token.text != ":" {
+            continue;
+        }
+        let cast = i > 0 && tokens[i - 1].text == ":" && tokens[i - 1].end == token.start;
+        let name = match tokens.get(i + 1) {
+            Some(name) => name,
+            None => continue,
+        };
+        let is_name = match name.kind {
+            TokenKind::Identifier => !name.text.starts_with('"') && !name.text.starts_with('`'),
+            TokenKind::Keyword => true,
+            _ => false,
+        };
+        if !cast && is_name && name.start == token.end {
+            params.push(i);
+        }
+    }
+    params
+}
+
+/// the names of the `:name` parameters of the statement, once each, in order of appearance
+pub fn param_names(sql: &str) -> Vec<String> {
+    let tokens = tokenize(sql);
+    let mut names: Vec<String> = vec![];
+    for i in param_tokens(&tokens) {
+        let name = &tokens[i + 1].text;
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    names
+}
+
+/// Replace the `:name` parameters by the bind parameters of the platform, so the values
+/// are never put in the sql text. Returns the statement and the parameter name of each
+/// bind parameter, in order, a name used twice is bound twice.
+pub fn bind_params(sql: &str, platform: Platform) -> (String, Vec<String>) {
+    let tokens = tokenize(sql);
+    let params = param_tokens(&tokens);
+    let mut bound = String::new();
+    let mut names = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        if params.contains(&i) {
+            names.push(tokens[i + 1].text.clone());
+            bound.push_str(&platform.placeholder(names.len()));
+            i += 2;
+        } else {
+            bound.push_str(&tokens[i].text);
+            i += 1;
+        }
+    }
+    (bound, names)
+}
+
+/// The table name when this is a plain `SELECT .. FROM table` over a single table,
+/// that is one without joins, grouping, set operations or sub queries.
+/// Only then can the results be edited and written back to the table,
+/// the columns of the select list that are a bare column, see `plain_columns`.
+pub fn single_table(sql: &str) -> Option<String> {
+    let tokens: Vec<Token> = tokenize(sql)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Whitespace && t.kind != TokenKind::Comment)
+        .collect();
+    if !tokens.first()?.is("SELECT") {
+        return None;
+    }
+    let disallowed = [
+        "JOIN",
+        "GROUP",
+        "HAVING",
+        "UNION",
+        "INTERSECT",
+        "EXCEPT",
+        "DISTINCT",
+        "WITH",
+    ];
+    let selects = tokens.iter().filter(|t| t.is("SELECT")).count();
+    if selects > 1 || tokens.iter().any(|t| disallowed.iter().any(|d| t.is(d))) {
+        return None;
+    }
+    let from = tokens.iter().position(|t| t.is("FROM"))?;
+    let rest = &tokens[from + 1..];
+    let count = name_token_count(rest);
+    if count == 0 {
+        return None;
+    }
+    // anything but a clause keyword after the name is an alias or another table
+    match rest.get(count) {
+        Some(after) if after.kind != TokenKind::Keyword && after.text != ";" => return None,
+        Some(after) if after.is("AS") => return None,
+        _ => (),
+    }
+    Some(rest[..count].iter().map(|t| t.text.as_str()).collect())
+}
+
+// the number of tokens making up a possibly schema qualified name, ie: `schema.table`
+fn name_token_count(tokens: &[Token]) -> usize {
+    let mut count = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        let expected = if i % 2 == 0 {
+            token.kind == TokenKind::Identifier
+        } else {
+            token.text == "."
+        };
+        if !expected {
+            break;
+        }
+        count = i + 1;
+    }
+    // a trailing `.` is not part of the name
+    if count % 2 == 0 && count > 0 {
+        count - 1
+    } else {
+        count
+    }
+}
+
+/// Which of the `count` result columns of a select are a bare column of its table, so an edit
+/// can be written back: `SELECT id, price * 2, name AS label FROM t` gives `[true, false, false]`.
+/// The columns of a `*` are all bare, none are when the select list can't be matched.
+pub fn plain_columns(sql: &str, count: usize) -> Vec<bool> {
+    let tokens: Vec<Token> = tokenize(sql)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Whitespace && t.kind != TokenKind::Comment)
+        .collect();
+    let start = match tokens.iter().position(|t| t.is("SELECT")) {
+        Some(start) => start + 1,
+        None => return vec![false; count],
+    };
+    // the items of the select list, split on the commas outside of parenthesis
+    let mut items: Vec<&[Token]> = vec![];
+    let mut depth = 0;
+    let mut item_start = start;
+    let mut end = tokens.len();
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token.text.as_str() {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            "," if depth == 0 => {
+                items.push(&tokens[item_start..i]);
+                item_start = i + 1;
+            }
+            _ if depth == 0 && (token.is("FROM") || token.text == ";") => {
+                end = i;
+                break;
+            }
+            _ => (),
+        }
+    }
+    items.push(&tokens[item_start..end.max(item_start)]);
+    let is_star = |item: &[Token]| {
+        matches!(item.last(), Some(t) if t.text == "*") && {
+            let count = name_token_count(item);
+            (count == 0 && item.len() == 1) || (count > 0 && item.len() == count + 2)
+        }
+    };
+    let stars = items.iter().filter(|item| is_star(item)).count();
+    let columns = items.len() - stars;
+    if stars > 1 || (stars == 0 && columns != count) || columns > count {
+        return vec![false; count];
+    }
+    let mut plain = vec![];
+    for item in items {
+        if is_star(item) {
+            plain.extend(vec![true; count - columns]);
+        } else {
+            // a name, maybe qualified by the table, with no alias nor operator after it
+            let names = name_token_count(item);
+            plain.push(names > 0 && names == item.len());
+        }
+    }
+    plain
+}
+
+/// What a statement does, from its leading words, see `statement_kind`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatementKind {
+    /// reads rows and changes nothing
+    Query,
+    /// inserts, updates or deletes rows
+    Data,
+    /// begins, commits or rolls back a transaction
+    Transaction,
+    /// anything else, a change of the schema or a setting for example
+    Other,
+}
+
+pub fn statement_kind(sql: &str) -> StatementKind {
+    let words: Vec<String> = tokenize(sql)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Whitespace && t.kind != TokenKind::Comment)
+        .take(2)
+        .map(|t| t.text.to_uppercase())
+        .collect();
+    let first = words.first().map(|w| w.as_str()).unwrap_or("");
+    let second = words.get(1).map(|w| w.as_str()).unwrap_or("");
+    match first {
+        "SELECT" | "SHOW" | "VALUES" | "DESCRIBE" => StatementKind::Query,
+        "INSERT" | "UPDATE" | "DELETE" | "MERGE" | "REPLACE" => StatementKind::Data,
+        // a rollback to a savepoint stays in the transaction
+        "ROLLBACK" if second == "TO" => StatementKind::Other,
+        "BEGIN" | "START" | "COMMIT" | "END" | "ROLLBACK" | "ABORT" => StatementKind::Transaction,
+        _ => StatementKind::Other,
+    }
+}
+
+/// unquote an identifier written as `"name"` or `` `name` ``
+pub fn unquote(ident: &str) -> String {
+    let trimmed = ident.trim();
+    let mut chars = trimmed.chars();
+    match (chars.next(), trimmed.chars().last()) {
+        (Some(q @ '"'), Some('"')) | (Some(q @ '`'), Some('`')) if trimmed.len() > 1 => {
+            let inner = &trimmed[1..trimmed.len() - 1];
+            let doubled = format!("{}{}", q, q);
+            inner.replace(&doubled, &q.to_string())
+        }
+        _ => trimmed.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_params() {
+        let sql = "SELECT id::text, ':skip' FROM orders -- :not\n\
+                   WHERE region = :region AND day >= :start_date OR region = :region";
+        assert_eq!(param_names(sql), vec!["region", "start_date"]);
+        let (bound, names) = bind_params(sql, Platform::Postgres);
+        assert_eq!(names, vec!["region", "start_date", "region"]);
+        assert!(bound.starts_with("SELECT id::text, ':skip' FROM orders -- :not\n"));
+        assert!(bound.ends_with("region = $1 AND day >= $2 OR region = $3"));
+        let (bound, _) = bind_params("SELECT * FROM t WHERE a = :a", Platform::Mysql);
+        assert_eq!(bound, "SELECT * FROM t WHERE a = ?");
+    }
+
+    #[test]
+    fn plain_columns_of_a_select() {
+        let sql = "SELECT id, t.name, price * 2, upper(code), label AS title, 1 FROM t";
+        let plain = plain_columns(sql, 6);
+        assert_eq!(plain, vec![true, true, false, false, false, false]);
+        assert_eq!(plain_columns("SELECT * FROM t", 3), vec![true; 3]);
+        let plain = plain_columns("SELECT t.*, a || b AS ab FROM t WHERE a = 1", 3);
+        assert_eq!(plain, vec![true, true, false]);
+        assert_eq!(
+            plain_columns("SELECT a, (b, c) FROM t", 2),
+            vec![true, false]
+        );
+        // the columns can't be matched to the select list
+        assert_eq!(plain_columns("SELECT a, b FROM t", 3), vec![false; 3]);
+        assert_eq!(plain_columns("SELECT *, * FROM t", 4), vec![false; 4]);
+    }
+
+    #[test]
+    fn kinds_of_statements() {
+        assert_eq!(statement_kind("-- rows\n select 1"), StatementKind::Query);
+        assert_eq!(statement_kind("delete from t"), StatementKind::Data);
+        assert_eq!(
+            statement_kind("BEGIN TRANSACTION"),
+            StatementKind::Transaction
+        );
+        assert_eq!(statement_kind("rollback;"), StatementKind::Transaction);
+        assert_eq!(
+            statement_kind("ROLLBACK TO SAVEPOINT s"),
+            StatementKind::Other
+        );
+        assert_eq!(statement_kind("DROP TABLE t"), StatementKind::Other);
+        assert_eq!(statement_kind(""), StatementKind::Other);
+    }
+}
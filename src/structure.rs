@@ -0,0 +1,224 @@
+//! the structure of a table, shown by the data tab instead of its rows:
+//! the columns, indexes, foreign keys in and out, check constraints, triggers and the ddl
+use crate::catalog::{self, ColumnInfo, ForeignKey, IndexInfo, TriggerInfo};
+use crate::db::{Database, DbError, ResultSet};
+use crate::tab::DataTab;
+use gtk;
+use gtk::prelude::*;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::{Adjustment, Button, Label, ScrolledWindow, TextView, WrapMode};
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// the definition of a table as read from the catalog
+#[derive(Debug, Clone, Default)]
+pub struct TableStructure {
+    pub columns: Vec<ColumnInfo>,
+    pub key: Vec<String>,
+    pub indexes: Vec<IndexInfo>,
+    pub foreign_keys: Vec<ForeignKey>,
+    /// the foreign keys of the other tables on this one, with the referencing table
+    pub references: Vec<(String, ForeignKey)>,
+    pub checks: Vec<String>,
+    pub triggers: Vec<TriggerInfo>,
+    pub ddl: String,
+}
+
+impl TableStructure {
+    pub fn load(db: &mut dyn Database, table: &str) -> Result<Self, DbError> {
+        Ok(TableStructure {
+            columns: catalog::columns(db, table)?,
+            key: catalog::primary_key(db, table)?,
+            indexes: catalog::indexes(db, table)?,
+            foreign_keys: catalog::foreign_keys(db, table)?,
+            references: catalog::referencing_keys(db, table)?,
+            checks: catalog::check_constraints(db, table)?,
+            triggers: catalog::triggers(db, table)?,
+            ddl: catalog::table_ddl(db, table)?,
+        })
+    }
+
+    /// the title and the rows of each section
+    pub fn sections(&self) -> Vec<(&'static str, ResultSet)> {
+        vec![
+            ("Columns", self.column_rows()),
+            ("Indexes", self.index_rows()),
+            ("Foreign keys", self.foreign_key_rows()),
+            ("Referenced by", self.reference_rows()),
+            (
+                "Check constraints",
+                rows(&["Check"], self.checks.iter().map(|c| vec![c.clone()])),
+            ),
+            (
+                "Triggers",
+                rows(
+                    &["Trigger", "Definition"],
+                    self.triggers
+                        .iter()
+                        .map(|t| vec![t.name.clone(), t.definition.clone()]),
+                ),
+            ),
+        ]
+    }
+
+    fn column_rows(&self) -> ResultSet {
+        rows(
+            &["Column", "Type", "Null", "Default", "Key"],
+            self.columns.iter().map(|c| {
+                let key = match self.key.iter().position(|k| *k == c.name) {
+                    Some(i) if self.key.len() > 1 => format!("PK {}", i + 1),
+                    Some(_) => "PK".to_string(),
+                    None if self
+                        .foreign_keys
+                        .iter()
+                        .any(|k| k.columns.contains(&c.name)) =>
+                    {
+                        "FK".to_string()
+                    }
+                    None => String::new(),
+                };
+                vec![
+                    c.name.clone(),
+                    c.type_name(),
+                    if c.nullable { "yes" } else { "no" }.to_string(),
+                    c.default.clone().unwrap_or_default(),
+                    key,
+                ]
+            }),
+        )
+    }
+
+    fn index_rows(&self) -> ResultSet {
+        rows(
+            &["Index", "Columns", "Unique", "Definition"],
+            self.indexes.iter().map(|i| {
+                vec![
+                    i.name.clone(),
+                    i.columns.join(", "),
+                    if i.unique { "yes" } else { "" }.to_string(),
+                    i.definition.clone().unwrap_or_default(),
+                ]
+            }),
+        )
+    }
+
+    fn foreign_key_rows(&self) -> ResultSet {
+        rows(
+            &["Columns", "References"],
+            self.foreign_keys.iter().map(|k| {
+                vec![
+                    k.columns.join(", "),
+                    format!("{} ({})", k.ref_table, k.ref_columns.join(", ")),
+                ]
+            }),
+        )
+    }
+
+    fn reference_rows(&self) -> ResultSet {
+        rows(
+            &["Table", "Columns", "On columns"],
+            self.references.iter().map(|(table, k)| {
+                vec![
+                    table.clone(),
+                    k.columns.join(", "),
+                    k.ref_columns.join(", "),
+                ]
+            }),
+        )
+    }
+}
+
+fn rows<I: Iterator<Item = Vec<String>>>(columns: &[&str], rows: I) -> ResultSet {
+    ResultSet {
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        rows: rows.map(|r| r.into_iter().map(Some).collect()).collect(),
+    }
+}
+
+/// the sections of the structure one below the other, read when it is first shown
+#[derive(Clone)]
+pub struct StructureView {
+    pub container: gtk::Box,
+    content: gtk::Box,
+    loaded: Rc<Cell<bool>>,
+}
+
+impl StructureView {
+    pub fn new() -> Self {
+        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scroll.set_vexpand(true);
+        let content = gtk::Box::new(Vertical, 5);
+        content.set_margin_start(10);
+        content.set_margin_end(10);
+        content.set_margin_top(10);
+        content.set_margin_bottom(10);
+        scroll.add(&content);
+        // the action of the data tab the view is in
+        let alter = Button::with_label("Alter table");
+        alter.set_action_name(Some("tab.alter-table"));
+        let actions = gtk::Box::new(Horizontal, 5);
+        actions.set_margin_start(10);
+        actions.set_margin_top(5);
+        actions.add(&alter);
+        let container = gtk::Box::new(Vertical, 0);
+        container.add(&actions);
+        container.pack_start(&scroll, true, true, 0);
+        StructureView {
+            container: container,
+            content: content,
+            loaded: Rc::new(Cell::new(false)),
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded.get()
+    }
+
+    /// replace the sections with those of the structure, or with the error reading it
+    pub fn show(&self, structure: Result<TableStructure, DbError>) {
+        for child in self.content.get_children() {
+            self.content.remove(&child);
+        }
+        self.loaded.set(true);
+        let structure = match structure {
+            Ok(structure) => structure,
+            Err(e) => {
+                let label = Label::new(Some(&format!("Unable to read the structure: {}", e)));
+                label.set_xalign(0.0);
+                self.content.add(&label);
+                self.content.show_all();
+                return;
+            }
+        };
+        for (title, rs) in structure.sections() {
+            self.content.add(&Self::create_header(title));
+            if rs.rows.is_empty() {
+                let none = Label::new(Some("None"));
+                none.set_xalign(0.0);
+                self.content.add(&none);
+                continue;
+            }
+            let (tree, _, _) = DataTab::create_result_list(&rs);
+            tree.set_vexpand(false);
+            self.content.add(&tree);
+        }
+        self.content.add(&Self::create_header("DDL"));
+        let ddl = TextView::new();
+        ddl.set_monospace(true);
+        ddl.set_editable(false);
+        ddl.set_wrap_mode(WrapMode::None);
+        if let Some(buffer) = ddl.get_buffer() {
+            buffer.set_text(&structure.ddl);
+        }
+        self.content.add(&ddl);
+        self.content.show_all();
+    }
+
+    fn create_header(title: &str) -> Label {
+        let label = Label::new(None);
+        label.set_markup(&format!("<b>{}</b>", glib::markup_escape_text(title)));
+        label.set_xalign(0.0);
+        label.set_margin_top(10);
+        label
+    }
+}
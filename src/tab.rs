@@ -1,15 +1,117 @@
-use glib::Type;
-use glib::Value;
+use crate::aggregate::{self, Aggregate};
+use crate::audit::{self, AuditLog, Change};
+use crate::catalog::{self, ColumnInfo, ForeignKey};
+use crate::clipboard;
+use crate::columns::{ChooserResponse, ColumnChooser};
+use crate::config::{TableConfig, ViewColumn, ViewConfig};
+use crate::db::{self, Database, DbError, Platform, ResultSet, SharedDb, Value};
+use crate::ddl::TableDef;
+use crate::definition::{Definition, Editor, Operation};
+use crate::designer::{DesignerResponse, FormDesigner};
+use crate::detail::Field;
+use crate::edit::{self, Conflict, Record, RowLock, Snapshot, UndoStack, VersionColumn};
+use crate::format::{self, Format};
+use crate::guard;
+use crate::history::HistoryView;
+use crate::layout::FormLayout;
+use crate::merge::MergeDialog;
+use crate::params::ParamForm;
+use crate::report::{self, FormReport, Line, ListReport, Report};
+use crate::sql;
+use crate::structure::{StructureView, TableStructure};
+use crate::table_editor::TableEditor;
+use crate::validate::{self, TableRules};
+use gio::prelude::*;
+use gio::{SimpleAction, SimpleActionGroup};
+use glib::translate::{from_glib_full, from_glib_none, ToGlibPtr};
+use glib::{Type, Variant};
 use gtk;
 use gtk::prelude::*;
 use gtk::Orientation::{Horizontal, Vertical};
 use gtk::{
-    Adjustment, Button, CellRendererText, CellRendererToggle, Entry, Grid, IconSize, Image, Label,
-    ListStore, ScrolledWindow, ToolButton, Toolbar, TreeView, TreeViewColumn,
+    Adjustment, Button, ButtonsType, CellRenderer, CellRendererCombo, CellRendererText,
+    CheckButton, CheckMenuItem, Clipboard, ComboBoxText, DestDefaults, Dialog, DialogFlags, Entry,
+    EntryCompletion, EntryIconPosition, Expander, FileChooserAction, FileChooserDialog, Grid,
+    IconSize, Image, InfoBar, Inhibit, Label, ListStore, Menu, MenuItem, MessageDialog,
+    MessageType, Notebook, PolicyType, PrintOperation, PrintOperationAction, ResponseType,
+    ScrolledWindow, SelectionMode, SeparatorMenuItem, SortType, TargetEntry, TargetFlags,
+    ToolButton, ToolItem, Toolbar, TreeIter, TreeModel, TreePath, TreeStore, TreeView,
+    TreeViewColumn, TreeViewColumnSizing, Window,
 };
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
 
 static VIEW_DETAIL: &'static str = "View detail";
 static LIST_VIEW: &'static str = "List view";
+static PAGE_SIZE: usize = 50;
+static ERROR_BACKGROUND: &'static str = "#f4b4b4";
+/// of the rows changed on the server since they were loaded
+static CHANGED_BACKGROUND: &'static str = "#fce8b2";
+/// the id of the views entry that shows all the columns
+static ALL_COLUMNS: &'static str = "";
+/// the most frequent values shown by "Show distinct values"
+static DISTINCT_LIMIT: usize = 100;
+/// the most rows loaded at once when a group is expanded, a "more…" row loads the next ones
+static GROUP_ROWS: usize = 1000;
+/// the drag target of the column headers, dropped on the group bar
+static COLUMN_TARGET: &'static str = "application/x-datawindow-column";
+
+/// the validation messages, keyed by row and column
+type Errors = Rc<RefCell<HashMap<(usize, usize), String>>>;
+
+/// the rows changed on the server by another session since they were loaded
+type ChangedRows = Rc<RefCell<HashSet<usize>>>;
+
+/// the values a column can take with their labels, by column, for the choice and lookup editors
+type Choices = Rc<Vec<Option<Vec<(String, String)>>>>;
+
+/// the condition and order of the loaded rows
+#[derive(Debug, Clone, Default)]
+struct PageQuery {
+    /// the condition of the where clause
+    filter: Option<String>,
+    /// the column and whether it is ascending
+    order_by: Option<(String, bool)>,
+}
+
+/// a group row of the list, its children are loaded when it is first expanded
+struct Group {
+    /// the value of each group column, from the outermost group
+    conditions: Vec<(String, Value)>,
+    loaded: bool,
+    /// for the "more…" row after the rows of a group: where its next rows start
+    more: Option<usize>,
+}
+
+/// what the tab shows, switched with the toolbar
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ViewMode {
+    List,
+    Detail,
+    Structure,
+}
+
+/// why the edits of a tab were not saved
+enum SaveError {
+    Db(DbError),
+    /// the rows changed on the server since they were loaded, nothing was written
+    Conflicts(Vec<Conflict>),
+}
+
+impl From<DbError> for SaveError {
+    fn from(e: DbError) -> Self {
+        SaveError::Db(e)
+    }
+}
+
+/// the definition of the table, empty when not connected
+struct TableSchema {
+    columns: Vec<ColumnInfo>,
+    key: Vec<String>,
+    rules: TableRules,
+    foreign_keys: Vec<ForeignKey>,
+}
 
 #[derive(Clone)]
 pub struct DataToolbar {
@@ -18,10 +120,16 @@ pub struct DataToolbar {
     pub refresh: ToolButton,
     pub detail: ToolButton,
     pub detail_icon: Image,
+    pub structure: ToolButton,
     pub delete: ToolButton,
     pub undo: ToolButton,
     pub redo: ToolButton,
     pub find: ToolButton,
+    pub print: ToolButton,
+    pub pdf: ToolButton,
+    pub columns: ToolButton,
+    /// the saved views of the table, by name
+    pub views: ComboBoxText,
 }
 
 impl DataToolbar {
@@ -65,16 +173,41 @@ impl DataToolbar {
         let find_button = ToolButton::new::<Image>(Some(&find_icon), Some("Search"));
         find_button.set_is_important(true);
 
+        let print_icon = Image::from_icon_name(Some("document-print"), IconSize::SmallToolbar);
+        let print_button = ToolButton::new::<Image>(Some(&print_icon), Some("Print"));
+        print_button.set_is_important(true);
+
+        let pdf_icon = Image::from_icon_name(Some("x-office-document"), IconSize::SmallToolbar);
+        let pdf_button = ToolButton::new::<Image>(Some(&pdf_icon), Some("PDF"));
+        pdf_button.set_tooltip_text(Some("Export to PDF"));
+
+        let columns_icon = Image::from_icon_name(Some("view-list"), IconSize::SmallToolbar);
+        let columns_button = ToolButton::new::<Image>(Some(&columns_icon), Some("Columns"));
+        columns_button.set_is_important(true);
+
+        let structure_icon =
+            Image::from_icon_name(Some("document-properties"), IconSize::SmallToolbar);
+        let structure_button = ToolButton::new::<Image>(Some(&structure_icon), Some("Structure"));
+        structure_button.set_tooltip_text(Some("Show the structure of the table"));
+
+        let views = ComboBoxText::new();
+        views.set_tooltip_text(Some("View"));
+
         DataToolbar {
             new: new_button,
             save: save_button,
             refresh: refresh_button,
             detail: detail_button,
             detail_icon: detail_icon,
+            structure: structure_button,
             delete: delete_button,
             undo: undo_button,
             redo: redo_button,
             find: find_button,
+            print: print_button,
+            pdf: pdf_button,
+            columns: columns_button,
+            views: views,
         }
     }
 
@@ -87,28 +220,278 @@ impl DataToolbar {
         toolbar.add(&self.undo);
         toolbar.add(&self.redo);
         toolbar.add(&self.find);
+        toolbar.add(&self.print);
+        toolbar.add(&self.pdf);
         toolbar.add(&self.detail);
+        toolbar.add(&self.structure);
+        toolbar.add(&self.columns);
+        let views_item = ToolItem::new();
+        views_item.add(&self.views);
+        toolbar.add(&views_item);
         toolbar
     }
 }
 
+/// previous and next buttons, for paging the list and for moving between records in the detail
+#[derive(Clone)]
+struct NavButtons {
+    container: gtk::Box,
+    prev: Button,
+    next: Button,
+}
+
 #[derive(Clone)]
 pub struct DataTab {
     pub toolbar: DataToolbar,
     pub list_content: gtk::Box,
     pub detail_content: gtk::Box,
+    /// the structure of the table, shown instead of the list or the detail
+    structure: StructureView,
+    /// the recorded changes of the record, next to the detail form
+    history: HistoryView,
+    table: String,
+    db: SharedDb,
+    /// the connection is read-only, nothing can be edited
+    read_only: bool,
+    /// the datawindow shown in the tab, none for a plain table
+    definition: Option<Rc<Definition>>,
+    /// the values of the parameters of the query of the datawindow
+    params: Rc<RefCell<Vec<Value>>>,
+    /// the parameters above the list, they are changed before running the query again
+    param_form: ParamForm,
+    param_bar: Expander,
+    config: Rc<RefCell<TableConfig>>,
+    columns: Rc<Vec<String>>,
+    /// the columns written back on save, not the computed ones of a query
+    plain: Rc<Vec<bool>>,
+    /// the primary key, the records are matched on all columns when it is empty
+    key: Rc<Vec<String>>,
+    /// how the rows are checked to be unchanged on the server before saving them
+    lock: Rc<RowLock>,
+    rules: Rc<TableRules>,
+    foreign_keys: Rc<Vec<ForeignKey>>,
+    query: Rc<RefCell<PageQuery>>,
+    page: Rc<Cell<usize>>,
+    list: TreeView,
+    /// the columns of the list in the order of the store, whatever their position
+    list_columns: Rc<Vec<TreeViewColumn>>,
+    /// the list of the frozen columns, on the left of the list, which only scrolls vertically
+    frozen_list: TreeView,
+    /// the columns of the frozen list in the order of the store, only the frozen ones are visible
+    frozen_columns: Rc<Vec<TreeViewColumn>>,
+    frozen_cells: Rc<Vec<CellRendererText>>,
+    /// the number of frozen columns
+    frozen: Rc<Cell<usize>>,
+    /// the columns the rows are grouped by, the list shows the group store when not empty
+    group_by: Rc<RefCell<Vec<String>>>,
+    /// the values of the columns then the index of the group, -1 on the rows
+    group_store: TreeStore,
+    groups: Rc<RefCell<Vec<Group>>>,
+    /// where the column headers are dropped to group by them
+    group_bar: gtk::Box,
+    /// set while the selection of one list is copied to the other
+    syncing_selection: Rc<Cell<bool>>,
+    /// the store index of the first and last clicked column of the selected range
+    range_columns: Rc<Cell<Option<(usize, usize)>>>,
+    store: ListStore,
+    cells: Rc<Vec<CellRendererText>>,
+    choices: Choices,
+    records: Rc<RefCell<Vec<Record>>>,
+    /// the original values of the deleted records
+    deleted: Rc<RefCell<Vec<Vec<Value>>>>,
+    undo: Rc<RefCell<UndoStack>>,
+    errors: Errors,
+    changed_rows: ChangedRows,
+    /// offers to reload the rows changed on the server
+    changed_bar: InfoBar,
+    status: Label,
+    /// the aggregates of the columns, next to the paging buttons, hidden when there are none
+    footer: gtk::Box,
+    list_nav: NavButtons,
+    record_nav: NavButtons,
+    fields: Rc<Vec<Field>>,
+    detail_entries: Rc<Vec<Entry>>,
+    /// the labels and entries of the detail, as laid out by `layout_form`
+    form: gtk::Box,
+    /// made in the form designer, the fields are one per row when there is none
+    form_layout: Rc<RefCell<Option<FormLayout>>>,
+    /// set while a record is put into the detail entries, so it is not taken as an edit
+    showing_record: Rc<Cell<bool>>,
+    /// set while the views are put into the toolbar, so it is not taken as a switch
+    filling_views: Rc<Cell<bool>>,
+    actions: SimpleActionGroup,
+    /// opens a table filtered on a condition, set by the window
+    open_table: Rc<RefCell<Option<Box<dyn Fn(&str, &str)>>>>,
 }
 
 impl DataTab {
-    pub fn new() -> Self {
+    pub fn new(table: &str, db: SharedDb) -> Self {
+        Self::create(table, db, None, vec![])
+    }
+
+    /// A tab on the table or the query of the datawindow, laid out as defined.
+    /// `params` are the values of the parameters of the query, in the order of their names.
+    pub fn from_definition(definition: Definition, params: Vec<Value>, db: SharedDb) -> Self {
+        let table = definition.table_name();
+        Self::create(&table, db, Some(Rc::new(definition)), params)
+    }
+
+    fn create(
+        table: &str,
+        db: SharedDb,
+        definition: Option<Rc<Definition>>,
+        params: Vec<Value>,
+    ) -> Self {
         let toolbar = DataToolbar::new();
-        let list_content = Self::create_list_content();
-        let detail_content = Self::create_detail_content();
+        let config = match &definition {
+            Some(definition) => definition.table_config(),
+            None => TableConfig::load(table),
+        };
+        let config = Rc::new(RefCell::new(config));
+        // there is no table to read the schema of behind a query, unless it is on a single one
+        let schema = match &definition {
+            Some(definition) if definition.query.is_some() && definition.base_table().is_none() => {
+                Self::unknown_schema(&config.borrow())
+            }
+            _ => Self::load_schema(&db, table, &config.borrow()),
+        };
+        let rs = Self::load_page(
+            &db,
+            table,
+            definition.as_deref(),
+            &params,
+            &PageQuery::default(),
+            0,
+        );
+        let param_fields = definition
+            .as_ref()
+            .map(|d| d.param_fields())
+            .unwrap_or_default();
+        let param_form = ParamForm::new(param_fields, &params);
+        let fields: Vec<Field> = rs
+            .columns
+            .iter()
+            .map(
+                |c| match schema.columns.iter().find(|info| &info.name == c) {
+                    Some(info) => Field::from_column_info(info),
+                    None => Field::new(c),
+                },
+            )
+            .collect();
+        let choices: Choices = Rc::new(Self::load_choices(&db, definition.as_deref(), &rs.columns));
+        let (list, store, mut cells) = Self::create_result_list(&rs);
+        let list_columns = list.get_columns();
+        let errors = Errors::default();
+        let changed_rows = ChangedRows::default();
+        let (frozen_list, mut frozen_cells) = Self::create_frozen_list(&store, &rs.columns);
+        let frozen_columns = frozen_list.get_columns();
+        for (list, cells) in vec![(&list, &mut cells), (&frozen_list, &mut frozen_cells)] {
+            Self::init_choice_cells(list, cells, &choices);
+            Self::init_column_formats(
+                list,
+                cells,
+                &rs.columns,
+                &config,
+                &errors,
+                &changed_rows,
+                &choices,
+            );
+            if let Some(definition) = &definition {
+                for (column, name) in list.get_columns().iter().zip(rs.columns.iter()) {
+                    column.set_title(definition.label(name));
+                }
+            }
+        }
+        let list_nav = Self::create_paging_buttons_for_list();
+        let group_store = Self::create_group_store(rs.columns.len());
+        let group_bar = gtk::Box::new(Horizontal, 5);
+        let footer = gtk::Box::new(Horizontal, 15);
+        let list_content =
+            Self::create_list_content(&frozen_list, &list, &group_bar, &footer, &list_nav);
+        let record_nav = Self::create_record_nav_buttons_for_detail();
+        let form_layout = match &definition {
+            Some(definition) => Some(definition.form_layout(&fields)),
+            None => FormLayout::load(table),
+        };
+        let form = gtk::Box::new(Vertical, 0);
+        let history = HistoryView::new();
+        let (detail_content, detail_entries) = Self::create_detail_content(
+            &fields,
+            &config.borrow(),
+            definition.as_deref(),
+            &form,
+            &history.container,
+            &record_nav,
+        );
+        Self::layout_form(&form, form_layout.as_ref(), &fields, &detail_entries);
+        let status = Label::new(None);
+        status.set_xalign(0.0);
+        let records = rs.rows.iter().cloned().map(Record::loaded).collect();
+        let lock = Self::row_lock(&schema.columns, &rs.columns, &config.borrow());
+        let read_only = db
+            .borrow()
+            .as_ref()
+            .map_or(false, |db| db.session().read_only);
+        let plain = match &definition {
+            Some(definition) => definition.plain_columns(rs.columns.len()),
+            None => vec![true; rs.columns.len()],
+        };
 
         let ctab = DataTab {
             toolbar: toolbar,
             list_content: list_content,
             detail_content: detail_content,
+            structure: StructureView::new(),
+            history: history,
+            table: table.to_string(),
+            db: db,
+            read_only: read_only,
+            definition: definition,
+            params: Rc::new(RefCell::new(params)),
+            param_form: param_form,
+            param_bar: Expander::new(None),
+            config: config,
+            columns: Rc::new(rs.columns),
+            plain: Rc::new(plain),
+            key: Rc::new(schema.key),
+            lock: Rc::new(lock),
+            rules: Rc::new(schema.rules),
+            foreign_keys: Rc::new(schema.foreign_keys),
+            query: Rc::new(RefCell::new(PageQuery::default())),
+            page: Rc::new(Cell::new(0)),
+            list: list,
+            list_columns: Rc::new(list_columns),
+            frozen_list: frozen_list,
+            frozen_columns: Rc::new(frozen_columns),
+            frozen_cells: Rc::new(frozen_cells),
+            frozen: Rc::new(Cell::new(0)),
+            group_by: Rc::new(RefCell::new(vec![])),
+            group_store: group_store,
+            groups: Rc::new(RefCell::new(vec![])),
+            group_bar: group_bar,
+            syncing_selection: Rc::new(Cell::new(false)),
+            range_columns: Rc::new(Cell::new(None)),
+            store: store,
+            cells: Rc::new(cells),
+            choices: choices,
+            records: Rc::new(RefCell::new(records)),
+            deleted: Rc::new(RefCell::new(vec![])),
+            undo: Rc::new(RefCell::new(UndoStack::default())),
+            errors: errors,
+            changed_rows: changed_rows,
+            changed_bar: InfoBar::new(),
+            status: status,
+            footer: footer,
+            list_nav: list_nav,
+            record_nav: record_nav,
+            fields: Rc::new(fields),
+            detail_entries: Rc::new(detail_entries),
+            form: form,
+            form_layout: Rc::new(RefCell::new(form_layout)),
+            showing_record: Rc::new(Cell::new(false)),
+            filling_views: Rc::new(Cell::new(false)),
+            actions: SimpleActionGroup::new(),
+            open_table: Rc::new(RefCell::new(None)),
         };
         ctab
     }
@@ -116,31 +499,127 @@ impl DataTab {
     fn init_detail_toolbar(&self) {
         self.list_content.set_visible(true);
         self.detail_content.set_visible(false);
-        let this = self.clone();
-        self.toolbar.detail.connect_clicked(move |_| {
-            println!("detail button clicked!..");
-            this.toggle_list_view();
+    }
+
+    /// The `tab.*` actions, used by the toolbar buttons and the keyboard shortcuts.
+    /// They are found by the window on the widget of the current page.
+    fn init_actions(&self) {
+        self.add_action("new", |this| this.new_record());
+        self.add_action("save", |this| this.save());
+        self.add_action("refresh", |this| match this.view_mode() {
+            ViewMode::Structure => this.load_structure(),
+            _ if this.confirm_discard() => this.load(this.page.get()),
+            _ => (),
         });
+        self.add_action("delete", |this| this.delete_selected());
+        self.add_action("edit", |this| this.edit_cell());
+        self.add_action("find", |this| this.find());
+        self.add_action("copy", |this| this.copy_range());
+        self.add_action("paste", |this| this.paste());
+        self.add_action("undo", |this| this.undo());
+        self.add_action("choose-columns", |this| this.choose_columns());
+        self.add_action("design-form", |this| this.design_form());
+        self.add_action("print", |this| this.print());
+        self.add_action("export-pdf", |this| this.export_pdf());
+        self.add_action("toggle-detail", |this| this.toggle_list_view());
+        self.add_action("toggle-structure", |this| this.toggle_structure());
+        self.add_action("alter-table", |this| this.alter_table());
+        self.add_action("toggle-history", |this| this.toggle_history());
+        self.add_action("revert-version", |this| this.revert_version());
+        self.add_action("run-query", |this| this.run_query());
+        self.add_action("previous-page", |this| {
+            let page = this.page.get();
+            if page > 0 && this.confirm_discard() {
+                this.load(page - 1);
+            }
+        });
+        self.add_action("next-page", |this| {
+            if this.confirm_discard() {
+                this.load(this.page.get() + 1);
+            }
+        });
+        self.toolbar.new.set_action_name(Some("tab.new"));
+        self.toolbar.save.set_action_name(Some("tab.save"));
+        self.toolbar.refresh.set_action_name(Some("tab.refresh"));
+        self.toolbar.delete.set_action_name(Some("tab.delete"));
+        self.toolbar.find.set_action_name(Some("tab.find"));
+        self.toolbar.undo.set_action_name(Some("tab.undo"));
+        self.toolbar.print.set_action_name(Some("tab.print"));
+        self.toolbar.pdf.set_action_name(Some("tab.export-pdf"));
+        self.toolbar
+            .columns
+            .set_action_name(Some("tab.choose-columns"));
+        self.toolbar
+            .detail
+            .set_action_name(Some("tab.toggle-detail"));
+        self.toolbar
+            .structure
+            .set_action_name(Some("tab.toggle-structure"));
+        self.list_nav
+            .prev
+            .set_action_name(Some("tab.previous-page"));
+        self.list_nav.next.set_action_name(Some("tab.next-page"));
+    }
+
+    fn add_action<F: Fn(&Self) + 'static>(&self, name: &str, activate: F) {
+        let action = SimpleAction::new(name, None);
+        let this = self.clone();
+        action.connect_activate(move |_, _| activate(&this));
+        self.actions.add_action(&action);
     }
 
     pub fn build_widget(&self) -> gtk::Box {
         let vbox = gtk::Box::new(Vertical, 0);
+        vbox.insert_action_group("tab", Some(&self.actions));
         vbox.add(&self.toolbar.build_widget());
+        if !self.param_form.is_empty() {
+            vbox.add(&self.create_param_bar());
+        }
         vbox.add(&self.list_content);
         vbox.add(&self.detail_content);
+        vbox.add(&self.structure.container);
+        vbox.add(&self.create_changed_bar());
+        vbox.add(&self.status);
         self.detail_content.set_no_show_all(true);
+        self.structure.container.set_no_show_all(true);
         self.init_detail_toolbar();
+        self.init_actions();
+        self.init_menu_actions();
+        self.disable_forbidden_actions();
+        self.init_header_menus();
+        self.init_grouping();
+        self.init_row_menu();
+        self.init_range_selection();
+        self.init_views();
+        self.init_list_selection();
+        self.init_navigation();
+        self.init_editing();
+        self.init_find();
+        self.update_footer();
         vbox
     }
 
-    fn set_list_view(&self, list_view: bool) {
-        self.detail_content.set_no_show_all(false);
-        self.detail_content.show_all();
-        self.list_content.set_visible(list_view);
-        self.detail_content.set_visible(!list_view);
-        let (icon_name, label) = match list_view {
-            true => ("view-fullscreen", VIEW_DETAIL),
-            false => ("view-restore", LIST_VIEW),
+    fn set_view(&self, mode: ViewMode) {
+        if mode == ViewMode::Detail {
+            self.detail_content.set_no_show_all(false);
+            self.detail_content.show_all();
+        }
+        if mode == ViewMode::Structure {
+            self.structure.container.set_no_show_all(false);
+            self.structure.container.show_all();
+            if !self.structure.is_loaded() {
+                self.load_structure();
+            }
+        }
+        self.list_content.set_visible(mode == ViewMode::List);
+        self.detail_content.set_visible(mode == ViewMode::Detail);
+        self.structure
+            .container
+            .set_visible(mode == ViewMode::Structure);
+        // the button switches to the detail from the list and the structure
+        let (icon_name, label) = match mode {
+            ViewMode::Detail => ("view-restore", LIST_VIEW),
+            _ => ("view-fullscreen", VIEW_DETAIL),
         };
         println!("icon name: {:#?}", self.toolbar.detail.get_icon_name());
         self.toolbar.detail.set_icon_name(Some(icon_name)); //FIXME doesn't change the icon
@@ -151,144 +630,3613 @@ impl DataTab {
         println!("now icon name: {:#?}", self.toolbar.detail.get_icon_name());
     }
 
+    fn view_mode(&self) -> ViewMode {
+        if self.structure.container.is_visible() {
+            ViewMode::Structure
+        } else if self.detail_content.is_visible() {
+            ViewMode::Detail
+        } else {
+            ViewMode::List
+        }
+    }
+
     fn toggle_list_view(&self) {
-        let list_view = self.list_content.is_visible();
-        self.set_list_view(!list_view);
+        match self.view_mode() {
+            ViewMode::Detail => self.set_view(ViewMode::List),
+            _ => self.set_view(ViewMode::Detail),
+        }
+    }
+
+    fn toggle_structure(&self) {
+        match self.view_mode() {
+            ViewMode::Structure => self.set_view(ViewMode::List),
+            _ => self.set_view(ViewMode::Structure),
+        }
+    }
+
+    /// read the structure from the catalog, again on refresh
+    fn load_structure(&self) {
+        let structure = match self.db.borrow_mut().as_mut() {
+            Some(db) => TableStructure::load(db.as_mut(), &self.table),
+            None => Err(DbError::Connection("not connected".to_string())),
+        };
+        self.structure.show(structure);
+    }
+
+    /// Change the table in the table editor.
+    /// The list keeps its columns until the tab is opened again.
+    fn alter_table(&self) {
+        let (platform, original) = match self.db.borrow_mut().as_mut() {
+            Some(db) => (db.platform(), TableDef::load(db.as_mut(), &self.table)),
+            None => return,
+        };
+        let original = match original {
+            Ok(original) => original,
+            Err(e) => {
+                println!("unable to read the definition of {}: {}", self.table, e);
+                return;
+            }
+        };
+        let editor = TableEditor::new(
+            self.window().as_ref(),
+            self.db.clone(),
+            platform,
+            Some(original),
+        );
+        if editor.run().is_some() {
+            self.load_structure();
+            self.status
+                .set_text("Altered, open the table again to see the changed columns");
+        }
+    }
+
+    /// without the definition of the table, only the rules from the config apply
+    fn unknown_schema(config: &TableConfig) -> TableSchema {
+        TableSchema {
+            columns: vec![],
+            key: vec![],
+            rules: TableRules::new(&[], &[], vec![], config),
+            foreign_keys: vec![],
+        }
+    }
+
+    /// The configured version column when it is in the list, a counter when it is a number.
+    /// Without it all the columns of the list that are in the table are compared.
+    fn row_lock(table_columns: &[ColumnInfo], columns: &[String], config: &TableConfig) -> RowLock {
+        let info = |column: &str| table_columns.iter().find(|c| c.name == column);
+        let version = config
+            .version
+            .as_ref()
+            .filter(|v| columns.contains(v))
+            .map(|v| match info(v) {
+                Some(info) if catalog::is_numeric(&info.data_type) => {
+                    VersionColumn::Counter(v.clone())
+                }
+                _ => VersionColumn::Timestamp(v.clone()),
+            });
+        RowLock {
+            columns: columns
+                .iter()
+                .filter(|c| info(c).is_some())
+                .cloned()
+                .collect(),
+            version: version,
+        }
+    }
+
+    /// the column definitions, keys and checks of the table,
+    /// only the rules from the config apply when not connected
+    fn load_schema(db: &SharedDb, table: &str, config: &TableConfig) -> TableSchema {
+        let mut db = db.borrow_mut();
+        let db = match db.as_mut() {
+            Some(db) => db,
+            None => return Self::unknown_schema(config),
+        };
+        let columns = catalog::columns(db.as_mut(), table).unwrap_or_else(|e| {
+            println!("unable to read the columns of {}: {}", table, e);
+            vec![]
+        });
+        let key = catalog::primary_key(db.as_mut(), table).unwrap_or_else(|e| {
+            println!("unable to read the primary key of {}: {}", table, e);
+            vec![]
+        });
+        let checks = catalog::check_constraints(db.as_mut(), table).unwrap_or_else(|e| {
+            println!("unable to read the check constraints of {}: {}", table, e);
+            vec![]
+        });
+        let unique_keys = catalog::unique_keys(db.as_mut(), table).unwrap_or_else(|e| {
+            println!("unable to read the unique keys of {}: {}", table, e);
+            vec![]
+        });
+        let foreign_keys = catalog::foreign_keys(db.as_mut(), table).unwrap_or_else(|e| {
+            println!("unable to read the foreign keys of {}: {}", table, e);
+            vec![]
+        });
+        let rules = TableRules::new(&columns, &checks, unique_keys, config);
+        TableSchema {
+            columns: columns,
+            key: key,
+            rules: rules,
+            foreign_keys: foreign_keys,
+        }
+    }
+
+    /// the rows of this page of the table, or some demo rows when not connected
+    fn load_page(
+        db: &SharedDb,
+        table: &str,
+        definition: Option<&Definition>,
+        params: &[Value],
+        query: &PageQuery,
+        page: usize,
+    ) -> ResultSet {
+        match db.borrow_mut().as_mut() {
+            Some(db) => {
+                let (from, values) = Self::source(db.platform(), table, definition, params);
+                let offset = page * PAGE_SIZE;
+                Self::select_rows(db.as_mut(), &from, &values, query, Some(PAGE_SIZE), offset)
+            }
+            None => Self::demo_page(page),
+        }
+    }
+
+    /// What the rows are selected from: the table, or the select of the datawindow
+    /// when it is on a query or filters the rows of the table.
+    /// The parameters of the query are bound, `params` has a value for each of its names.
+    fn source(
+        platform: Platform,
+        table: &str,
+        definition: Option<&Definition>,
+        params: &[Value],
+    ) -> (String, Vec<Value>) {
+        let definition = match definition {
+            Some(definition) => definition,
+            None => return (platform.quote_table(table), vec![]),
+        };
+        let select = match definition.select(platform) {
+            Some(select) => select,
+            None => return (platform.quote_table(table), vec![]),
+        };
+        let (select, bound) = sql::bind_params(&select, platform);
+        let names = definition.param_names();
+        let values = bound
+            .iter()
+            .map(|name| {
+                let i = names.iter().position(|n| n == name)?;
+                params.get(i).cloned().flatten()
+            })
+            .collect();
+        (format!("({}) AS source", select), values)
+    }
+
+    /// the source of the rows with the values of its parameters
+    fn from_clause(&self, platform: Platform) -> (String, Vec<Value>) {
+        Self::source(
+            platform,
+            &self.table,
+            self.definition.as_deref(),
+            &self.params.borrow(),
+        )
+    }
+
+    /// The rows matching the query, the page from the offset on or all of them without a limit.
+    /// `from` is the quoted table or a subquery with these parameters.
+    fn select_rows(
+        db: &mut dyn Database,
+        from: &str,
+        params: &[Value],
+        query: &PageQuery,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> ResultSet {
+        let platform = db.platform();
+        let condition = match &query.filter {
+            Some(filter) => format!(" WHERE {}", filter),
+            None => String::new(),
+        };
+        let order = match &query.order_by {
+            Some((column, ascending)) => format!(
+                " ORDER BY {} {}",
+                platform.quote_ident(column),
+                if *ascending { "ASC" } else { "DESC" }
+            ),
+            None => String::new(),
+        };
+        let page = match limit {
+            Some(limit) => format!(" LIMIT {} OFFSET {}", limit, offset),
+            None => String::new(),
+        };
+        let sql = format!("SELECT * FROM {}{}{}{}", from, condition, order, page);
+        match db.execute(&sql, params) {
+            Ok(result) => result.rows(),
+            Err(e) => {
+                println!("unable to load {}: {}", from, e);
+                ResultSet::default()
+            }
+        }
+    }
+
+    fn demo_page(page: usize) -> ResultSet {
+        let start = page * PAGE_SIZE;
+        ResultSet {
+            columns: vec![
+                "Column1".to_string(),
+                "Column2".to_string(),
+                "Active".to_string(),
+            ],
+            rows: (start..start + PAGE_SIZE)
+                .map(|i| {
+                    vec![
+                        Some(format!("I am a content in a tab{}", i)),
+                        Some(format!("Cell 2 tab {}", i)),
+                        Some("true".to_string()),
+                    ]
+                })
+                .collect(),
+        }
+    }
+
+    /// only show the rows matching this condition, ie: a saved filter
+    pub fn set_filter(&self, filter: Option<&str>) {
+        if !self.confirm_discard() {
+            return;
+        }
+        self.query.borrow_mut().filter = filter.map(String::from);
+        self.load(0);
+        match filter {
+            Some(filter) => self.status.set_text(&format!("Filtered on {}", filter)),
+            None => self.status.set_text(""),
+        }
+    }
+
+    /// Load this page into the list, staying on the current page when it has no rows.
+    /// Unsaved edits are dropped. When grouped, the groups are loaded instead.
+    pub fn load(&self, page: usize) {
+        if self.is_grouped() {
+            self.reload_groups();
+            self.update_footer();
+            return;
+        }
+        let query = self.query.borrow().clone();
+        let rs = Self::load_page(
+            &self.db,
+            &self.table,
+            self.definition.as_deref(),
+            &self.params.borrow(),
+            &query,
+            page,
+        );
+        if rs.rows.is_empty() && page > 0 {
+            return;
+        }
+        // the aggregates are on all the pages, they only change on a reload or a new query
+        let reload = page == 0 || page == self.page.get();
+        self.page.set(page);
+        self.store.clear();
+        Self::fill_store(&self.store, &rs);
+        *self.records.borrow_mut() = rs.rows.into_iter().map(Record::loaded).collect();
+        self.deleted.borrow_mut().clear();
+        self.undo.borrow_mut().clear();
+        self.errors.borrow_mut().clear();
+        self.changed_rows.borrow_mut().clear();
+        self.changed_bar.hide();
+        if reload {
+            self.update_footer();
+        }
+    }
+
+    /// the collapsed parameters show their values, expanded they can be changed
+    fn create_param_bar(&self) -> Expander {
+        let run = Button::with_label("Run");
+        run.set_action_name(Some("tab.run-query"));
+        let hbox = gtk::Box::new(Horizontal, 10);
+        hbox.pack_start(&self.param_form.grid, false, false, 0);
+        hbox.pack_start(&run, false, false, 0);
+        hbox.set_margin_start(10);
+        self.param_bar.add(&hbox);
+        self.update_param_bar();
+        self.param_bar.clone()
+    }
+
+    fn update_param_bar(&self) {
+        self.param_bar
+            .set_label(Some(&format!("Parameters: {}", self.param_form.summary())));
+    }
+
+    /// run the query again with the parameters of the bar, once the unsaved edits may be dropped
+    fn run_query(&self) {
+        if !self.confirm_discard() {
+            return;
+        }
+        *self.params.borrow_mut() = self.param_form.values();
+        self.update_param_bar();
+        self.param_bar.set_expanded(false);
+        self.load(0);
     }
 
     /// a contents to the notebook
-    fn create_list_content() -> gtk::Box {
+    /// The frozen list scrolls vertically with the list, it is shown when columns are frozen.
+    fn create_list_content(
+        frozen: &TreeView,
+        list: &TreeView,
+        group_bar: &gtk::Box,
+        footer: &gtk::Box,
+        paging: &NavButtons,
+    ) -> gtk::Box {
+        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scroll.add(list);
+        let frozen_scroll =
+            ScrolledWindow::new(None::<&Adjustment>, scroll.get_vadjustment().as_ref());
+        frozen_scroll.set_policy(PolicyType::Never, PolicyType::External);
+        frozen_scroll.add(frozen);
+        frozen.show_all();
+        frozen_scroll.set_no_show_all(true);
+        let hbox = gtk::Box::new(Horizontal, 0);
+        hbox.pack_start(&frozen_scroll, false, false, 0);
+        hbox.pack_start(&scroll, true, true, 0);
+        footer.set_no_show_all(true);
+        let bottom = gtk::Box::new(Horizontal, 0);
+        bottom.pack_start(&paging.container, false, false, 0);
+        bottom.pack_end(footer, false, false, 10);
+        let vbox = gtk::Box::new(Vertical, 0);
+        vbox.pack_start(group_bar, false, false, 5);
+        vbox.add(&hbox);
+        vbox.pack_start(&bottom, false, false, 10);
+        vbox
+    }
+
+    /// the columns of the list as text, then the index of the group
+    fn create_group_store(columns: usize) -> TreeStore {
+        let mut types = vec![Type::String; columns];
+        types.push(Type::I32);
+        TreeStore::new(&types)
+    }
+
+    /// a list on the same store with a hidden column for every column of the list
+    fn create_frozen_list(
+        store: &ListStore,
+        columns: &[String],
+    ) -> (TreeView, Vec<CellRendererText>) {
+        let tree = TreeView::new();
+        tree.set_vexpand(true);
+        tree.set_model(Some(store));
+        tree.set_headers_visible(true);
+        let mut cells = vec![];
+        for (i, name) in columns.iter().enumerate() {
+            let (column, cell) = Self::create_text_column(name, i as i32);
+            column.set_visible(false);
+            column.set_reorderable(false);
+            tree.append_column(&column);
+            cells.push(cell);
+        }
+        (tree, cells)
+    }
+
+    fn create_text_column(title: &str, index: i32) -> (TreeViewColumn, CellRendererText) {
+        let column = TreeViewColumn::new();
+        let cell = CellRendererText::new();
+        column.set_title(title);
+        column.set_resizable(true);
+        column.set_min_width(1); //1pixel
+        column.set_max_width(500);
+        column.pack_start(&cell, true);
+        column.add_attribute(&cell, "text", index);
+        column.set_reorderable(true);
+        (column, cell)
+    }
+
+    /// a list of these results where every column is rendered as text,
+    /// this is also used for showing the results of the sql console
+    pub fn create_result_list(rs: &ResultSet) -> (TreeView, ListStore, Vec<CellRendererText>) {
         let tree = TreeView::new();
         tree.set_hexpand(true);
         tree.set_vexpand(true);
-        let column_types = [Type::String, Type::String, Type::Bool];
+        let column_types = vec![Type::String; rs.columns.len()];
         let store = ListStore::new(&column_types);
         tree.set_model(Some(&store));
         tree.set_headers_visible(true);
-        let column1 = TreeViewColumn::new();
-        let cell1 = CellRendererText::new();
-        let sort_icon = Image::from_icon_name(Some("window-close"), IconSize::Button);
-        let sort_button = Button::new();
-        sort_button.add(&sort_icon);
-        column1.pack_start(&cell1, true);
-        tree.add(&sort_button);
-        column1.set_min_width(1); //1pixel
-        column1.set_max_width(500);
-        column1.add_attribute(&cell1, "text", 0);
-        column1.set_reorderable(true);
-        column1.set_resizable(true);
-        column1.set_title("Column1");
-        tree.append_column(&column1);
-
-        let column2 = TreeViewColumn::new();
-        let cell2 = CellRendererText::new();
-        column2.set_title("Column2");
-        column2.set_resizable(true);
-        column2.pack_start(&cell2, true);
-        column2.add_attribute(&cell2, "text", 1);
-        column2.set_reorderable(true);
-        tree.append_column(&column2);
-
-        let column3 = TreeViewColumn::new();
-        let cell3 = CellRendererToggle::new();
-        column3.set_title("Active");
-        column3.set_resizable(true);
-        column3.pack_start(&cell3, true);
-        column3.add_attribute(&cell3, "activatable", 2);
-        column3.set_reorderable(true);
-        tree.append_column(&column3);
-
-        let selection = tree.get_selection();
-        selection.connect_changed(|tree_selection| {
-            let (model, iter) = tree_selection.get_selected().unwrap();
-            if let Some(path) = model.get_path(&iter) {
-                let value = model.get_value(&iter, 1);
-                let value_string: String = value
-                    .get()
-                    .expect("must not error")
-                    .expect("must be a string");
-                println!("selected row {} {}", path.to_string(), value_string);
-            }
-        });
-        for i in 0..50 {
+        let mut cells = vec![];
+        for (i, name) in rs.columns.iter().enumerate() {
+            let (column, cell) = Self::create_text_column(name, i as i32);
+            tree.append_column(&column);
+            cells.push(cell);
+        }
+        Self::fill_store(&store, rs);
+        (tree, store, cells)
+    }
+
+    fn fill_store(store: &ListStore, rs: &ResultSet) {
+        for row in rs.rows.iter() {
             let iter = store.append();
-            store.set_value(
-                &iter,
-                0,
-                &Value::from(&format!("I am a content in a tab{}", i)),
+            for (i, value) in row.iter().enumerate() {
+                store.set_value(&iter, i as u32, &value.as_deref().to_value());
+            }
+        }
+    }
+
+    /// The values and labels of the columns with a choice or lookup editor.
+    /// The lookups are read from their table, they have no values when not connected.
+    fn load_choices(
+        db: &SharedDb,
+        definition: Option<&Definition>,
+        columns: &[String],
+    ) -> Vec<Option<Vec<(String, String)>>> {
+        let definition = match definition {
+            Some(definition) => definition,
+            None => return vec![None; columns.len()],
+        };
+        let mut db = db.borrow_mut();
+        columns
+            .iter()
+            .map(|column| {
+                let column = definition.columns.get(column)?;
+                if let Some(lookup) = &column.lookup {
+                    let db = match db.as_mut() {
+                        Some(db) => db,
+                        None => return Some(vec![]),
+                    };
+                    let sql = lookup.query(db.platform());
+                    return match db.execute(&sql, &[]) {
+                        Ok(result) => Some(
+                            result
+                                .rows()
+                                .rows
+                                .into_iter()
+                                .filter_map(|row| {
+                                    let key = row.get(0).cloned().flatten()?;
+                                    let label = row.get(1).cloned().flatten();
+                                    Some((key.clone(), label.unwrap_or(key)))
+                                })
+                                .collect(),
+                        ),
+                        Err(e) => {
+                            println!("unable to read the lookup {}: {}", lookup.table, e);
+                            Some(vec![])
+                        }
+                    };
+                }
+                match &column.editor {
+                    Editor::Choice { values } => {
+                        Some(values.iter().map(|v| (v.clone(), v.clone())).collect())
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// replace the text cells of the columns with choices by combo cells listing the labels
+    fn init_choice_cells(list: &TreeView, cells: &mut Vec<CellRendererText>, choices: &Choices) {
+        for (i, choices) in choices.iter().enumerate() {
+            let (choices, column) = match (choices, list.get_column(i as i32)) {
+                (Some(choices), Some(column)) => (choices, column),
+                _ => continue,
+            };
+            let model = ListStore::new(&[Type::String]);
+            for (_, label) in choices.iter() {
+                let iter = model.append();
+                model.set_value(&iter, 0, &label.to_value());
+            }
+            let cell = CellRendererCombo::new();
+            cell.set_property_model(Some(&model));
+            cell.set_property_text_column(0);
+            cell.set_property_has_entry(false);
+            column.clear();
+            column.pack_start(&cell, true);
+            column.add_attribute(&cell, "text", i as i32);
+            cells[i] = cell.upcast();
+        }
+    }
+
+    /// Display the values in the format set for their column,
+    /// the format is looked up on each render so changes show up right away.
+    /// The values of a column with choices are displayed as their label.
+    /// Invalid values get a red background.
+    fn init_column_formats(
+        list: &TreeView,
+        cells: &[CellRendererText],
+        columns: &[String],
+        config: &Rc<RefCell<TableConfig>>,
+        errors: &Errors,
+        changed_rows: &ChangedRows,
+        choices: &Choices,
+    ) {
+        for (i, (cell, name)) in cells.iter().zip(columns.iter()).enumerate() {
+            let column = match list.get_column(i as i32) {
+                Some(column) => column,
+                None => continue,
+            };
+            let name = name.clone();
+            let config = config.clone();
+            let errors = errors.clone();
+            let changed_rows = changed_rows.clone();
+            let choices = choices.clone();
+            TreeViewColumnExt::set_cell_data_func(
+                &column,
+                cell,
+                Some(Box::new(
+                    move |_: &TreeViewColumn,
+                          cell: &CellRenderer,
+                          model: &TreeModel,
+                          iter: &TreeIter| {
+                        // the rows of the grouped list are not the records, nor indexed like them
+                        let row = model
+                            .get_path(iter)
+                            .filter(|_| model.is::<ListStore>())
+                            .and_then(|path| path.get_indices().first().cloned());
+                        let invalid = row
+                            .map(|row| errors.borrow().contains_key(&(row as usize, i)))
+                            .unwrap_or(false);
+                        let changed = row
+                            .map(|row| changed_rows.borrow().contains(&(row as usize)))
+                            .unwrap_or(false);
+                        let background = if invalid {
+                            ERROR_BACKGROUND
+                        } else {
+                            CHANGED_BACKGROUND
+                        };
+                        let _ = cell.set_property("cell-background", &background);
+                        let _ = cell.set_property("cell-background-set", &(invalid || changed));
+                        if let Some(choices) = &choices[i] {
+                            let value: Option<String> =
+                                model.get_value(iter, i as i32).get().ok().flatten();
+                            let label = value
+                                .and_then(|v| choices.iter().find(|(key, _)| *key == v))
+                                .map(|(_, label)| label);
+                            if let Some(label) = label {
+                                let _ = cell.set_property("text", label);
+                            }
+                            return;
+                        }
+                        let config = config.borrow();
+                        let format = match config.format(&name) {
+                            Some(format) => format,
+                            None => return,
+                        };
+                        let value: Option<String> =
+                            model.get_value(iter, i as i32).get().ok().flatten();
+                        if let Some(value) = value {
+                            let _ = cell.set_property("text", &format.format(&value));
+                        }
+                    },
+                )),
             );
-            store.set_value(&iter, 1, &Value::from(&format!("Cell 2 tab {}", i)));
-            store.set_value(&iter, 2, &Value::from(&true));
-            /*
-            if let Some(path) = model.get_path(&iter) {
-                selection.select_path(&path);
+        }
+    }
+
+    /// the actions of the row and header menus, on the column given as parameter
+    fn init_menu_actions(&self) {
+        self.add_action("copy-row", |this| this.copy_selected_row());
+        self.add_action("duplicate-row", |this| this.duplicate_selected());
+        self.add_action("clear-filter", |this| this.set_filter(None));
+        self.add_action("clear-sort", |this| this.set_order(None));
+        self.add_action("show-all-columns", |this| {
+            for (column, frozen) in this.list_columns.iter().zip(this.frozen_columns.iter()) {
+                if !frozen.get_visible() {
+                    column.set_visible(true);
+                }
+            }
+        });
+        self.add_action("unfreeze-columns", |this| this.set_frozen(0));
+        self.add_column_action("copy-cell", |this, column| this.copy_cell(column));
+        self.add_column_action("open-reference", |this, column| this.open_reference(column));
+        self.add_column_action("open-child", |this, title| this.open_child(title));
+        self.add_column_action("filter-by-value", |this, column| {
+            if let Some(value) = this.selected_value(column) {
+                this.filter_on(column, value);
+            }
+        });
+        self.add_column_action("sort-ascending", |this, column| {
+            this.set_order(Some((column.to_string(), true)))
+        });
+        self.add_column_action("sort-descending", |this, column| {
+            this.set_order(Some((column.to_string(), false)))
+        });
+        self.add_column_action("hide-column", |this, column| this.hide_column(column));
+        self.add_column_action("freeze-columns", |this, column| {
+            let position = this
+                .column_index(column)
+                .and_then(|i| this.visible_columns().iter().position(|c| *c == i));
+            if let Some(position) = position {
+                this.set_frozen(position + 1);
+            }
+        });
+        self.add_column_action("distinct-values", |this, column| {
+            this.show_distinct_values(column)
+        });
+        self.add_column_action("group-by", |this, column| this.add_group_column(column));
+        self.add_choice_action("set-format", |this, column, label| {
+            let format = Format::presets().into_iter().find(|(l, _)| *l == label);
+            if let Some((_, format)) = format {
+                this.set_column_format(column, format);
+            }
+        });
+        self.add_choice_action("set-aggregate", |this, column, label| {
+            let aggregate = Aggregate::all().into_iter().find(|a| a.label() == label);
+            this.set_column_aggregate(column, aggregate);
+        });
+        self.add_action("ungroup", |this| {
+            if !this.confirm_discard() {
+                return;
+            }
+            this.set_group_by(vec![]);
+            this.load(this.page.get());
+        });
+    }
+
+    fn add_column_action<F: Fn(&Self, &str) + 'static>(&self, name: &str, activate: F) {
+        let action = SimpleAction::new(name, Some(glib::VariantTy::new("s").unwrap()));
+        let this = self.clone();
+        action.connect_activate(move |_, parameter| {
+            if let Some(column) = parameter.and_then(|p| p.get::<String>()) {
+                activate(&this, &column);
+            }
+        });
+        self.actions.add_action(&action);
+    }
+
+    /// an action on a column with the label of the choice made for it, ie: its format
+    fn add_choice_action<F: Fn(&Self, &str, &str) + 'static>(&self, name: &str, activate: F) {
+        let action = SimpleAction::new(name, Some(glib::VariantTy::new("(ss)").unwrap()));
+        let this = self.clone();
+        action.connect_activate(move |_, parameter| {
+            if let Some((column, choice)) = parameter.and_then(Self::pair_of) {
+                activate(&this, &column, &choice);
             }
-            */
+        });
+        self.actions.add_action(&action);
+    }
+
+    // glib has no tuple variants yet
+    fn pair_variant(first: &str, second: &str) -> Variant {
+        let children = [first.to_variant(), second.to_variant()];
+        let pointers: Vec<*mut glib_sys::GVariant> =
+            children.iter().map(|c| c.to_glib_none().0).collect();
+        unsafe { from_glib_none(glib_sys::g_variant_new_tuple(pointers.as_ptr(), 2)) }
+    }
+
+    fn pair_of(pair: &Variant) -> Option<(String, String)> {
+        if pair.type_().to_str() != "(ss)" {
+            return None;
         }
-        //tree.set_size_request(200, 600);
-        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
-        scroll.add(&tree);
-        //scroll.set_min_content_height(600);
-        let vbox = gtk::Box::new(Vertical, 0);
-        //vbox.pack_start(&scroll, true, true, 10);
-        let paging = Self::create_paging_buttons_for_list();
-        //vbox.pack_start(&paging, true, true, 10);
-        vbox.add(&scroll);
-        vbox.pack_start(&paging, false, false, 10);
-        vbox
+        let child = |i| -> Variant {
+            unsafe {
+                from_glib_full(glib_sys::g_variant_get_child_value(
+                    pair.to_glib_none().0,
+                    i,
+                ))
+            }
+        };
+        Some((child(0).get()?, child(1).get()?))
     }
 
-    fn create_detail_content() -> gtk::Box {
-        let vbox = gtk::Box::new(Vertical, 0);
-        let hbox = gtk::Box::new(Horizontal, 0);
-        let close_btn = Button::new();
-        let close_image = Image::from_icon_name(Some("window-close"), IconSize::Menu);
-        close_btn.add(&close_image);
+    fn set_action_enabled(&self, name: &str, enabled: bool) {
+        let action = self
+            .actions
+            .lookup_action(name)
+            .and_then(|a| a.downcast::<SimpleAction>().ok());
+        if let Some(action) = action {
+            action.set_enabled(enabled);
+        }
+    }
 
-        vbox.add(&hbox);
-        hbox.pack_end(&close_btn, false, false, 0);
-        let nav_records = Self::create_record_nav_buttons_for_detail();
-        hbox.pack_end(&nav_records, false, false, 5);
-        let grid = Grid::new();
+    fn column_index(&self, column: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c == column)
+    }
 
-        for i in 0..5 {
-            let label = Label::new(Some(&format!("column {}", i + 1)));
-            let entry = Entry::new();
-            entry.set_text(&format!("value {}", (i + 1) * 100));
-            grid.attach(&label, 0, i * 2, 1, 2);
-            grid.attach(&entry, 2, i * 2, 2, 1);
+    /// the store index of the column of the list or of the frozen list
+    fn store_column(&self, column: &TreeViewColumn) -> Option<usize> {
+        self.list_columns
+            .iter()
+            .position(|c| c == column)
+            .or_else(|| self.frozen_columns.iter().position(|c| c == column))
+    }
+
+    /// the table column shown in this list column
+    fn column_name(&self, column: &TreeViewColumn) -> Option<String> {
+        let i = self.store_column(column)?;
+        self.columns.get(i).cloned()
+    }
+
+    fn init_header_menus(&self) {
+        let columns = self.list_columns.iter().chain(self.frozen_columns.iter());
+        for (i, column) in columns.enumerate() {
+            let name = self.columns[i % self.columns.len()].clone();
+            column.set_clickable(true);
+            if let Some(button) = column.get_button() {
+                let this = self.clone();
+                button.connect_button_press_event(move |_, event| {
+                    if event.get_button() == 3 {
+                        let menu = this.create_header_menu(&name);
+                        this.popup_menu(&menu, event);
+                        Inhibit(true)
+                    } else {
+                        Inhibit(false)
+                    }
+                });
+            }
         }
-        vbox.pack_start(&grid, true, true, 10);
-        vbox
     }
 
-    fn create_record_nav_buttons_for_detail() -> gtk::Box {
-        let paging = gtk::Box::new(Horizontal, 0);
-        let prev_icon = Image::from_icon_name(Some("go-previous"), IconSize::SmallToolbar);
-        let prev_button = Button::new();
-        prev_button.add(&prev_icon);
-        paging.pack_start(&prev_button, false, false, 0);
+    /// right clicking a row selects it and puts the cursor on the clicked cell
+    fn init_row_menu(&self) {
+        for list in vec![&self.list, &self.frozen_list] {
+            self.connect_row_menu(list);
+        }
+    }
 
-        let next_icon = Image::from_icon_name(Some("go-next"), IconSize::SmallToolbar);
-        let next_button = Button::new();
-        next_button.add(&next_icon);
-        paging.pack_start(&next_button, false, false, 0);
+    fn connect_row_menu(&self, list: &TreeView) {
+        let this = self.clone();
+        list.connect_button_press_event(move |list, event| {
+            if event.get_button() != 3 {
+                return Inhibit(false);
+            }
+            let (x, y) = event.get_position();
+            let (path, column) = match list.get_path_at_pos(x as i32, y as i32) {
+                Some((Some(path), column, _, _)) => (path, column),
+                _ => return Inhibit(false),
+            };
+            list.grab_focus();
+            // keep a selected range to copy it
+            if !list.get_selection().path_is_selected(&path) {
+                list.set_cursor(&path, column.as_ref(), false);
+            }
+            let name = column.and_then(|c| this.column_name(&c));
+            let menu = this.create_row_menu(name.as_deref());
+            this.popup_menu(&menu, event);
+            Inhibit(true)
+        });
+    }
 
-        paging
+    /// the menu is attached to the list so its items find the `tab.*` actions
+    fn popup_menu(&self, menu: &Menu, event: &gdk::EventButton) {
+        menu.set_property_attach_widget(Some(&self.list));
+        menu.show_all();
+        menu.popup_easy(event.get_button(), event.get_time());
     }
-    //paging
-    fn create_paging_buttons_for_list() -> gtk::Box {
-        let paging = gtk::Box::new(Horizontal, 0);
-        let prev_icon = Image::from_icon_name(Some("go-previous"), IconSize::SmallToolbar);
-        let prev_button = Button::new();
-        prev_button.add(&prev_icon);
-        paging.pack_start(&prev_button, false, false, 0);
 
-        let next_icon = Image::from_icon_name(Some("go-next"), IconSize::SmallToolbar);
-        let next_button = Button::new();
-        next_button.add(&next_icon);
-        paging.pack_start(&next_button, false, false, 0);
+    fn action_item(label: &str, action: &str, column: Option<&str>) -> MenuItem {
+        let item = MenuItem::with_label(label);
+        item.set_action_name(Some(action));
+        if let Some(column) = column {
+            item.set_action_target_value(Some(&column.to_variant()));
+        }
+        item
+    }
+
+    /// an item of the choices of a column, marked when it is the current one
+    fn choice_item(label: &str, action: &str, column: &str, active: bool) -> CheckMenuItem {
+        let item = CheckMenuItem::with_label(label);
+        item.set_draw_as_radio(true);
+        // set before the action, setting it emits activate
+        item.set_active(active);
+        item.set_action_name(Some(action));
+        item.set_action_target_value(Some(&Self::pair_variant(column, label)));
+        item
+    }
+
+    fn create_row_menu(&self, column: Option<&str>) -> Menu {
+        let menu = Menu::new();
+        if let Some(column) = column {
+            menu.append(&Self::action_item(
+                "Copy cell",
+                "tab.copy-cell",
+                Some(column),
+            ));
+        }
+        menu.append(&Self::action_item("Copy row", "tab.copy-row", None));
+        menu.append(&Self::action_item("Copy selection", "tab.copy", None));
+        menu.append(&Self::action_item("Paste", "tab.paste", None));
+        menu.append(&SeparatorMenuItem::new());
+        menu.append(&Self::action_item(
+            "Duplicate row",
+            "tab.duplicate-row",
+            None,
+        ));
+        menu.append(&Self::action_item("Delete", "tab.delete", None));
+        if let Some(column) = column {
+            let references = self
+                .foreign_keys
+                .iter()
+                .any(|fk| fk.columns.iter().any(|c| c == column));
+            self.set_action_enabled("open-reference", references);
+            menu.append(&SeparatorMenuItem::new());
+            menu.append(&Self::action_item(
+                "Open referenced record",
+                "tab.open-reference",
+                Some(column),
+            ));
+            menu.append(&Self::action_item(
+                "Filter by this value",
+                "tab.filter-by-value",
+                Some(column),
+            ));
+        }
+        let children = self.definition.iter().flat_map(|d| d.children.iter());
+        for (i, child) in children.enumerate() {
+            if i == 0 {
+                menu.append(&SeparatorMenuItem::new());
+            }
+            menu.append(&Self::action_item(
+                &format!("Open {}", child.title),
+                "tab.open-child",
+                Some(&child.title),
+            ));
+        }
+        if self.query.borrow().filter.is_some() {
+            menu.append(&Self::action_item("Clear filter", "tab.clear-filter", None));
+        }
+        menu
+    }
+
+    fn create_header_menu(&self, column: &str) -> Menu {
+        let menu = Menu::new();
+        let sort_items = vec![
+            ("Sort ascending", "tab.sort-ascending"),
+            ("Sort descending", "tab.sort-descending"),
+        ];
+        for (label, action) in sort_items {
+            menu.append(&Self::action_item(label, action, Some(column)));
+        }
+        if self.query.borrow().order_by.is_some() {
+            menu.append(&Self::action_item("Clear sort", "tab.clear-sort", None));
+        }
+        menu.append(&SeparatorMenuItem::new());
+        menu.append(&Self::action_item(
+            "Hide column",
+            "tab.hide-column",
+            Some(column),
+        ));
+        menu.append(&Self::action_item(
+            "Choose columns\u{2026}",
+            "tab.choose-columns",
+            None,
+        ));
+        if (0..self.columns.len()).any(|i| !self.is_shown(i)) {
+            menu.append(&Self::action_item(
+                "Show all columns",
+                "tab.show-all-columns",
+                None,
+            ));
+        }
+        menu.append(&Self::action_item(
+            "Freeze columns up to here",
+            "tab.freeze-columns",
+            Some(column),
+        ));
+        if self.frozen.get() > 0 {
+            menu.append(&Self::action_item(
+                "Unfreeze columns",
+                "tab.unfreeze-columns",
+                None,
+            ));
+        }
+        menu.append(&Self::action_item(
+            "Group by this column",
+            "tab.group-by",
+            Some(column),
+        ));
+        if self.is_grouped() {
+            menu.append(&Self::action_item("Ungroup", "tab.ungroup", None));
+        }
+        menu.append(&SeparatorMenuItem::new());
+        let format_item = MenuItem::with_label("Format");
+        let formats = Menu::new();
+        let current = self.config.borrow().format(column).cloned();
+        for (label, format) in Format::presets() {
+            let active = format == current;
+            formats.append(&Self::choice_item(label, "tab.set-format", column, active));
+        }
+        format_item.set_submenu(Some(&formats));
+        menu.append(&format_item);
+        menu.append(&self.create_aggregate_item(column));
+        menu.append(&Self::action_item(
+            "Show distinct values",
+            "tab.distinct-values",
+            Some(column),
+        ));
+        menu
+    }
+
+    fn set_column_format(&self, column: &str, format: Option<Format>) {
+        let mut config = self.config.borrow_mut();
+        config.set_format(column, format);
+        self.save_config(&config);
+        self.list.queue_draw();
+    }
+
+    /// the aggregates that apply to the column, sum and average only on numbers
+    fn create_aggregate_item(&self, column: &str) -> MenuItem {
+        let data_type = self
+            .column_index(column)
+            .map(|i| self.fields[i].data_type().to_string())
+            .unwrap_or_default();
+        // all of them when the type is not known
+        let numeric = data_type.is_empty() || catalog::is_numeric(&data_type);
+        let aggregate_item = MenuItem::with_label("Aggregate");
+        let aggregates = Menu::new();
+        let current = self.config.borrow().aggregate(column);
+        let choices = Aggregate::all()
+            .into_iter()
+            .filter(|a| numeric || !a.numeric_only())
+            .map(|a| (a.label(), Some(a)));
+        for (label, aggregate) in vec![("None", None)].into_iter().chain(choices) {
+            let active = aggregate == current;
+            aggregates.append(&Self::choice_item(
+                label,
+                "tab.set-aggregate",
+                column,
+                active,
+            ));
+        }
+        aggregate_item.set_submenu(Some(&aggregates));
+        aggregate_item
+    }
 
-        paging
+    fn set_column_aggregate(&self, column: &str, aggregate: Option<Aggregate>) {
+        {
+            let mut config = self.config.borrow_mut();
+            config.set_aggregate(column, aggregate);
+            self.save_config(&config);
+        }
+        self.update_footer();
+    }
+
+    /// the columns that have an aggregate, in the order of the table
+    fn aggregates(&self) -> Vec<(String, Aggregate)> {
+        let config = self.config.borrow();
+        self.columns
+            .iter()
+            .filter_map(|c| Some((c.to_string(), config.aggregate(c)?)))
+            .collect()
+    }
+
+    /// the value of each aggregate, on all the rows matching the filter,
+    /// or on the loaded rows when not connected
+    fn compute_aggregates(&self, aggregates: &[(String, Aggregate)]) -> Vec<Value> {
+        if let Some(db) = self.db.borrow_mut().as_mut() {
+            let filter = self.query.borrow().filter.clone();
+            let (from, params) = self.from_clause(db.platform());
+            let sql = aggregate::query(db.platform(), &from, filter.as_deref(), aggregates);
+            return match db.execute(&sql, &params) {
+                Ok(result) => result.rows().rows.into_iter().next().unwrap_or_default(),
+                Err(e) => {
+                    println!("unable to compute the aggregates of {}: {}", self.table, e);
+                    vec![]
+                }
+            };
+        }
+        let records = self.records.borrow();
+        aggregates
+            .iter()
+            .map(|(column, aggregate)| {
+                let i = self.column_index(column)?;
+                let values: Vec<Value> = records.iter().map(|r| r.values[i].clone()).collect();
+                aggregate.compute(&values)
+            })
+            .collect()
+    }
+
+    /// show the aggregates chosen for the columns, in the format of the column
+    fn update_footer(&self) {
+        for child in self.footer.get_children() {
+            self.footer.remove(&child);
+        }
+        let aggregates = self.aggregates();
+        self.footer.set_visible(!aggregates.is_empty());
+        if aggregates.is_empty() {
+            return;
+        }
+        let values = self.compute_aggregates(&aggregates);
+        for (i, (column, aggregate)) in aggregates.iter().enumerate() {
+            let value = values.get(i).cloned().flatten();
+            let label = Label::new(Some(&self.aggregate_text(column, *aggregate, value)));
+            label.set_selectable(true);
+            self.footer.add(&label);
+            label.show();
+        }
+    }
+
+    /// ie: "Sum of total: $1,200.00", in the format of the column
+    fn aggregate_text(&self, column: &str, aggregate: Aggregate, value: Value) -> String {
+        let config = self.config.borrow();
+        let text = match (value, config.format(column)) {
+            (None, _) => String::new(),
+            (Some(value), _) if aggregate == Aggregate::Count => value,
+            (Some(value), Some(format)) => format.format(&value),
+            // the average has more decimals than anyone wants to read
+            (Some(value), None) if aggregate == Aggregate::Avg => value
+                .parse::<f64>()
+                .map(|avg| format!("{:.2}", avg))
+                .unwrap_or(value),
+            (Some(value), None) => value,
+        };
+        format!("{} of {}: {}", aggregate.label(), column, text)
+    }
+
+    /// show the view that was last used on this table
+    fn init_views(&self) {
+        self.fill_views();
+        let this = self.clone();
+        self.toolbar.views.connect_changed(move |views| {
+            if this.filling_views.get() {
+                return;
+            }
+            let name = views
+                .get_active_id()
+                .map(|id| id.to_string())
+                .filter(|id| id != ALL_COLUMNS);
+            this.switch_view(name.as_deref());
+        });
+        let view = self.config.borrow().view.clone();
+        if let Some(name) = view {
+            self.switch_view(Some(&name));
+        }
+    }
+
+    /// put the saved views in the toolbar, the current one active
+    fn fill_views(&self) {
+        self.filling_views.set(true);
+        let views = &self.toolbar.views;
+        views.remove_all();
+        views.append(Some(ALL_COLUMNS), "All columns");
+        let config = self.config.borrow();
+        for name in config.views.keys() {
+            views.append(Some(name), name);
+        }
+        let active = config
+            .view
+            .as_deref()
+            .filter(|name| config.views.contains_key(*name));
+        views.set_active_id(Some(active.unwrap_or(ALL_COLUMNS)));
+        self.filling_views.set(false);
+    }
+
+    /// show a saved view, or all the columns when `None`, and remember it for the next time
+    fn switch_view(&self, name: Option<&str>) {
+        if !self.confirm_discard() {
+            // back to the view still shown, a view just saved or deleted is kept
+            self.save_config(&self.config.borrow());
+            self.fill_views();
+            return;
+        }
+        let view = {
+            let mut config = self.config.borrow_mut();
+            let view = name.and_then(|name| config.views.get(name).cloned());
+            config.view = view.as_ref().and(name.map(String::from));
+            self.save_config(&config);
+            view
+        };
+        self.fill_views();
+        self.apply_view(&view.unwrap_or_default());
+    }
+
+    /// lay out the columns, sort and filter the rows as in the view
+    fn apply_view(&self, view: &ViewConfig) {
+        self.set_layout(&view.columns);
+        self.set_frozen(view.frozen);
+        self.set_group_by(view.group_by.clone());
+        let filter = view.filter.clone();
+        match &filter {
+            Some(filter) => self.status.set_text(&format!("Filtered on {}", filter)),
+            None => self.status.set_text(""),
+        }
+        self.query.borrow_mut().filter = filter;
+        self.sort_rows(view.sort.clone().map(|sort| (sort, !view.descending)));
+    }
+
+    /// show these columns in this order, all the columns when empty
+    fn set_layout(&self, columns: &[ViewColumn]) {
+        self.set_frozen(0);
+        let layout: Vec<ViewColumn> = if columns.is_empty() {
+            self.columns
+                .iter()
+                .map(|name| ViewColumn {
+                    name: name.to_string(),
+                    width: None,
+                })
+                .collect()
+        } else {
+            columns.to_vec()
+        };
+        let mut previous: Option<TreeViewColumn> = None;
+        for column in layout.iter() {
+            let list_column = match self.column_index(&column.name) {
+                Some(i) => &self.list_columns[i],
+                None => continue,
+            };
+            self.list.move_column_after(list_column, previous.as_ref());
+            list_column.set_visible(true);
+            list_column.set_reorderable(true);
+            match column.width {
+                Some(width) => {
+                    list_column.set_sizing(TreeViewColumnSizing::Fixed);
+                    list_column.set_fixed_width(width);
+                }
+                None => {
+                    list_column.set_sizing(TreeViewColumnSizing::GrowOnly);
+                    list_column.set_fixed_width(-1);
+                }
+            }
+            previous = Some(list_column.clone());
+        }
+        for (name, list_column) in self.columns.iter().zip(self.list_columns.iter()) {
+            if !layout.iter().any(|c| &c.name == name) {
+                list_column.set_visible(false);
+            }
+        }
+    }
+
+    /// the columns as they are shown now, with the sort and filter of the rows
+    fn current_view(&self) -> ViewConfig {
+        let columns = self
+            .visible_columns()
+            .into_iter()
+            .map(|i| {
+                let width = Some(self.shown_column(i).get_fixed_width()).filter(|w| *w > 0);
+                ViewColumn {
+                    name: self.columns[i].to_string(),
+                    width: width,
+                }
+            })
+            .collect();
+        let query = self.query.borrow();
+        ViewConfig {
+            sort: query
+                .order_by
+                .as_ref()
+                .map(|(column, _)| column.to_string()),
+            descending: query.order_by.as_ref().map(|(_, asc)| !asc) == Some(true),
+            filter: query.filter.clone(),
+            frozen: self.frozen.get(),
+            group_by: self.group_by.borrow().clone(),
+            columns: columns,
+        }
+    }
+
+    fn choose_columns(&self) {
+        let window = self.window();
+        let view_name = self.config.borrow().view.clone();
+        let chooser = ColumnChooser::new(
+            window.as_ref(),
+            &self.columns,
+            &self.current_view(),
+            view_name.as_deref(),
+        );
+        match chooser.run() {
+            Some(ChooserResponse::Apply(view)) if self.confirm_discard() => self.apply_view(&view),
+            Some(ChooserResponse::Apply(_)) => (),
+            Some(ChooserResponse::Save(name, view)) => {
+                self.config.borrow_mut().views.insert(name.clone(), view);
+                self.switch_view(Some(&name));
+            }
+            Some(ChooserResponse::Delete(name)) => {
+                self.config.borrow_mut().views.remove(&name);
+                self.switch_view(None);
+            }
+            None => (),
+        }
+    }
+
+    /// the config of a datawindow comes from its definition, the changes to it are not saved
+    fn save_config(&self, config: &TableConfig) {
+        if self.definition.is_some() {
+            return;
+        }
+        if let Err(e) = config.save(&self.table) {
+            println!("unable to save the config of {}: {}", self.table, e);
+        }
+    }
+
+    /// the window of the tab, the parent of its dialogs
+    fn window(&self) -> Option<Window> {
+        self.list
+            .get_toplevel()
+            .and_then(|w| w.downcast::<Window>().ok())
+    }
+
+    fn platform(&self) -> Platform {
+        match self.db.borrow().as_ref() {
+            Some(db) => db.platform(),
+            None => Platform::Postgres,
+        }
+    }
+
+    /// whether the saves are kept in the open transaction of the manual commit mode
+    fn in_transaction(&self) -> bool {
+        match self.db.borrow().as_ref() {
+            Some(db) => db.session().transaction.is_some(),
+            None => false,
+        }
+    }
+
+    fn selected_value(&self, column: &str) -> Option<Value> {
+        let row = self.selected_row()?;
+        let i = self.column_index(column)?;
+        self.records.borrow().get(row).map(|r| r.values[i].clone())
+    }
+
+    fn copy_to_clipboard(&self, text: &str) {
+        Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(text);
+    }
+
+    fn copy_cell(&self, column: &str) {
+        if let Some(value) = self.selected_value(column) {
+            self.copy_to_clipboard(value.as_deref().unwrap_or(""));
+        }
+    }
+
+    fn copy_selected_row(&self) {
+        let row = match self.selected_row() {
+            Some(row) => row,
+            None => return,
+        };
+        let values = match self.records.borrow().get(row) {
+            Some(record) => record.values.clone(),
+            None => return,
+        };
+        self.copy_to_clipboard(&clipboard::to_tsv(&[values]));
+    }
+
+    /// a new record with the values of the selected one, except its primary key
+    fn duplicate_selected(&self) {
+        let values = match self.selected_row() {
+            Some(row) => self.records.borrow()[row].values.clone(),
+            None => return,
+        };
+        let mut record = Record::blank(self.columns.len());
+        for (i, column) in self.columns.iter().enumerate() {
+            if !self.key.contains(column) {
+                record.values[i] = values[i].clone();
+            }
+        }
+        self.push_undo(None);
+        self.append_record(record);
+        self.select_row(self.records.borrow().len() - 1);
+    }
+
+    /// add a record at the end of the list
+    fn append_record(&self, record: Record) {
+        let iter = self.store.append();
+        for (i, value) in record.values.iter().enumerate() {
+            self.store
+                .set_value(&iter, i as u32, &value.as_deref().to_value());
+        }
+        self.records.borrow_mut().push(record);
+    }
+
+    /// only show the rows with this value in the column, on top of the current filter
+    fn filter_on(&self, column: &str, value: Value) {
+        let condition = edit::literal_condition(self.platform(), &[column.to_string()], &[value]);
+        let filter = match self.query.borrow().filter.clone() {
+            Some(filter) => format!("({}) AND {}", filter, condition),
+            None => condition,
+        };
+        self.set_filter(Some(&filter));
+    }
+
+    fn set_order(&self, order_by: Option<(String, bool)>) {
+        if self.confirm_discard() {
+            self.sort_rows(order_by);
+        }
+    }
+
+    /// show the sort of the columns and read the rows again in this order
+    fn sort_rows(&self, order_by: Option<(String, bool)>) {
+        let sorted = order_by
+            .as_ref()
+            .and_then(|(column, ascending)| Some((self.column_index(column)?, *ascending)));
+        let columns = self.list_columns.iter().zip(self.frozen_columns.iter());
+        for (i, (list_column, frozen_column)) in columns.enumerate() {
+            for column in vec![list_column, frozen_column] {
+                match sorted {
+                    Some((index, ascending)) if index == i => {
+                        column.set_sort_indicator(true);
+                        column.set_sort_order(if ascending {
+                            SortType::Ascending
+                        } else {
+                            SortType::Descending
+                        });
+                    }
+                    _ => column.set_sort_indicator(false),
+                }
+            }
+        }
+        self.query.borrow_mut().order_by = order_by;
+        self.load(0);
+    }
+
+    /// whether the column is shown, in the list or in the frozen list
+    fn is_shown(&self, i: usize) -> bool {
+        self.list_columns[i].get_visible() || self.frozen_columns[i].get_visible()
+    }
+
+    /// the list column or the frozen list column that shows this store column
+    fn shown_column(&self, i: usize) -> &TreeViewColumn {
+        if self.frozen_columns[i].get_visible() {
+            &self.frozen_columns[i]
+        } else {
+            &self.list_columns[i]
+        }
+    }
+
+    fn hide_column(&self, column: &str) {
+        let i = match self.column_index(column) {
+            Some(i) => i,
+            None => return,
+        };
+        if self.frozen_columns[i].get_visible() {
+            self.frozen_columns[i].set_visible(false);
+            self.set_frozen(self.frozen.get().saturating_sub(1));
+        } else {
+            self.list_columns[i].set_visible(false);
+        }
+    }
+
+    /// Pin the first `n` visible columns on the left, in the frozen list which does not
+    /// scroll sideways. The columns keep their width when they move between the lists.
+    fn set_frozen(&self, n: usize) {
+        let visible = self.visible_columns();
+        let n = n.min(visible.len());
+        let mut previous_frozen: Option<&TreeViewColumn> = None;
+        let mut previous: Option<&TreeViewColumn> = None;
+        for (position, i) in visible.into_iter().enumerate() {
+            let (frozen_column, list_column) = (&self.frozen_columns[i], &self.list_columns[i]);
+            let pinned = position < n;
+            let (from, to) = match pinned {
+                true => (list_column, frozen_column),
+                false => (frozen_column, list_column),
+            };
+            if from.get_visible() {
+                to.set_sizing(from.get_sizing());
+                to.set_fixed_width(from.get_fixed_width());
+            }
+            if pinned {
+                self.frozen_list
+                    .move_column_after(frozen_column, previous_frozen);
+                previous_frozen = Some(frozen_column);
+            }
+            // the columns are shown in the same order when they are unfrozen
+            self.list.move_column_after(list_column, previous);
+            previous = Some(list_column);
+            frozen_column.set_visible(pinned);
+            list_column.set_visible(!pinned);
+        }
+        self.frozen.set(n);
+        if let Some(scroll) = self.frozen_list.get_parent() {
+            scroll.set_visible(n > 0);
+        }
+    }
+
+    /// open the table referenced by the foreign key of this column on the selected record
+    fn open_reference(&self, column: &str) {
+        let row = match self.selected_row() {
+            Some(row) => row,
+            None => return,
+        };
+        let fk = match self
+            .foreign_keys
+            .iter()
+            .find(|fk| fk.columns.iter().any(|c| c == column))
+        {
+            Some(fk) => fk,
+            None => return,
+        };
+        let values: Option<Vec<Value>> = {
+            let records = self.records.borrow();
+            fk.columns
+                .iter()
+                .map(|c| self.column_index(c).map(|i| records[row].values[i].clone()))
+                .collect()
+        };
+        let values = match values {
+            Some(values) if values.iter().any(|v| v.is_some()) => values,
+            _ => {
+                self.status.set_text("No referenced record");
+                return;
+            }
+        };
+        let condition = edit::literal_condition(self.platform(), &fk.ref_columns, &values);
+        if let Some(open_table) = self.open_table.borrow().as_ref() {
+            open_table(&fk.ref_table, &condition);
+        }
+    }
+
+    /// open the rows of a child table of the datawindow that belong to the selected record
+    fn open_child(&self, title: &str) {
+        let child = match self
+            .definition
+            .iter()
+            .flat_map(|d| d.children.iter())
+            .find(|c| c.title == title)
+        {
+            Some(child) => child,
+            None => return,
+        };
+        let row = match self.selected_row() {
+            Some(row) => row,
+            None => return,
+        };
+        let values: Option<Vec<Value>> = {
+            let records = self.records.borrow();
+            child
+                .link
+                .values()
+                .map(|c| self.column_index(c).map(|i| records[row].values[i].clone()))
+                .collect()
+        };
+        let values = match values {
+            Some(values) => values,
+            None => {
+                println!("the link of {} is not on columns of {}", title, self.table);
+                return;
+            }
+        };
+        let columns: Vec<String> = child.link.keys().cloned().collect();
+        let condition = edit::literal_condition(self.platform(), &columns, &values);
+        if let Some(open_table) = self.open_table.borrow().as_ref() {
+            open_table(&child.table, &condition);
+        }
+    }
+
+    /// `open_table` is called with a table and the condition of the rows to show
+    pub fn connect_open_table<F: Fn(&str, &str) + 'static>(&self, open_table: F) {
+        *self.open_table.borrow_mut() = Some(Box::new(open_table));
+    }
+
+    /// the most frequent values of the column with their count, over the filtered rows
+    fn distinct_values(&self, column: &str) -> ResultSet {
+        let filter = self.query.borrow().filter.clone();
+        let mut db = self.db.borrow_mut();
+        let db = match db.as_mut() {
+            Some(db) => db,
+            None => return self.demo_distinct_values(column),
+        };
+        let platform = db.platform();
+        let condition = match filter {
+            Some(filter) => format!(" WHERE {}", filter),
+            None => String::new(),
+        };
+        let (from, params) = self.from_clause(platform);
+        let sql = format!(
+            "SELECT {0}, COUNT(*) AS count FROM {1}{2} GROUP BY {0} ORDER BY 2 DESC LIMIT {3}",
+            platform.quote_ident(column),
+            from,
+            condition,
+            DISTINCT_LIMIT
+        );
+        match db.execute(&sql, &params) {
+            Ok(result) => result.rows(),
+            Err(e) => {
+                println!("unable to read the values of {}: {}", column, e);
+                ResultSet::default()
+            }
+        }
+    }
+
+    /// when not connected the values are counted in the loaded rows
+    fn demo_distinct_values(&self, column: &str) -> ResultSet {
+        let i = match self.column_index(column) {
+            Some(i) => i,
+            None => return ResultSet::default(),
+        };
+        let mut counts: Vec<(Value, usize)> = vec![];
+        for record in self.records.borrow().iter() {
+            match counts.iter_mut().find(|(v, _)| *v == record.values[i]) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((record.values[i].clone(), 1)),
+            }
+        }
+        counts.sort_by(|a, b| b.1.cmp(&a.1));
+        ResultSet {
+            columns: vec![column.to_string(), "count".to_string()],
+            rows: counts
+                .into_iter()
+                .take(DISTINCT_LIMIT)
+                .map(|(value, count)| vec![value, Some(count.to_string())])
+                .collect(),
+        }
+    }
+
+    /// a dialog with the distinct values, activating one filters the rows on it
+    fn show_distinct_values(&self, column: &str) {
+        let rs = self.distinct_values(column);
+        let window = self.window();
+        let dialog = Dialog::with_buttons(
+            Some(&format!("Distinct values of {}", column)),
+            window.as_ref(),
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            &[("Close", ResponseType::Close)],
+        );
+        let (list, _, _) = Self::create_result_list(&rs);
+        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scroll.add(&list);
+        scroll.set_size_request(350, 400);
+        dialog.get_content_area().pack_start(&scroll, true, true, 0);
+        let chosen: Rc<Cell<Option<usize>>> = Rc::new(Cell::new(None));
+        let chosen_row = chosen.clone();
+        let dialog_ref = dialog.clone();
+        list.connect_row_activated(move |_, path, _| {
+            chosen_row.set(path.get_indices().first().map(|row| *row as usize));
+            dialog_ref.response(ResponseType::Apply);
+        });
+        dialog.show_all();
+        let response = dialog.run();
+        dialog.close();
+        if response != ResponseType::Apply {
+            return;
+        }
+        if let Some(row) = chosen.get().and_then(|row| rs.rows.get(row)) {
+            self.filter_on(column, row[0].clone());
+        }
+    }
+
+    /// drag a column header onto the group bar to group by that column
+    fn init_grouping(&self) {
+        let targets = vec![TargetEntry::new(COLUMN_TARGET, TargetFlags::SAME_APP, 0)];
+        for (column, name) in self.list_columns.iter().zip(self.columns.iter()) {
+            if let Some(button) = column.get_button() {
+                button.drag_source_set(
+                    gdk::ModifierType::BUTTON1_MASK,
+                    &targets,
+                    gdk::DragAction::COPY,
+                );
+                let name = name.clone();
+                button.connect_drag_data_get(move |_, _, data, _, _| {
+                    data.set_text(&name);
+                });
+            }
+        }
+        self.group_bar
+            .drag_dest_set(DestDefaults::ALL, &targets, gdk::DragAction::COPY);
+        let this = self.clone();
+        self.group_bar
+            .connect_drag_data_received(move |_, _, _, _, data, _, _| {
+                if let Some(column) = data.get_text() {
+                    this.add_group_column(&column);
+                }
+            });
+        let this = self.clone();
+        self.list.connect_test_expand_row(move |_, iter, _| {
+            if this.is_grouped() {
+                this.expand_group(iter);
+            }
+            Inhibit(false)
+        });
+        let this = self.clone();
+        self.list.connect_row_activated(move |_, path, _| {
+            if this.is_grouped() {
+                this.load_more(path);
+            }
+        });
+        self.update_group_bar();
+    }
+
+    fn is_grouped(&self) -> bool {
+        !self.group_by.borrow().is_empty()
+    }
+
+    /// Group the rows by these columns, or show the plain list when empty.
+    /// The groups are read only, they are loaded by `load`.
+    fn set_group_by(&self, columns: Vec<String>) {
+        let columns: Vec<String> = columns
+            .into_iter()
+            .filter(|c| self.columns.contains(c))
+            .collect();
+        let grouped = !columns.is_empty();
+        *self.group_by.borrow_mut() = columns;
+        if grouped {
+            self.set_frozen(0);
+            self.errors.borrow_mut().clear();
+            self.list.set_model(Some(&self.group_store));
+        } else {
+            self.list.set_model(Some(&self.store));
+            self.group_store.clear();
+            self.groups.borrow_mut().clear();
+        }
+        for (i, cell) in self.cells.iter().enumerate() {
+            cell.set_property_editable(!grouped && self.is_editable(i));
+        }
+        let editing = vec![
+            "new",
+            "paste",
+            "delete",
+            "duplicate-row",
+            "edit",
+            "undo",
+            "freeze-columns",
+            "previous-page",
+            "next-page",
+        ];
+        for name in editing {
+            self.set_action_enabled(name, !grouped && self.allows_action(name));
+        }
+        self.update_group_bar();
+    }
+
+    fn add_group_column(&self, column: &str) {
+        let mut columns = self.group_by.borrow().clone();
+        if columns.iter().any(|c| c == column) || !self.confirm_discard() {
+            return;
+        }
+        columns.push(column.to_string());
+        self.set_group_by(columns);
+        self.load(0);
+    }
+
+    fn remove_group_column(&self, column: &str) {
+        if !self.confirm_discard() {
+            return;
+        }
+        let mut columns = self.group_by.borrow().clone();
+        columns.retain(|c| c != column);
+        self.set_group_by(columns);
+        self.load(self.page.get());
+    }
+
+    /// a button for each group column, which stops grouping by it, or a hint when not grouped
+    fn update_group_bar(&self) {
+        for child in self.group_bar.get_children() {
+            self.group_bar.remove(&child);
+        }
+        let group_by = self.group_by.borrow().clone();
+        if group_by.is_empty() {
+            let hint = Label::new(Some("Drag a column header here to group by it"));
+            hint.set_sensitive(false);
+            self.group_bar.add(&hint);
+        }
+        for column in group_by {
+            let button = Button::with_label(&format!("{} \u{2715}", column));
+            button.set_tooltip_text(Some("Stop grouping by this column"));
+            let this = self.clone();
+            button.connect_clicked(move |_| this.remove_group_column(&column));
+            self.group_bar.add(&button);
+        }
+        self.group_bar.show_all();
+    }
+
+    /// the top level groups, collapsed
+    fn reload_groups(&self) {
+        self.group_store.clear();
+        self.groups.borrow_mut().clear();
+        self.fill_group(None, &[]);
+    }
+
+    /// load the rows or the groups of a group the first time it is expanded
+    fn expand_group(&self, iter: &TreeIter) {
+        let id: i32 = self
+            .group_store
+            .get_value(iter, self.columns.len() as i32)
+            .get_some()
+            .unwrap_or(-1);
+        if id < 0 {
+            return;
+        }
+        let conditions = {
+            let mut groups = self.groups.borrow_mut();
+            match groups.get_mut(id as usize) {
+                Some(group) if !group.loaded => {
+                    group.loaded = true;
+                    group.conditions.clone()
+                }
+                _ => return,
+            }
+        };
+        if let Some(placeholder) = self.group_store.iter_children(Some(iter)) {
+            self.group_store.remove(&placeholder);
+        }
+        self.fill_group(Some(iter), &conditions);
+    }
+
+    /// Put the groups of the next group column under the parent, or the rows at the last level.
+    /// A group row shows its value and count in the group column, and the subtotals
+    /// in the columns that have an aggregate.
+    fn fill_group(&self, parent: Option<&TreeIter>, conditions: &[(String, Value)]) {
+        let column = self.group_by.borrow().get(conditions.len()).cloned();
+        let (column, position) =
+            match column.and_then(|c| Some((c.clone(), self.column_index(&c)?))) {
+                Some(found) => found,
+                None => return self.fill_rows(parent, conditions, 0),
+            };
+        let aggregates = self.aggregates();
+        for (value, count, subtotals) in self.load_groups(conditions, &column, &aggregates) {
+            let mut row: Vec<Value> = vec![None; self.columns.len()];
+            for ((aggregated, _), subtotal) in aggregates.iter().zip(subtotals) {
+                if let Some(i) = self.column_index(aggregated) {
+                    row[i] = subtotal;
+                }
+            }
+            row[position] = Some(format!(
+                "{} ({})",
+                value.as_deref().unwrap_or("(null)"),
+                count
+            ));
+            let mut group_conditions = conditions.to_vec();
+            group_conditions.push((column.clone(), value));
+            let id = {
+                let mut groups = self.groups.borrow_mut();
+                groups.push(Group {
+                    conditions: group_conditions,
+                    loaded: false,
+                    more: None,
+                });
+                groups.len() - 1
+            };
+            let iter = self.group_store.append(parent);
+            self.set_group_values(&iter, &row, id as i32);
+            // so the group can be expanded, replaced by its children when it is
+            let placeholder = self.group_store.append(Some(&iter));
+            self.set_group_values(&placeholder, &[], -1);
+        }
+    }
+
+    /// Put the rows of the group under the parent from this offset, at most `GROUP_ROWS` of them,
+    /// followed by a "more…" row when there are others.
+    fn fill_rows(&self, parent: Option<&TreeIter>, conditions: &[(String, Value)], offset: usize) {
+        let rows = self.load_group_rows(conditions, Some(GROUP_ROWS + 1), offset);
+        for row in rows.iter().take(GROUP_ROWS) {
+            let iter = self.group_store.append(parent);
+            self.set_group_values(&iter, row, -1);
+        }
+        if rows.len() <= GROUP_ROWS {
+            return;
+        }
+        let id = {
+            let mut groups = self.groups.borrow_mut();
+            groups.push(Group {
+                conditions: conditions.to_vec(),
+                loaded: true,
+                more: Some(offset + GROUP_ROWS),
+            });
+            groups.len() - 1
+        };
+        let mut row: Vec<Value> = vec![None; self.columns.len()];
+        let shown = self
+            .list
+            .get_columns()
+            .into_iter()
+            .find(|c| c.get_visible());
+        if let Some(i) = shown.and_then(|c| self.store_column(&c)) {
+            row[i] = Some("more\u{2026}".to_string());
+        }
+        let iter = self.group_store.append(parent);
+        self.set_group_values(&iter, &row, id as i32);
+    }
+
+    /// replace the activated "more…" row by the next rows of its group
+    fn load_more(&self, path: &TreePath) {
+        let iter = match self.group_store.get_iter(path) {
+            Some(iter) => iter,
+            None => return,
+        };
+        let id: i32 = self
+            .group_store
+            .get_value(&iter, self.columns.len() as i32)
+            .get_some()
+            .unwrap_or(-1);
+        if id < 0 {
+            return;
+        }
+        let (conditions, offset) = match self.groups.borrow().get(id as usize) {
+            Some(Group {
+                conditions,
+                more: Some(offset),
+                ..
+            }) => (conditions.clone(), *offset),
+            _ => return,
+        };
+        let parent = self.group_store.iter_parent(&iter);
+        self.group_store.remove(&iter);
+        self.fill_rows(parent.as_ref(), &conditions, offset);
+    }
+
+    fn set_group_values(&self, iter: &TreeIter, values: &[Value], group: i32) {
+        for (i, value) in values.iter().enumerate() {
+            self.group_store
+                .set_value(iter, i as u32, &value.as_deref().to_value());
+        }
+        self.group_store
+            .set_value(iter, self.columns.len() as u32, &group.to_value());
+    }
+
+    /// the condition of the rows of a group, within the filter of the list
+    fn group_filter(&self, conditions: &[(String, Value)]) -> Option<String> {
+        let filter = self.query.borrow().filter.clone();
+        if conditions.is_empty() {
+            return filter;
+        }
+        let (columns, values): (Vec<String>, Vec<Value>) = conditions.iter().cloned().unzip();
+        let condition = edit::literal_condition(self.platform(), &columns, &values);
+        Some(match filter {
+            Some(filter) => format!("({}) AND {}", filter, condition),
+            None => condition,
+        })
+    }
+
+    /// the values of the column in the group with their count and the subtotals,
+    /// computed on the loaded rows when not connected
+    fn load_groups(
+        &self,
+        conditions: &[(String, Value)],
+        column: &str,
+        aggregates: &[(String, Aggregate)],
+    ) -> Vec<(Value, usize, Vec<Value>)> {
+        let filter = self.group_filter(conditions);
+        if let Some(db) = self.db.borrow_mut().as_mut() {
+            let (from, params) = self.from_clause(db.platform());
+            let sql =
+                aggregate::group_query(db.platform(), &from, filter.as_deref(), column, aggregates);
+            return match db.execute(&sql, &params) {
+                Ok(result) => result
+                    .rows()
+                    .rows
+                    .into_iter()
+                    .map(|mut row| {
+                        let value = row.remove(0);
+                        let count = row.remove(0).and_then(|c| c.parse().ok()).unwrap_or(0);
+                        (value, count, row)
+                    })
+                    .collect(),
+                Err(e) => {
+                    println!("unable to group {} by {}: {}", self.table, column, e);
+                    vec![]
+                }
+            };
+        }
+        let i = match self.column_index(column) {
+            Some(i) => i,
+            None => return vec![],
+        };
+        let mut groups: BTreeMap<Value, Vec<Vec<Value>>> = BTreeMap::new();
+        for row in self.load_group_rows(conditions, None, 0) {
+            groups.entry(row[i].clone()).or_default().push(row);
+        }
+        groups
+            .into_iter()
+            .map(|(value, rows)| {
+                let subtotals = aggregates
+                    .iter()
+                    .map(|(aggregated, aggregate)| {
+                        let j = self.column_index(aggregated)?;
+                        let values: Vec<Value> = rows.iter().map(|r| r[j].clone()).collect();
+                        aggregate.compute(&values)
+                    })
+                    .collect();
+                (value, rows.len(), subtotals)
+            })
+            .collect()
+    }
+
+    /// the rows of the group in the order of the list, the loaded ones when not connected
+    fn load_group_rows(
+        &self,
+        conditions: &[(String, Value)],
+        limit: Option<usize>,
+        offset: usize,
+    ) -> Vec<Vec<Value>> {
+        let query = PageQuery {
+            filter: self.group_filter(conditions),
+            order_by: self.query.borrow().order_by.clone(),
+        };
+        if let Some(db) = self.db.borrow_mut().as_mut() {
+            let (from, params) = self.from_clause(db.platform());
+            return Self::select_rows(db.as_mut(), &from, &params, &query, limit, offset).rows;
+        }
+        let indexes: Vec<(Option<usize>, &Value)> = conditions
+            .iter()
+            .map(|(column, value)| (self.column_index(column), value))
+            .collect();
+        self.records
+            .borrow()
+            .iter()
+            .filter(|r| {
+                indexes
+                    .iter()
+                    .all(|(i, value)| i.map(|i| &r.values[i] == *value) == Some(true))
+            })
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|r| r.values.clone())
+            .collect()
+    }
+
+    /// the list with its visible columns, or the detail of each loaded record
+    fn report(&self) -> Rc<dyn Report> {
+        match self.view_mode() {
+            ViewMode::Detail => Rc::new(self.form_report()),
+            _ => Rc::new(self.list_report()),
+        }
+    }
+
+    /// the value as it is displayed, in the format of the column
+    fn display_value(&self, column: usize, value: &Value) -> String {
+        let value = value.as_deref().unwrap_or("");
+        let choice = self.choices[column]
+            .as_ref()
+            .and_then(|choices| choices.iter().find(|(v, _)| v == value));
+        if let Some((_, label)) = choice {
+            return label.clone();
+        }
+        match self.config.borrow().format(&self.columns[column]) {
+            Some(format) if !value.is_empty() => format.format(value),
+            _ => value.to_string(),
+        }
+    }
+
+    /// the title of the datawindow, or the table
+    fn title(&self) -> &str {
+        match &self.definition {
+            Some(definition) => definition.title(),
+            None => &self.table,
+        }
+    }
+
+    /// the label of the column in the datawindow, or its name
+    fn label(&self, column: usize) -> &str {
+        match &self.definition {
+            Some(definition) => definition.label(&self.columns[column]),
+            None => &self.columns[column],
+        }
+    }
+
+    /// All the rows matching the filter in the visible columns, with the groups and the totals.
+    fn list_report(&self) -> ListReport {
+        let columns = self.visible_columns();
+        let mut lines = vec![];
+        self.report_lines(&[], &columns, &mut lines);
+        let aggregates = self.aggregates();
+        if !aggregates.is_empty() {
+            let values = self.compute_aggregates(&aggregates);
+            lines.push(Line::Total(self.aggregates_text(&aggregates, values)));
+        }
+        let mut subtitle = vec![];
+        if let Some(filter) = self.query.borrow().filter.as_ref() {
+            subtitle.push(format!("Filtered on {}", filter));
+        }
+        if self.is_grouped() {
+            subtitle.push(format!("Grouped by {}", self.group_by.borrow().join(", ")));
+        }
+        let titles = columns.iter().map(|i| self.label(*i).to_string()).collect();
+        ListReport::new(self.title(), &subtitle.join(", "), titles, lines)
+    }
+
+    fn aggregates_text(&self, aggregates: &[(String, Aggregate)], values: Vec<Value>) -> String {
+        let texts: Vec<String> = aggregates
+            .iter()
+            .zip(values.into_iter())
+            .map(|((column, aggregate), value)| self.aggregate_text(column, *aggregate, value))
+            .collect();
+        texts.join(", ")
+    }
+
+    /// the groups of the next group column each followed by its lines, or the rows of the group
+    fn report_lines(
+        &self,
+        conditions: &[(String, Value)],
+        columns: &[usize],
+        lines: &mut Vec<Line>,
+    ) {
+        let column = match self.group_by.borrow().get(conditions.len()).cloned() {
+            Some(column) => column,
+            None => {
+                for row in self.load_group_rows(conditions, None, 0) {
+                    let values = columns
+                        .iter()
+                        .map(|i| self.display_value(*i, &row[*i]))
+                        .collect();
+                    lines.push(Line::Row(values));
+                }
+                return;
+            }
+        };
+        let index = match self.column_index(&column) {
+            Some(index) => index,
+            None => return,
+        };
+        let aggregates = self.aggregates();
+        for (value, count, subtotals) in self.load_groups(conditions, &column, &aggregates) {
+            let mut text = format!(
+                "{}: {} ({})",
+                column,
+                self.display_value(index, &value),
+                count
+            );
+            if !aggregates.is_empty() {
+                text = format!("{}  {}", text, self.aggregates_text(&aggregates, subtotals));
+            }
+            lines.push(Line::Group(conditions.len(), text));
+            let mut group_conditions = conditions.to_vec();
+            group_conditions.push((column.clone(), value));
+            self.report_lines(&group_conditions, columns, lines);
+        }
+    }
+
+    fn form_report(&self) -> FormReport {
+        let records = self
+            .records
+            .borrow()
+            .iter()
+            .map(|r| {
+                r.values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| self.display_value(i, value))
+                    .collect()
+            })
+            .collect();
+        FormReport {
+            title: self.title().to_string(),
+            labels: (0..self.columns.len())
+                .map(|i| self.label(i).to_string())
+                .collect(),
+            records: records,
+        }
+    }
+
+    /// the print dialog, where the orientation is chosen in the page setup
+    fn print(&self) {
+        let report = self.report();
+        let operation = PrintOperation::new();
+        operation.set_job_name(self.title());
+        operation.set_embed_page_setup(true);
+        let counted = report.clone();
+        operation.connect_begin_print(move |operation, context| {
+            if let Some(cr) = context.get_cairo_context() {
+                let pages = counted.page_count(&cr, context.get_width(), context.get_height());
+                operation.set_n_pages(pages as i32);
+            }
+        });
+        operation.connect_draw_page(move |_, context, page| {
+            if let Some(cr) = context.get_cairo_context() {
+                let (width, height) = (context.get_width(), context.get_height());
+                report.draw_page(&cr, width, height, page as usize);
+            }
+        });
+        let window = self.window();
+        if let Err(e) = operation.run(PrintOperationAction::PrintDialog, window.as_ref()) {
+            self.status.set_text(&format!("Unable to print: {}", e));
+        }
+    }
+
+    fn export_pdf(&self) {
+        let window = self.window();
+        let dialog = FileChooserDialog::with_buttons(
+            Some("Export to PDF"),
+            window.as_ref(),
+            FileChooserAction::Save,
+            &[
+                ("Cancel", ResponseType::Cancel),
+                ("Export", ResponseType::Accept),
+            ],
+        );
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name(&format!("{}.pdf", self.title()));
+        let landscape = CheckButton::with_label("Landscape");
+        dialog.set_extra_widget(&landscape);
+        let path = match dialog.run() {
+            ResponseType::Accept => dialog.get_filename(),
+            _ => None,
+        };
+        let landscape = landscape.get_active();
+        dialog.close();
+        let path = match path {
+            Some(path) => path,
+            None => return,
+        };
+        match report::write_pdf(self.report().as_ref(), &path, landscape) {
+            Ok(pages) => {
+                self.status
+                    .set_text(&format!("Exported {} page(s) to {}", pages, path.display()))
+            }
+            Err(e) => self
+                .status
+                .set_text(&format!("Unable to export to PDF: {}", e)),
+        }
+    }
+
+    /// many rows can be selected for copying a range, the detail shows the one at the cursor
+    fn init_list_selection(&self) {
+        for list in vec![&self.list, &self.frozen_list] {
+            list.get_selection().set_mode(SelectionMode::Multiple);
+            list.set_rubber_banding(true);
+        }
+        let this = self.clone();
+        self.list.get_selection().connect_changed(move |_| {
+            if let Some(row) = this.selected_row() {
+                this.show_record(row);
+            }
+        });
+        self.sync_selection(&self.list, &self.frozen_list);
+        self.sync_selection(&self.frozen_list, &self.list);
+    }
+
+    /// select the same rows in the other list
+    fn sync_selection(&self, from: &TreeView, to: &TreeView) {
+        let this = self.clone();
+        let to = to.clone();
+        from.get_selection().connect_changed(move |selection| {
+            if this.syncing_selection.get() {
+                return;
+            }
+            this.syncing_selection.set(true);
+            let target = to.get_selection();
+            target.unselect_all();
+            let (paths, _) = selection.get_selected_rows();
+            for path in paths.iter() {
+                target.select_path(path);
+            }
+            this.syncing_selection.set(false);
+        });
+    }
+
+    fn show_record(&self, row: usize) {
+        let values = match self.records.borrow().get(row) {
+            Some(record) => record.values.clone(),
+            None => return,
+        };
+        self.showing_record.set(true);
+        for (entry, value) in self.detail_entries.iter().zip(values.iter()) {
+            entry.set_text(value.as_deref().unwrap_or(""));
+        }
+        self.showing_record.set(false);
+        self.mark_detail_errors();
+        self.load_history();
+    }
+
+    /// put an error icon on the detail entries of the selected record that are invalid,
+    /// with the message in the tooltip next to the field info
+    fn mark_detail_errors(&self) {
+        let row = self.selected_row();
+        let errors = self.errors.borrow();
+        let layout = self.form_layout.borrow();
+        for (i, (entry, field)) in self
+            .detail_entries
+            .iter()
+            .zip(self.fields.iter())
+            .enumerate()
+        {
+            let error = row.and_then(|row| errors.get(&(row, i)));
+            let icon = error.map(|_| "dialog-error");
+            entry.set_icon_from_icon_name(EntryIconPosition::Secondary, icon);
+            let info = Self::field_info(layout.as_ref(), field);
+            let tooltip = match error {
+                Some(message) if info.is_empty() => message.to_string(),
+                Some(message) => format!("{}\n{}", message, info),
+                None => info,
+            };
+            entry.set_tooltip_text(Some(&tooltip).filter(|t| !t.is_empty()).map(|t| t.as_str()));
+        }
+    }
+
+    /// the row at the cursor when it is selected, or else the first selected row,
+    /// none when grouped
+    fn selected_row(&self) -> Option<usize> {
+        if self.is_grouped() {
+            return None;
+        }
+        let (cursor, _) = self.list.get_cursor();
+        match cursor {
+            Some(path) if self.list.get_selection().path_is_selected(&path) => {
+                path.get_indices().first().map(|row| *row as usize)
+            }
+            _ => self.selected_rows().first().cloned(),
+        }
+    }
+
+    fn selected_rows(&self) -> Vec<usize> {
+        if self.is_grouped() {
+            return vec![];
+        }
+        let (paths, _) = self.list.get_selection().get_selected_rows();
+        let mut rows: Vec<usize> = paths
+            .iter()
+            .filter_map(|path| path.get_indices().first().map(|row| *row as usize))
+            .collect();
+        rows.sort();
+        rows
+    }
+
+    /// select only this row and put the cursor on it
+    fn select_row(&self, row: usize) {
+        let path = TreePath::from_indicesv(&[row as i32]);
+        self.list.set_cursor(&path, None::<&TreeViewColumn>, false);
+    }
+
+    /// edits in the list cells and in the detail entries are kept in the records until saved
+    fn init_editing(&self) {
+        let cells = self.cells.iter().enumerate();
+        for (i, cell) in cells.chain(self.frozen_cells.iter().enumerate()) {
+            cell.set_property_editable(self.is_editable(i));
+            let this = self.clone();
+            cell.connect_edited(move |_, path, new_text| {
+                if let Some(row) = path.get_indices().first() {
+                    let value = this.choice_value(i, new_text);
+                    this.edit_value(*row as usize, i, &value);
+                }
+            });
+        }
+        for (i, entry) in self.detail_entries.iter().enumerate() {
+            entry.set_editable(self.is_editable(i));
+            if let Some(choices) = &self.choices[i] {
+                Self::complete_choices(entry, choices);
+            }
+            let this = self.clone();
+            entry.connect_changed(move |entry| {
+                if this.showing_record.get() {
+                    return;
+                }
+                if let Some(row) = this.selected_row() {
+                    this.edit_value(row, i, &entry.get_text());
+                }
+            });
+        }
+    }
+
+    /// the entry proposes the labels of the choices, picking one puts its value
+    fn complete_choices(entry: &Entry, choices: &[(String, String)]) {
+        let model = ListStore::new(&[Type::String, Type::String]);
+        for (value, label) in choices.iter() {
+            let iter = model.append();
+            model.set_value(&iter, 0, &value.to_value());
+            model.set_value(&iter, 1, &label.to_value());
+        }
+        let completion = EntryCompletion::new();
+        completion.set_model(Some(&model));
+        completion.set_text_column(1);
+        completion.set_minimum_key_length(0);
+        completion.connect_match_selected(|completion, model, iter| {
+            let value: Option<String> = model.get_value(iter, 0).get().ok().flatten();
+            if let (Some(entry), Some(value)) = (completion.get_entry(), value) {
+                if let Ok(entry) = entry.downcast::<Entry>() {
+                    entry.set_text(&value);
+                }
+            }
+            Inhibit(true)
+        });
+        entry.set_completion(Some(&completion));
+    }
+
+    /// the value of the choice with this label, the combo cells edit the labels
+    fn choice_value(&self, column: usize, text: &str) -> String {
+        self.choices[column]
+            .as_ref()
+            .and_then(|choices| choices.iter().find(|(_, label)| label == text))
+            .map(|(value, _)| value.clone())
+            .unwrap_or_else(|| text.to_string())
+    }
+
+    /// Whether the datawindow allows the operation, a table allows them all
+    /// unless the connection is read-only.
+    /// The rows of a query are only written back when it has the key of its table.
+    fn allows(&self, operation: Operation) -> bool {
+        if self.read_only {
+            return false;
+        }
+        match &self.definition {
+            Some(definition) if definition.query.is_some() => {
+                let is_plain =
+                    |k: &String| matches!(self.column_index(k), Some(i) if self.plain[i]);
+                let has_key = !self.key.is_empty() && self.key.iter().all(is_plain);
+                has_key && definition.allows(operation)
+            }
+            Some(definition) => definition.allows(operation),
+            None => true,
+        }
+    }
+
+    /// the new records can be edited when inserting is allowed, the others when updating is
+    fn is_editable(&self, column: usize) -> bool {
+        if !self.plain[column] {
+            return false;
+        }
+        let read_only = match &self.definition {
+            Some(definition) => definition.editor(&self.columns[column]) == Editor::ReadOnly,
+            None => false,
+        };
+        !read_only && (self.allows(Operation::Insert) || self.allows(Operation::Update))
+    }
+
+    fn can_edit(&self, row: usize, column: usize) -> bool {
+        let operation = match self.records.borrow().get(row) {
+            Some(record) if record.is_new() => Operation::Insert,
+            Some(_) => Operation::Update,
+            None => return false,
+        };
+        self.is_editable(column) && self.allows(operation)
+    }
+
+    /// whether the action is allowed by the operations of the datawindow
+    fn allows_action(&self, name: &str) -> bool {
+        match name {
+            "save" | "revert-version" | "alter-table" if self.read_only => false,
+            "new" | "duplicate-row" => self.allows(Operation::Insert),
+            "delete" => self.allows(Operation::Delete),
+            "edit" | "paste" => self.allows(Operation::Insert) || self.allows(Operation::Update),
+            // the layout of a datawindow is in its definition
+            "design-form" => self.definition.is_none(),
+            // a query on several tables has no structure to show
+            "toggle-structure" | "alter-table" | "toggle-history" => match &self.definition {
+                Some(definition) => definition.query.is_none() || definition.base_table().is_some(),
+                None => true,
+            },
+            _ => true,
+        }
+    }
+
+    fn disable_forbidden_actions(&self) {
+        let names = vec![
+            "new",
+            "save",
+            "revert-version",
+            "duplicate-row",
+            "delete",
+            "edit",
+            "paste",
+            "design-form",
+            "toggle-structure",
+            "alter-table",
+            "toggle-history",
+        ];
+        for name in names {
+            if !self.allows_action(name) {
+                self.set_action_enabled(name, false);
+            }
+        }
+    }
+
+    /// start editing the cell under the cursor, or the first cell
+    fn edit_cell(&self) {
+        if self.view_mode() == ViewMode::Structure {
+            self.set_view(ViewMode::List);
+        }
+        if self.view_mode() == ViewMode::Detail {
+            if let Some(entry) = self.detail_entries.first() {
+                entry.grab_focus();
+            }
+            return;
+        }
+        let (path, column) = self.list.get_cursor();
+        let path = match path.or_else(|| {
+            self.selected_row()
+                .map(|r| TreePath::from_indicesv(&[r as i32]))
+        }) {
+            Some(path) => path,
+            None => return,
+        };
+        let column = column.or_else(|| self.list.get_column(0));
+        self.list.grab_focus();
+        self.list.set_cursor(&path, column.as_ref(), true);
+    }
+
+    /// the interactive search of the list, matching the text anywhere in any column
+    fn init_find(&self) {
+        let columns = self.columns.len() as i32;
+        self.list.set_enable_search(true);
+        self.list.set_search_equal_func(move |model, _, key, iter| {
+            let key = key.to_lowercase();
+            let found = (0..columns).any(|i| {
+                let value: Option<String> = model.get_value(iter, i).get().ok().flatten();
+                value.map(|v| v.to_lowercase().contains(&key)) == Some(true)
+            });
+            // gtk expects false for a match
+            !found
+        });
+    }
+
+    fn find(&self) {
+        if self.view_mode() != ViewMode::List {
+            self.set_view(ViewMode::List);
+        }
+        self.list.grab_focus();
+        self.list.emit_start_interactive_search();
+    }
+
+    /// Clicking a cell starts a range of columns, shift clicking or shift with the arrow keys
+    /// extends it. The rows of the range are the selected rows.
+    fn init_range_selection(&self) {
+        for list in vec![&self.list, &self.frozen_list] {
+            self.connect_range_selection(list);
+        }
+    }
+
+    fn connect_range_selection(&self, list: &TreeView) {
+        let this = self.clone();
+        list.connect_button_press_event(move |list, event| {
+            if event.get_button() != 1 {
+                return Inhibit(false);
+            }
+            let (x, y) = event.get_position();
+            let column = match list.get_path_at_pos(x as i32, y as i32) {
+                Some((_, Some(column), _, _)) => column,
+                _ => return Inhibit(false),
+            };
+            let index = match this.store_column(&column) {
+                Some(index) => index,
+                None => return Inhibit(false),
+            };
+            let range = match this.range_columns.get() {
+                Some((start, _)) if event.get_state().contains(gdk::ModifierType::SHIFT_MASK) => {
+                    (start, index)
+                }
+                _ => (index, index),
+            };
+            this.range_columns.set(Some(range));
+            Inhibit(false)
+        });
+        let this = self.clone();
+        list.connect_key_press_event(move |_, event| {
+            let keyval = event.get_keyval();
+            let shift = event.get_state().contains(gdk::ModifierType::SHIFT_MASK);
+            let forward = keyval == gdk::keys::constants::Right;
+            if !shift || !(forward || keyval == gdk::keys::constants::Left) {
+                return Inhibit(false);
+            }
+            let visible = this.visible_columns();
+            let (start, end) = match this.range_columns.get() {
+                Some(range) => range,
+                None => match visible.first() {
+                    Some(first) => (*first, *first),
+                    None => return Inhibit(false),
+                },
+            };
+            let position = visible.iter().position(|c| *c == end).unwrap_or(0);
+            let position = if forward {
+                (position + 1).min(visible.len() - 1)
+            } else {
+                position.saturating_sub(1)
+            };
+            this.range_columns.set(Some((start, visible[position])));
+            this.status.set_text(&this.range_description());
+            Inhibit(true)
+        });
+    }
+
+    /// the store index of the visible columns, in the order they are shown,
+    /// the frozen ones first
+    fn visible_columns(&self) -> Vec<usize> {
+        let frozen = self.frozen_list.get_columns();
+        let scrolling = self.list.get_columns();
+        frozen
+            .iter()
+            .chain(scrolling.iter())
+            .filter(|c| c.get_visible())
+            .filter_map(|c| self.store_column(c))
+            .collect()
+    }
+
+    /// The rows and columns of the selected range, by store index.
+    /// All the visible columns when no cell was clicked.
+    fn selected_range(&self) -> Option<(Vec<usize>, Vec<usize>)> {
+        let rows = self.selected_rows();
+        let (first, last) = (*rows.first()?, *rows.last()?);
+        let visible = self.visible_columns();
+        let position = |column| visible.iter().position(|c| *c == column);
+        let columns = match self.range_columns.get() {
+            Some((start, end)) => match (position(start), position(end)) {
+                (Some(a), Some(b)) => visible[a.min(b)..=a.max(b)].to_vec(),
+                _ => visible,
+            },
+            None => visible,
+        };
+        Some(((first..=last).collect(), columns))
+    }
+
+    fn range_description(&self) -> String {
+        match self.selected_range() {
+            Some((rows, columns)) => format!("{} x {} cells selected", rows.len(), columns.len()),
+            None => String::new(),
+        }
+    }
+
+    /// copy the selected range as tab separated text and as an html table
+    fn copy_range(&self) {
+        let (rows, columns) = match self.selected_range() {
+            Some(range) => range,
+            None => return,
+        };
+        let values: Vec<Vec<Value>> = {
+            let records = self.records.borrow();
+            rows.iter()
+                .filter_map(|row| records.get(*row))
+                .map(|record| columns.iter().map(|c| record.values[*c].clone()).collect())
+                .collect()
+        };
+        let text = clipboard::to_tsv(&values);
+        let html = clipboard::to_html(&values);
+        let targets = [
+            TargetEntry::new("text/html", TargetFlags::empty(), 1),
+            TargetEntry::new("UTF8_STRING", TargetFlags::empty(), 0),
+            TargetEntry::new("text/plain;charset=utf-8", TargetFlags::empty(), 0),
+            TargetEntry::new("text/plain", TargetFlags::empty(), 0),
+            TargetEntry::new("STRING", TargetFlags::empty(), 0),
+        ];
+        let copied = Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_with_data(
+            &targets,
+            move |_, selection, info| {
+                if info == 1 {
+                    selection.set(&gdk::Atom::intern("text/html"), 8, html.as_bytes());
+                } else {
+                    selection.set_text(&text);
+                }
+            },
+        );
+        if copied {
+            self.status.set_text(&format!(
+                "Copied {} x {} cells",
+                values.len(),
+                columns.len()
+            ));
+        }
+    }
+
+    /// Paste tab separated text from the cell at the top left of the selection,
+    /// over the visible columns and adding the rows that are missing.
+    /// The values are converted to the column types and validated, the paste is undone at once.
+    fn paste(&self) {
+        let text = match Clipboard::get(&gdk::SELECTION_CLIPBOARD).wait_for_text() {
+            Some(text) => text,
+            None => return,
+        };
+        let cells = clipboard::parse_tsv(&text);
+        if cells.is_empty() {
+            return;
+        }
+        let (first_row, first_column) = match self.selected_range() {
+            Some((rows, columns)) => (rows[0], columns.first().cloned()),
+            None => (self.records.borrow().len(), None),
+        };
+        let visible = self.visible_columns();
+        let start = first_column
+            .and_then(|c| visible.iter().position(|v| *v == c))
+            .unwrap_or(0);
+        self.push_undo(None);
+        let mut pasted = 0;
+        for (i, line) in cells.iter().enumerate() {
+            let row = first_row + i;
+            if row >= self.records.borrow().len() {
+                if !self.allows(Operation::Insert) {
+                    break;
+                }
+                self.append_record(Record::blank(self.columns.len()));
+            }
+            for (column, text) in visible[start..].iter().zip(line.iter()) {
+                if !self.can_edit(row, *column) {
+                    continue;
+                }
+                let value = clipboard::convert(self.fields[*column].data_type(), text);
+                self.set_value(row, *column, value);
+                pasted += 1;
+            }
+        }
+        let errors = self.validate();
+        let invalid = errors.len();
+        *self.errors.borrow_mut() = errors;
+        self.list.queue_draw();
+        self.mark_detail_errors();
+        match invalid {
+            0 => self.status.set_text(&format!("Pasted {} cells", pasted)),
+            _ => self.status.set_text(&format!(
+                "Pasted {} cells, {} invalid value(s)",
+                pasted, invalid
+            )),
+        }
+    }
+
+    /// remember the pending changes before an edit of this cell, or of many cells
+    fn push_undo(&self, cell: Option<(usize, usize)>) {
+        let before = Snapshot {
+            records: self.records.borrow().clone(),
+            deleted: self.deleted.borrow().clone(),
+        };
+        self.undo.borrow_mut().push(before, cell);
+    }
+
+    /// put back the pending changes as they were before the last edit
+    fn undo(&self) {
+        let snapshot = match self.undo.borrow_mut().pop() {
+            Some(snapshot) => snapshot,
+            None => {
+                self.status.set_text("Nothing to undo");
+                return;
+            }
+        };
+        let row = self.selected_row();
+        self.store.clear();
+        *self.deleted.borrow_mut() = snapshot.deleted;
+        self.records.borrow_mut().clear();
+        for record in snapshot.records {
+            self.append_record(record);
+        }
+        self.errors.borrow_mut().clear();
+        let count = self.records.borrow().len();
+        if let Some(row) = row.filter(|_| count > 0) {
+            self.select_row(row.min(count - 1));
+        }
+        self.status.set_text("Undone");
+    }
+
+    /// a value typed by the user, which can be undone, an empty text is taken as null
+    fn edit_value(&self, row: usize, column: usize, text: &str) {
+        if !self.can_edit(row, column) {
+            return;
+        }
+        let value: Value = Some(text.to_string()).filter(|t| !t.is_empty());
+        let changed = self
+            .records
+            .borrow()
+            .get(row)
+            .map(|r| r.values[column] != value);
+        if changed == Some(true) {
+            self.push_undo(Some((row, column)));
+            self.set_value(row, column, value);
+        }
+    }
+
+    fn set_value(&self, row: usize, column: usize, value: Value) {
+        {
+            let mut records = self.records.borrow_mut();
+            let record = match records.get_mut(row) {
+                Some(record) => record,
+                None => return,
+            };
+            if record.values[column] == value {
+                return;
+            }
+            record.values[column] = value.clone();
+        }
+        if let Some(iter) = self.store.iter_nth_child(None, row as i32) {
+            self.store
+                .set_value(&iter, column as u32, &value.as_deref().to_value());
+        }
+        if self.errors.borrow_mut().remove(&(row, column)).is_some() {
+            self.mark_detail_errors();
+        }
+    }
+
+    fn new_record(&self) {
+        self.push_undo(None);
+        self.append_record(Record::blank(self.columns.len()));
+        self.select_row(self.records.borrow().len() - 1);
+    }
+
+    /// delete all the selected rows
+    fn delete_selected(&self) {
+        let rows = self.selected_rows();
+        if rows.is_empty() {
+            return;
+        }
+        self.push_undo(None);
+        for row in rows.into_iter().rev() {
+            self.delete_row(row);
+        }
+    }
+
+    fn delete_row(&self, row: usize) {
+        let record = self.records.borrow_mut().remove(row);
+        if let Some(original) = record.original {
+            self.deleted.borrow_mut().push(original);
+        }
+        // the errors of the rows below move up with them
+        let mut errors = self.errors.borrow_mut();
+        *errors = errors
+            .drain()
+            .filter(|((r, _), _)| *r != row)
+            .map(|((r, c), message)| ((if r > row { r - 1 } else { r }, c), message))
+            .collect();
+        drop(errors);
+        let mut changed_rows = self.changed_rows.borrow_mut();
+        *changed_rows = changed_rows
+            .drain()
+            .filter(|r| *r != row)
+            .map(|r| if r > row { r - 1 } else { r })
+            .collect();
+        drop(changed_rows);
+        if let Some(iter) = self.store.iter_nth_child(None, row as i32) {
+            self.store.remove(&iter);
+        }
+    }
+
+    /// Save the edits in one transaction,
+    /// nothing is sent to the database while any of the values is invalid.
+    pub fn save(&self) {
+        if self.read_only {
+            self.status.set_text("The connection is read-only");
+            return;
+        }
+        let errors = self.validate();
+        let invalid = errors.len();
+        *self.errors.borrow_mut() = errors;
+        self.list.queue_draw();
+        self.mark_detail_errors();
+        if invalid > 0 {
+            self.status.set_text(&format!(
+                "{} invalid value(s), fix them before saving",
+                invalid
+            ));
+            return;
+        }
+        if !self.confirm_guarded_save() {
+            self.status.set_text("Save cancelled");
+            return;
+        }
+        match self.write_changes() {
+            Ok(changes) if changes.is_empty() => self.status.set_text("Nothing to save"),
+            Ok(changes) if self.in_transaction() => {
+                // recorded in the audit log once the transaction is committed
+                self.status.set_text(&format!(
+                    "Saved {} change(s) in the open transaction",
+                    changes.len()
+                ));
+                self.load(self.page.get());
+            }
+            Ok(changes) => {
+                let n = changes.len();
+                match audit::record(&changes) {
+                    Ok(()) => self.status.set_text(&format!("Saved {} change(s)", n)),
+                    Err(e) => self.status.set_text(&format!(
+                        "Saved {} change(s), unable to write the audit log: {}",
+                        n, e
+                    )),
+                }
+                self.load(self.page.get());
+            }
+            Err(SaveError::Conflicts(conflicts)) => self.resolve_conflicts(conflicts),
+            Err(SaveError::Db(e)) => self.status.set_text(&format!("Unable to save: {}", e)),
+        }
+    }
+
+    /// on a guarded connection the changes are shown, and saved once its name is typed
+    fn confirm_guarded_save(&self) -> bool {
+        let name = match self.db.borrow().as_ref() {
+            Some(db) => db.session().guard.clone(),
+            None => None,
+        };
+        let name = match name {
+            Some(name) => name,
+            None => return true,
+        };
+        let changes = self.pending_changes();
+        if changes.is_empty() {
+            return true;
+        }
+        guard::confirm_save(self.window().as_ref(), &name, &audit::summary(&changes))
+    }
+
+    /// the changes the save would make, in the order of its statements
+    fn pending_changes(&self) -> Vec<Change> {
+        let database = self.database();
+        let change = |original: Option<&[Value]>, values: Option<&[Value]>| {
+            Change::new(
+                &database,
+                &self.table,
+                &self.columns,
+                &self.key,
+                original,
+                values,
+            )
+        };
+        let mut changes: Vec<Change> = self
+            .deleted
+            .borrow()
+            .iter()
+            .map(|original| change(Some(original.as_slice()), None))
+            .collect();
+        for record in self.records.borrow().iter().filter(|r| r.is_changed()) {
+            changes.push(change(
+                record.original.as_deref(),
+                Some(record.values.as_slice()),
+            ));
+        }
+        changes
+    }
+
+    fn validate(&self) -> HashMap<(usize, usize), String> {
+        let records = self.records.borrow();
+        let mut errors = HashMap::new();
+        for (row, record) in records.iter().enumerate() {
+            if !record.is_changed() {
+                continue;
+            }
+            let problems = self
+                .rules
+                .validate(&self.columns, &record.values, record.is_new());
+            for (column, message) in problems {
+                if let Some(i) = self.columns.iter().position(|c| *c == column) {
+                    errors.entry((row, i)).or_insert(message);
+                }
+            }
+        }
+        for key in self.rules.unique_keys.iter() {
+            for (row, column, message) in self.find_duplicates(&records, key) {
+                errors.entry((row, column)).or_insert(message);
+            }
+        }
+        errors
+    }
+
+    /// the cells of the edited records that repeat the values of a unique key,
+    /// either in another record of this page or in the table on the server
+    fn find_duplicates(&self, records: &[Record], key: &[String]) -> Vec<(usize, usize, String)> {
+        let indexes: Vec<usize> = key
+            .iter()
+            .filter_map(|k| self.columns.iter().position(|c| c == k))
+            .collect();
+        if indexes.len() != key.len() {
+            return vec![];
+        }
+        // a key with a null in it never conflicts
+        let key_of = |values: &[Value]| -> Option<Vec<String>> {
+            indexes.iter().map(|i| values[*i].clone()).collect()
+        };
+        let mut db = self.db.borrow_mut();
+        let mut duplicates = vec![];
+        for (row, record) in records.iter().enumerate() {
+            let values = match key_of(&record.values) {
+                Some(values) if record.is_changed() => values,
+                _ => continue,
+            };
+            if record.original.as_ref().and_then(|o| key_of(o)).as_ref() == Some(&values) {
+                continue;
+            }
+            let in_page = records
+                .iter()
+                .enumerate()
+                .any(|(other, r)| other != row && key_of(&r.values).as_ref() == Some(&values));
+            let on_server = !in_page
+                && match db.as_mut() {
+                    Some(db) => self.exists_on_server(db.as_mut(), key, &values, record),
+                    None => false,
+                };
+            if in_page || on_server {
+                let message = format!("{} must be unique", key.join(", "));
+                for i in indexes.iter() {
+                    duplicates.push((row, *i, message.clone()));
+                }
+            }
+        }
+        duplicates
+    }
+
+    fn exists_on_server(
+        &self,
+        db: &mut dyn Database,
+        key: &[String],
+        values: &[String],
+        record: &Record,
+    ) -> bool {
+        let mut params: Vec<Value> = values.iter().cloned().map(Some).collect();
+        // a saved record must not be counted as its own duplicate
+        let own_key: Option<Vec<Value>> = match &record.original {
+            Some(original) if !self.key.is_empty() => self
+                .key
+                .iter()
+                .map(|k| self.columns.iter().position(|c| c == k))
+                .map(|i| i.map(|i| original[i].clone()))
+                .collect(),
+            _ => None,
+        };
+        let exclude = match own_key {
+            Some(own_key) => {
+                params.extend(own_key);
+                Some(self.key.as_slice())
+            }
+            None => None,
+        };
+        let sql = validate::unique_query(db.platform(), &self.table, key, exclude);
+        match db.execute(&sql, &params) {
+            Ok(result) => {
+                let count = result.rows().rows.first().and_then(|r| r[0].clone());
+                count.and_then(|c| c.parse::<u64>().ok()).unwrap_or(0) > 0
+            }
+            Err(e) => {
+                println!("unable to check that {} is unique: {}", key.join(", "), e);
+                false
+            }
+        }
+    }
+
+    /// The change of a row made by each of the statements that were executed.
+    /// Nothing is written when any of the rows was changed on the server since it was loaded,
+    /// the other sessions are notified of the changes where the database allows it.
+    fn write_changes(&self) -> Result<Vec<Change>, SaveError> {
+        let mut db = self.db.borrow_mut();
+        let db = match db.as_mut() {
+            Some(db) => db,
+            None => return Err(DbError::Connection("not connected".to_string()).into()),
+        };
+        let platform = db.platform();
+        let database = db.session().database.clone();
+        let mut statements = vec![];
+        let mut changes = vec![];
+        let change = |original: Option<&[Value]>, values: Option<&[Value]>| {
+            Change::new(
+                &database,
+                &self.table,
+                &self.columns,
+                &self.key,
+                original,
+                values,
+            )
+        };
+        for original in self.deleted.borrow().iter() {
+            statements.push(edit::delete(
+                platform,
+                &self.table,
+                &self.columns,
+                &self.key,
+                original,
+            ));
+            changes.push(change(Some(original.as_slice()), None));
+        }
+        for record in self.records.borrow().iter() {
+            if record.is_new() {
+                // the computed columns of a query are not in the table
+                let values: Vec<Value> = record
+                    .values
+                    .iter()
+                    .zip(self.plain.iter())
+                    .map(|(value, plain)| if *plain { value.clone() } else { None })
+                    .collect();
+                statements.push(edit::insert(platform, &self.table, &self.columns, &values));
+                changes.push(change(None, Some(record.values.as_slice())));
+            } else if let Some(update) = edit::update(
+                platform,
+                &self.table,
+                &self.columns,
+                &self.key,
+                record,
+                &self.lock,
+            ) {
+                statements.push(update);
+                changes.push(change(
+                    record.original.as_deref(),
+                    Some(record.values.as_slice()),
+                ));
+            }
+        }
+        if statements.is_empty() {
+            return Ok(changes);
+        }
+        // the notices are not counted in the pending statements of the transaction
+        let data_statements = statements.len();
+        if platform == Platform::Postgres {
+            for change in changes.iter() {
+                // the other sessions know the row by the key it had
+                let key = change.previous_key.as_ref().unwrap_or(&change.key);
+                let notice = edit::change_notice(&self.table, change.action.as_str(), key);
+                let sql = format!(
+                    "NOTIFY {}, {}",
+                    platform.quote_ident(edit::CHANGES_CHANNEL),
+                    platform.quote_literal(&notice)
+                );
+                statements.push((sql, vec![]));
+            }
+        }
+        let db = db.as_mut();
+        db::begin_save(db)?;
+        let conflicts = match self.find_conflicts(db) {
+            Ok(conflicts) => conflicts,
+            Err(e) => {
+                db::cancel_save(db);
+                return Err(e.into());
+            }
+        };
+        if !conflicts.is_empty() {
+            db::cancel_save(db);
+            return Err(SaveError::Conflicts(conflicts));
+        }
+        for (sql, params) in statements.iter() {
+            if let Err(e) = db.execute(sql, params) {
+                db::cancel_save(db);
+                return Err(e.into());
+            }
+        }
+        if let Err(e) = db::end_save(db, data_statements, &changes) {
+            db::cancel_save(db);
+            return Err(e.into());
+        }
+        Ok(changes)
+    }
+
+    /// The edited and deleted rows that are not on the server as they were loaded anymore,
+    /// read again in the transaction of the save.
+    fn find_conflicts(&self, db: &mut dyn Database) -> Result<Vec<Conflict>, DbError> {
+        if self.lock.columns.is_empty() {
+            return Ok(vec![]);
+        }
+        let platform = db.platform();
+        let deleted = self.deleted.borrow();
+        let records = self.records.borrow();
+        let edited = records
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| record.is_changed())
+            .filter_map(|(row, record)| record.original.as_ref().map(|o| (Some(row), o)));
+        let mut conflicts = vec![];
+        for (row, original) in deleted.iter().map(|o| (None, o)).chain(edited) {
+            let (sql, params) =
+                self.lock
+                    .select(platform, &self.table, &self.columns, &self.key, original);
+            let current = db
+                .execute(&sql, &params)?
+                .rows()
+                .rows
+                .into_iter()
+                .next()
+                .map(|values| self.lock.current_row(&self.columns, original, &values));
+            let changed = match &current {
+                Some(current) => self.lock.is_changed(&self.columns, original, current),
+                // a deleted row that is already gone is not a conflict
+                None => row.is_some(),
+            };
+            if changed {
+                conflicts.push(Conflict {
+                    row: row,
+                    original: original.clone(),
+                    current: current,
+                });
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// Let the user merge the edits with the rows changed on the server,
+    /// as an edit that can be undone, written by the next save.
+    fn resolve_conflicts(&self, conflicts: Vec<Conflict>) {
+        self.push_undo(None);
+        let resolved = conflicts
+            .iter()
+            .filter(|conflict| self.resolve_conflict(conflict))
+            .count();
+        self.list.queue_draw();
+        if let Some(row) = self.selected_row() {
+            self.show_record(row);
+        }
+        self.status.set_text(&format!(
+            "{} row(s) changed on the server since they were loaded, {} resolved, save again to write them",
+            conflicts.len(),
+            resolved
+        ));
+    }
+
+    fn resolve_conflict(&self, conflict: &Conflict) -> bool {
+        match (conflict.row, &conflict.current) {
+            (Some(row), Some(current)) => {
+                let mine = match self.records.borrow().get(row) {
+                    Some(record) => record.values.clone(),
+                    None => return false,
+                };
+                let window = self.window();
+                let dialog = MergeDialog::new(
+                    window.as_ref(),
+                    &self.columns,
+                    &conflict.original,
+                    &mine,
+                    current,
+                );
+                let merged = match dialog.run() {
+                    Some(merged) => merged,
+                    None => return false,
+                };
+                if let Some(record) = self.records.borrow_mut().get_mut(row) {
+                    record.original = Some(current.clone());
+                }
+                for (column, value) in merged.into_iter().enumerate() {
+                    self.set_value(row, column, value);
+                }
+                true
+            }
+            // without a primary key the row is found by all its values,
+            // it may as well have been changed as deleted
+            (Some(_), None) if self.key.is_empty() => {
+                self.tell(
+                    "The record was changed or deleted on the server since it was loaded.\n\
+                     The table has no primary key to tell which, refresh to see the rows as they are now.",
+                );
+                false
+            }
+            (Some(row), None) => {
+                if !self.ask(
+                    "The record was deleted on the server since it was loaded.\nInsert it again?",
+                ) {
+                    return false;
+                }
+                if let Some(record) = self.records.borrow_mut().get_mut(row) {
+                    record.original = None;
+                }
+                true
+            }
+            (None, Some(current)) => {
+                if !self.ask("The deleted record was changed on the server since it was loaded.\nDelete it anyway?") {
+                    return false;
+                }
+                let mut deleted = self.deleted.borrow_mut();
+                if let Some(original) = deleted.iter_mut().find(|o| **o == conflict.original) {
+                    *original = current.clone();
+                }
+                true
+            }
+            (None, None) => false,
+        }
+    }
+
+    /// whether the page has edits that are not saved
+    fn is_edited(&self) -> bool {
+        !self.deleted.borrow().is_empty() || self.records.borrow().iter().any(|r| r.is_changed())
+    }
+
+    /// Ask before the rows are read again, dropping the edits that are not saved.
+    /// True when there are none or they can be dropped.
+    fn confirm_discard(&self) -> bool {
+        !self.is_edited() || self.ask("The edits that are not saved will be lost. Go on?")
+    }
+
+    fn tell(&self, message: &str) {
+        let window = self.window();
+        let dialog = MessageDialog::new(
+            window.as_ref(),
+            DialogFlags::MODAL,
+            MessageType::Info,
+            ButtonsType::Close,
+            message,
+        );
+        dialog.run();
+        dialog.close();
+    }
+
+    /// a yes or no question
+    fn ask(&self, question: &str) -> bool {
+        let window = self.window();
+        let dialog = MessageDialog::new(
+            window.as_ref(),
+            DialogFlags::MODAL,
+            MessageType::Question,
+            ButtonsType::YesNo,
+            question,
+        );
+        let answer = dialog.run();
+        dialog.close();
+        answer == ResponseType::Yes
+    }
+
+    fn create_changed_bar(&self) -> InfoBar {
+        let bar = &self.changed_bar;
+        bar.set_message_type(MessageType::Info);
+        bar.add_button("Refresh", ResponseType::Accept);
+        bar.set_show_close_button(true);
+        let label = Label::new(None);
+        bar.get_content_area().add(&label);
+        bar.set_no_show_all(true);
+        let this = self.clone();
+        bar.connect_response(move |bar, response| {
+            if response == ResponseType::Accept {
+                if !this.confirm_discard() {
+                    return;
+                }
+                this.load(this.page.get());
+            }
+            bar.hide();
+        });
+        bar.clone()
+    }
+
+    /// Mark the loaded row changed by another session, and offer to reload the page.
+    /// `key` is the condition on the key of the row, see `audit::row_key`.
+    pub fn notify_change(&self, table: &str, action: &str, key: &str) {
+        if table != self.table {
+            return;
+        }
+        let rows: Vec<usize> = self
+            .records
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| match &record.original {
+                Some(original) => audit::row_key(&self.columns, &self.key, original) == key,
+                None => false,
+            })
+            .map(|(row, _)| row)
+            .collect();
+        // the inserted rows may belong to this page, and the rows whose key was too long
+        // to be sent may be any of them
+        if rows.is_empty() && action != audit::Action::Insert.as_str() && !key.is_empty() {
+            return;
+        }
+        let mut changed_rows = self.changed_rows.borrow_mut();
+        changed_rows.extend(rows);
+        let message = match changed_rows.len() {
+            0 if action == audit::Action::Insert.as_str() => {
+                "Rows were added on the server".to_string()
+            }
+            0 => "Rows of the table changed on the server".to_string(),
+            n => format!("{} row(s) changed on the server since they were loaded", n),
+        };
+        drop(changed_rows);
+        self.show_changed_bar(&message);
+        self.list.queue_draw();
+        self.frozen_list.queue_draw();
+    }
+
+    /// The saves of the open transaction were undone: the page is read again,
+    /// unless it has edits that are not saved yet.
+    pub fn rolled_back(&self) {
+        if self.is_edited() {
+            self.show_changed_bar(
+                "The transaction was rolled back, the saved rows may have changed",
+            );
+        } else {
+            self.load(self.page.get());
+        }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn db(&self) -> SharedDb {
+        self.db.clone()
+    }
+
+    /// the url of the database of the tab, the rows of the audit log are recorded with it
+    fn database(&self) -> String {
+        match self.db.borrow().as_ref() {
+            Some(db) => db.session().database.clone(),
+            None => String::new(),
+        }
+    }
+
+    fn show_changed_bar(&self, message: &str) {
+        for child in self.changed_bar.get_content_area().get_children() {
+            if let Ok(label) = child.downcast::<Label>() {
+                label.set_text(message);
+            }
+        }
+        self.changed_bar.set_no_show_all(false);
+        self.changed_bar.show_all();
+    }
+
+    fn toggle_history(&self) {
+        let history = &self.history.container;
+        if history.get_visible() {
+            history.hide();
+            return;
+        }
+        if self.view_mode() != ViewMode::Detail {
+            self.set_view(ViewMode::Detail);
+        }
+        history.set_no_show_all(false);
+        history.show_all();
+        self.load_history();
+    }
+
+    /// the recorded changes of the selected record, when the history is shown
+    fn load_history(&self) {
+        if !self.history.container.is_visible() {
+            return;
+        }
+        let record = self
+            .selected_row()
+            .and_then(|row| self.records.borrow().get(row).cloned());
+        let changes = match record.and_then(|r| r.original) {
+            _ if !audit::enabled() => Err(
+                "The audit log is off, set audit = true in settings.toml to keep it".to_string(),
+            ),
+            Some(original) => {
+                let key = audit::row_key(&self.columns, &self.key, &original);
+                AuditLog::open()
+                    .and_then(|log| log.history(&self.database(), &self.table, &key))
+                    .map_err(|e| format!("Unable to read the audit log: {}", e))
+            }
+            None => Err("The record is not saved yet".to_string()),
+        };
+        self.history.show(changes);
+    }
+
+    /// Put the values of the selected record back as they were after the change selected
+    /// in the history, as an edit that can be undone and is saved like the others.
+    fn revert_version(&self) {
+        let row = match self.selected_row() {
+            Some(row) => row,
+            None => return,
+        };
+        let change = match self.history.selected() {
+            Some(change) => change,
+            None => {
+                self.status.set_text("Select a version in the history");
+                return;
+            }
+        };
+        let version = match change.version() {
+            Some(version) => version,
+            None => {
+                self.status
+                    .set_text("The record was deleted by this change");
+                return;
+            }
+        };
+        let edits: Vec<(usize, Value)> = {
+            let records = self.records.borrow();
+            let values = match records.get(row) {
+                Some(record) => &record.values,
+                None => return,
+            };
+            version
+                .into_iter()
+                .filter_map(|(column, value)| {
+                    let i = self.columns.iter().position(|c| c == column)?;
+                    Some((i, value.clone())).filter(|(i, value)| values[*i] != *value)
+                })
+                .filter(|(i, _)| self.can_edit(row, *i))
+                .collect()
+        };
+        if edits.is_empty() {
+            self.status
+                .set_text("The record already has the values of this version");
+            return;
+        }
+        self.push_undo(None);
+        let count = edits.len();
+        for (column, value) in edits {
+            self.set_value(row, column, value);
+        }
+        self.show_record(row);
+        self.status.set_text(&format!(
+            "Reverted {} value(s) to the version of {}, save to keep them",
+            count, change.changed_at
+        ));
+    }
+
+    fn init_navigation(&self) {
+        let this = self.clone();
+        self.record_nav.prev.connect_clicked(move |_| {
+            this.select_relative(false);
+        });
+        let this = self.clone();
+        self.record_nav.next.connect_clicked(move |_| {
+            this.select_relative(true);
+        });
+    }
+
+    /// move the selection to the next or previous record
+    fn select_relative(&self, forward: bool) {
+        let count = self.records.borrow().len();
+        let row = match self.selected_row() {
+            Some(row) if forward && row + 1 < count => row + 1,
+            Some(row) if !forward && row > 0 => row - 1,
+            Some(_) => return,
+            None if count > 0 => 0,
+            None => return,
+        };
+        self.select_row(row);
+    }
+
+    /// the child tables of a datawindow are opened from the buttons next to the navigation
+    fn create_detail_content(
+        fields: &[Field],
+        config: &TableConfig,
+        definition: Option<&Definition>,
+        form: &gtk::Box,
+        history: &gtk::Box,
+        nav_records: &NavButtons,
+    ) -> (gtk::Box, Vec<Entry>) {
+        let vbox = gtk::Box::new(Vertical, 0);
+        let hbox = gtk::Box::new(Horizontal, 0);
+        let close_btn = Button::new();
+        let close_image = Image::from_icon_name(Some("window-close"), IconSize::Menu);
+        close_btn.add(&close_image);
+        let design_btn = Button::new();
+        let design_image = Image::from_icon_name(Some("document-properties"), IconSize::Menu);
+        design_btn.add(&design_image);
+        design_btn.set_tooltip_text(Some("Design the form"));
+        design_btn.set_action_name(Some("tab.design-form"));
+        let history_btn = Button::new();
+        let history_image = Image::from_icon_name(Some("document-open-recent"), IconSize::Menu);
+        history_btn.add(&history_image);
+        history_btn.set_tooltip_text(Some("History of the record"));
+        history_btn.set_action_name(Some("tab.toggle-history"));
+
+        vbox.add(&hbox);
+        hbox.pack_end(&close_btn, false, false, 0);
+        if definition.is_none() {
+            hbox.pack_end(&design_btn, false, false, 0);
+        }
+        hbox.pack_end(&history_btn, false, false, 0);
+        hbox.pack_end(&nav_records.container, false, false, 5);
+        for child in definition.iter().flat_map(|d| d.children.iter()) {
+            let button = Button::with_label(&child.title);
+            button.set_action_name(Some("tab.open-child"));
+            button.set_action_target_value(Some(&child.title.to_variant()));
+            hbox.pack_start(&button, false, false, 5);
+        }
+
+        let mut entries = vec![];
+        for field in fields.iter() {
+            let entry = Entry::new();
+            if let Some(mask) = config.mask(field.column()) {
+                Self::apply_edit_mask(&entry, mask);
+            }
+            entries.push(entry);
+        }
+        // the history is shown on demand, on the right of the form
+        let body = gtk::Box::new(Horizontal, 0);
+        body.pack_start(form, true, true, 0);
+        body.pack_end(history, false, false, 0);
+        history.set_no_show_all(true);
+        vbox.pack_start(&body, true, true, 10);
+        (vbox, entries)
+    }
+
+    /// the tooltip set in the form designer, or the column definition
+    fn field_info(layout: Option<&FormLayout>, field: &Field) -> String {
+        let tooltip = layout
+            .and_then(|layout| {
+                let (page, i) = layout.find(field.column())?;
+                layout.pages[page].items[i].tooltip.clone()
+            })
+            .filter(|tooltip| !tooltip.is_empty());
+        tooltip.unwrap_or_else(|| field.info().to_string())
+    }
+
+    /// Put the labels and the entries into the form, as laid out in the form designer
+    /// or one per row. The entries are moved, they stay the same.
+    /// The fields that are on none of the pages are not shown.
+    fn layout_form(
+        form: &gtk::Box,
+        layout: Option<&FormLayout>,
+        fields: &[Field],
+        entries: &[Entry],
+    ) {
+        for entry in entries.iter() {
+            let parent = entry
+                .get_parent()
+                .and_then(|p| p.downcast::<gtk::Container>().ok());
+            if let Some(parent) = parent {
+                parent.remove(entry);
+            }
+        }
+        for child in form.get_children() {
+            form.remove(&child);
+        }
+        let columns: Vec<String> = fields.iter().map(|f| f.column().to_string()).collect();
+        let automatic = FormLayout::automatic(fields);
+        let layout = layout.unwrap_or(&automatic);
+        let mut pages = vec![];
+        for page in layout.pages.iter() {
+            let grid = Grid::new();
+            grid.set_row_spacing(5);
+            grid.set_column_spacing(10);
+            // a field takes two columns of the grid, the label then the entry
+            for item in page.items.iter() {
+                let i = item
+                    .column
+                    .as_ref()
+                    .and_then(|column| columns.iter().position(|c| c == column));
+                match i {
+                    Some(i) => {
+                        let label = Label::new(Some(item.title()));
+                        label.set_xalign(0.0);
+                        let entry = &entries[i];
+                        let info = Self::field_info(Some(layout), &fields[i]);
+                        entry.set_tooltip_text(Some(info.as_str()).filter(|t| !t.is_empty()));
+                        grid.attach(&label, item.left * 2, item.top, 1, item.height);
+                        grid.attach(
+                            entry,
+                            item.left * 2 + 1,
+                            item.top,
+                            item.width * 2 - 1,
+                            item.height,
+                        );
+                    }
+                    None if item.is_section() => {
+                        let label = Label::new(None);
+                        label.set_markup(&format!(
+                            "<b>{}</b>",
+                            glib::markup_escape_text(item.title())
+                        ));
+                        label.set_xalign(0.0);
+                        label.set_margin_top(10);
+                        grid.attach(&label, item.left * 2, item.top, item.width * 2, 1);
+                    }
+                    // a column that is no longer in the table
+                    None => (),
+                }
+            }
+            pages.push((page.title.clone(), grid));
+        }
+        if pages.len() == 1 {
+            form.pack_start(&pages[0].1, true, true, 0);
+        } else {
+            let notebook = Notebook::new();
+            for (title, grid) in pages.iter() {
+                notebook.append_page(grid, Some(&Label::new(Some(title))));
+            }
+            form.pack_start(&notebook, true, true, 0);
+        }
+        form.show_all();
+    }
+
+    /// lay out the detail in the form designer, the layout is saved for the next time
+    fn design_form(&self) {
+        let layout = self
+            .form_layout
+            .borrow()
+            .clone()
+            .unwrap_or_else(|| FormLayout::automatic(&self.fields));
+        let designer = FormDesigner::new(self.window().as_ref(), &self.columns, layout);
+        let layout = match designer.run() {
+            Some(DesignerResponse::Save(layout)) => {
+                if let Err(e) = layout.save(&self.table) {
+                    println!("unable to save the layout of {}: {}", self.table, e);
+                }
+                Some(layout)
+            }
+            Some(DesignerResponse::Automatic) => {
+                if let Err(e) = FormLayout::delete(&self.table) {
+                    println!("unable to delete the layout of {}: {}", self.table, e);
+                }
+                None
+            }
+            None => return,
+        };
+        *self.form_layout.borrow_mut() = layout;
+        Self::layout_form(
+            &self.form,
+            self.form_layout.borrow().as_ref(),
+            &self.fields,
+            &self.detail_entries,
+        );
+        self.mark_detail_errors();
+    }
+
+    /// reformat the text of the entry to the mask as it is being typed
+    fn apply_edit_mask(entry: &Entry, mask: &str) {
+        let mask = mask.to_string();
+        entry.set_placeholder_text(Some(format::mask_pattern(&mask)));
+        entry.connect_changed(move |entry| {
+            let text = entry.get_text();
+            let masked = format::apply_mask(&mask, &text);
+            if masked != text.as_str() {
+                entry.set_text(&masked);
+                entry.set_position(-1);
+            }
+        });
+    }
+
+    fn create_record_nav_buttons_for_detail() -> NavButtons {
+        let paging = gtk::Box::new(Horizontal, 0);
+        let prev_icon = Image::from_icon_name(Some("go-previous"), IconSize::SmallToolbar);
+        let prev_button = Button::new();
+        prev_button.add(&prev_icon);
+        paging.pack_start(&prev_button, false, false, 0);
+
+        let next_icon = Image::from_icon_name(Some("go-next"), IconSize::SmallToolbar);
+        let next_button = Button::new();
+        next_button.add(&next_icon);
+        paging.pack_start(&next_button, false, false, 0);
+
+        NavButtons {
+            container: paging,
+            prev: prev_button,
+            next: next_button,
+        }
+    }
+    //paging
+    fn create_paging_buttons_for_list() -> NavButtons {
+        let paging = gtk::Box::new(Horizontal, 0);
+        let prev_icon = Image::from_icon_name(Some("go-previous"), IconSize::SmallToolbar);
+        let prev_button = Button::new();
+        prev_button.add(&prev_icon);
+        paging.pack_start(&prev_button, false, false, 0);
+
+        let next_icon = Image::from_icon_name(Some("go-next"), IconSize::SmallToolbar);
+        let next_button = Button::new();
+        next_button.add(&next_icon);
+        paging.pack_start(&next_button, false, false, 0);
+
+        NavButtons {
+            container: paging,
+            prev: prev_button,
+            next: next_button,
+        }
     }
 }
@@ -0,0 +1,582 @@
+//! the table editor, a dialog to create a table or alter one:
+//! the columns are added, renamed, dropped or changed, and indexes and foreign keys are added.
+//! The statements are previewed before they are run,
+//! and on a guarded connection they run once the name of the connection is typed.
+use crate::catalog::{self, ForeignKey};
+use crate::db::{Platform, SharedDb};
+use crate::ddl::{self, ColumnDef, IndexDef, Script, TableDef};
+use crate::guard;
+use glib::Type;
+use gtk;
+use gtk::prelude::*;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::{
+    Adjustment, Button, ButtonsType, CellRendererCombo, CellRendererText, CellRendererToggle,
+    Dialog, DialogFlags, Entry, Label, ListStore, MessageDialog, MessageType, Notebook,
+    ResponseType, ScrolledWindow, TextView, TreeView, TreeViewColumn, Window,
+};
+
+// the columns of the store of the columns
+/// the name in the database, empty for an added column
+static ORIGINAL: u32 = 0;
+static NAME: u32 = 1;
+static DATA_TYPE: u32 = 2;
+static NULLABLE: u32 = 3;
+/// an sql expression, empty for none
+static DEFAULT: u32 = 4;
+static PRIMARY_KEY: u32 = 5;
+
+// the columns of the stores of the indexes and foreign keys
+static INDEX_NAME: u32 = 0;
+/// the column names separated by commas
+static INDEX_COLUMNS: u32 = 1;
+static UNIQUE: u32 = 2;
+static KEY_COLUMNS: u32 = 0;
+static REF_TABLE: u32 = 1;
+static REF_COLUMNS: u32 = 2;
+/// whether the index or foreign key is added, the existing ones are not edited
+static ADDED: u32 = 3;
+
+#[derive(Clone)]
+pub struct TableEditor {
+    dialog: Dialog,
+    db: SharedDb,
+    platform: Platform,
+    /// the definition of the altered table, none when creating one
+    original: Option<TableDef>,
+    name: Entry,
+    columns: ListStore,
+    column_list: TreeView,
+    indexes: ListStore,
+    index_list: TreeView,
+    foreign_keys: ListStore,
+    key_list: TreeView,
+}
+
+impl TableEditor {
+    /// `original` is the table to alter, a new table is created without it
+    pub fn new(
+        parent: Option<&Window>,
+        db: SharedDb,
+        platform: Platform,
+        original: Option<TableDef>,
+    ) -> Self {
+        let title = match &original {
+            Some(table) => format!("Alter {}", table.name),
+            None => "Create table".to_string(),
+        };
+        let dialog = Dialog::with_buttons(
+            Some(&title),
+            parent,
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Cancel", ResponseType::Cancel),
+                ("Preview SQL", ResponseType::Accept),
+            ],
+        );
+        dialog.set_default_size(700, 450);
+        let columns = ListStore::new(&[
+            Type::String,
+            Type::String,
+            Type::String,
+            Type::Bool,
+            Type::String,
+            Type::Bool,
+        ]);
+        let indexes = ListStore::new(&[Type::String, Type::String, Type::Bool, Type::Bool]);
+        let foreign_keys = ListStore::new(&[Type::String, Type::String, Type::String, Type::Bool]);
+        let editor = TableEditor {
+            dialog: dialog,
+            db: db,
+            platform: platform,
+            column_list: Self::create_column_list(&columns, platform),
+            columns: columns,
+            index_list: Self::create_list(
+                &indexes,
+                &[("Index", INDEX_NAME), ("Columns", INDEX_COLUMNS)],
+                Some(("Unique", UNIQUE)),
+            ),
+            indexes: indexes,
+            key_list: Self::create_list(
+                &foreign_keys,
+                &[
+                    ("Columns", KEY_COLUMNS),
+                    ("References", REF_TABLE),
+                    ("Referenced columns", REF_COLUMNS),
+                ],
+                None,
+            ),
+            foreign_keys: foreign_keys,
+            name: Entry::new(),
+            original: original,
+        };
+        editor.build_content();
+        editor.fill();
+        editor
+    }
+
+    fn create_column_list(store: &ListStore, platform: Platform) -> TreeView {
+        let list = TreeView::new();
+        list.set_model(Some(store));
+        Self::append_text_column(&list, store, "Column", NAME, None);
+        let types = ListStore::new(&[Type::String]);
+        for data_type in ddl::common_types(platform).iter() {
+            let iter = types.append();
+            types.set_value(&iter, 0, &data_type.to_value());
+        }
+        let type_cell = CellRendererCombo::new();
+        type_cell.set_property_model(Some(&types));
+        type_cell.set_property_text_column(0);
+        type_cell.set_property_has_entry(true);
+        type_cell.set_property_editable(true);
+        let type_column = TreeViewColumn::new();
+        type_column.pack_start(&type_cell, true);
+        type_column.add_attribute(&type_cell, "text", DATA_TYPE as i32);
+        type_column.set_title("Type");
+        type_column.set_expand(true);
+        list.append_column(&type_column);
+        let edited_store = store.clone();
+        type_cell.connect_edited(move |_, path, text| {
+            if let Some(iter) = edited_store.get_iter(&path) {
+                edited_store.set_value(&iter, DATA_TYPE, &text.trim().to_value());
+            }
+        });
+        Self::append_toggle_column(&list, store, "Null", NULLABLE, None);
+        Self::append_text_column(&list, store, "Default", DEFAULT, None);
+        Self::append_toggle_column(&list, store, "Key", PRIMARY_KEY, None);
+        list
+    }
+
+    /// a list of text columns and a toggle, editable on the added rows
+    fn create_list(
+        store: &ListStore,
+        columns: &[(&str, u32)],
+        toggle: Option<(&str, u32)>,
+    ) -> TreeView {
+        let list = TreeView::new();
+        list.set_model(Some(store));
+        for (title, column) in columns.iter() {
+            Self::append_text_column(&list, store, title, *column, Some(ADDED));
+        }
+        if let Some((title, column)) = toggle {
+            Self::append_toggle_column(&list, store, title, column, Some(ADDED));
+        }
+        list
+    }
+
+    fn append_text_column(
+        list: &TreeView,
+        store: &ListStore,
+        title: &str,
+        column: u32,
+        editable: Option<u32>,
+    ) {
+        let cell = CellRendererText::new();
+        let tree_column = TreeViewColumn::new();
+        tree_column.pack_start(&cell, true);
+        tree_column.add_attribute(&cell, "text", column as i32);
+        match editable {
+            Some(editable) => tree_column.add_attribute(&cell, "editable", editable as i32),
+            None => cell.set_property_editable(true),
+        }
+        tree_column.set_title(title);
+        tree_column.set_expand(true);
+        list.append_column(&tree_column);
+        let edited_store = store.clone();
+        cell.connect_edited(move |_, path, text| {
+            if let Some(iter) = edited_store.get_iter(&path) {
+                edited_store.set_value(&iter, column, &text.trim().to_value());
+            }
+        });
+    }
+
+    fn append_toggle_column(
+        list: &TreeView,
+        store: &ListStore,
+        title: &str,
+        column: u32,
+        editable: Option<u32>,
+    ) {
+        let cell = CellRendererToggle::new();
+        let tree_column = TreeViewColumn::new();
+        tree_column.pack_start(&cell, false);
+        tree_column.add_attribute(&cell, "active", column as i32);
+        if let Some(editable) = editable {
+            tree_column.add_attribute(&cell, "activatable", editable as i32);
+        }
+        tree_column.set_title(title);
+        list.append_column(&tree_column);
+        let toggled_store = store.clone();
+        cell.connect_toggled(move |cell, path| {
+            if let Some(iter) = toggled_store.get_iter(&path) {
+                toggled_store.set_value(&iter, column, &(!cell.get_active()).to_value());
+            }
+        });
+    }
+
+    fn build_content(&self) {
+        let name = gtk::Box::new(Horizontal, 5);
+        name.add(&Label::new(Some("Table")));
+        name.pack_start(&self.name, true, true, 0);
+        // a table is renamed with sql, not here
+        self.name.set_sensitive(self.original.is_none());
+
+        let notebook = Notebook::new();
+        let pages: Vec<(&str, &TreeView, Box<dyn Fn(&Self)>)> = vec![
+            (
+                "Columns",
+                &self.column_list,
+                Box::new(|this: &Self| this.add_column()),
+            ),
+            (
+                "Indexes",
+                &self.index_list,
+                Box::new(|this: &Self| {
+                    let (_, table) = catalog::split_table_name(&this.table_name());
+                    let name = format!("{}_idx{}", table, this.row_count(&this.indexes));
+                    Self::append_row(
+                        &this.index_list,
+                        &this.indexes,
+                        &[&name, &"", &false, &true],
+                    );
+                }),
+            ),
+            (
+                "Foreign keys",
+                &self.key_list,
+                Box::new(|this: &Self| {
+                    Self::append_row(&this.key_list, &this.foreign_keys, &[&"", &"", &"", &true]);
+                }),
+            ),
+        ];
+        for (title, list, add) in pages {
+            let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+            scroll.add(list);
+            scroll.set_vexpand(true);
+            let add_button = Button::with_label("Add");
+            let this = self.clone();
+            add_button.connect_clicked(move |_| add(&this));
+            let remove_button = Button::with_label("Remove");
+            let removed_list = list.clone();
+            remove_button.connect_clicked(move |_| Self::remove_selected(&removed_list));
+            let buttons = gtk::Box::new(Vertical, 5);
+            buttons.add(&add_button);
+            buttons.add(&remove_button);
+            let page = gtk::Box::new(Horizontal, 5);
+            page.set_border_width(5);
+            page.pack_start(&scroll, true, true, 0);
+            page.pack_start(&buttons, false, false, 0);
+            notebook.append_page(&page, Some(&Label::new(Some(title))));
+        }
+
+        let content = self.dialog.get_content_area();
+        content.set_spacing(10);
+        content.set_border_width(10);
+        content.pack_start(&name, false, false, 0);
+        content.pack_start(&notebook, true, true, 0);
+    }
+
+    /// put the original definition in the widgets, or a key column for a new table
+    fn fill(&self) {
+        let table = match &self.original {
+            Some(table) => table.clone(),
+            None => {
+                let mut id = ColumnDef::new("id", ddl::common_types(self.platform)[0]);
+                id.nullable = false;
+                id.primary_key = true;
+                TableDef {
+                    columns: vec![id],
+                    ..TableDef::default()
+                }
+            }
+        };
+        self.name.set_text(&table.name);
+        for column in table.columns.iter() {
+            let iter = self.columns.append();
+            self.columns.set(
+                &iter,
+                &[ORIGINAL, NAME, DATA_TYPE, NULLABLE, DEFAULT, PRIMARY_KEY],
+                &[
+                    &column.original.as_deref().unwrap_or(""),
+                    &column.name,
+                    &column.data_type,
+                    &column.nullable,
+                    &column.default.as_deref().unwrap_or(""),
+                    &column.primary_key,
+                ],
+            );
+        }
+        for index in table.indexes.iter() {
+            let iter = self.indexes.append();
+            self.indexes.set(
+                &iter,
+                &[INDEX_NAME, INDEX_COLUMNS, UNIQUE, ADDED],
+                &[
+                    &index.name,
+                    &index.columns.join(", "),
+                    &index.unique,
+                    &false,
+                ],
+            );
+        }
+        for key in table.foreign_keys.iter() {
+            let iter = self.foreign_keys.append();
+            self.foreign_keys.set(
+                &iter,
+                &[KEY_COLUMNS, REF_TABLE, REF_COLUMNS, ADDED],
+                &[
+                    &key.columns.join(", "),
+                    &key.ref_table,
+                    &key.ref_columns.join(", "),
+                    &false,
+                ],
+            );
+        }
+    }
+
+    fn table_name(&self) -> String {
+        self.name.get_text().trim().to_string()
+    }
+
+    fn row_count(&self, store: &ListStore) -> i32 {
+        store.iter_n_children(None) + 1
+    }
+
+    fn add_column(&self) {
+        let name = format!("column{}", self.row_count(&self.columns));
+        let data_type = ddl::common_types(self.platform)[0];
+        Self::append_row(
+            &self.column_list,
+            &self.columns,
+            &[&"", &name, &data_type, &true, &"", &false],
+        );
+    }
+
+    /// append a row and start editing its first column
+    fn append_row(list: &TreeView, store: &ListStore, values: &[&dyn ToValue]) {
+        let iter = store.append();
+        let columns: Vec<u32> = (0..values.len() as u32).collect();
+        store.set(&iter, &columns, values);
+        let path = store.get_path(&iter);
+        let column = list.get_column(0);
+        list.grab_focus();
+        list.set_cursor(&path, column.as_ref(), true);
+    }
+
+    fn remove_selected(list: &TreeView) {
+        let (model, iter) = match list.get_selection().get_selected() {
+            Some(selected) => selected,
+            None => return,
+        };
+        if let Some(store) = model.downcast_ref::<ListStore>() {
+            store.remove(&iter);
+        }
+    }
+
+    /// the text and the flags of each row of a store
+    fn rows(store: &ListStore, texts: &[u32], flags: &[u32]) -> Vec<(Vec<String>, Vec<bool>)> {
+        let mut rows = vec![];
+        let iter = match store.get_iter_first() {
+            Some(iter) => iter,
+            None => return rows,
+        };
+        loop {
+            let text = texts
+                .iter()
+                .map(|c| {
+                    store
+                        .get_value(&iter, *c as i32)
+                        .get::<String>()
+                        .ok()
+                        .flatten()
+                        .unwrap_or_default()
+                })
+                .collect();
+            let flag = flags
+                .iter()
+                .map(|c| {
+                    store
+                        .get_value(&iter, *c as i32)
+                        .get_some()
+                        .unwrap_or(false)
+                })
+                .collect();
+            rows.push((text, flag));
+            if !store.iter_next(&iter) {
+                return rows;
+            }
+        }
+    }
+
+    fn split_names(names: &str) -> Vec<String> {
+        names
+            .split(',')
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .collect()
+    }
+
+    /// the definition as edited
+    fn definition(&self) -> TableDef {
+        let original = self.original.clone().unwrap_or_default();
+        let columns = Self::rows(
+            &self.columns,
+            &[ORIGINAL, NAME, DATA_TYPE, DEFAULT],
+            &[NULLABLE, PRIMARY_KEY],
+        );
+        let indexes = Self::rows(&self.indexes, &[INDEX_NAME, INDEX_COLUMNS], &[UNIQUE]);
+        let foreign_keys = Self::rows(
+            &self.foreign_keys,
+            &[KEY_COLUMNS, REF_TABLE, REF_COLUMNS],
+            &[],
+        );
+        let columns = columns
+            .into_iter()
+            .map(|(text, flags)| {
+                let name = Some(text[0].clone()).filter(|o| !o.is_empty());
+                // the editor does not show the collation and autoincrement, they are kept
+                let kept = original
+                    .columns
+                    .iter()
+                    .find(|c| Some(&c.name) == name.as_ref());
+                ColumnDef {
+                    original: name.clone(),
+                    name: text[1].clone(),
+                    data_type: text[2].clone(),
+                    default: Some(text[3].clone()).filter(|d| !d.is_empty()),
+                    nullable: flags[0],
+                    primary_key: flags[1],
+                    collation: kept.and_then(|c| c.collation.clone()),
+                    autoincrement: kept.map_or(false, |c| c.autoincrement),
+                }
+            })
+            .collect();
+        TableDef {
+            name: self.table_name(),
+            columns: columns,
+            indexes: indexes
+                .into_iter()
+                .map(|(text, flags)| IndexDef {
+                    name: text[0].clone(),
+                    columns: Self::split_names(&text[1]),
+                    unique: flags[0],
+                })
+                .collect(),
+            foreign_keys: foreign_keys
+                .into_iter()
+                .map(|(text, _)| ForeignKey {
+                    columns: Self::split_names(&text[0]),
+                    ref_table: text[1].clone(),
+                    ref_columns: Self::split_names(&text[2]),
+                })
+                .collect(),
+            ..original
+        }
+    }
+
+    fn script(&self) -> Result<Script, String> {
+        let table = self.definition();
+        match &self.original {
+            Some(original) => table.alter_script(self.platform, original),
+            None => table.create_script(self.platform),
+        }
+    }
+
+    /// show the statements, true when they are to be run
+    fn preview(&self, script: &Script) -> bool {
+        let dialog = Dialog::with_buttons(
+            Some("SQL preview"),
+            Some(&self.dialog),
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Back", ResponseType::Cancel),
+                ("Run", ResponseType::Accept),
+            ],
+        );
+        dialog.set_default_size(600, 400);
+        let text = TextView::new();
+        text.set_monospace(true);
+        text.set_editable(false);
+        if let Some(buffer) = text.get_buffer() {
+            buffer.set_text(&script.preview());
+        }
+        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scroll.add(&text);
+        scroll.set_vexpand(true);
+        let content = dialog.get_content_area();
+        content.set_spacing(10);
+        content.set_border_width(10);
+        content.pack_start(&scroll, true, true, 0);
+        if !script.transactional {
+            let warning = Label::new(Some(
+                "The statements can not be rolled back, those run before a failing one stay.",
+            ));
+            warning.set_xalign(0.0);
+            content.add(&warning);
+        }
+        dialog.show_all();
+        let run = dialog.run() == ResponseType::Accept;
+        dialog.close();
+        run
+    }
+
+    /// true unless the connection is guarded and its name was not typed to run the script
+    fn confirm_guarded(&self, script: &Script) -> bool {
+        let guard = self
+            .db
+            .borrow()
+            .as_ref()
+            .and_then(|db| db.session().guard.clone());
+        match guard {
+            Some(name) => {
+                let parent = self.dialog.upcast_ref::<Window>();
+                guard::confirm_save(Some(parent), &name, &script.preview())
+            }
+            None => true,
+        }
+    }
+
+    fn show_error(&self, message: &str) {
+        let error = MessageDialog::new(
+            Some(&self.dialog),
+            DialogFlags::MODAL,
+            MessageType::Error,
+            ButtonsType::Close,
+            message,
+        );
+        error.run();
+        error.close();
+    }
+
+    /// Until the statements are run or the editor is cancelled.
+    /// Returns the name of the table that was created or altered.
+    pub fn run(&self) -> Option<String> {
+        self.dialog.show_all();
+        let mut done = None;
+        while self.dialog.run() == ResponseType::Accept {
+            let script = match self.script() {
+                Ok(script) => script,
+                Err(e) => {
+                    self.show_error(&e);
+                    continue;
+                }
+            };
+            if !self.preview(&script) || !self.confirm_guarded(&script) {
+                continue;
+            }
+            let result = match self.db.borrow_mut().as_mut() {
+                Some(db) => script.run(db.as_mut()).map_err(|e| e.to_string()),
+                None => Err("Not connected".to_string()),
+            };
+            match result {
+                Ok(()) => {
+                    done = Some(self.table_name());
+                    break;
+                }
+                Err(e) => self.show_error(&format!("The statements failed: {}", e)),
+            }
+        }
+        self.dialog.close();
+        done
+    }
+}
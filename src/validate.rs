@@ -0,0 +1,597 @@
+//! client side validation of the edits, done before they are saved
+//!
+//! The rules come from the table definition: not null, the length of varchar columns,
+//! the precision of numeric columns, check constraints that can be parsed and unique keys.
+//! Extra rules such as a regex pattern or a range can be set per column in the table config.
+use crate::catalog::ColumnInfo;
+use crate::config::TableConfig;
+use crate::db::{Platform, Value};
+use crate::sql::{tokenize, Token, TokenKind};
+use regex::Regex;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Column(String),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+/// a check constraint this client knows how to evaluate
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    Compare(Operand, Op, Operand),
+    Between(Operand, Operand, Operand),
+    In(Operand, Vec<Operand>),
+    NotNull(Operand),
+    And(Vec<Check>),
+}
+
+impl Check {
+    /// parse a check constraint such as `CHECK ((price > (0)::numeric))`,
+    /// `None` when it uses anything more than comparisons, `IN`, `BETWEEN` and `AND`
+    pub fn parse(expr: &str) -> Option<Check> {
+        let tokens = strip_casts(
+            tokenize(expr)
+                .into_iter()
+                .filter(|t| t.kind != TokenKind::Whitespace && t.kind != TokenKind::Comment)
+                .collect(),
+        );
+        let start = match tokens.first() {
+            Some(t) if t.text.eq_ignore_ascii_case("CHECK") => 1,
+            _ => 0,
+        };
+        let mut parser = CheckParser {
+            tokens: &tokens,
+            pos: start,
+        };
+        let check = parser.conjunction()?;
+        if parser.pos == tokens.len() {
+            Some(check)
+        } else {
+            None
+        }
+    }
+
+    /// the columns this check is about
+    pub fn columns(&self) -> Vec<String> {
+        let mut operands = vec![];
+        match self {
+            Check::Compare(a, _, b) => operands.extend(vec![a, b]),
+            Check::Between(a, low, high) => operands.extend(vec![a, low, high]),
+            Check::In(a, list) => {
+                operands.push(a);
+                operands.extend(list.iter());
+            }
+            Check::NotNull(a) => operands.push(a),
+            Check::And(checks) => return checks.iter().flat_map(|c| c.columns()).collect(),
+        }
+        operands
+            .into_iter()
+            .filter_map(|o| match o {
+                Operand::Column(c) => Some(c.to_string()),
+                Operand::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    /// Whether the check holds for these values.
+    /// Like in sql, a check on a null value is not a violation.
+    pub fn holds(&self, value_of: &dyn Fn(&str) -> Value) -> bool {
+        let resolve = |operand: &Operand| match operand {
+            Operand::Column(c) => value_of(c),
+            Operand::Literal(l) => Some(l.to_string()),
+        };
+        match self {
+            Check::Compare(a, op, b) => match (resolve(a), resolve(b)) {
+                (Some(a), Some(b)) => compare(&a, *op, &b),
+                _ => true,
+            },
+            Check::Between(a, low, high) => match (resolve(a), resolve(low), resolve(high)) {
+                (Some(a), Some(low), Some(high)) => {
+                    compare(&a, Op::GtEq, &low) && compare(&a, Op::LtEq, &high)
+                }
+                _ => true,
+            },
+            Check::In(a, list) => match resolve(a) {
+                Some(a) => list
+                    .iter()
+                    .any(|item| resolve(item).map(|i| compare(&a, Op::Eq, &i)) == Some(true)),
+                None => true,
+            },
+            Check::NotNull(a) => resolve(a).is_some(),
+            Check::And(checks) => checks.iter().all(|c| c.holds(value_of)),
+        }
+    }
+}
+
+fn compare(a: &str, op: Op, b: &str) -> bool {
+    let ordering = match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => match a.partial_cmp(&b) {
+            Some(ordering) => ordering,
+            None => return true,
+        },
+        _ => a.cmp(b),
+    };
+    use std::cmp::Ordering::*;
+    match op {
+        Op::Eq => ordering == Equal,
+        Op::NotEq => ordering != Equal,
+        Op::Lt => ordering == Less,
+        Op::LtEq => ordering != Greater,
+        Op::Gt => ordering == Greater,
+        Op::GtEq => ordering != Less,
+    }
+}
+
+/// remove the `::type` casts postgres puts all over the constraint definitions
+fn strip_casts(tokens: Vec<Token>) -> Vec<Token> {
+    let mut stripped = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_cast =
+            tokens[i].text == ":" && tokens.get(i + 1).map(|t| t.text.as_str()) == Some(":");
+        if !is_cast {
+            stripped.push(tokens[i].clone());
+            i += 1;
+            continue;
+        }
+        i += 2;
+        // the type name may be several words like `character varying` and may be an array
+        while i < tokens.len() && tokens[i].kind == TokenKind::Identifier {
+            i += 1;
+        }
+        if tokens.get(i).map(|t| t.text.as_str()) == Some("[")
+            && tokens.get(i + 1).map(|t| t.text.as_str()) == Some("]")
+        {
+            i += 2;
+        }
+    }
+    stripped
+}
+
+struct CheckParser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> CheckParser<'a> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.text.as_str())
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.peek().map(|t| t.eq_ignore_ascii_case(text)) == Some(true)
+    }
+
+    fn expect(&mut self, text: &str) -> Option<()> {
+        if self.peek_is(text) {
+            self.pos += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn conjunction(&mut self) -> Option<Check> {
+        let mut checks = vec![self.unary()?];
+        while self.peek_is("AND") {
+            self.pos += 1;
+            checks.push(self.unary()?);
+        }
+        if checks.len() == 1 {
+            checks.pop()
+        } else {
+            Some(Check::And(checks))
+        }
+    }
+
+    fn unary(&mut self) -> Option<Check> {
+        if self.peek_is("(") {
+            // either a parenthesized expression or a predicate starting with `(column)`
+            let start = self.pos;
+            self.pos += 1;
+            if let Some(check) = self.conjunction() {
+                if self.expect(")").is_some() {
+                    return Some(check);
+                }
+            }
+            self.pos = start;
+        }
+        self.predicate()
+    }
+
+    fn predicate(&mut self) -> Option<Check> {
+        let left = self.operand()?;
+        if self.peek_is("BETWEEN") {
+            self.pos += 1;
+            let low = self.operand()?;
+            self.expect("AND")?;
+            let high = self.operand()?;
+            return Some(Check::Between(left, low, high));
+        }
+        if self.peek_is("IN") {
+            self.pos += 1;
+            self.expect("(")?;
+            let list = self.operand_list(")")?;
+            return Some(Check::In(left, list));
+        }
+        if self.peek_is("IS") {
+            self.pos += 1;
+            self.expect("NOT")?;
+            self.expect("NULL")?;
+            return Some(Check::NotNull(left));
+        }
+        let op = self.operator()?;
+        if op == Op::Eq && self.peek_is("ANY") {
+            // postgres shows `IN (..)` as `= ANY (ARRAY[..])`
+            self.pos += 1;
+            self.expect("(")?;
+            let nested = self.expect("(").is_some();
+            self.expect("ARRAY")?;
+            self.expect("[")?;
+            let list = self.operand_list("]")?;
+            if nested {
+                self.expect(")")?;
+            }
+            self.expect(")")?;
+            return Some(Check::In(left, list));
+        }
+        let right = self.operand()?;
+        Some(Check::Compare(left, op, right))
+    }
+
+    fn operator(&mut self) -> Option<Op> {
+        let first = self.peek()?.to_string();
+        self.pos += 1;
+        let second = self.peek().map(|s| s.to_string());
+        let (op, consumed) = match (first.as_str(), second.as_deref()) {
+            ("<", Some("=")) => (Op::LtEq, true),
+            ("<", Some(">")) => (Op::NotEq, true),
+            (">", Some("=")) => (Op::GtEq, true),
+            ("!", Some("=")) => (Op::NotEq, true),
+            ("<", _) => (Op::Lt, false),
+            (">", _) => (Op::Gt, false),
+            ("=", _) => (Op::Eq, false),
+            _ => return None,
+        };
+        if consumed {
+            self.pos += 1;
+        }
+        Some(op)
+    }
+
+    fn operand_list(&mut self, close: &str) -> Option<Vec<Operand>> {
+        let mut list = vec![self.operand()?];
+        while self.peek_is(",") {
+            self.pos += 1;
+            list.push(self.operand()?);
+        }
+        self.expect(close)?;
+        Some(list)
+    }
+
+    fn operand(&mut self) -> Option<Operand> {
+        if self.peek_is("(") {
+            self.pos += 1;
+            let operand = self.operand()?;
+            self.expect(")")?;
+            return Some(operand);
+        }
+        let negative = self.peek_is("-");
+        if negative {
+            self.pos += 1;
+        }
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        match token.kind {
+            TokenKind::Number if negative => Some(Operand::Literal(format!("-{}", token.text))),
+            TokenKind::Number => Some(Operand::Literal(token.text.to_string())),
+            TokenKind::Literal if !negative => {
+                let inner = &token.text[1..token.text.len().saturating_sub(1).max(1)];
+                Some(Operand::Literal(inner.replace("''", "'")))
+            }
+            TokenKind::Identifier if !negative => {
+                Some(Operand::Column(crate::sql::unquote(&token.text)))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ColumnRules {
+    column: String,
+    data_type: String,
+    required: bool,
+    has_default: bool,
+    max_length: Option<usize>,
+    precision: Option<(u32, u32)>,
+    pattern: Option<Regex>,
+    min: Option<f64>,
+    max: Option<f64>,
+    message: Option<String>,
+}
+
+/// the validation rules of a table
+#[derive(Debug, Default)]
+pub struct TableRules {
+    columns: Vec<ColumnRules>,
+    checks: Vec<(String, Check)>,
+    pub unique_keys: Vec<Vec<String>>,
+}
+
+impl TableRules {
+    pub fn new(
+        columns: &[ColumnInfo],
+        checks: &[String],
+        unique_keys: Vec<Vec<String>>,
+        config: &TableConfig,
+    ) -> Self {
+        let mut rules: Vec<ColumnRules> = columns
+            .iter()
+            .map(|c| ColumnRules {
+                column: c.name.to_string(),
+                data_type: c.data_type.to_lowercase(),
+                required: !c.nullable,
+                has_default: c.default.is_some(),
+                max_length: c.max_length,
+                precision: c.precision,
+                pattern: None,
+                min: None,
+                max: None,
+                message: None,
+            })
+            .collect();
+        for (name, column) in config.columns.iter() {
+            let index = match rules.iter().position(|r| &r.column == name) {
+                Some(index) => index,
+                None => {
+                    rules.push(ColumnRules {
+                        column: name.to_string(),
+                        data_type: String::new(),
+                        required: false,
+                        has_default: false,
+                        max_length: None,
+                        precision: None,
+                        pattern: None,
+                        min: None,
+                        max: None,
+                        message: None,
+                    });
+                    rules.len() - 1
+                }
+            };
+            let rule = &mut rules[index];
+            rule.pattern = column.pattern.as_ref().and_then(|p| match Regex::new(p) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    println!("ignoring invalid pattern of {}: {}", name, e);
+                    None
+                }
+            });
+            rule.min = column.min;
+            rule.max = column.max;
+            rule.message = column.message.clone();
+        }
+        TableRules {
+            columns: rules,
+            checks: checks
+                .iter()
+                .filter_map(|c| Check::parse(c).map(|check| (c.to_string(), check)))
+                .collect(),
+            unique_keys: unique_keys,
+        }
+    }
+
+    /// The problems with this record, as the column and the message.
+    /// The defaults of not null columns are filled in by the database for new records.
+    pub fn validate(
+        &self,
+        columns: &[String],
+        values: &[Value],
+        is_new: bool,
+    ) -> Vec<(String, String)> {
+        let value_of = |column: &str| -> Value {
+            columns
+                .iter()
+                .position(|c| c == column)
+                .and_then(|i| values.get(i).cloned())
+                .flatten()
+        };
+        let mut errors = vec![];
+        for rule in self.columns.iter() {
+            if !columns.contains(&rule.column) {
+                continue;
+            }
+            let message = match value_of(&rule.column) {
+                None if rule.required && !(is_new && rule.has_default) => {
+                    Some("is required".to_string())
+                }
+                None => None,
+                Some(value) => rule.check(&value),
+            };
+            if let Some(message) = message {
+                errors.push((rule.column.to_string(), message));
+            }
+        }
+        for (definition, check) in self.checks.iter() {
+            if !check.holds(&value_of) {
+                for column in check.columns() {
+                    if columns.contains(&column) {
+                        errors.push((column, format!("violates {}", definition)));
+                    }
+                }
+            }
+        }
+        errors
+    }
+}
+
+impl ColumnRules {
+    fn check(&self, value: &str) -> Option<String> {
+        if let Some(max_length) = self.max_length {
+            if value.chars().count() > max_length {
+                return Some(format!("is longer than {} characters", max_length));
+            }
+        }
+        if let Some(message) = check_type(&self.data_type, self.precision, value) {
+            return Some(message);
+        }
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(value) {
+                return Some(self.message_or(&format!("does not match {}", pattern.as_str())));
+            }
+        }
+        if self.min.is_some() || self.max.is_some() {
+            let number: f64 = match value.trim().parse() {
+                Ok(number) => number,
+                Err(_) => return Some(self.message_or("must be a number")),
+            };
+            if let Some(min) = self.min.filter(|min| number < *min) {
+                return Some(self.message_or(&format!("must be at least {}", min)));
+            }
+            if let Some(max) = self.max.filter(|max| number > *max) {
+                return Some(self.message_or(&format!("must be at most {}", max)));
+            }
+        }
+        None
+    }
+
+    fn message_or(&self, default: &str) -> String {
+        self.message.clone().unwrap_or_else(|| default.to_string())
+    }
+}
+
+fn check_type(data_type: &str, precision: Option<(u32, u32)>, value: &str) -> Option<String> {
+    let value = value.trim();
+    match data_type {
+        "smallint" | "int2" => match value.parse::<i16>() {
+            Ok(_) => None,
+            Err(_) => Some("must be a whole number between -32768 and 32767".to_string()),
+        },
+        "integer" | "int" | "int4" | "bigint" | "int8" => match value.parse::<i64>() {
+            Ok(_) => None,
+            Err(_) => Some("must be a whole number".to_string()),
+        },
+        "real" | "float" | "float4" | "float8" | "double" | "double precision" => {
+            match value.parse::<f64>() {
+                Ok(_) => None,
+                Err(_) => Some("must be a number".to_string()),
+            }
+        }
+        _ => {
+            let (precision, scale) = precision?;
+            let unsigned = value.trim_start_matches(|c| c == '-' || c == '+');
+            let mut parts = unsigned.splitn(2, '.');
+            let integer = parts.next().unwrap_or("");
+            let fraction = parts.next().unwrap_or("");
+            let is_number = !(integer.is_empty() && fraction.is_empty())
+                && integer.chars().all(|c| c.is_ascii_digit())
+                && fraction.chars().all(|c| c.is_ascii_digit());
+            if !is_number {
+                return Some("must be a number".to_string());
+            }
+            let integer_digits = integer.trim_start_matches('0').len() as u32;
+            // a hand written scale may be more than the precision
+            let integer_max = precision.saturating_sub(scale);
+            if integer_digits > integer_max || fraction.len() as u32 > scale {
+                Some(format!(
+                    "must have at most {} digits before and {} after the decimal point",
+                    integer_max, scale
+                ))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Count the other records that have the same values on this unique key,
+/// `exclude` is the primary key of the record itself when it is already saved.
+pub fn unique_query(
+    platform: Platform,
+    table: &str,
+    key: &[String],
+    exclude: Option<&[String]>,
+) -> String {
+    let mut n = 0;
+    let mut condition = |columns: &[String]| -> String {
+        columns
+            .iter()
+            .map(|c| {
+                n += 1;
+                format!("{} = {}", platform.quote_ident(c), platform.placeholder(n))
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    };
+    let mut sql = format!(
+        "SELECT COUNT(*) FROM {} WHERE {}",
+        platform.quote_table(table),
+        condition(key)
+    );
+    if let Some(exclude) = exclude {
+        sql.push_str(&format!(" AND NOT ({})", condition(exclude)));
+    }
+    sql
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str) -> Operand {
+        Operand::Column(name.to_string())
+    }
+
+    fn literal(value: &str) -> Operand {
+        Operand::Literal(value.to_string())
+    }
+
+    #[test]
+    fn checks_are_parsed_and_evaluated() {
+        let check = Check::parse("CHECK ((price > (0)::numeric))").unwrap();
+        assert_eq!(check, Check::Compare(column("price"), Op::Gt, literal("0")));
+        let check =
+            Check::parse("quantity BETWEEN 1 AND 10 AND status IN ('new', 'paid')").unwrap();
+        assert_eq!(check.columns(), vec!["quantity", "status"]);
+        assert!(Check::parse("length(name) > 2").is_none());
+
+        let row = |quantity: Option<&str>, status: &str| {
+            let quantity = quantity.map(String::from);
+            let status = status.to_string();
+            move |c: &str| match c {
+                "quantity" => quantity.clone(),
+                _ => Some(status.clone()),
+            }
+        };
+        assert!(check.holds(&row(Some("5"), "paid")));
+        assert!(!check.holds(&row(Some("11"), "paid")));
+        assert!(!check.holds(&row(Some("5"), "lost")));
+        // like in sql, a null is not a violation
+        assert!(check.holds(&row(None, "new")));
+        // compared as numbers when both are
+        let check = Check::parse("total >= 9").unwrap();
+        assert!(check.holds(&|_: &str| Some("10".to_string())));
+    }
+
+    #[test]
+    fn numbers_fit_the_precision() {
+        assert_eq!(check_type("numeric", Some((5, 2)), "123.45"), None);
+        assert!(check_type("numeric", Some((5, 2)), "1234.5").is_some());
+        assert!(check_type("numeric", Some((5, 2)), "1.234").is_some());
+        assert!(check_type("numeric", Some((5, 2)), "12a").is_some());
+        // a scale more than the precision allows no integer digit
+        assert_eq!(check_type("numeric", Some((2, 3)), "0.123"), None);
+        assert!(check_type("numeric", Some((2, 3)), "1.5").is_some());
+    }
+}
@@ -1,38 +1,178 @@
+use crate::audit;
+use crate::catalog::{self, DbObject, ObjectKind};
+use crate::compare_tab::{self, CompareSide, CompareTab};
+use crate::config::{self, ConnectionProfile, Settings, TableConfig};
 use crate::connection::Connection;
+use crate::connections::{self, Connections, OpenConnection};
+use crate::console::SqlConsole;
+use crate::db::{self, Database, Session, SharedDb};
+use crate::definition::Definition;
+use crate::diagram_tab::DiagramTab;
+use crate::edit;
+use crate::palette::{CommandPalette, PaletteItem};
+use crate::params::{self, ParamForm};
+use crate::shortcuts;
+use crate::sidebar::Sidebar;
 use crate::tab::DataTab;
-use glib::Type;
-use glib::Value;
+use crate::table_editor::TableEditor;
+use gio::prelude::*;
+use gio::{SimpleAction, SimpleActionGroup};
 use gtk;
 use gtk::prelude::*;
 use gtk::Orientation::{Horizontal, Vertical};
 use gtk::WindowPosition::Center;
 use gtk::WindowType::Toplevel;
 use gtk::{
-    Adjustment, Button, CellRendererText, IconSize, Image, Inhibit, Label, ListStore, Notebook,
-    ReliefStyle, ScrolledWindow, ToolButton, Toolbar, TreeView, TreeViewColumn, Window,
+    Button, ButtonsType, DialogFlags, IconSize, Image, Inhibit, Label, MessageDialog, MessageType,
+    Notebook, ReliefStyle, ResponseType, SeparatorToolItem, ToggleToolButton, ToolButton, ToolItem,
+    Toolbar, Widget, Window,
 };
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// in milliseconds
+static NOTIFICATION_INTERVAL: u32 = 2000;
+static TRANSACTION_INTERVAL: u32 = 1000;
+
+#[derive(Clone)]
+struct WindowToolbar {
+    connect: ToolButton,
+    console: ToolButton,
+    diagram: ToolButton,
+    compare: ToolButton,
+    shortcuts: ToolButton,
+    /// the manual commit mode of the connection, with its open transaction
+    manual_commit: ToggleToolButton,
+    commit: ToolButton,
+    rollback: ToolButton,
+    transaction: Label,
+}
+
+impl WindowToolbar {
+    fn new() -> Self {
+        let open_icon = Image::from_icon_name(Some("network-server"), IconSize::LargeToolbar);
+        let open_button = ToolButton::new::<Image>(Some(&open_icon), Some("Connect to Server"));
+        open_button.set_is_important(true);
+
+        let console_icon =
+            Image::from_icon_name(Some("utilities-terminal"), IconSize::LargeToolbar);
+        let console_button = ToolButton::new::<Image>(Some(&console_icon), Some("SQL Console"));
+        console_button.set_is_important(true);
+
+        let diagram_icon = Image::from_icon_name(Some("x-office-drawing"), IconSize::LargeToolbar);
+        let diagram_button = ToolButton::new::<Image>(Some(&diagram_icon), Some("Diagram"));
+        diagram_button.set_is_important(true);
+
+        let compare_icon = Image::from_icon_name(Some("view-dual"), IconSize::LargeToolbar);
+        let compare_button = ToolButton::new::<Image>(Some(&compare_icon), Some("Compare"));
+        compare_button.set_is_important(true);
+
+        let shortcuts_icon = Image::from_icon_name(
+            Some("preferences-desktop-keyboard-shortcuts"),
+            IconSize::LargeToolbar,
+        );
+        let shortcuts_button =
+            ToolButton::new::<Image>(Some(&shortcuts_icon), Some("Keyboard Shortcuts"));
+
+        let manual_icon = Image::from_icon_name(Some("document-edit"), IconSize::LargeToolbar);
+        let manual_button = ToggleToolButton::new();
+        manual_button.set_icon_widget(Some(&manual_icon));
+        manual_button.set_label(Some("Manual Commit"));
+        manual_button.set_is_important(true);
+        manual_button.set_tooltip_text(Some(
+            "Keep the saves of all the tabs in one transaction until it is committed",
+        ));
+
+        let commit_icon = Image::from_icon_name(Some("document-save"), IconSize::LargeToolbar);
+        let commit_button = ToolButton::new::<Image>(Some(&commit_icon), Some("Commit"));
+        commit_button.set_is_important(true);
+
+        let rollback_icon = Image::from_icon_name(Some("edit-undo"), IconSize::LargeToolbar);
+        let rollback_button = ToolButton::new::<Image>(Some(&rollback_icon), Some("Rollback"));
+        rollback_button.set_is_important(true);
+
+        let transaction = Label::new(None);
+        transaction.set_margin_start(5);
+
+        WindowToolbar {
+            connect: open_button,
+            console: console_button,
+            diagram: diagram_button,
+            compare: compare_button,
+            shortcuts: shortcuts_button,
+            manual_commit: manual_button,
+            commit: commit_button,
+            rollback: rollback_button,
+            transaction: transaction,
+        }
+    }
+
+    // add a toolbar to the vertical box of the window
+    fn build_widget(&self) -> Toolbar {
+        let toolbar = Toolbar::new();
+        toolbar.add(&self.connect);
+        toolbar.add(&self.console);
+        toolbar.add(&self.diagram);
+        toolbar.add(&self.compare);
+        toolbar.add(&self.shortcuts);
+        toolbar.add(&SeparatorToolItem::new());
+        toolbar.add(&self.manual_commit);
+        toolbar.add(&self.commit);
+        toolbar.add(&self.rollback);
+        let status = ToolItem::new();
+        status.add(&self.transaction);
+        toolbar.add(&status);
+        toolbar
+    }
+}
+
+/// a tab of the notebook
+#[derive(Clone)]
+struct Page {
+    widget: Widget,
+    /// none for the tabs opened while not connected
+    connection: Option<OpenConnection>,
+    /// the data tabs are passed the notifications of their connection
+    data_tab: Option<DataTab>,
+}
 
 #[derive(Clone)]
 pub struct DataWindow {
-    window_list: TreeView,
-    list_widget: ScrolledWindow,
-    toolbar: Toolbar,
+    sidebar: Sidebar,
+    toolbar: WindowToolbar,
     open_tabs: Notebook,
+    pages: Rc<RefCell<Vec<Page>>>,
+    connections: Connections,
+    /// when the transactions that were warned about as open too long were started
+    warned_transactions: Rc<RefCell<Vec<Instant>>>,
+    /// read once when the window opens
+    settings: Rc<Settings>,
+    actions: SimpleActionGroup,
+    palette: CommandPalette,
     pub window: Window,
 }
 
 impl DataWindow {
     pub fn new() -> Self {
-        let (window_list, list_widget) = Self::create_window_list();
-        let toolbar = Self::create_toolbar();
+        let sidebar = Sidebar::new();
+        sidebar.set_objects(Self::demo_objects());
+        Self::list_datawindows(&sidebar);
+        let toolbar = WindowToolbar::new();
         let open_tabs = Self::create_tabs();
+        let palette = CommandPalette::new(&open_tabs);
         let window = Window::new(Toplevel);
 
         DataWindow {
-            window_list: window_list,
-            list_widget: list_widget,
+            sidebar: sidebar,
             toolbar: toolbar,
             open_tabs: open_tabs,
+            pages: Rc::new(RefCell::new(vec![])),
+            connections: Connections::default(),
+            warned_transactions: Rc::new(RefCell::new(vec![])),
+            settings: Rc::new(Settings::load()),
+            actions: SimpleActionGroup::new(),
+            palette: palette,
             window: window,
         }
     }
@@ -41,78 +181,664 @@ impl DataWindow {
         self.window.set_title("DataWindow - GTK");
         self.window.set_default_size(1024, 768);
         let vbox = gtk::Box::new(Vertical, 0);
-        vbox.add(&self.toolbar);
+        vbox.add(&self.toolbar.build_widget());
         self.window.add(&vbox);
         let hbox = gtk::Box::new(Horizontal, 0);
         vbox.add(&hbox);
-        hbox.add(&self.list_widget);
+        hbox.add(&self.sidebar.container);
         hbox.add(&self.open_tabs);
         self.window.set_position(Center);
         for i in 0..3 {
             self.add_data_tab(&format!("Window {}", i));
         }
         self.init_window_list_selection();
-        let _this = self.clone();
+        self.init_actions();
+        self.init_toolbar();
+        self.init_shortcuts();
+        self.init_notifications();
+        self.init_transaction_status();
+        let this = self.clone();
         self.window.show_all();
-        self.window.connect_delete_event(|_, _| {
+        self.window.connect_delete_event(move |_, _| {
+            for connection in this.connections.all() {
+                if !this.confirm_rollback(&connection, "Closing the window") {
+                    return Inhibit(true);
+                }
+            }
             gtk::main_quit();
             Inhibit(false)
         });
     }
 
-    // this is a list of the items to be viewed
-    fn create_window_list() -> (TreeView, ScrolledWindow) {
-        let tree = TreeView::new();
-        let column_types = [Type::String];
-        let store = ListStore::new(&column_types);
-        //let model = store.get_model();
-        tree.set_model(Some(&store));
-        tree.set_headers_visible(false);
-        let column = TreeViewColumn::new();
-        let cell = CellRendererText::new();
-        column.pack_start(&cell, true);
-        column.add_attribute(&cell, "text", 0);
-        tree.append_column(&column);
-        let _selection = tree.get_selection();
-        for i in 0..50 {
-            let iter = store.append();
-            store.set_value(&iter, 0, &Value::from(&format!("Window {}", i)));
-        }
-        let scroll = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
-        scroll.add(&tree);
-        scroll.set_size_request(200, 600);
-        (tree, scroll)
+    /// the sidebar shows some demo tables until connected
+    fn demo_objects() -> Vec<DbObject> {
+        (0..50)
+            .map(|i| DbObject {
+                schema: None,
+                name: format!("Window {}", i),
+                kind: ObjectKind::Table,
+                row_estimate: None,
+            })
+            .collect()
     }
 
     fn init_window_list_selection(&self) {
         let this = self.clone();
-        self.window_list.get_selection().connect_changed(move |_| {
-            let (model, iter) = this.window_list.get_selection().get_selected().unwrap();
-            if let Some(_path) = model.get_path(&iter) {
-                let value = model.get_value(&iter, 0);
-                let value_string: String = value
-                    .get()
-                    .expect("must not error")
-                    .expect("must be a string");
-                this.add_data_tab(&value_string);
-                this.window.show_all(); //Notes: Important to call show all in order to redraw the added tabs
-            }
+        self.sidebar.connect_open(move |connection, table| {
+            let connection = connection.and_then(|name| this.connections.find(name));
+            this.open_table(connection.as_ref(), table);
+        });
+        let this = self.clone();
+        self.sidebar.connect_open_datawindow(move |name| {
+            this.open_datawindow(name);
+        });
+        let this = self.clone();
+        self.sidebar.connect_disconnect(move |name| {
+            this.disconnect(name);
         });
     }
 
-    // add a toolbar to the vertical box of the window
-    fn create_toolbar() -> Toolbar {
-        let toolbar = Toolbar::new();
-        let open_icon = Image::from_icon_name(Some("network-server"), IconSize::LargeToolbar);
-        let open_button = ToolButton::new::<Image>(Some(&open_icon), Some("Connect to Server"));
-        open_button.set_is_important(true);
-        open_button.connect_clicked(move |_| {
+    /// the definitions are read again on each connection, to pick up the new ones
+    fn list_datawindows(sidebar: &Sidebar) {
+        let datawindows = Definition::load_all()
+            .into_iter()
+            .map(|d| (d.name.clone(), d.title().to_string()))
+            .collect();
+        sidebar.set_datawindows(datawindows);
+    }
+
+    /// the `win.*` actions, the actions of a data tab are in its own `tab.*` group
+    fn init_actions(&self) {
+        self.window.insert_action_group("win", Some(&self.actions));
+        self.add_action("connect", |this| {
             println!("Openning connection box");
             let connection = Connection::new();
-            connection.build_widget();
+            let this = this.clone();
+            connection.build_widget(move |url, database| {
+                this.add_connection(&connections::url_name(url), None, database);
+            });
         });
-        toolbar.add(&open_button);
-        toolbar
+        self.add_action("console", |this| {
+            let connection = this.current_connection();
+            let console = SqlConsole::new(Self::database_of(connection.as_ref()));
+            this.append_tab(
+                "SQL Console",
+                &console.build_widget(),
+                connection.as_ref(),
+                None,
+            );
+            this.window.show_all();
+        });
+        self.add_action("create-table", |this| this.create_table());
+        self.add_action("diagram", |this| {
+            let connection = this.current_connection();
+            let diagram = DiagramTab::new(Self::database_of(connection.as_ref()));
+            let window = this.clone();
+            let table_connection = connection.clone();
+            diagram.connect_open_table(move |table| {
+                window.open_table(table_connection.as_ref(), table);
+            });
+            this.append_tab(
+                "Diagram",
+                &diagram.build_widget(),
+                connection.as_ref(),
+                None,
+            );
+            this.window.show_all();
+        });
+        self.add_action("compare", |this| this.compare_tables());
+        self.add_action("next-tab", |this| this.switch_tab(true));
+        self.add_action("previous-tab", |this| this.switch_tab(false));
+        self.add_action("close-tab", |this| {
+            if let Some(page) = this.open_tabs.get_current_page() {
+                this.open_tabs.remove_page(Some(page));
+            }
+        });
+        self.add_action("commit", |this| {
+            if let Some(connection) = this.current_connection() {
+                this.commit(&connection);
+            }
+        });
+        self.add_action("rollback", |this| match this.current_connection() {
+            Some(connection) if this.confirm_rollback(&connection, "Rolling back") => {
+                this.rollback(&connection)
+            }
+            _ => (),
+        });
+        self.add_action("palette", |this| this.show_palette());
+        self.add_action("shortcuts", |this| {
+            if let Some(help) = shortcuts::create_help_window() {
+                help.set_transient_for(Some(&this.window));
+                help.show_all();
+            }
+        });
+    }
+
+    /// Add the newly opened database to the connections of the window,
+    /// listening to the changes saved by the other sessions.
+    fn add_connection(
+        &self,
+        name: &str,
+        profile: Option<&ConnectionProfile>,
+        mut database: Box<dyn Database>,
+    ) {
+        if let Err(e) = database.listen(edit::CHANGES_CHANNEL) {
+            println!("not listening to the changes: {}", e);
+        }
+        let db: SharedDb = Rc::new(RefCell::new(Some(database)));
+        let connection = self.connections.add(name, profile, db);
+        if profile.map_or(false, |p| p.confirm_destructive) {
+            if let Some(database) = connection.db.borrow_mut().as_mut() {
+                database.session_mut().guard = Some(connection.name.clone());
+            }
+        }
+        self.reload_window_list(&connection);
+        self.update_transaction_status();
+    }
+
+    /// close a connection, its tabs are left without a database
+    fn disconnect(&self, name: &str) {
+        let connection = match self.connections.find(name) {
+            Some(connection) => connection,
+            None => return,
+        };
+        if !self.confirm_rollback(&connection, "Disconnecting") {
+            return;
+        }
+        self.connections.remove(name);
+        *connection.db.borrow_mut() = None;
+        self.sidebar.remove_connection(name);
+        self.update_transaction_status();
+    }
+
+    /// The connection the actions of the window work on: the one of the current tab,
+    /// else the one selected in the sidebar, else the last opened.
+    fn current_connection(&self) -> Option<OpenConnection> {
+        if let Some(widget) = self
+            .open_tabs
+            .get_nth_page(self.open_tabs.get_current_page())
+        {
+            let pages = self.pages.borrow();
+            let page = pages.iter().find(|page| page.widget == widget);
+            if let Some(connection) = page.and_then(|page| page.connection.clone()) {
+                return Some(connection);
+            }
+        }
+        self.sidebar
+            .selected_connection()
+            .and_then(|name| self.connections.find(&name))
+            .or_else(|| self.connections.last())
+    }
+
+    /// the database of a tab, one that is never connected for a tab opened while not connected
+    fn database_of(connection: Option<&OpenConnection>) -> SharedDb {
+        match connection {
+            Some(connection) => connection.db.clone(),
+            None => Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// the commit mode and open transaction of the connection, none while a save is running
+    fn session_of(connection: &OpenConnection) -> Option<Session> {
+        let db = connection.db.try_borrow().ok()?;
+        db.as_ref().map(|db| db.session().clone())
+    }
+
+    /// the number of statements saved in the open transaction, none when there is none
+    fn pending_statements(connection: &OpenConnection) -> Option<usize> {
+        let session = Self::session_of(connection)?;
+        session.transaction.as_ref().map(|t| t.statements)
+    }
+
+    /// ask whether to go on with what rolls back the open transaction, true when there is none
+    fn confirm_rollback(&self, connection: &OpenConnection, what: &str) -> bool {
+        let statements = match Self::pending_statements(connection) {
+            Some(statements) => statements,
+            None => return true,
+        };
+        let dialog = MessageDialog::new(
+            Some(&self.window),
+            DialogFlags::MODAL,
+            MessageType::Warning,
+            ButtonsType::YesNo,
+            &format!(
+                "{} discards the {} statement(s) saved in the open transaction of {}. Go on?",
+                what, statements, connection.name
+            ),
+        );
+        let answer = dialog.run();
+        dialog.close();
+        answer == ResponseType::Yes
+    }
+
+    /// commit the open transaction and record its changes in the audit log
+    fn commit(&self, connection: &OpenConnection) {
+        let result = match connection.db.borrow_mut().as_mut() {
+            Some(db) => db::commit(db.as_mut()),
+            None => return,
+        };
+        let error = match result {
+            Ok(changes) => match audit::record(&changes) {
+                Ok(()) => None,
+                Err(e) => Some(format!("Committed, unable to write the audit log: {}", e)),
+            },
+            Err(e) => Some(format!("Unable to commit: {}", e)),
+        };
+        self.update_transaction_status();
+        if let Some(error) = error {
+            self.show_error(&error);
+        }
+    }
+
+    /// roll back the open transaction, the data tabs show the rows as they are again
+    fn rollback(&self, connection: &OpenConnection) {
+        let result = match connection.db.borrow_mut().as_mut() {
+            Some(db) => db::rollback(db.as_mut()),
+            None => return,
+        };
+        self.update_transaction_status();
+        if let Err(e) = result {
+            self.show_error(&format!("Unable to roll back: {}", e));
+        }
+        for tab in self.data_tabs_of(&connection.name) {
+            tab.rolled_back();
+        }
+    }
+
+    /// Compare two of the open tables, the current one on the left at first.
+    /// They may be on different connections.
+    fn compare_tables(&self) {
+        let current = self
+            .open_tabs
+            .get_current_page()
+            .and_then(|n| self.open_tabs.get_nth_page(Some(n)));
+        let mut sides = vec![];
+        let mut selected = 0;
+        {
+            let mut pages = self.pages.borrow_mut();
+            pages.retain(|page| page.widget.get_parent().is_some());
+            for page in pages.iter() {
+                let data_tab = match &page.data_tab {
+                    Some(data_tab) => data_tab,
+                    None => continue,
+                };
+                if Some(&page.widget) == current.as_ref() {
+                    selected = sides.len();
+                }
+                let title = match &page.connection {
+                    Some(connection) => format!("{}: {}", connection.name, data_tab.table()),
+                    None => data_tab.table().to_string(),
+                };
+                sides.push(CompareSide {
+                    title: title,
+                    table: data_tab.table().to_string(),
+                    db: data_tab.db(),
+                });
+            }
+        }
+        if sides.len() < 2 {
+            self.show_error("Open the two tables to compare first");
+            return;
+        }
+        let (left, right) = match compare_tab::choose_tables(Some(&self.window), &sides, selected) {
+            Some(chosen) => chosen,
+            None => return,
+        };
+        if left == right {
+            self.show_error("Choose two different tables");
+            return;
+        }
+        let title = format!("Compare {} / {}", sides[left].table, sides[right].table);
+        let tab = CompareTab::new(sides[left].clone(), sides[right].clone());
+        self.append_tab(&title, &tab.build_widget(), None, None);
+        self.window.show_all();
+    }
+
+    /// the data tabs open on this connection, the closed tabs are forgotten
+    fn data_tabs_of(&self, connection: &str) -> Vec<DataTab> {
+        let mut pages = self.pages.borrow_mut();
+        pages.retain(|page| page.widget.get_parent().is_some());
+        pages
+            .iter()
+            .filter(|page| page.connection.as_ref().map(|c| c.name.as_str()) == Some(connection))
+            .filter_map(|page| page.data_tab.clone())
+            .collect()
+    }
+
+    fn show_error(&self, message: &str) {
+        let error = MessageDialog::new(
+            Some(&self.window),
+            DialogFlags::MODAL,
+            MessageType::Error,
+            ButtonsType::Close,
+            message,
+        );
+        error.run();
+        error.close();
+    }
+
+    /// keep the toolbar showing the open transaction, every second
+    fn init_transaction_status(&self) {
+        let this = self.clone();
+        self.toolbar.manual_commit.connect_toggled(move |button| {
+            let manual_commit = button.get_active();
+            let db = Self::database_of(this.current_connection().as_ref());
+            let pending = match db.borrow_mut().as_mut() {
+                Some(db) if db.session().manual_commit != manual_commit => {
+                    let pending = db.session().transaction.is_some();
+                    if manual_commit || !pending {
+                        db.session_mut().manual_commit = manual_commit;
+                    }
+                    pending
+                }
+                _ => false,
+            };
+            if pending && !manual_commit {
+                this.show_error("Commit or roll back the open transaction first");
+            }
+            this.update_transaction_status();
+        });
+        let this = self.clone();
+        self.open_tabs.connect_switch_page(move |_, _, _| {
+            // the current page changes after the signal
+            let this = this.clone();
+            glib::idle_add_local(move || {
+                this.update_transaction_status();
+                glib::Continue(false)
+            });
+        });
+        self.update_transaction_status();
+        let this = self.clone();
+        glib::timeout_add_local(TRANSACTION_INTERVAL, move || {
+            this.update_transaction_status();
+            glib::Continue(true)
+        });
+    }
+
+    /// The mode of the current connection, what is pending in its transaction and for how long.
+    /// The transactions of all the connections are warned about once when they stay open too long.
+    fn update_transaction_status(&self) {
+        let connection = self.current_connection();
+        // a save may be running
+        let session = match &connection {
+            Some(connection) if connection.db.try_borrow().is_err() => return,
+            Some(connection) => Self::session_of(connection),
+            None => None,
+        };
+        let connected = session.is_some();
+        let session = session.unwrap_or_default();
+        let toolbar = &self.toolbar;
+        if toolbar.manual_commit.get_active() != session.manual_commit {
+            toolbar.manual_commit.set_active(session.manual_commit);
+        }
+        toolbar.manual_commit.set_sensitive(connected);
+        let transaction = session.transaction.as_ref();
+        for name in ["commit", "rollback"].iter() {
+            if let Some(action) = self.actions.lookup_action(name) {
+                if let Ok(action) = action.downcast::<SimpleAction>() {
+                    action.set_enabled(transaction.is_some());
+                }
+            }
+        }
+        let warning = self.settings.transaction_warning();
+        match transaction {
+            Some(transaction) => {
+                let open = transaction.started.elapsed();
+                let text = format!(
+                    "{} statement(s) pending, open for {:02}:{:02}",
+                    transaction.statements,
+                    open.as_secs() / 60,
+                    open.as_secs() % 60
+                );
+                if open >= warning {
+                    toolbar.transaction.set_markup(&format!(
+                        "<span foreground=\"red\">{}</span>",
+                        glib::markup_escape_text(&text)
+                    ));
+                } else {
+                    toolbar.transaction.set_text(&text);
+                }
+            }
+            None if session.manual_commit => toolbar.transaction.set_text("No open transaction"),
+            None => toolbar.transaction.set_text(""),
+        }
+        self.warn_long_transactions(warning);
+    }
+
+    fn warn_long_transactions(&self, warning: Duration) {
+        let mut open = vec![];
+        for connection in self.connections.all() {
+            let started = Self::session_of(&connection)
+                .and_then(|session| session.transaction)
+                .map(|transaction| transaction.started);
+            if let Some(started) = started {
+                open.push((connection, started));
+            }
+        }
+        let mut warned = self.warned_transactions.borrow_mut();
+        // the ones that were committed or rolled back
+        warned.retain(|started| open.iter().any(|(_, s)| s == started));
+        for (connection, started) in open {
+            if started.elapsed() < warning || warned.contains(&started) {
+                continue;
+            }
+            warned.push(started);
+            let dialog = MessageDialog::new(
+                Some(&self.window),
+                DialogFlags::DESTROY_WITH_PARENT,
+                MessageType::Warning,
+                ButtonsType::Close,
+                &format!(
+                    "The transaction of {} has been open for {} minute(s), \
+                     the rows it changed stay locked for the other sessions until it is committed or rolled back.",
+                    connection.name,
+                    started.elapsed().as_secs() / 60
+                ),
+            );
+            dialog.connect_response(|dialog, _| dialog.close());
+            dialog.show();
+        }
+    }
+
+    /// pass the changes notified by each database to the data tabs open on it, every few seconds
+    fn init_notifications(&self) {
+        let this = self.clone();
+        glib::timeout_add_local(NOTIFICATION_INTERVAL, move || {
+            for connection in this.connections.all() {
+                let payloads = match connection.db.try_borrow_mut() {
+                    Ok(mut db) => db.as_mut().map(|db| db.notifications()),
+                    Err(_) => None,
+                };
+                let payloads = payloads.unwrap_or_default();
+                if payloads.is_empty() {
+                    continue;
+                }
+                let tabs = this.data_tabs_of(&connection.name);
+                for payload in payloads {
+                    if let Some((table, action, key)) = edit::parse_change_notice(&payload) {
+                        for tab in tabs.iter() {
+                            tab.notify_change(table, action, key);
+                        }
+                    }
+                }
+            }
+            glib::Continue(true)
+        });
+    }
+
+    fn add_action<F: Fn(&Self) + 'static>(&self, name: &str, activate: F) {
+        let action = SimpleAction::new(name, None);
+        let this = self.clone();
+        action.connect_activate(move |_, _| activate(&this));
+        self.actions.add_action(&action);
+    }
+
+    fn init_toolbar(&self) {
+        self.toolbar.connect.set_action_name(Some("win.connect"));
+        self.toolbar.console.set_action_name(Some("win.console"));
+        self.toolbar.diagram.set_action_name(Some("win.diagram"));
+        self.toolbar.compare.set_action_name(Some("win.compare"));
+        self.toolbar.commit.set_action_name(Some("win.commit"));
+        self.toolbar.rollback.set_action_name(Some("win.rollback"));
+        self.toolbar
+            .shortcuts
+            .set_action_name(Some("win.shortcuts"));
+    }
+
+    /// The shortcuts are matched before the focused widget gets the key,
+    /// except for the keys like `Delete` or `<Primary>c` while typing in an entry.
+    fn init_shortcuts(&self) {
+        let this = self.clone();
+        self.window.connect_key_press_event(move |window, event| {
+            let shortcut = match shortcuts::find(event) {
+                Some(shortcut) => shortcut,
+                None => return Inhibit(false),
+            };
+            let typing = window
+                .get_focus()
+                .map(|w| w.is::<gtk::Entry>() || w.is::<gtk::TextView>())
+                .unwrap_or(false);
+            if typing && shortcut.is_text_editing() {
+                return Inhibit(false);
+            }
+            Inhibit(this.activate_action(shortcut.action))
+        });
+    }
+
+    /// Activate an action by its detailed name, the `tab.*` actions on the current page.
+    /// Returns false when there is no such action, ie: `tab.save` on the console.
+    fn activate_action(&self, detailed_name: &str) -> bool {
+        let mut parts = detailed_name.splitn(2, '.');
+        let (prefix, name) = match (parts.next(), parts.next()) {
+            (Some(prefix), Some(name)) => (prefix, name),
+            _ => return false,
+        };
+        let group = match prefix {
+            "win" => Some(self.actions.clone().upcast::<gio::ActionGroup>()),
+            _ => self
+                .open_tabs
+                .get_nth_page(self.open_tabs.get_current_page())
+                .and_then(|page| page.get_action_group(prefix)),
+        };
+        match group {
+            Some(group) if group.has_action(name) && group.get_action_enabled(name) => {
+                group.activate_action(name, None);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// the tables of all the schemas of the current connection, the saved filters,
+    /// the connections and the actions
+    fn palette_items(&self) -> Vec<PaletteItem> {
+        let mut items = vec![];
+        let db = Self::database_of(self.current_connection().as_ref());
+        if let Some(db) = db.borrow_mut().as_mut() {
+            match catalog::list_tables(db.as_mut()) {
+                Ok(tables) => items.extend(tables.into_iter().map(PaletteItem::Table)),
+                Err(e) => println!("unable to list the tables: {}", e),
+            }
+        }
+        for (table, table_config) in TableConfig::load_all() {
+            for (name, condition) in table_config.filters {
+                items.push(PaletteItem::Filter {
+                    table: table.to_string(),
+                    name: name,
+                    condition: condition,
+                });
+            }
+        }
+        items.extend(
+            config::load_profiles()
+                .into_iter()
+                .map(PaletteItem::Profile),
+        );
+        items.extend(PaletteItem::actions());
+        items
+    }
+
+    fn show_palette(&self) {
+        let this = self.clone();
+        self.palette
+            .popup(self.palette_items(), move |item| match item {
+                PaletteItem::Table(table) => {
+                    this.open_table(this.current_connection().as_ref(), &table);
+                }
+                PaletteItem::Filter {
+                    table, condition, ..
+                } => {
+                    let tab = this.open_table(this.current_connection().as_ref(), &table);
+                    tab.set_filter(Some(&condition));
+                }
+                PaletteItem::Profile(profile) => this.connect_profile(&profile),
+                PaletteItem::Action { action, .. } => {
+                    this.activate_action(&action);
+                }
+            });
+    }
+
+    fn connect_profile(&self, profile: &ConnectionProfile) {
+        match db::connect(&profile.url) {
+            Ok(mut database) => {
+                database.session_mut().manual_commit = profile.manual_commit;
+                if profile.read_only {
+                    if let Err(e) = db::set_read_only(database.as_mut()) {
+                        println!("the session of {} is not read-only: {}", profile.name, e);
+                    }
+                }
+                self.add_connection(&profile.name, Some(profile), database);
+            }
+            Err(e) => self.show_error(&format!("Unable to connect to {}: {}", profile.name, e)),
+        }
+    }
+
+    /// create a table of the current connection in the table editor, and open it
+    fn create_table(&self) {
+        let connection = match self.current_connection() {
+            Some(connection) => connection,
+            None => return,
+        };
+        let platform = match connection.db.borrow().as_ref() {
+            Some(db) => db.platform(),
+            None => return,
+        };
+        let editor = TableEditor::new(Some(&self.window), connection.db.clone(), platform, None);
+        if let Some(table) = editor.run() {
+            self.reload_window_list(&connection);
+            self.open_table(Some(&connection), &table);
+        }
+    }
+
+    /// go to the next or previous tab, wrapping around at the ends
+    fn switch_tab(&self, forward: bool) {
+        let pages = self.open_tabs.get_n_pages();
+        let current = match self.open_tabs.get_current_page() {
+            Some(current) if pages > 0 => current,
+            _ => return,
+        };
+        let page = if forward {
+            (current + 1) % pages
+        } else {
+            (current + pages - 1) % pages
+        };
+        self.open_tabs.set_current_page(Some(page));
+    }
+
+    /// list the tables of the connection in the sidebar
+    fn reload_window_list(&self, connection: &OpenConnection) {
+        Self::list_datawindows(&self.sidebar);
+        let objects = match connection.db.borrow_mut().as_mut() {
+            Some(db) => catalog::list_objects(db.as_mut()),
+            None => return,
+        };
+        match objects {
+            Ok(objects) => {
+                self.sidebar
+                    .set_connection_objects(&connection.name, &connection.color, objects)
+            }
+            Err(e) => println!("unable to list the tables of {}: {}", connection.name, e),
+        }
     }
 
     // add tabs to the horizontal box that divites the list from the contents of that list
@@ -122,9 +848,81 @@ impl DataWindow {
         notebook
     }
 
-    pub fn add_data_tab(&self, title: &str) {
+    /// open a table of a connection picked by the user, it is added to the recently opened
+    fn open_table(&self, connection: Option<&OpenConnection>, table: &str) -> DataTab {
+        let tab = DataTab::new(table, Self::database_of(connection));
+        self.append_data_tab(table, &tab, connection);
+        self.sidebar.add_recent(table);
+        self.window.show_all(); //Notes: Important to call show all in order to redraw the added tabs
+        tab
+    }
+
+    /// Open a tab on the datawindow defined in this file of the datawindows directory,
+    /// on the current connection.
+    /// The parameters of its query are asked first, it is not opened when that is cancelled.
+    fn open_datawindow(&self, name: &str) {
+        let definition = match Definition::load(name) {
+            Some(definition) => definition,
+            None => return,
+        };
+        let title = definition.title().to_string();
+        let mut params = definition.param_defaults();
+        if !params.is_empty() {
+            let form = ParamForm::new(definition.param_fields(), &params);
+            if !params::prompt(Some(&self.window), &title, &form) {
+                return;
+            }
+            params = form.values();
+        }
+        let connection = self.current_connection();
+        let data_tab =
+            DataTab::from_definition(definition, params, Self::database_of(connection.as_ref()));
+        self.append_data_tab(&title, &data_tab, connection.as_ref());
+        self.window.show_all();
+    }
+
+    /// add a data tab on the current connection
+    pub fn add_data_tab(&self, title: &str) -> DataTab {
         println!("Adding {}", title);
+        let connection = self.current_connection();
+        let data_tab = DataTab::new(title, Self::database_of(connection.as_ref()));
+        self.append_data_tab(title, &data_tab, connection.as_ref());
+        data_tab
+    }
+
+    /// the tables referenced from a data tab are opened on its connection
+    fn append_data_tab(
+        &self,
+        title: &str,
+        data_tab: &DataTab,
+        connection: Option<&OpenConnection>,
+    ) {
+        let this = self.clone();
+        let table_connection = connection.cloned();
+        data_tab.connect_open_table(move |table, filter| {
+            let tab = this.open_table(table_connection.as_ref(), table);
+            tab.set_filter(Some(filter));
+        });
+        let data_widget = data_tab.build_widget();
+        self.append_tab(title, &data_widget, connection, Some(data_tab));
+    }
+
+    /// Append a closable and reorderable tab and switch to it,
+    /// its label shows the badge of its connection.
+    fn append_tab<P: IsA<Widget>>(
+        &self,
+        title: &str,
+        widget: &P,
+        connection: Option<&OpenConnection>,
+        data_tab: Option<&DataTab>,
+    ) {
         let tab = gtk::Box::new(Horizontal, 0);
+        if let Some(connection) = connection {
+            let badge = Label::new(None);
+            badge.set_markup(&connection.badge_markup());
+            badge.set_margin_end(5);
+            tab.add(&badge);
+        }
         let sheet = Label::new(Some(title));
         let close_btn = Button::new();
 
@@ -134,8 +932,12 @@ impl DataWindow {
         close_btn.add(&close_image);
         tab.add(&sheet);
         tab.add(&close_btn);
-        let data_tab = DataTab::new();
-        let data_widget = data_tab.build_widget();
+        let data_widget = widget.clone().upcast::<Widget>();
+        self.pages.borrow_mut().push(Page {
+            widget: data_widget.clone(),
+            connection: connection.cloned(),
+            data_tab: data_tab.cloned(),
+        });
         data_widget.show_all(); // needed in order for setting current page works
         self.open_tabs.append_page(&data_widget, Some(&tab));
         self.open_tabs.set_tab_reorderable(&data_widget, true);